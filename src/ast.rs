@@ -0,0 +1,416 @@
+//! Propositional formulae which aren't in conjunctive normal form, and
+//! their conversion to it.
+//!
+//! The conversion is the Tseitin encoding: each connective gets a new
+//! atom, with clauses saying it's true exactly when the connective
+//! is.  Unlike distributing 'or' over 'and', this only makes a formula
+//! linearly bigger; the result isn't equivalent, as it has more atoms,
+//! but its models are the models of the original, extended.
+//!
+//! The Plaisted-Greenbaum encoding only keeps the direction of each
+//! definition which is needed: where a connective only occurs
+//! positively, its atom only has to imply it, and where it only occurs
+//! negatively, only be implied by it.  This gives fewer clauses, and
+//! the same models of the original atoms.
+//!
+//! Before either encoding, constants are folded away, and identical
+//! subformulae are only given one atom.
+
+use std::collections::HashMap;
+
+use crate::cnf::{Clause, Formula, Literal, Var, VarAllocator};
+
+/// A propositional formula.
+#[derive(Clone, Debug, Hash, PartialEq, PartialOrd, Eq, Ord)]
+pub enum Expr {
+    Const(bool),
+    /// An atom, numbered from 1.
+    Var(isize),
+    Not(Box<Expr>),
+    /// A conjunction, which is true if empty.
+    And(Vec<Expr>),
+    /// A disjunction, which is false if empty.
+    Or(Vec<Expr>),
+    Implies(Box<Expr>, Box<Expr>),
+    Iff(Box<Expr>, Box<Expr>),
+}
+
+/// Options for converting to CNF.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub struct EncodeOptions {
+    /// Use the Plaisted-Greenbaum encoding, rather than Tseitin.
+    pub plaisted_greenbaum: bool,
+}
+
+impl Expr {
+    /// Check if a formula is true, given the truth of each atom.
+    pub fn eval<F: Fn(isize) -> bool>(&self, value: &F) -> bool {
+        match self {
+            Expr::Const(b) => *b,
+            Expr::Var(id) => value(*id),
+            Expr::Not(e) => !e.eval(value),
+            Expr::And(es) => es.iter().all(|e| e.eval(value)),
+            Expr::Or(es) => es.iter().any(|e| e.eval(value)),
+            Expr::Implies(a, b) => !a.eval(value) || b.eval(value),
+            Expr::Iff(a, b) => a.eval(value) == b.eval(value),
+        }
+    }
+
+    /// The highest atom in a formula, or 0 if there are none.
+    pub fn max_var(&self) -> isize {
+        match self {
+            Expr::Const(_) => 0,
+            Expr::Var(id) => *id,
+            Expr::Not(e) => e.max_var(),
+            Expr::And(es) | Expr::Or(es) => es.iter().map(Expr::max_var).max().unwrap_or(0),
+            Expr::Implies(a, b) | Expr::Iff(a, b) => a.max_var().max(b.max_var()),
+        }
+    }
+
+    /// Convert a formula to CNF with the Tseitin encoding, numbering
+    /// the new atoms from one above the highest atom in the formula.
+    pub fn to_cnf(&self) -> Formula {
+        self.to_cnf_from(&mut VarAllocator::new())
+    }
+
+    /// Like 'to_cnf', but taking the new atoms from 'vars', so several
+    /// formulae can be converted without their new atoms clashing.
+    /// The atoms of the formula are reserved first.
+    pub fn to_cnf_from(&self, vars: &mut VarAllocator) -> Formula {
+        self.to_cnf_with(&EncodeOptions::default(), vars)
+    }
+
+    /// Like 'to_cnf_from', but with a choice of encoding.
+    pub fn to_cnf_with(&self, options: &EncodeOptions, vars: &mut VarAllocator) -> Formula {
+        if self.max_var() > 0 {
+            vars.reserve(Var::new(self.max_var() as usize));
+        }
+        match self.simplify() {
+            Expr::Const(true) => Formula::default(),
+            Expr::Const(false) => Formula::new(vec![Clause::from(Vec::new())]),
+            expr => {
+                let mut encoder = Encoder {
+                    clauses: Vec::new(),
+                    vars,
+                    atoms: HashMap::new(),
+                };
+                let root = encoder.encode(&expr, true, !options.plaisted_greenbaum);
+                encoder.clauses.push(Clause::from(vec![root]));
+                Formula::new(encoder.clauses)
+            }
+        }
+    }
+
+    /// Convert a formula to negation normal form: only atoms are
+    /// negated, and there are only conjunctions and disjunctions
+    /// above them.  Each 'Iff' is expanded, copying its sides, so the
+    /// result can be much bigger.
+    pub fn nnf(&self) -> Expr {
+        self.nnf_signed(true)
+    }
+
+    /// Convert a formula, or its negation if 'positive' is false, to
+    /// negation normal form.
+    fn nnf_signed(&self, positive: bool) -> Expr {
+        match self {
+            Expr::Const(b) => Expr::Const(*b == positive),
+            Expr::Var(_) if positive => self.clone(),
+            Expr::Var(_) => Expr::Not(Box::new(self.clone())),
+            Expr::Not(e) => e.nnf_signed(!positive),
+            Expr::And(es) if positive => Expr::And(es.iter().map(|e| e.nnf_signed(true)).collect()),
+            Expr::And(es) => Expr::Or(es.iter().map(|e| e.nnf_signed(false)).collect()),
+            Expr::Or(es) if positive => Expr::Or(es.iter().map(|e| e.nnf_signed(true)).collect()),
+            Expr::Or(es) => Expr::And(es.iter().map(|e| e.nnf_signed(false)).collect()),
+            Expr::Implies(a, b) if positive => {
+                Expr::Or(vec![a.nnf_signed(false), b.nnf_signed(true)])
+            }
+            Expr::Implies(a, b) => Expr::And(vec![a.nnf_signed(true), b.nnf_signed(false)]),
+            Expr::Iff(a, b) => Expr::Or(vec![
+                Expr::And(vec![a.nnf_signed(true), b.nnf_signed(positive)]),
+                Expr::And(vec![a.nnf_signed(false), b.nnf_signed(!positive)]),
+            ]),
+        }
+    }
+
+    /// Fold constants away, so the result is either a constant or has
+    /// none; remove double negations; and flatten nested conjunctions
+    /// and disjunctions.  The result is equivalent.
+    pub fn simplify(&self) -> Expr {
+        match self {
+            Expr::Const(_) | Expr::Var(_) => self.clone(),
+            Expr::Not(e) => match e.simplify() {
+                Expr::Const(b) => Expr::Const(!b),
+                Expr::Not(e) => *e,
+                e => Expr::Not(Box::new(e)),
+            },
+            Expr::And(es) => Expr::junction(es, true),
+            Expr::Or(es) => Expr::junction(es, false),
+            Expr::Implies(a, b) => match (a.simplify(), b.simplify()) {
+                (Expr::Const(false), _) | (_, Expr::Const(true)) => Expr::Const(true),
+                (Expr::Const(true), b) => b,
+                (a, Expr::Const(false)) => Expr::Not(Box::new(a)).simplify(),
+                (a, b) => Expr::Implies(Box::new(a), Box::new(b)),
+            },
+            Expr::Iff(a, b) => match (a.simplify(), b.simplify()) {
+                (Expr::Const(x), Expr::Const(y)) => Expr::Const(x == y),
+                (Expr::Const(true), e) | (e, Expr::Const(true)) => e,
+                (Expr::Const(false), e) | (e, Expr::Const(false)) => {
+                    Expr::Not(Box::new(e)).simplify()
+                }
+                (a, b) => Expr::Iff(Box::new(a), Box::new(b)),
+            },
+        }
+    }
+
+    /// Simplify a conjunction, if 'and' is set, or a disjunction.
+    fn junction(es: &[Expr], and: bool) -> Expr {
+        let mut flat = Vec::with_capacity(es.len());
+        for e in es {
+            match e.simplify() {
+                // the identity.
+                Expr::Const(b) if b == and => (),
+                // the absorbing element.
+                Expr::Const(_) => return Expr::Const(!and),
+                Expr::And(inner) if and => flat.extend(inner),
+                Expr::Or(inner) if !and => flat.extend(inner),
+                e => flat.push(e),
+            }
+        }
+        match flat.len() {
+            0 => Expr::Const(and),
+            1 => flat.remove(0),
+            _ if and => Expr::And(flat),
+            _ => Expr::Or(flat),
+        }
+    }
+}
+
+/// The state of a conversion to CNF.
+struct Encoder<'a> {
+    clauses: Vec<Clause>,
+    vars: &'a mut VarAllocator,
+    /// The literal of each subformula encoded so far, with the
+    /// directions it was encoded in.
+    atoms: HashMap<(Expr, bool, bool), Literal>,
+}
+
+impl Encoder<'_> {
+    /// Add the clauses defining a literal for the formula, and return
+    /// it.  If 'pos' is set, the literal implies the formula, and if
+    /// 'neg' is set, the formula implies the literal.
+    fn encode(&mut self, expr: &Expr, pos: bool, neg: bool) -> Literal {
+        match expr {
+            Expr::Var(id) => return Literal::new(*id),
+            Expr::Not(e) => return self.encode(e, neg, pos).negate(),
+            _ => (),
+        }
+        let key = (expr.clone(), pos, neg);
+        if let Some(lit) = self.atoms.get(&key) {
+            return *lit;
+        }
+
+        let t = match expr {
+            Expr::Const(b) => {
+                let t = self.vars.fresh().positive();
+                self.clauses
+                    .push(Clause::from(vec![if *b { t } else { t.negate() }]));
+                t
+            }
+            Expr::Var(_) | Expr::Not(_) => unreachable!("atoms aren't defined"),
+            Expr::And(es) => {
+                let lits: Vec<Literal> = es.iter().map(|e| self.encode(e, pos, neg)).collect();
+                let t = self.vars.fresh().positive();
+                if pos {
+                    for lit in &lits {
+                        self.clauses.push(Clause::from(vec![t.negate(), *lit]));
+                    }
+                }
+                if neg {
+                    let mut all = vec![t];
+                    all.extend(lits.iter().map(|lit| lit.negate()));
+                    self.clauses.push(Clause::from(all));
+                }
+                t
+            }
+            Expr::Or(es) => {
+                let lits: Vec<Literal> = es.iter().map(|e| self.encode(e, pos, neg)).collect();
+                let t = self.vars.fresh().positive();
+                if neg {
+                    for lit in &lits {
+                        self.clauses.push(Clause::from(vec![t, lit.negate()]));
+                    }
+                }
+                if pos {
+                    let mut any = vec![t.negate()];
+                    any.extend(lits);
+                    self.clauses.push(Clause::from(any));
+                }
+                t
+            }
+            Expr::Implies(a, b) => {
+                let or = Expr::Or(vec![Expr::Not(a.clone()), (**b).clone()]);
+                self.encode(&or, pos, neg)
+            }
+            Expr::Iff(a, b) => {
+                // both sides occur both ways.
+                let a = self.encode(a, true, true);
+                let b = self.encode(b, true, true);
+                let t = self.vars.fresh().positive();
+                if pos {
+                    self.clauses
+                        .push(Clause::from(vec![t.negate(), a.negate(), b]));
+                    self.clauses
+                        .push(Clause::from(vec![t.negate(), a, b.negate()]));
+                }
+                if neg {
+                    self.clauses.push(Clause::from(vec![t, a, b]));
+                    self.clauses
+                        .push(Clause::from(vec![t, a.negate(), b.negate()]));
+                }
+                t
+            }
+        };
+        self.atoms.insert(key, t);
+        t
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cnf::*;
+
+    fn var(id: isize) -> Box<Expr> {
+        Box::new(Expr::Var(id))
+    }
+
+    /// Formulae over the atoms 1 to 3.
+    fn examples() -> Vec<Expr> {
+        vec![
+            Expr::And(vec![
+                Expr::Or(vec![Expr::Var(1), Expr::Not(var(2))]),
+                Expr::Implies(var(2), var(3)),
+            ]),
+            Expr::Iff(
+                Box::new(Expr::And(vec![Expr::Var(1), Expr::Var(2)])),
+                Box::new(Expr::Not(var(3))),
+            ),
+            Expr::And(vec![
+                Expr::Iff(var(1), var(2)),
+                Expr::Iff(var(1), Box::new(Expr::Not(var(2)))),
+            ]),
+            Expr::Or(Vec::new()),
+            Expr::And(Vec::new()),
+            Expr::Or(vec![Expr::Var(3)]),
+            Expr::Implies(
+                Box::new(Expr::Or(vec![Expr::Const(false), Expr::Var(1)])),
+                Box::new(Expr::Not(Box::new(Expr::Iff(
+                    var(2),
+                    Box::new(Expr::Const(true)),
+                )))),
+            ),
+            Expr::Not(Box::new(Expr::Iff(
+                Box::new(Expr::Implies(var(1), var(3))),
+                Box::new(Expr::Const(false)),
+            ))),
+        ]
+    }
+
+    /// Check a function of the atoms 1 to 3 for every assignment.
+    fn for_all_assignments<F: Fn(&dyn Fn(isize) -> bool)>(check: F) {
+        for bits in 0..1u32 << 3 {
+            check(&|id: isize| bits & (1 << (id - 1)) != 0);
+        }
+    }
+
+    #[test]
+    fn converts_to_equisatisfiable_cnf() {
+        let exprs = examples();
+
+        let pg = EncodeOptions {
+            plaisted_greenbaum: true,
+        };
+        for (expr, options) in exprs
+            .iter()
+            .flat_map(|expr| vec![(expr, EncodeOptions::default()), (expr, pg)])
+        {
+            // not every formula mentions all three atoms.
+            let mut vars = VarAllocator::new();
+            vars.reserve(Var::new(3));
+            let cnf = expr.to_cnf_with(&options, &mut vars);
+            // the models of the formula are the models of the cnf,
+            // restricted to the original atoms.
+            for_all_assignments(|value| {
+                let mut constrained = cnf.clone();
+                for id in 1..=3 {
+                    let lit = if value(id) { id } else { -id };
+                    constrained.insert_clause(Clause::new(vec![lit]));
+                }
+                assert_eq!(expr.eval(&value), crate::sat(constrained), "{:?}", expr);
+            });
+        }
+
+        // only one direction of the and is needed.
+        let expr = Expr::And(vec![Expr::Var(1), Expr::Var(2)]);
+        assert_eq!(4, expr.to_cnf().len());
+        assert_eq!(3, expr.to_cnf_with(&pg, &mut VarAllocator::new()).len());
+
+        let expr = Expr::Not(var(2));
+        assert_eq!(Formula::new(vec![Clause::new(vec![-2])]), expr.to_cnf());
+    }
+
+    #[test]
+    fn converts_to_nnf() {
+        fn is_nnf(expr: &Expr) -> bool {
+            match expr {
+                Expr::Const(_) | Expr::Var(_) => true,
+                Expr::Not(e) => matches!(**e, Expr::Var(_)),
+                Expr::And(es) | Expr::Or(es) => es.iter().all(is_nnf),
+                Expr::Implies(_, _) | Expr::Iff(_, _) => false,
+            }
+        }
+
+        for expr in examples() {
+            let nnf = expr.nnf();
+            assert!(is_nnf(&nnf), "{:?}", nnf);
+            for_all_assignments(|value| assert_eq!(expr.eval(&value), nnf.eval(&value)));
+        }
+    }
+
+    #[test]
+    fn folds_constants() {
+        for expr in examples() {
+            let simple = expr.simplify();
+            for_all_assignments(|value| assert_eq!(expr.eval(&value), simple.eval(&value)));
+        }
+
+        let expr = Expr::And(vec![
+            Expr::Var(1),
+            Expr::Const(true),
+            Expr::Or(vec![Expr::Const(true), Expr::Var(2)]),
+        ]);
+        assert_eq!(Expr::Var(1), expr.simplify());
+        let expr = Expr::Implies(var(1), Box::new(Expr::Const(false)));
+        assert_eq!(Expr::Not(var(1)), expr.simplify());
+        let expr = Expr::And(vec![
+            Expr::Var(1),
+            Expr::And(vec![Expr::Var(2), Expr::Var(3)]),
+        ]);
+        assert_eq!(
+            Expr::And(vec![Expr::Var(1), Expr::Var(2), Expr::Var(3)]),
+            expr.simplify()
+        );
+        let expr = Expr::Or(vec![Expr::Not(var(1)), Expr::Const(true)]);
+        assert_eq!(Formula::new(Vec::new()), expr.to_cnf());
+    }
+
+    #[test]
+    fn encodes_shared_subformulae_once() {
+        // 1 || 2, and (1 || 2) -> 3: atom 4 is 1 || 2, 5 is the
+        // implication, and 6 the conjunction.
+        let or = Expr::Or(vec![Expr::Var(1), Expr::Var(2)]);
+        let expr = Expr::And(vec![or.clone(), Expr::Implies(Box::new(or), var(3))]);
+        let max = expr.to_cnf().iter().flatten().map(|lit| lit.get_id()).max();
+        assert_eq!(Some(6), max);
+    }
+}
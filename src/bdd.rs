@@ -0,0 +1,372 @@
+//! Truth tables and reduced ordered binary decision diagrams (BDDs)
+//! of small formulae, for analysis and teaching.
+//!
+//! Both are over the atoms 1 to n, where n is the highest atom in the
+//! formula, so atoms which don't occur still count towards the number
+//! of models.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::cnf::{Formula, Literal};
+use crate::graph::Graph;
+
+/// The most atoms a formula can have to have its truth table made:
+/// the table has 2^n rows.
+pub const MAX_TRUTH_TABLE_ATOMS: usize = 20;
+
+/// The truth of a formula under every assignment to its atoms.
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub struct TruthTable {
+    num_atoms: usize,
+    /// The truth of the formula for each assignment, where atom k is
+    /// true in row i if bit k - 1 of i is set.
+    rows: Vec<bool>,
+}
+
+impl TruthTable {
+    /// The number of atoms.
+    pub fn num_atoms(&self) -> usize {
+        self.num_atoms
+    }
+
+    /// The truth of the formula under each assignment, in order: see
+    /// 'assignment'.
+    pub fn rows(&self) -> &[bool] {
+        &self.rows
+    }
+
+    /// The assignment of a row, as a literal for each atom.
+    pub fn assignment(&self, row: usize) -> Vec<Literal> {
+        (1..=self.num_atoms)
+            .map(|id| {
+                let lit = Literal::new(id as isize);
+                if row & (1 << (id - 1)) != 0 {
+                    lit
+                } else {
+                    lit.negate()
+                }
+            })
+            .collect()
+    }
+
+    /// The number of assignments which make the formula true.
+    pub fn count_models(&self) -> usize {
+        self.rows.iter().filter(|row| **row).count()
+    }
+}
+
+impl fmt::Display for TruthTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for id in 1..=self.num_atoms {
+            write!(f, "{} ", id)?;
+        }
+        writeln!(f, "| F")?;
+        for (row, value) in self.rows.iter().enumerate() {
+            for id in 1..=self.num_atoms {
+                let width = id.to_string().len();
+                let bit = (row >> (id - 1)) & 1;
+                write!(f, "{:width$} ", bit, width = width)?;
+            }
+            writeln!(f, "| {}", u8::from(*value))?;
+        }
+        Ok(())
+    }
+}
+
+/// A reduced ordered BDD, with the atoms in ascending order.  No two
+/// nodes are the same, and no node has the same child on both sides,
+/// so two formulae with the same models have BDDs of the same shape.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub struct Bdd {
+    num_atoms: usize,
+    /// The nodes, with the false and true leaves first, and every
+    /// node after its children.
+    nodes: Vec<Node>,
+    root: usize,
+}
+
+/// A node of a BDD: if the atom is true, go to 'high', and if not, go
+/// to 'low'.  The leaves have atom 0.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+struct Node {
+    atom: usize,
+    low: usize,
+    high: usize,
+}
+
+/// The false and true leaves.
+const FALSE: usize = 0;
+const TRUE: usize = 1;
+
+impl Bdd {
+    /// The number of nodes, including the leaves which are used.
+    pub fn size(&self) -> usize {
+        let mut seen = vec![false; self.nodes.len()];
+        let mut todo = vec![self.root];
+        while let Some(n) = todo.pop() {
+            if !seen[n] {
+                seen[n] = true;
+                if n > TRUE {
+                    todo.push(self.nodes[n].low);
+                    todo.push(self.nodes[n].high);
+                }
+            }
+        }
+        seen.into_iter().filter(|s| *s).count()
+    }
+
+    /// Check if an assignment, given as the truth of each atom, makes
+    /// the formula true.
+    pub fn eval<F: Fn(usize) -> bool>(&self, value: F) -> bool {
+        let mut n = self.root;
+        while n > TRUE {
+            let node = self.nodes[n];
+            n = if value(node.atom) {
+                node.high
+            } else {
+                node.low
+            };
+        }
+        n == TRUE
+    }
+
+    /// The number of assignments to the atoms which make the formula
+    /// true, or 'u128::MAX' if there are more than that.
+    pub fn count_models(&self) -> u128 {
+        // the number of models of each node, over the atoms from its
+        // own (or n + 1 for a leaf).
+        let level = |n: usize| {
+            if n > TRUE {
+                self.nodes[n].atom
+            } else {
+                self.num_atoms + 1
+            }
+        };
+        let mut counts: Vec<u128> = vec![0; self.nodes.len()];
+        counts[TRUE] = 1;
+        for n in TRUE + 1..self.nodes.len() {
+            let Node { atom, low, high } = self.nodes[n];
+            counts[n] = scale(counts[low], level(low) - atom - 1)
+                .saturating_add(scale(counts[high], level(high) - atom - 1));
+        }
+        scale(counts[self.root], level(self.root) - 1)
+    }
+
+    /// Convert to a labelled graph, for exporting: dashed edges in the
+    /// usual drawing are labelled 0, and solid edges 1.
+    pub fn to_graph(&self) -> Graph {
+        let mut graph = Graph::directed();
+        let mut indices = HashMap::new();
+        let mut todo = vec![self.root];
+        while let Some(n) = todo.pop() {
+            if indices.contains_key(&n) {
+                continue;
+            }
+            let label = match n {
+                FALSE => "0".to_string(),
+                TRUE => "1".to_string(),
+                _ => self.nodes[n].atom.to_string(),
+            };
+            indices.insert(n, graph.add_node(label));
+            if n > TRUE {
+                todo.push(self.nodes[n].high);
+                todo.push(self.nodes[n].low);
+            }
+        }
+        let mut nodes: Vec<usize> = indices.keys().copied().filter(|n| *n > TRUE).collect();
+        nodes.sort_unstable();
+        for n in nodes {
+            let Node { low, high, .. } = self.nodes[n];
+            graph.add_edge(indices[&n], indices[&low], Some("0".to_string()));
+            graph.add_edge(indices[&n], indices[&high], Some("1".to_string()));
+        }
+        graph
+    }
+}
+
+/// Builds a BDD, sharing nodes through a unique table.
+struct Builder {
+    nodes: Vec<Node>,
+    unique: HashMap<Node, usize>,
+    /// The conjunctions computed so far.
+    conjunctions: HashMap<(usize, usize), usize>,
+}
+
+impl Builder {
+    fn new() -> Builder {
+        let leaf = Node {
+            atom: 0,
+            low: FALSE,
+            high: FALSE,
+        };
+        Builder {
+            nodes: vec![leaf, Node { high: TRUE, ..leaf }],
+            unique: HashMap::new(),
+            conjunctions: HashMap::new(),
+        }
+    }
+
+    /// The node for an atom with the given children.
+    fn node(&mut self, atom: usize, low: usize, high: usize) -> usize {
+        if low == high {
+            return low;
+        }
+        let node = Node { atom, low, high };
+        if let Some(n) = self.unique.get(&node) {
+            return *n;
+        }
+        self.nodes.push(node);
+        self.unique.insert(node, self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    /// The node for a clause.
+    fn clause(&mut self, lits: &[Literal]) -> usize {
+        let mut lits = lits.to_vec();
+        lits.sort_by_key(|lit| lit.get_id());
+        lits.dedup();
+        if lits.windows(2).any(|pair| pair[0] == pair[1].negate()) {
+            return TRUE;
+        }
+        // build from the highest atom down, so each node is above its
+        // children.
+        let mut n = FALSE;
+        for lit in lits.iter().rev() {
+            let atom = lit.get_id() as usize;
+            n = if lit.is_negated() {
+                self.node(atom, TRUE, n)
+            } else {
+                self.node(atom, n, TRUE)
+            };
+        }
+        n
+    }
+
+    /// The conjunction of two nodes.
+    fn and(&mut self, a: usize, b: usize) -> usize {
+        if a == FALSE || b == FALSE {
+            return FALSE;
+        }
+        if a == TRUE || a == b {
+            return b;
+        }
+        if b == TRUE {
+            return a;
+        }
+        let key = (a.min(b), a.max(b));
+        if let Some(n) = self.conjunctions.get(&key) {
+            return *n;
+        }
+
+        let (na, nb) = (self.nodes[a], self.nodes[b]);
+        let atom = na.atom.min(nb.atom);
+        let (a_low, a_high) = if na.atom == atom {
+            (na.low, na.high)
+        } else {
+            (a, a)
+        };
+        let (b_low, b_high) = if nb.atom == atom {
+            (nb.low, nb.high)
+        } else {
+            (b, b)
+        };
+        let low = self.and(a_low, b_low);
+        let high = self.and(a_high, b_high);
+        let n = self.node(atom, low, high);
+        self.conjunctions.insert(key, n);
+        n
+    }
+}
+
+impl Formula {
+    /// Compute the truth table, if the formula has at most
+    /// 'MAX_TRUTH_TABLE_ATOMS' atoms.
+    pub fn truth_table(&self) -> Option<TruthTable> {
+        let num_atoms = max_atom(self);
+        if num_atoms > MAX_TRUTH_TABLE_ATOMS {
+            return None;
+        }
+
+        let rows = (0..1usize << num_atoms)
+            .map(|row| {
+                self.iter().all(|lits| {
+                    lits.iter().any(|lit| {
+                        let bit = row & (1 << (lit.get_id() - 1)) != 0;
+                        bit != lit.is_negated()
+                    })
+                })
+            })
+            .collect();
+        Some(TruthTable { num_atoms, rows })
+    }
+
+    /// Compute the BDD, as the conjunction of the BDDs of the clauses.
+    /// This can be exponentially big.
+    pub fn to_bdd(&self) -> Bdd {
+        let mut builder = Builder::new();
+        let mut root = TRUE;
+        for lits in self.iter() {
+            let n = builder.clause(lits);
+            root = builder.and(root, n);
+        }
+        Bdd {
+            num_atoms: max_atom(self),
+            nodes: builder.nodes,
+            root,
+        }
+    }
+}
+
+/// Multiply a count by 2^k, saturating.
+fn scale(count: u128, k: usize) -> u128 {
+    match 1u128.checked_shl(k as u32) {
+        Some(factor) if k < 128 => count.saturating_mul(factor),
+        _ if count == 0 => 0,
+        _ => u128::MAX,
+    }
+}
+
+/// The highest atom in a formula.
+fn max_atom(formula: &Formula) -> usize {
+    formula
+        .iter()
+        .flatten()
+        .map(|lit| lit.get_id() as usize)
+        .max()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cnf;
+
+    #[test]
+    fn truth_table_and_bdd_agree() {
+        let formula = cnf![[1, -2], [2, 3, -4], [-1, 4], [-3, -1]];
+        let table = formula.truth_table().unwrap();
+        let bdd = formula.to_bdd();
+
+        assert_eq!(4, table.num_atoms());
+        for (row, value) in table.rows().iter().enumerate() {
+            let assignment = table.assignment(row);
+            let holds = formula
+                .iter()
+                .all(|lits| lits.iter().any(|lit| assignment.contains(lit)));
+            assert_eq!(holds, *value);
+            assert_eq!(holds, bdd.eval(|atom| row & (1 << (atom - 1)) != 0));
+        }
+        assert_eq!(table.count_models() as u128, bdd.count_models());
+    }
+
+    #[test]
+    fn counts_models_of_bdds() {
+        assert_eq!(1, cnf![].to_bdd().count_models());
+        assert_eq!(0, cnf![[]].to_bdd().count_models());
+        // 3, 4, and 5 are free.
+        assert_eq!(24, cnf![[1, 2], [-5, 5]].to_bdd().count_models());
+        assert_eq!(4, cnf![[1, 2]].to_bdd().size());
+        assert_eq!(u128::MAX, cnf![[200, -200]].to_bdd().count_models());
+        assert_eq!(None, cnf![[21]].truth_table());
+    }
+}
@@ -0,0 +1,125 @@
+//! A cache of solver results, for callers which repeatedly ask about
+//! the same formulae.  Formulae are looked up by a hash of their
+//! canonical form, like 'Formula::fingerprint', so two formulae which differ only in the order or repetition of
+//! their literals and clauses share a cache entry.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use crate::cnf::{Formula, Literal};
+use crate::sat_assignment;
+
+/// A cached formula (in canonical form) and its result.
+type Entry = (Formula, Option<Vec<Literal>>);
+
+/// A cache of SAT results.  Only the empty theory is supported, as
+/// the result of an SMT query depends on the state of the theory as
+/// well as the formula.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Cache {
+    entries: HashMap<u64, Vec<Entry>>,
+    hits: usize,
+    misses: usize,
+}
+
+impl Cache {
+    /// Construct a new empty cache.
+    pub fn new() -> Cache {
+        Cache::default()
+    }
+
+    /// Check if a formula is satisfiable, consulting the cache first.
+    pub fn sat(&mut self, formula: Formula) -> bool {
+        self.sat_assignment(formula).is_some()
+    }
+
+    /// Find a satisfying assignment for a formula, consulting the
+    /// cache first.
+    pub fn sat_assignment(&mut self, formula: Formula) -> Option<Vec<Literal>> {
+        let canonical = formula.canonical();
+        let mut hasher = DefaultHasher::new();
+        canonical.hash(&mut hasher);
+
+        let bucket = self.entries.entry(hasher.finish()).or_default();
+        for (cached_formula, result) in bucket.iter() {
+            // fingerprints can collide, so check the formula too.
+            if *cached_formula == canonical {
+                self.hits += 1;
+                return result.clone();
+            }
+        }
+
+        self.misses += 1;
+        let result = sat_assignment(formula);
+        bucket.push((canonical, result.clone()));
+        result
+    }
+
+    /// The number of formulae in the cache.
+    pub fn len(&self) -> usize {
+        self.entries.values().map(Vec::len).sum()
+    }
+
+    /// Check if the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Remove all entries from the cache, and reset the hit and miss
+    /// counts.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.hits = 0;
+        self.misses = 0;
+    }
+
+    /// The number of queries answered from the cache.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// The number of queries which had to be solved.
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cnf::*;
+
+    #[test]
+    fn equivalent_formulae_share_an_entry() {
+        let mut cache = Cache::new();
+
+        assert!(cache.sat(Formula::new(vec![
+            Clause::new(vec![1, 2]),
+            Clause::new(vec![-1]),
+        ])));
+        assert!(cache.sat(Formula::new(vec![
+            Clause::new(vec![-1, -1]),
+            Clause::new(vec![2, 1]),
+        ])));
+
+        assert_eq!(1, cache.len());
+        assert_eq!(1, cache.hits());
+        assert_eq!(1, cache.misses());
+    }
+
+    #[test]
+    fn caches_unsat_results() {
+        let mut cache = Cache::new();
+        let formula = Formula::new(vec![Clause::new(vec![1]), Clause::new(vec![-1])]);
+
+        assert!(!cache.sat(formula.clone()));
+        assert!(!cache.sat(formula.clone()));
+        assert_eq!(1, cache.hits());
+
+        cache.clear();
+        assert_eq!((0, 0), (cache.hits(), cache.misses()));
+        assert!(!cache.sat(formula));
+        assert_eq!((0, 1), (cache.hits(), cache.misses()));
+    }
+}
@@ -1,10 +1,20 @@
 //! Types and utility functions for conjunctive normal form.
 
+pub mod encodings;
+
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::ops::Index;
+
+use crate::rng::Rng;
 
 /// A literal is either an atom (a positive number) or the negation of
 /// that atom (a negative number).
-#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, PartialOrd, Eq, Ord)]
 pub struct Literal(pub isize);
 
 impl Literal {
@@ -33,6 +43,17 @@ impl Literal {
         let Literal(atom) = self;
         atom.abs()
     }
+
+    /// Get the variable of a literal.
+    pub fn var(self) -> Var {
+        Var(self.get_id() as usize)
+    }
+}
+
+impl From<Var> for Literal {
+    fn from(var: Var) -> Literal {
+        var.positive()
+    }
 }
 
 impl fmt::Display for Literal {
@@ -42,65 +63,761 @@ impl fmt::Display for Literal {
     }
 }
 
-/// A clause is a disjunction of literals.
-#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
-pub struct Clause(pub Vec<Literal>);
+/// A variable, or atom: a literal without its sign.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, PartialOrd, Eq, Ord)]
+pub struct Var(pub usize);
+
+impl Var {
+    /// Construct a variable from its number.
+    pub fn new(id: usize) -> Var {
+        if id == 0 {
+            panic!("cannot construct a variable numbered zero");
+        }
+        Var(id)
+    }
+
+    /// Get the number of a variable.
+    pub fn index(self) -> usize {
+        let Var(id) = self;
+        id
+    }
+
+    /// The literal which is true when the variable is.
+    pub fn positive(self) -> Literal {
+        Literal::new(self.index() as isize)
+    }
+
+    /// The literal which is true when the variable is false.
+    pub fn negative(self) -> Literal {
+        self.positive().negate()
+    }
+
+    /// The literal which is true when the variable has the given value.
+    pub fn literal(self, value: bool) -> Literal {
+        if value {
+            self.positive()
+        } else {
+            self.negative()
+        }
+    }
+}
+
+impl From<Literal> for Var {
+    fn from(lit: Literal) -> Var {
+        lit.var()
+    }
+}
+
+impl fmt::Display for Var {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Var(id) = self;
+        write!(f, "{}", id)
+    }
+}
+
+/// Construct a clause from numeric literals: 'clause![1, -2]' is
+/// 'Clause::new(vec![1, -2])'.
+#[macro_export]
+macro_rules! clause {
+    ($($lit:expr),* $(,)?) => {
+        $crate::cnf::Clause::new(vec![$($lit),*])
+    };
+}
+
+/// Construct a formula from clauses of numeric literals:
+/// 'cnf![[1, -2], [2]]' is the formula '(1 || -2) && (2)'.
+#[macro_export]
+macro_rules! cnf {
+    ($([$($lit:expr),* $(,)?]),* $(,)?) => {
+        $crate::cnf::Formula::new(vec![$($crate::clause![$($lit),*]),*])
+    };
+}
+
+/// A clause is a disjunction of literals.  The constructors and
+/// methods keep it in normal form, see 'normalize', apart from
+/// 'unnormalized', for code which needs a particular order.
+#[derive(Clone, Debug, Hash, PartialEq, PartialOrd, Eq, Ord)]
+pub struct Clause(Vec<Literal>);
 
 impl Clause {
-    /// Construct a new clause from numeric literals.
+    /// Construct a new clause from numeric literals, in normal form.
     pub fn new(lits: Vec<isize>) -> Clause {
-        Clause(lits.into_iter().map(Literal::new).collect())
+        lits.into_iter().map(Literal::new).collect()
     }
 
-    /// Add a literal to a clause.  Two literals corresponding to the
-    /// same atom, but in positive and negative forms, can exist in
-    /// the same clause.
-    pub fn insert_literal(&mut self, lit: Literal) {
+    /// Construct a clause with the literals in the order given, and
+    /// any repeats kept, such as a RAT lemma, whose first literal is
+    /// its pivot.
+    pub fn unnormalized(lits: Vec<Literal>) -> Clause {
+        Clause(lits)
+    }
+
+    /// The literals of a clause.
+    pub fn literals(&self) -> &[Literal] {
+        let Clause(lits) = self;
+        lits
+    }
+
+    /// Take the literals of a clause.
+    pub fn into_literals(self) -> Vec<Literal> {
         let Clause(lits) = self;
-        lits.push(lit);
+        lits
+    }
+
+    /// The number of literals.
+    pub fn len(&self) -> usize {
+        self.literals().len()
+    }
+
+    /// Check if a clause has no literals, so is always false.
+    pub fn is_empty(&self) -> bool {
+        self.literals().is_empty()
+    }
+
+    /// Put a clause into normal form: the literals are sorted by
+    /// variable, with the negative literal first if both occur, and
+    /// repeated literals are removed.  Two clauses with the same
+    /// literals have the same normal form.  Returns true if the clause
+    /// is a tautology: it contains a literal and its negation.
+    pub fn normalize(&mut self) -> bool {
+        let Clause(lits) = self;
+        lits.sort_by_key(normal_order);
+        lits.dedup();
+        lits.windows(2).any(|pair| pair[0].var() == pair[1].var())
+    }
+
+    /// Check if a clause is in normal form.
+    pub fn is_normalized(&self) -> bool {
+        let Clause(lits) = self;
+        lits.windows(2)
+            .all(|pair| normal_order(&pair[0]) < normal_order(&pair[1]))
+    }
+
+    /// Check if a clause contains a literal and its negation, so is
+    /// always true.
+    pub fn is_tautology(&self) -> bool {
+        let Clause(lits) = self;
+        let present: HashSet<Literal> = lits.iter().copied().collect();
+        lits.iter().any(|lit| present.contains(&lit.negate()))
+    }
+
+    /// Check if every literal of this clause is in another, so the
+    /// other clause is true whenever this one is.  Both clauses must
+    /// be in normal form.
+    pub fn subsumes(&self, other: &Clause) -> bool {
+        let (Clause(lits), Clause(others)) = (self, other);
+        let mut others = others.iter().peekable();
+        for lit in lits {
+            while others
+                .peek()
+                .is_some_and(|other| normal_order(other) < normal_order(lit))
+            {
+                others.next();
+            }
+            if others.next() != Some(lit) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Add a literal to a clause, keeping it in normal form.  Two
+    /// literals corresponding to the same atom, but in positive and
+    /// negative forms, can exist in the same clause.
+    pub fn insert_literal(&mut self, lit: Literal) {
+        self.extend(Some(lit));
+    }
+
+    /// Check if a clause is Horn: it has at most one positive literal.
+    pub fn is_horn(&self) -> bool {
+        is_horn(self.literals())
     }
 }
 
+/// Check if the literals of a clause include at most one positive one.
+fn is_horn(lits: &[Literal]) -> bool {
+    lits.iter().filter(|lit| !lit.is_negated()).count() <= 1
+}
+
 impl fmt::Display for Clause {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "(")?;
-        let Clause(lits) = self;
-        if let Some((first, rest)) = lits.split_first() {
-            write!(f, "{}", first)?;
-            for lit in rest {
-                write!(f, " || {}", lit)?;
-            }
+        write_clause(f, self.literals())
+    }
+}
+
+/// Write the literals of a clause like its 'Display' instance.
+fn write_clause(f: &mut fmt::Formatter, lits: &[Literal]) -> fmt::Result {
+    write!(f, "(")?;
+    if let Some((first, rest)) = lits.split_first() {
+        write!(f, "{}", first)?;
+        for lit in rest {
+            write!(f, " || {}", lit)?;
         }
-        write!(f, ")")
     }
+    write!(f, ")")
 }
 
-/// A formula is a conjunction of clauses.
-#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
-pub struct Formula(pub Vec<Clause>);
+impl FromIterator<Literal> for Clause {
+    fn from_iter<I: IntoIterator<Item = Literal>>(iter: I) -> Clause {
+        Clause::from(iter.into_iter().collect::<Vec<Literal>>())
+    }
+}
+
+impl From<Vec<Literal>> for Clause {
+    fn from(lits: Vec<Literal>) -> Clause {
+        let mut clause = Clause(lits);
+        clause.normalize();
+        clause
+    }
+}
+
+impl Extend<Literal> for Clause {
+    fn extend<I: IntoIterator<Item = Literal>>(&mut self, iter: I) {
+        let Clause(lits) = self;
+        lits.extend(iter);
+        self.normalize();
+    }
+}
+
+/// The order of literals in a clause in normal form.
+fn normal_order(lit: &Literal) -> (Var, bool) {
+    (lit.var(), !lit.is_negated())
+}
+
+/// A formula is a conjunction of clauses.  The literals of every
+/// clause are stored in one buffer, so a formula takes two allocations
+/// however many clauses it has, and its clauses are next to each other
+/// in memory.
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct Formula {
+    /// The literals of all the clauses, one after another.
+    lits: Vec<Literal>,
+    /// Where the literals of each clause start in 'lits', followed by
+    /// the end of the last clause.
+    starts: Vec<usize>,
+}
+
+impl Default for Formula {
+    fn default() -> Formula {
+        Formula {
+            lits: Vec::new(),
+            starts: vec![0],
+        }
+    }
+}
 
 impl Formula {
     /// Construct a new formula from a clause.
     pub fn new(clauses: Vec<Clause>) -> Formula {
-        Formula(clauses.clone())
+        let mut starts = Vec::with_capacity(clauses.len() + 1);
+        starts.push(0);
+        let mut formula = Formula {
+            lits: Vec::with_capacity(clauses.iter().map(Clause::len).sum()),
+            starts,
+        };
+        formula.extend(clauses);
+        formula
     }
 
     /// Add a clause to a formula.
     pub fn insert_clause(&mut self, clause: Clause) {
-        let Formula(clauses) = self;
-        clauses.push(clause);
+        self.push(clause.literals());
+    }
+
+    /// Add the literals of a clause, as they are.
+    fn push(&mut self, lits: &[Literal]) {
+        self.lits.extend_from_slice(lits);
+        self.starts.push(self.lits.len());
+    }
+
+    /// The number of clauses.
+    pub fn len(&self) -> usize {
+        self.starts.len() - 1
+    }
+
+    /// Check if a formula has no clauses, so is always true.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterate over the literals of each clause, in order.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &[Literal]> + '_ {
+        self.starts
+            .windows(2)
+            .map(move |bounds| &self.lits[bounds[0]..bounds[1]])
+    }
+
+    /// Copy a clause out of the formula.
+    pub fn clause(&self, i: usize) -> Clause {
+        Clause::unnormalized(self[i].to_vec())
+    }
+
+    /// Copy the clauses out of the formula.
+    pub fn clauses(&self) -> Vec<Clause> {
+        (0..self.len()).map(|i| self.clause(i)).collect()
+    }
+
+    /// Remove the clauses after the first 'len'.
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len() {
+            self.starts.truncate(len + 1);
+            self.lits.truncate(self.starts[len]);
+        }
+    }
+
+    /// Keep the clauses for which a function returns true, after
+    /// letting it change their literals.
+    fn retain_mut<F: FnMut(&mut Vec<Literal>) -> bool>(&mut self, mut keep: F) {
+        let mut retained = Formula::default();
+        let mut lits = Vec::new();
+        for clause in self.iter() {
+            lits.clear();
+            lits.extend_from_slice(clause);
+            if keep(&mut lits) {
+                retained.push(&lits);
+            }
+        }
+        *self = retained;
+    }
+
+    /// Put a formula into a canonical form: each clause is put into
+    /// normal form, and then the clauses are sorted and deduplicated.
+    /// Two formulae which differ only in the order or repetition of
+    /// their literals and clauses have the same canonical form.
+    pub fn canonical(&self) -> Formula {
+        let mut clauses: Vec<Clause> = self
+            .iter()
+            .map(|lits| lits.iter().copied().collect())
+            .collect();
+        clauses.sort();
+        clauses.dedup();
+        Formula::new(clauses)
+    }
+
+    /// Randomly rename the atoms, reorder the clauses, and reorder the
+    /// literals of each clause, so the result is the same problem in
+    /// a different disguise.  The atoms are permuted among 1 to the
+    /// highest atom, and the new atom of each old one is returned.
+    /// The same seed gives the same shuffle.
+    pub fn shuffle(&mut self, seed: u64) -> BTreeMap<Var, Var> {
+        let mut rng = Rng::new(seed);
+        let num_atoms = self
+            .lits
+            .iter()
+            .map(|lit| lit.var().index())
+            .max()
+            .unwrap_or(0);
+
+        let mut atoms: Vec<usize> = (1..=num_atoms).collect();
+        shuffle(&mut atoms, &mut rng);
+        let renaming: BTreeMap<Var, Var> = atoms
+            .into_iter()
+            .enumerate()
+            .map(|(i, atom)| (Var::new(i + 1), Var::new(atom)))
+            .collect();
+
+        let mut clauses: Vec<Vec<Literal>> = self.iter().map(<[Literal]>::to_vec).collect();
+        for lits in clauses.iter_mut() {
+            for lit in lits.iter_mut() {
+                *lit = renaming[&lit.var()].literal(!lit.is_negated());
+            }
+            shuffle(lits, &mut rng);
+        }
+        shuffle(&mut clauses, &mut rng);
+        let mut shuffled = Formula::default();
+        for lits in &clauses {
+            shuffled.push(lits);
+        }
+        *self = shuffled;
+        renaming
+    }
+
+    /// Remove repeated literals from each clause, and then clauses
+    /// containing a literal and its negation, which are always true,
+    /// and clauses with the same literals as an earlier one.  The
+    /// surviving literals and clauses keep their order.  For more
+    /// thorough simplifications, see 'preprocess::simplify'.
+    pub fn simplify(&mut self) -> CleanupStats {
+        let mut stats = CleanupStats::default();
+        let mut seen = HashSet::new();
+        self.retain_mut(|lits| {
+            let mut present = HashSet::new();
+            let before = lits.len();
+            lits.retain(|lit| present.insert(*lit));
+            stats.duplicate_literals += before - lits.len();
+
+            if lits.iter().any(|lit| present.contains(&lit.negate())) {
+                stats.tautologies += 1;
+                return false;
+            }
+            let mut key = lits.clone();
+            key.sort();
+            if !seen.insert(key) {
+                stats.duplicate_clauses += 1;
+                return false;
+            }
+            true
+        });
+        stats
+    }
+
+    /// Simplify a formula on the assumption that a literal is true:
+    /// clauses containing it are satisfied, so removed, and its
+    /// negation is removed from the others.
+    pub fn assign(&mut self, lit: Literal) {
+        self.condition(&[lit]);
+    }
+
+    /// Simplify a formula on the assumption that some literals are
+    /// true, as with 'assign'.  The literals shouldn't contradict each
+    /// other.
+    pub fn condition(&mut self, lits: &[Literal]) {
+        let assigned: HashSet<Literal> = lits.iter().copied().collect();
+        self.retain_mut(|lits| {
+            if lits.iter().any(|lit| assigned.contains(lit)) {
+                return false;
+            }
+            lits.retain(|lit| !assigned.contains(&lit.negate()));
+            true
+        });
+    }
+
+    /// Assign the literals of unit clauses, until there are none left
+    /// or there is an empty clause, and return the literals assigned
+    /// in order.  An empty clause means the formula is unsatisfiable.
+    pub fn propagate_units(&mut self) -> Vec<Literal> {
+        let mut assigned = Vec::new();
+        loop {
+            if self.iter().any(|lits| lits.is_empty()) {
+                break;
+            }
+            let units: BTreeSet<Literal> = self
+                .iter()
+                .filter(|lits| lits.len() == 1)
+                .map(|lits| lits[0])
+                .collect();
+            if units.is_empty() {
+                break;
+            }
+            // contradictory units leave an empty clause.
+            let units: Vec<Literal> = units
+                .iter()
+                .copied()
+                .filter(|lit| lit.is_negated() || !units.contains(&lit.negate()))
+                .collect();
+            self.condition(&units);
+            assigned.extend(units);
+        }
+        assigned
+    }
+
+    /// Find the pure literals: those whose negation doesn't occur in
+    /// the formula.  Making a pure literal true can't falsify any
+    /// clause, so it preserves satisfiability.
+    pub fn pure_literals(&self) -> Vec<Literal> {
+        let lits: BTreeSet<Literal> = self.lits.iter().copied().collect();
+        lits.iter()
+            .copied()
+            .filter(|lit| !lits.contains(&lit.negate()))
+            .collect()
+    }
+
+    /// Check if every clause of a formula is Horn.  Horn formulae can
+    /// be solved in linear time, see 'horn::solve'.
+    pub fn is_horn(&self) -> bool {
+        self.iter().all(is_horn)
+    }
+
+    /// Summarise the shape of a formula.
+    pub fn stats(&self) -> FormulaStats {
+        let mut atoms = BTreeSet::new();
+        let mut stats = FormulaStats {
+            num_vars: 0,
+            num_clauses: self.len(),
+            clause_lengths: BTreeMap::new(),
+            positive_literals: 0,
+            negative_literals: 0,
+            horn_clauses: 0,
+        };
+        for lits in self.iter() {
+            *stats.clause_lengths.entry(lits.len()).or_insert(0) += 1;
+            for lit in lits {
+                atoms.insert(lit.get_id());
+                if lit.is_negated() {
+                    stats.negative_literals += 1;
+                } else {
+                    stats.positive_literals += 1;
+                }
+            }
+            if is_horn(lits) {
+                stats.horn_clauses += 1;
+            }
+        }
+        stats.num_vars = atoms.len();
+        stats
+    }
+
+    /// Construct a formula from clauses of named literals, where a
+    /// name starting with '-' is negated, numbering new names as they
+    /// are seen.
+    pub fn from_names(clauses: &[&[&str]], vars: &mut VarMap) -> Formula {
+        clauses
+            .iter()
+            .map(|names| names.iter().map(|name| vars.lit(name)).collect())
+            .collect()
+    }
+
+    /// Render a formula like its 'Display' instance, but with the
+    /// names of atoms rather than their numbers.  Atoms without names
+    /// are rendered as numbers.
+    pub fn to_names(&self, vars: &VarMap) -> String {
+        let clauses: Vec<String> = self
+            .iter()
+            .map(|lits| {
+                let lits: Vec<String> = lits.iter().map(|lit| vars.render(*lit)).collect();
+                format!("({})", lits.join(" || "))
+            })
+            .collect();
+        clauses.join(" && ")
+    }
+
+    /// Compute a hash of the canonical form of a formula.  This is
+    /// stable within a single build of the library, but not across
+    /// compiler versions, so don't persist it.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.canonical().hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Put a slice in a random order, with the Fisher-Yates shuffle.
+fn shuffle<T>(items: &mut [T], rng: &mut Rng) {
+    for i in (1..items.len()).rev() {
+        items.swap(i, rng.below(i + 1));
+    }
+}
+
+/// A source of new atoms, above every atom in use, for encodings which
+/// need auxiliary atoms.  Sharing one between encodings keeps their
+/// atoms apart.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub struct VarAllocator {
+    /// The next atom to hand out.
+    next: usize,
+}
+
+impl Default for VarAllocator {
+    fn default() -> VarAllocator {
+        VarAllocator { next: 1 }
+    }
+}
+
+impl VarAllocator {
+    /// Construct an allocator which starts from 1.
+    pub fn new() -> VarAllocator {
+        VarAllocator::default()
+    }
+
+    /// Construct an allocator which starts above every atom in a
+    /// formula.
+    pub fn above(formula: &Formula) -> VarAllocator {
+        let mut vars = VarAllocator::new();
+        vars.reserve_formula(formula);
+        vars
+    }
+
+    /// Take a new atom.
+    pub fn fresh(&mut self) -> Var {
+        self.next += 1;
+        Var::new(self.next - 1)
+    }
+
+    /// Mark a variable as in use, so it's never handed out.
+    pub fn reserve(&mut self, var: Var) {
+        self.next = self.next.max(var.index() + 1);
+    }
+
+    /// Mark every atom in a formula as in use.
+    pub fn reserve_formula(&mut self, formula: &Formula) {
+        for lit in &formula.lits {
+            self.reserve(lit.var());
+        }
+    }
+
+    /// The atom which will be handed out next: every atom below it has
+    /// been handed out or reserved.
+    pub fn next_var(&self) -> Var {
+        Var::new(self.next)
+    }
+}
+
+/// What 'Formula::simplify' removed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub struct CleanupStats {
+    /// The number of clauses removed because an earlier clause had the
+    /// same literals.
+    pub duplicate_clauses: usize,
+    /// The number of literals removed from clauses because they were
+    /// already in them.
+    pub duplicate_literals: usize,
+    /// The number of clauses removed because they contain a literal
+    /// and its negation.
+    pub tautologies: usize,
+}
+
+/// A numbering of named atoms, from 1.
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd, Eq, Ord)]
+pub struct VarMap {
+    ids: BTreeMap<String, Var>,
+    /// The name of each atom, offset by one.
+    names: Vec<String>,
+}
+
+impl VarMap {
+    /// Construct an empty numbering.
+    pub fn new() -> VarMap {
+        VarMap::default()
+    }
+
+    /// Get the variable of a name, numbering it if it's new.
+    pub fn var(&mut self, name: &str) -> Var {
+        if let Some(var) = self.ids.get(name) {
+            return *var;
+        }
+        self.names.push(name.to_string());
+        let var = Var::new(self.names.len());
+        self.ids.insert(name.to_string(), var);
+        var
+    }
+
+    /// Get the literal of a name, which is negated if it starts with
+    /// '-', numbering it if it's new.
+    pub fn lit(&mut self, name: &str) -> Literal {
+        match name.strip_prefix('-') {
+            Some(name) => self.var(name).negative(),
+            None => self.var(name).positive(),
+        }
+    }
+
+    /// Get the variable of a name, if it has one.
+    pub fn id(&self, name: &str) -> Option<Var> {
+        self.ids.get(name).copied()
+    }
+
+    /// Get the name of a variable, if it has one.
+    pub fn name(&self, var: Var) -> Option<&str> {
+        self.names
+            .get(var.index().checked_sub(1)?)
+            .map(String::as_str)
+    }
+
+    /// The number of names.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Check if there are no names.
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Get the truth of each named atom which a model assigns.
+    pub fn assignment(&self, model: &[Literal]) -> BTreeMap<String, bool> {
+        model
+            .iter()
+            .filter_map(|lit| {
+                self.name(lit.var())
+                    .map(|name| (name.to_string(), !lit.is_negated()))
+            })
+            .collect()
+    }
+
+    /// Render a literal by name, with a '-' if it's negated, or by
+    /// number if its atom has no name.
+    pub fn render(&self, lit: Literal) -> String {
+        match self.name(lit.var()) {
+            Some(name) if lit.is_negated() => format!("-{}", name),
+            Some(name) => name.to_string(),
+            None => lit.to_string(),
+        }
+    }
+}
+
+/// The shape of a formula, from 'Formula::stats'.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub struct FormulaStats {
+    /// The number of distinct atoms which occur.
+    pub num_vars: usize,
+    pub num_clauses: usize,
+    /// The number of clauses of each length.
+    pub clause_lengths: BTreeMap<usize, usize>,
+    /// The number of occurrences of positive literals.
+    pub positive_literals: usize,
+    /// The number of occurrences of negative literals.
+    pub negative_literals: usize,
+    /// The number of clauses with at most one positive literal.
+    pub horn_clauses: usize,
+}
+
+impl FormulaStats {
+    /// Check if every clause is Horn.
+    pub fn is_horn(&self) -> bool {
+        self.horn_clauses == self.num_clauses
     }
 }
 
 impl fmt::Display for Formula {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let Formula(clauses) = self;
-        if let Some((first, rest)) = clauses.split_first() {
-            write!(f, "{}", first)?;
-            for clause in rest {
-                write!(f, " && {}", clause)?;
+        for (i, lits) in self.iter().enumerate() {
+            if i > 0 {
+                write!(f, " && ")?;
             }
+            write_clause(f, lits)?;
         }
         Ok(())
     }
 }
+
+impl fmt::Debug for Formula {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Formula").field(&self.clauses()).finish()
+    }
+}
+
+/// Formulae are ordered by their clauses, in order.
+impl Ord for Formula {
+    fn cmp(&self, other: &Formula) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl PartialOrd for Formula {
+    fn partial_cmp(&self, other: &Formula) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Index<usize> for Formula {
+    type Output = [Literal];
+
+    fn index(&self, i: usize) -> &[Literal] {
+        &self.lits[self.starts[i]..self.starts[i + 1]]
+    }
+}
+
+impl FromIterator<Clause> for Formula {
+    fn from_iter<I: IntoIterator<Item = Clause>>(iter: I) -> Formula {
+        let mut formula = Formula::default();
+        formula.extend(iter);
+        formula
+    }
+}
+
+impl Extend<Clause> for Formula {
+    fn extend<I: IntoIterator<Item = Clause>>(&mut self, iter: I) {
+        for clause in iter {
+            self.insert_clause(clause);
+        }
+    }
+}
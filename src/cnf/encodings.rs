@@ -0,0 +1,425 @@
+//! Encodings of common constraints as clauses.
+
+pub mod circuit;
+pub mod graph;
+pub mod int;
+pub mod pb;
+
+use std::ops::Range;
+
+use crate::cnf::{Clause, Literal, Var, VarAllocator};
+
+/// Ways of encoding that at most one of some literals is true.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub enum Encoding {
+    /// A binary clause for each pair: no new atoms, but quadratically
+    /// many clauses.
+    #[default]
+    Pairwise,
+    /// Split the literals into groups of three, each with a commander
+    /// atom which is true when one of its group is, and recursively
+    /// require at most one commander: a linear number of clauses and
+    /// about half as many new atoms as literals.
+    Commander,
+    /// A ladder of new atoms, where each literal being true fixes which
+    /// rungs are true: a linear number of clauses and one fewer new
+    /// atom than literals.
+    Ladder,
+}
+
+/// Ways of encoding that at most (or at least) k of some literals are
+/// true.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub enum Cardinality {
+    /// Sinz's sequential counter: k new atoms for each literal, which
+    /// count the true literals so far.
+    #[default]
+    SequentialCounter,
+    /// A binary tree of unary counters, each of which counts the true
+    /// literals below it, up to k + 1: fewer clauses when k is large.
+    Totalizer,
+    /// Batcher's odd-even merge sorting network, which sorts the
+    /// literals so the true ones come first: O(n log^2 n) clauses,
+    /// whatever k is, and the sorted outputs propagate well.
+    SortingNetwork,
+}
+
+/// The number of literals at or below which the commander encoding
+/// is pairwise.
+const COMMANDER_BASE: usize = 6;
+
+/// The size of the groups in the commander encoding.
+const COMMANDER_GROUP: usize = 3;
+
+/// Encode that at most one of the literals is true.  New atoms are
+/// taken from 'vars', after reserving the atoms of the literals.
+pub fn at_most_one(lits: &[Literal], encoding: Encoding, vars: &mut VarAllocator) -> Vec<Clause> {
+    reserve(lits, vars);
+    match encoding {
+        Encoding::Pairwise => pairwise(lits),
+        Encoding::Commander => commander(lits, vars),
+        Encoding::Ladder => ladder(lits, vars),
+    }
+}
+
+/// Encode that at most 'k' of the literals are true.  New atoms are
+/// taken from 'vars', after reserving the atoms of the literals, and
+/// their range is returned with the clauses.
+pub fn at_most_k(
+    lits: &[Literal],
+    k: usize,
+    encoding: Cardinality,
+    vars: &mut VarAllocator,
+) -> (Vec<Clause>, Range<Var>) {
+    reserve(lits, vars);
+    let first = vars.next_var();
+    let clauses = if k == 0 {
+        lits.iter()
+            .map(|lit| Clause::from(vec![lit.negate()]))
+            .collect()
+    } else if lits.len() <= k {
+        Vec::new()
+    } else {
+        match encoding {
+            Cardinality::SequentialCounter => sequential_counter(lits, k, vars),
+            Cardinality::Totalizer => totalizer(lits, k, vars),
+            Cardinality::SortingNetwork => sorting_network(lits, k, vars),
+        }
+    };
+    (clauses, first..vars.next_var())
+}
+
+/// Encode that at least 'k' of the literals are true, as at most
+/// n - k of their negations.
+pub fn at_least_k(
+    lits: &[Literal],
+    k: usize,
+    encoding: Cardinality,
+    vars: &mut VarAllocator,
+) -> (Vec<Clause>, Range<Var>) {
+    reserve(lits, vars);
+    if k > lits.len() {
+        return (
+            vec![Clause::from(Vec::new())],
+            vars.next_var()..vars.next_var(),
+        );
+    }
+    let negated: Vec<Literal> = lits.iter().map(|lit| lit.negate()).collect();
+    at_most_k(&negated, lits.len() - k, encoding, vars)
+}
+
+/// Encode that exactly 'k' of the literals are true.
+pub fn exactly_k(
+    lits: &[Literal],
+    k: usize,
+    encoding: Cardinality,
+    vars: &mut VarAllocator,
+) -> (Vec<Clause>, Range<Var>) {
+    reserve(lits, vars);
+    let first = vars.next_var();
+    let (mut clauses, _) = at_most_k(lits, k, encoding, vars);
+    clauses.extend(at_least_k(lits, k, encoding, vars).0);
+    (clauses, first..vars.next_var())
+}
+
+/// Encode that at most 'k' of the literals are true, with 0 < k < n,
+/// with Sinz's sequential counter: s(i, j) means more than j of the
+/// first i + 1 literals are true.
+fn sequential_counter(lits: &[Literal], k: usize, vars: &mut VarAllocator) -> Vec<Clause> {
+    let n = lits.len();
+    let counters: Vec<Vec<Literal>> = (0..n - 1)
+        .map(|_| (0..k).map(|_| vars.fresh().positive()).collect())
+        .collect();
+    let s = |i: usize, j: usize| counters[i][j];
+
+    let mut clauses = vec![Clause::from(vec![lits[0].negate(), s(0, 0)])];
+    for j in 1..k {
+        clauses.push(Clause::from(vec![s(0, j).negate()]));
+    }
+    for (i, lit) in lits.iter().enumerate().take(n - 1).skip(1) {
+        clauses.push(Clause::from(vec![lit.negate(), s(i, 0)]));
+        clauses.push(Clause::from(vec![s(i - 1, 0).negate(), s(i, 0)]));
+        for j in 1..k {
+            clauses.push(Clause::from(vec![
+                lit.negate(),
+                s(i - 1, j - 1).negate(),
+                s(i, j),
+            ]));
+            clauses.push(Clause::from(vec![s(i - 1, j).negate(), s(i, j)]));
+        }
+        clauses.push(Clause::from(vec![lit.negate(), s(i - 1, k - 1).negate()]));
+    }
+    clauses.push(Clause::from(vec![
+        lits[n - 1].negate(),
+        s(n - 2, k - 1).negate(),
+    ]));
+    clauses
+}
+
+/// Encode that at most 'k' of the literals are true, with 0 < k < n,
+/// with a totalizer.
+fn totalizer(lits: &[Literal], k: usize, vars: &mut VarAllocator) -> Vec<Clause> {
+    let mut clauses = Vec::new();
+    let outputs = count(lits, k + 1, vars, &mut clauses);
+    if let Some(over) = outputs.get(k) {
+        clauses.push(Clause::from(vec![over.negate()]));
+    }
+    clauses
+}
+
+/// Count the true literals in unary, up to 'limit': output j is true
+/// if more than j of the literals are.
+fn count(
+    lits: &[Literal],
+    limit: usize,
+    vars: &mut VarAllocator,
+    clauses: &mut Vec<Clause>,
+) -> Vec<Literal> {
+    if lits.len() <= 1 {
+        return lits.to_vec();
+    }
+
+    let (left, right) = lits.split_at(lits.len() / 2);
+    let a = count(left, limit, vars, clauses);
+    let b = count(right, limit, vars, clauses);
+    let outputs: Vec<Literal> = (0..lits.len().min(limit))
+        .map(|_| vars.fresh().positive())
+        .collect();
+    // more than i on the left and j on the right is more than i + j.
+    for i in 0..=a.len() {
+        for j in 0..=b.len() {
+            if i + j == 0 || i + j > outputs.len() {
+                continue;
+            }
+            let mut clause = Vec::with_capacity(3);
+            if i > 0 {
+                clause.push(a[i - 1].negate());
+            }
+            if j > 0 {
+                clause.push(b[j - 1].negate());
+            }
+            clause.push(outputs[i + j - 1]);
+            clauses.push(Clause::from(clause));
+        }
+    }
+    outputs
+}
+
+/// Encode that at most 'k' of the literals are true, with 0 < k < n,
+/// with a sorting network.
+fn sorting_network(lits: &[Literal], k: usize, vars: &mut VarAllocator) -> Vec<Clause> {
+    // pad to a power of two with false values.
+    let mut values: Vec<Option<Literal>> = lits.iter().copied().map(Some).collect();
+    values.resize(lits.len().next_power_of_two(), None);
+    let mut clauses = Vec::new();
+    let sorted = sort(values, vars, &mut clauses);
+    if let Some(over) = sorted[k] {
+        clauses.push(Clause::from(vec![over.negate()]));
+    }
+    clauses
+}
+
+/// Sort some values, a power of two of them, so the true ones come
+/// first.  'None' is false.  Only the upward direction is encoded: an
+/// output is true if enough inputs are.
+fn sort(
+    values: Vec<Option<Literal>>,
+    vars: &mut VarAllocator,
+    clauses: &mut Vec<Clause>,
+) -> Vec<Option<Literal>> {
+    if values.len() <= 1 {
+        return values;
+    }
+    let mut left = values;
+    let right = left.split_off(left.len() / 2);
+    let left = sort(left, vars, clauses);
+    let right = sort(right, vars, clauses);
+    merge(left, right, vars, clauses)
+}
+
+/// Merge two sorted sequences of the same length, a power of two.
+fn merge(
+    a: Vec<Option<Literal>>,
+    b: Vec<Option<Literal>>,
+    vars: &mut VarAllocator,
+    clauses: &mut Vec<Clause>,
+) -> Vec<Option<Literal>> {
+    if a.len() == 1 {
+        let (max, min) = compare(a[0], b[0], vars, clauses);
+        return vec![max, min];
+    }
+
+    let evens = |v: &[Option<Literal>]| v.iter().step_by(2).copied().collect();
+    let odds = |v: &[Option<Literal>]| v.iter().skip(1).step_by(2).copied().collect();
+    let c = merge(evens(&a), evens(&b), vars, clauses);
+    let d = merge(odds(&a), odds(&b), vars, clauses);
+    let mut merged = Vec::with_capacity(c.len() + d.len());
+    merged.push(c[0]);
+    for i in 0..d.len() - 1 {
+        let (max, min) = compare(d[i], c[i + 1], vars, clauses);
+        merged.push(max);
+        merged.push(min);
+    }
+    merged.push(d[d.len() - 1]);
+    merged
+}
+
+/// A comparator: the larger and smaller of two values.
+fn compare(
+    x: Option<Literal>,
+    y: Option<Literal>,
+    vars: &mut VarAllocator,
+    clauses: &mut Vec<Clause>,
+) -> (Option<Literal>, Option<Literal>) {
+    match (x, y) {
+        (Some(x), Some(y)) => {
+            let max = vars.fresh().positive();
+            let min = vars.fresh().positive();
+            clauses.push(Clause::from(vec![x.negate(), max]));
+            clauses.push(Clause::from(vec![y.negate(), max]));
+            clauses.push(Clause::from(vec![x.negate(), y.negate(), min]));
+            (Some(max), Some(min))
+        }
+        (Some(lit), None) | (None, Some(lit)) => (Some(lit), None),
+        (None, None) => (None, None),
+    }
+}
+
+/// Mark the atoms of some literals as in use.
+fn reserve(lits: &[Literal], vars: &mut VarAllocator) {
+    for lit in lits {
+        vars.reserve(lit.var());
+    }
+}
+
+/// Encode that at most one literal is true, with no two true.
+fn pairwise(lits: &[Literal]) -> Vec<Clause> {
+    let mut clauses = Vec::new();
+    for (i, a) in lits.iter().enumerate() {
+        for b in &lits[i + 1..] {
+            clauses.push(Clause::from(vec![a.negate(), b.negate()]));
+        }
+    }
+    clauses
+}
+
+/// Encode that at most one literal is true, with commander atoms.
+fn commander(lits: &[Literal], vars: &mut VarAllocator) -> Vec<Clause> {
+    if lits.len() <= COMMANDER_BASE {
+        return pairwise(lits);
+    }
+
+    let mut clauses = Vec::new();
+    let mut commanders = Vec::new();
+    for group in lits.chunks(COMMANDER_GROUP) {
+        let c = vars.fresh().positive();
+        commanders.push(c);
+        clauses.extend(pairwise(group));
+        // the commander is true exactly when one of the group is.
+        let mut any = vec![c.negate()];
+        for lit in group {
+            clauses.push(Clause::from(vec![lit.negate(), c]));
+            any.push(*lit);
+        }
+        clauses.push(Clause::from(any));
+    }
+    clauses.extend(commander(&commanders, vars));
+    clauses
+}
+
+/// Encode that at most one literal is true, with a ladder: rung i is
+/// true if the true literal comes after literal i, and each rung
+/// implies the ones below it.
+fn ladder(lits: &[Literal], vars: &mut VarAllocator) -> Vec<Clause> {
+    if lits.len() <= 1 {
+        return Vec::new();
+    }
+
+    let rungs: Vec<Literal> = (1..lits.len()).map(|_| vars.fresh().positive()).collect();
+    let mut clauses = Vec::new();
+    for pair in rungs.windows(2) {
+        clauses.push(Clause::from(vec![pair[1].negate(), pair[0]]));
+    }
+    for (i, lit) in lits.iter().enumerate() {
+        if i > 0 {
+            clauses.push(Clause::from(vec![lit.negate(), rungs[i - 1]]));
+        }
+        if i < rungs.len() {
+            clauses.push(Clause::from(vec![lit.negate(), rungs[i].negate()]));
+        }
+    }
+    clauses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cnf::*;
+
+    #[test]
+    fn encodes_at_most_one() {
+        for encoding in [Encoding::Pairwise, Encoding::Commander, Encoding::Ladder] {
+            for n in 0..=8 {
+                let lits: Vec<Literal> = (1..=n).map(Literal::new).collect();
+                let mut vars = VarAllocator::new();
+                let clauses = at_most_one(&lits, encoding, &mut vars);
+                for bits in 0..1u32 << n {
+                    let mut formula = Formula::new(clauses.clone());
+                    for lit in &lits {
+                        let value = bits & (1 << (lit.get_id() - 1)) != 0;
+                        formula.insert_clause(Clause::from(vec![if value {
+                            *lit
+                        } else {
+                            lit.negate()
+                        }]));
+                    }
+                    assert_eq!(
+                        bits.count_ones() <= 1,
+                        crate::sat(formula),
+                        "{:?}",
+                        encoding
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn encodes_cardinality() {
+        for encoding in [
+            Cardinality::SequentialCounter,
+            Cardinality::Totalizer,
+            Cardinality::SortingNetwork,
+        ] {
+            for n in 0..=6 {
+                let lits: Vec<Literal> = (1..=n).map(Literal::new).collect();
+                for k in 0..=n as usize + 1 {
+                    for constraint in 0..3 {
+                        let mut vars = VarAllocator::new();
+                        let (clauses, aux) = match constraint {
+                            0 => at_most_k(&lits, k, encoding, &mut vars),
+                            1 => at_least_k(&lits, k, encoding, &mut vars),
+                            _ => exactly_k(&lits, k, encoding, &mut vars),
+                        };
+                        assert_eq!(Var::new(n as usize + 1)..vars.next_var(), aux);
+                        for bits in 0..1u32 << n {
+                            let count = bits.count_ones() as usize;
+                            let holds = match constraint {
+                                0 => count <= k,
+                                1 => count >= k,
+                                _ => count == k,
+                            };
+                            let mut formula = Formula::new(clauses.clone());
+                            for lit in &lits {
+                                let value = bits & (1 << (lit.get_id() - 1)) != 0;
+                                let lit = if value { *lit } else { lit.negate() };
+                                formula.insert_clause(Clause::from(vec![lit]));
+                            }
+                            assert_eq!(holds, crate::sat(formula), "{:?}", encoding);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
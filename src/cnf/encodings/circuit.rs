@@ -0,0 +1,287 @@
+//! Gadgets for encoding circuits over literals.  Each gadget adds the
+//! clauses defining its outputs to 'clauses', taking the new atoms for
+//! the outputs from 'vars', and returns the outputs.
+//!
+//! Vectors of literals are unsigned numbers, least significant bit
+//! first.  Vectors of different lengths are padded with false bits.
+
+use crate::cnf::{Clause, Literal, VarAllocator};
+
+/// A literal which is always true or always false.
+pub fn constant(value: bool, vars: &mut VarAllocator, clauses: &mut Vec<Clause>) -> Literal {
+    let lit = vars.fresh().positive();
+    clauses.push(Clause::from(vec![if value { lit } else { lit.negate() }]));
+    lit
+}
+
+/// A literal which is true if all of the inputs are.
+pub fn and(lits: &[Literal], vars: &mut VarAllocator, clauses: &mut Vec<Clause>) -> Literal {
+    let out = vars.fresh().positive();
+    let mut all = vec![out];
+    for lit in lits {
+        clauses.push(Clause::from(vec![out.negate(), *lit]));
+        all.push(lit.negate());
+    }
+    clauses.push(Clause::from(all));
+    out
+}
+
+/// A literal which is true if any of the inputs are.
+pub fn or(lits: &[Literal], vars: &mut VarAllocator, clauses: &mut Vec<Clause>) -> Literal {
+    let negated: Vec<Literal> = lits.iter().map(|lit| lit.negate()).collect();
+    and(&negated, vars, clauses).negate()
+}
+
+/// A literal which is true if exactly one of the inputs is.
+pub fn xor(a: Literal, b: Literal, vars: &mut VarAllocator, clauses: &mut Vec<Clause>) -> Literal {
+    let out = vars.fresh().positive();
+    clauses.push(Clause::from(vec![a.negate(), b.negate(), out.negate()]));
+    clauses.push(Clause::from(vec![a, b, out.negate()]));
+    clauses.push(Clause::from(vec![a.negate(), b, out]));
+    clauses.push(Clause::from(vec![a, b.negate(), out]));
+    out
+}
+
+/// A literal which is the 'then' input if the condition is true, and
+/// the 'otherwise' input if not.
+pub fn ite(
+    condition: Literal,
+    then: Literal,
+    otherwise: Literal,
+    vars: &mut VarAllocator,
+    clauses: &mut Vec<Clause>,
+) -> Literal {
+    let out = vars.fresh().positive();
+    clauses.push(Clause::from(vec![condition.negate(), then.negate(), out]));
+    clauses.push(Clause::from(vec![condition.negate(), then, out.negate()]));
+    clauses.push(Clause::from(vec![condition, otherwise.negate(), out]));
+    clauses.push(Clause::from(vec![condition, otherwise, out.negate()]));
+    // redundant, but they help propagation.
+    clauses.push(Clause::from(vec![then.negate(), otherwise.negate(), out]));
+    clauses.push(Clause::from(vec![then, otherwise, out.negate()]));
+    out
+}
+
+/// The sum and carry of two literals.
+pub fn half_adder(
+    a: Literal,
+    b: Literal,
+    vars: &mut VarAllocator,
+    clauses: &mut Vec<Clause>,
+) -> (Literal, Literal) {
+    let s = vars.fresh().positive();
+    let carry = vars.fresh().positive();
+    clauses.push(Clause::from(vec![a.negate(), b.negate(), s.negate()]));
+    clauses.push(Clause::from(vec![a, b, s.negate()]));
+    clauses.push(Clause::from(vec![a.negate(), b, s]));
+    clauses.push(Clause::from(vec![a, b.negate(), s]));
+    clauses.push(Clause::from(vec![a.negate(), b.negate(), carry]));
+    clauses.push(Clause::from(vec![a, carry.negate()]));
+    clauses.push(Clause::from(vec![b, carry.negate()]));
+    (s, carry)
+}
+
+/// The sum and carry of three literals.
+pub fn full_adder(
+    a: Literal,
+    b: Literal,
+    c: Literal,
+    vars: &mut VarAllocator,
+    clauses: &mut Vec<Clause>,
+) -> (Literal, Literal) {
+    let s = vars.fresh().positive();
+    let carry = vars.fresh().positive();
+    // s is the parity of a, b, and c.
+    for signs in 0..8 {
+        let lits: Vec<Literal> = [a, b, c]
+            .iter()
+            .enumerate()
+            .map(|(i, lit)| {
+                if signs & (1 << i) != 0 {
+                    lit.negate()
+                } else {
+                    *lit
+                }
+            })
+            .collect();
+        let odd = (signs as u32).count_ones() % 2 == 1;
+        let mut clause = lits;
+        clause.push(if odd { s } else { s.negate() });
+        clauses.push(Clause::from(clause));
+    }
+    // carry is the majority of a, b, and c.
+    for (x, y) in [(a, b), (a, c), (b, c)] {
+        clauses.push(Clause::from(vec![x.negate(), y.negate(), carry]));
+        clauses.push(Clause::from(vec![x, y, carry.negate()]));
+    }
+    (s, carry)
+}
+
+/// The sum of two numbers, with a ripple-carry adder.  The sum has
+/// one more bit than the longer input, unless an input is empty.
+pub fn add(
+    xs: &[Literal],
+    ys: &[Literal],
+    vars: &mut VarAllocator,
+    clauses: &mut Vec<Clause>,
+) -> Vec<Literal> {
+    let mut sum = Vec::with_capacity(xs.len().max(ys.len()) + 1);
+    let mut carry = None;
+    for i in 0..xs.len().max(ys.len()) {
+        let bits: Vec<Literal> = [xs.get(i).copied(), ys.get(i).copied(), carry]
+            .iter()
+            .flatten()
+            .copied()
+            .collect();
+        let (s, c) = match bits[..] {
+            [a, b, c] => {
+                let (s, c) = full_adder(a, b, c, vars, clauses);
+                (s, Some(c))
+            }
+            [a, b] => {
+                let (s, c) = half_adder(a, b, vars, clauses);
+                (s, Some(c))
+            }
+            _ => (bits[0], None),
+        };
+        sum.push(s);
+        carry = c;
+    }
+    sum.extend(carry);
+    sum
+}
+
+/// A literal which is true if the first number is less than the
+/// second, with a ripple comparator.
+pub fn less_than(
+    xs: &[Literal],
+    ys: &[Literal],
+    vars: &mut VarAllocator,
+    clauses: &mut Vec<Clause>,
+) -> Literal {
+    let (xs, ys) = pad(xs, ys, vars, clauses);
+    // whether the bits so far are less, from the least significant.
+    let mut less = None;
+    for (x, y) in xs.into_iter().zip(ys) {
+        let here = and(&[x.negate(), y], vars, clauses);
+        less = Some(match less {
+            None => here,
+            Some(below) => {
+                let equal = xor(x, y, vars, clauses).negate();
+                let still = and(&[equal, below], vars, clauses);
+                or(&[here, still], vars, clauses)
+            }
+        });
+    }
+    match less {
+        Some(less) => less,
+        None => constant(false, vars, clauses),
+    }
+}
+
+/// A literal which is true if the first number is at most the second.
+pub fn less_or_equal(
+    xs: &[Literal],
+    ys: &[Literal],
+    vars: &mut VarAllocator,
+    clauses: &mut Vec<Clause>,
+) -> Literal {
+    less_than(ys, xs, vars, clauses).negate()
+}
+
+/// A literal which is true if two numbers are equal.
+pub fn equal(
+    xs: &[Literal],
+    ys: &[Literal],
+    vars: &mut VarAllocator,
+    clauses: &mut Vec<Clause>,
+) -> Literal {
+    let (xs, ys) = pad(xs, ys, vars, clauses);
+    let bits: Vec<Literal> = xs
+        .into_iter()
+        .zip(ys)
+        .map(|(x, y)| xor(x, y, vars, clauses).negate())
+        .collect();
+    and(&bits, vars, clauses)
+}
+
+/// A multiplexer: the 'then' number if the condition is true, and the
+/// 'otherwise' number if not.
+pub fn mux(
+    condition: Literal,
+    then: &[Literal],
+    otherwise: &[Literal],
+    vars: &mut VarAllocator,
+    clauses: &mut Vec<Clause>,
+) -> Vec<Literal> {
+    let (then, otherwise) = pad(then, otherwise, vars, clauses);
+    then.into_iter()
+        .zip(otherwise)
+        .map(|(t, e)| ite(condition, t, e, vars, clauses))
+        .collect()
+}
+
+/// Pad two numbers with false bits to the same length.
+fn pad(
+    xs: &[Literal],
+    ys: &[Literal],
+    vars: &mut VarAllocator,
+    clauses: &mut Vec<Clause>,
+) -> (Vec<Literal>, Vec<Literal>) {
+    let (mut xs, mut ys) = (xs.to_vec(), ys.to_vec());
+    if xs.len() != ys.len() {
+        let f = constant(false, vars, clauses);
+        let len = xs.len().max(ys.len());
+        xs.resize(len, f);
+        ys.resize(len, f);
+    }
+    (xs, ys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cnf::*;
+
+    #[test]
+    fn encodes_arithmetic() {
+        // x has two bits, y has three, and 6 is the condition.
+        let xs: Vec<Literal> = (1..=2).map(Literal::new).collect();
+        let ys: Vec<Literal> = (3..=5).map(Literal::new).collect();
+        let condition = Literal::new(6);
+        let mut vars = VarAllocator::new();
+        vars.reserve(Var::new(6));
+        let mut clauses = Vec::new();
+        let sum = add(&xs, &ys, &mut vars, &mut clauses);
+        let lt = less_than(&xs, &ys, &mut vars, &mut clauses);
+        let le = less_or_equal(&xs, &ys, &mut vars, &mut clauses);
+        let eq = equal(&xs, &ys, &mut vars, &mut clauses);
+        let chosen = mux(condition, &xs, &ys, &mut vars, &mut clauses);
+
+        for bits in 0..1u32 << 6 {
+            let (x, y, c) = (bits & 3, (bits >> 2) & 7, bits & 32 != 0);
+            let mut formula = Formula::new(clauses.clone());
+            for id in 1..=6 {
+                let lit = Literal::new(id);
+                formula.insert_clause(Clause::from(vec![if bits & (1 << (id - 1)) != 0 {
+                    lit
+                } else {
+                    lit.negate()
+                }]));
+            }
+            let model = crate::sat_assignment(formula).unwrap();
+            let value = |lits: &[Literal]| {
+                lits.iter()
+                    .enumerate()
+                    .filter(|(_, lit)| model.contains(lit))
+                    .map(|(i, _)| 1 << i)
+                    .sum::<u32>()
+            };
+            assert_eq!(x + y, value(&sum));
+            assert_eq!(x < y, model.contains(&lt));
+            assert_eq!(x <= y, model.contains(&le));
+            assert_eq!(x == y, model.contains(&eq));
+            assert_eq!(if c { x } else { y }, value(&chosen));
+        }
+    }
+}
@@ -0,0 +1,299 @@
+//! Encodings of graph problems, with decoders which turn a model of
+//! the formula back into an answer about the graph.  Nodes are
+//! numbered as in 'Graph', and edge labels are ignored.
+
+use std::collections::HashSet;
+
+use crate::cnf::encodings::{at_least_k, at_most_one, Cardinality, Encoding};
+use crate::cnf::{Clause, Formula, Literal, VarAllocator};
+use crate::graph::Graph;
+
+/// A colouring of the nodes with k colours, where adjacent nodes have
+/// different colours.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub struct Colouring {
+    pub formula: Formula,
+    /// The atom of each colour of each node, indexed by node.
+    colours: Vec<Vec<Literal>>,
+}
+
+/// A set of k nodes, no two of which are adjacent.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub struct IndependentSet {
+    pub formula: Formula,
+    /// The atom of each node.
+    members: Vec<Literal>,
+}
+
+/// A set of k nodes, every two of which are adjacent.  In a directed
+/// graph, they must be adjacent in both directions.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub struct Clique {
+    pub formula: Formula,
+    /// The atom of each node.
+    members: Vec<Literal>,
+}
+
+/// A cycle which visits every node exactly once.  The cycle starts at
+/// node 0.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub struct HamiltonianCycle {
+    pub formula: Formula,
+    /// The atom of each position of each node, indexed by node.
+    positions: Vec<Vec<Literal>>,
+}
+
+impl Colouring {
+    /// Encode the colourings of a graph with k colours.
+    pub fn new(graph: &Graph, k: usize) -> Colouring {
+        let num_nodes = graph.nodes().len();
+        let mut vars = VarAllocator::new();
+        let colours: Vec<Vec<Literal>> = (0..num_nodes)
+            .map(|_| (0..k).map(|_| vars.fresh().positive()).collect())
+            .collect();
+
+        let mut clauses = Vec::new();
+        for node in &colours {
+            clauses.extend(exactly_one(node, &mut vars));
+        }
+        for (from, to, _) in graph.edges() {
+            for (u, v) in colours[*from].iter().zip(&colours[*to]) {
+                clauses.push(Clause::from(vec![u.negate(), v.negate()]));
+            }
+        }
+
+        Colouring {
+            formula: Formula::new(clauses),
+            colours,
+        }
+    }
+
+    /// Get the colour of each node, indexed by node.
+    pub fn decode(&self, model: &[Literal]) -> Vec<usize> {
+        let model: HashSet<Literal> = model.iter().copied().collect();
+        self.colours
+            .iter()
+            .map(|node| node.iter().position(|lit| model.contains(lit)).unwrap_or(0))
+            .collect()
+    }
+}
+
+impl IndependentSet {
+    /// Encode the independent sets of k nodes of a graph.
+    pub fn new(graph: &Graph, k: usize) -> IndependentSet {
+        let num_nodes = graph.nodes().len();
+        let mut vars = VarAllocator::new();
+        let members: Vec<Literal> = (0..num_nodes).map(|_| vars.fresh().positive()).collect();
+
+        let mut clauses = Vec::new();
+        for (from, to, _) in graph.edges() {
+            clauses.push(Clause::from(vec![
+                members[*from].negate(),
+                members[*to].negate(),
+            ]));
+        }
+        let (at_least, _) = at_least_k(&members, k, Cardinality::default(), &mut vars);
+        clauses.extend(at_least);
+
+        IndependentSet {
+            formula: Formula::new(clauses),
+            members,
+        }
+    }
+
+    /// Get the nodes in the set, in ascending order.
+    pub fn decode(&self, model: &[Literal]) -> Vec<usize> {
+        members_of(&self.members, model)
+    }
+}
+
+impl Clique {
+    /// Encode the cliques of k nodes of a graph.
+    pub fn new(graph: &Graph, k: usize) -> Clique {
+        let num_nodes = graph.nodes().len();
+        let adjacent = adjacency(graph);
+        let mut vars = VarAllocator::new();
+        let members: Vec<Literal> = (0..num_nodes).map(|_| vars.fresh().positive()).collect();
+
+        let mut clauses = Vec::new();
+        for u in 0..num_nodes {
+            for v in u + 1..num_nodes {
+                if !adjacent.contains(&(u, v)) || !adjacent.contains(&(v, u)) {
+                    clauses.push(Clause::from(vec![members[u].negate(), members[v].negate()]));
+                }
+            }
+        }
+        let (at_least, _) = at_least_k(&members, k, Cardinality::default(), &mut vars);
+        clauses.extend(at_least);
+
+        Clique {
+            formula: Formula::new(clauses),
+            members,
+        }
+    }
+
+    /// Get the nodes in the clique, in ascending order.
+    pub fn decode(&self, model: &[Literal]) -> Vec<usize> {
+        members_of(&self.members, model)
+    }
+}
+
+impl HamiltonianCycle {
+    /// Encode the Hamiltonian cycles of a graph.  In a directed graph
+    /// the cycle follows the direction of the edges.
+    pub fn new(graph: &Graph) -> HamiltonianCycle {
+        let num_nodes = graph.nodes().len();
+        let adjacent = adjacency(graph);
+        let mut vars = VarAllocator::new();
+        let positions: Vec<Vec<Literal>> = (0..num_nodes)
+            .map(|_| (0..num_nodes).map(|_| vars.fresh().positive()).collect())
+            .collect();
+
+        let mut clauses = Vec::new();
+        // every node has one position, and every position one node.
+        for node in &positions {
+            clauses.extend(exactly_one(node, &mut vars));
+        }
+        for i in 0..num_nodes {
+            let at: Vec<Literal> = positions.iter().map(|node| node[i]).collect();
+            clauses.extend(exactly_one(&at, &mut vars));
+        }
+        // the cycle can start anywhere, so it may as well start at 0.
+        if let Some(first) = positions.first() {
+            clauses.push(Clause::from(vec![first[0]]));
+        }
+        // consecutive nodes are adjacent.
+        for u in 0..num_nodes {
+            for v in 0..num_nodes {
+                if adjacent.contains(&(u, v)) {
+                    continue;
+                }
+                for i in 0..num_nodes {
+                    let next = (i + 1) % num_nodes;
+                    clauses.push(Clause::from(vec![
+                        positions[u][i].negate(),
+                        positions[v][next].negate(),
+                    ]));
+                }
+            }
+        }
+
+        HamiltonianCycle {
+            formula: Formula::new(clauses),
+            positions,
+        }
+    }
+
+    /// Get the nodes in the order they're visited.
+    pub fn decode(&self, model: &[Literal]) -> Vec<usize> {
+        let model: HashSet<Literal> = model.iter().copied().collect();
+        let mut order: Vec<(usize, usize)> = self
+            .positions
+            .iter()
+            .enumerate()
+            .map(|(node, at)| {
+                (
+                    at.iter().position(|lit| model.contains(lit)).unwrap_or(0),
+                    node,
+                )
+            })
+            .collect();
+        order.sort();
+        order.into_iter().map(|(_, node)| node).collect()
+    }
+}
+
+/// Encode that exactly one of the literals is true.
+fn exactly_one(lits: &[Literal], vars: &mut VarAllocator) -> Vec<Clause> {
+    let mut clauses = at_most_one(lits, Encoding::default(), vars);
+    clauses.push(Clause::from(lits.to_vec()));
+    clauses
+}
+
+/// The pairs of nodes joined by an edge, in both directions if the
+/// graph is undirected.
+fn adjacency(graph: &Graph) -> HashSet<(usize, usize)> {
+    let mut adjacent = HashSet::new();
+    for (from, to, _) in graph.edges() {
+        adjacent.insert((*from, *to));
+        if !graph.is_directed() {
+            adjacent.insert((*to, *from));
+        }
+    }
+    adjacent
+}
+
+/// The nodes whose atoms are true in the model.
+fn members_of(members: &[Literal], model: &[Literal]) -> Vec<usize> {
+    let model: HashSet<Literal> = model.iter().copied().collect();
+    members
+        .iter()
+        .enumerate()
+        .filter(|(_, lit)| model.contains(lit))
+        .map(|(node, _)| node)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An undirected graph with the given number of nodes and edges.
+    fn graph(num_nodes: usize, edges: &[(usize, usize)]) -> Graph {
+        let mut graph = Graph::undirected();
+        for i in 0..num_nodes {
+            graph.add_node(i.to_string());
+        }
+        for (from, to) in edges {
+            graph.add_edge(*from, *to, None);
+        }
+        graph
+    }
+
+    #[test]
+    fn colours_graphs() {
+        // a 5-cycle needs three colours.
+        let cycle = graph(5, &[(0, 1), (1, 2), (2, 3), (3, 4), (4, 0)]);
+        assert!(!crate::sat(Colouring::new(&cycle, 2).formula));
+
+        let colouring = Colouring::new(&cycle, 3);
+        let colours = colouring.decode(&crate::sat_assignment(colouring.formula.clone()).unwrap());
+        for (from, to, _) in cycle.edges() {
+            assert!(colours[*from] < 3);
+            assert_ne!(colours[*from], colours[*to]);
+        }
+    }
+
+    #[test]
+    fn finds_independent_sets_and_cliques() {
+        // a triangle with a tail: 0-1-2-0, 2-3.
+        let g = graph(4, &[(0, 1), (1, 2), (2, 0), (2, 3)]);
+
+        let independent = IndependentSet::new(&g, 2);
+        let set = independent.decode(&crate::sat_assignment(independent.formula.clone()).unwrap());
+        assert!(set.len() >= 2);
+        assert!(set.iter().all(|node| *node != 2));
+        assert!(!crate::sat(IndependentSet::new(&g, 3).formula));
+
+        let clique = Clique::new(&g, 3);
+        let set = clique.decode(&crate::sat_assignment(clique.formula.clone()).unwrap());
+        assert_eq!(vec![0, 1, 2], set);
+        assert!(!crate::sat(Clique::new(&g, 4).formula));
+    }
+
+    #[test]
+    fn finds_hamiltonian_cycles() {
+        let square = graph(4, &[(0, 1), (1, 2), (2, 3), (3, 0), (0, 2)]);
+        let cycle = HamiltonianCycle::new(&square);
+        let order = cycle.decode(&crate::sat_assignment(cycle.formula.clone()).unwrap());
+        assert_eq!(4, order.len());
+        assert_eq!(0, order[0]);
+        let adjacent = adjacency(&square);
+        for i in 0..4 {
+            assert!(adjacent.contains(&(order[i], order[(i + 1) % 4])));
+        }
+
+        let star = graph(4, &[(0, 1), (0, 2), (0, 3)]);
+        assert!(!crate::sat(HamiltonianCycle::new(&star).formula));
+    }
+}
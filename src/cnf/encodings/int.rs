@@ -0,0 +1,291 @@
+//! Bounded integer variables, encoded as literals, with constraints
+//! between them.  An integer variable takes a value from 0 up to its
+//! maximum.
+
+use std::collections::HashSet;
+
+use crate::cnf::encodings::{at_most_one, circuit, Encoding};
+use crate::cnf::{Clause, Literal, VarAllocator};
+
+/// Ways of encoding an integer variable.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub enum IntEncoding {
+    /// A literal for each value, exactly one of which is true: many
+    /// atoms, but constraints between one-hot variables propagate
+    /// completely.
+    OneHot,
+    /// A literal for each bit of the value, least significant first:
+    /// logarithmically many atoms, but constraints need circuits.
+    #[default]
+    Binary,
+}
+
+/// An integer variable.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub struct IntVar {
+    encoding: IntEncoding,
+    max: u64,
+    /// A literal for each value or bit, depending on the encoding.
+    lits: Vec<Literal>,
+}
+
+impl IntVar {
+    /// Construct an integer variable with values from 0 to 'max'.
+    /// New atoms are taken from 'vars', and the clauses restricting
+    /// the variable to its values are added to 'clauses'.
+    pub fn new(
+        max: u64,
+        encoding: IntEncoding,
+        vars: &mut VarAllocator,
+        clauses: &mut Vec<Clause>,
+    ) -> IntVar {
+        let lits: Vec<Literal> = match encoding {
+            IntEncoding::OneHot => (0..=max).map(|_| vars.fresh().positive()).collect(),
+            IntEncoding::Binary => (0..width(max)).map(|_| vars.fresh().positive()).collect(),
+        };
+        match encoding {
+            IntEncoding::OneHot => {
+                clauses.extend(at_most_one(&lits, Encoding::default(), vars));
+                clauses.push(Clause::from(lits.clone()));
+            }
+            IntEncoding::Binary => clauses.extend(at_most_constant(&lits, max)),
+        }
+        IntVar {
+            encoding,
+            max,
+            lits,
+        }
+    }
+
+    /// The largest value of the variable.
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    /// The literals of the variable: one for each value, or one for
+    /// each bit, depending on the encoding.
+    pub fn literals(&self) -> &[Literal] {
+        &self.lits
+    }
+
+    /// Clauses which make the variable equal to a value.
+    pub fn fix(&self, value: u64) -> Vec<Clause> {
+        if value > self.max {
+            return vec![Clause::from(Vec::new())];
+        }
+        match self.encoding {
+            IntEncoding::OneHot => vec![Clause::from(vec![self.lits[value as usize]])],
+            IntEncoding::Binary => self
+                .lits
+                .iter()
+                .enumerate()
+                .map(|(i, lit)| {
+                    Clause::from(vec![if value & (1 << i) != 0 {
+                        *lit
+                    } else {
+                        lit.negate()
+                    }])
+                })
+                .collect(),
+        }
+    }
+
+    /// Get the value of the variable in a model.  This is 'None' if
+    /// the model doesn't give a one-hot variable a value.
+    pub fn value(&self, model: &[Literal]) -> Option<u64> {
+        let model: HashSet<Literal> = model.iter().copied().collect();
+        match self.encoding {
+            IntEncoding::OneHot => self
+                .lits
+                .iter()
+                .position(|lit| model.contains(lit))
+                .map(|i| i as u64),
+            IntEncoding::Binary => Some(
+                self.lits
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, lit)| model.contains(lit))
+                    .map(|(i, _)| 1 << i)
+                    .sum(),
+            ),
+        }
+    }
+
+    /// The bits of the value, least significant first, defining them
+    /// if the variable is one-hot.
+    fn bits(&self, vars: &mut VarAllocator, clauses: &mut Vec<Clause>) -> Vec<Literal> {
+        match self.encoding {
+            IntEncoding::OneHot => (0..width(self.max))
+                .map(|bit| {
+                    let values: Vec<Literal> = (0..=self.max)
+                        .filter(|value| value & (1 << bit) != 0)
+                        .map(|value| self.lits[value as usize])
+                        .collect();
+                    circuit::or(&values, vars, clauses)
+                })
+                .collect(),
+            IntEncoding::Binary => self.lits.clone(),
+        }
+    }
+
+    /// The literal of a value of a one-hot variable, if it has one.
+    fn one_hot(&self, value: u64) -> Option<Literal> {
+        self.lits.get(value as usize).copied()
+    }
+
+    /// Check if both variables are one-hot.
+    fn both_one_hot(&self, other: &IntVar) -> bool {
+        self.encoding == IntEncoding::OneHot && other.encoding == IntEncoding::OneHot
+    }
+}
+
+/// Encode that x < y.
+pub fn less_than(x: &IntVar, y: &IntVar, vars: &mut VarAllocator) -> Vec<Clause> {
+    compare(x, y, true, vars)
+}
+
+/// Encode that x <= y.
+pub fn less_or_equal(x: &IntVar, y: &IntVar, vars: &mut VarAllocator) -> Vec<Clause> {
+    compare(x, y, false, vars)
+}
+
+/// Encode that x = y.
+pub fn equal(x: &IntVar, y: &IntVar, vars: &mut VarAllocator) -> Vec<Clause> {
+    let mut clauses = Vec::new();
+    if x.both_one_hot(y) {
+        // each value of one is a value of the other.
+        for (a, b) in [(x, y), (y, x)] {
+            for (value, lit) in a.lits.iter().enumerate() {
+                let mut clause = vec![lit.negate()];
+                clause.extend(b.one_hot(value as u64));
+                clauses.push(Clause::from(clause));
+            }
+        }
+    } else {
+        let xs = x.bits(vars, &mut clauses);
+        let ys = y.bits(vars, &mut clauses);
+        let eq = circuit::equal(&xs, &ys, vars, &mut clauses);
+        clauses.push(Clause::from(vec![eq]));
+    }
+    clauses
+}
+
+/// Encode that x + y = z.
+pub fn sum(x: &IntVar, y: &IntVar, z: &IntVar, vars: &mut VarAllocator) -> Vec<Clause> {
+    let mut clauses = Vec::new();
+    if x.both_one_hot(y) && z.encoding == IntEncoding::OneHot {
+        for (i, a) in x.lits.iter().enumerate() {
+            for (j, b) in y.lits.iter().enumerate() {
+                let mut clause = vec![a.negate(), b.negate()];
+                clause.extend(z.one_hot((i + j) as u64));
+                clauses.push(Clause::from(clause));
+            }
+        }
+    } else {
+        let xs = x.bits(vars, &mut clauses);
+        let ys = y.bits(vars, &mut clauses);
+        let zs = z.bits(vars, &mut clauses);
+        let total = circuit::add(&xs, &ys, vars, &mut clauses);
+        let eq = circuit::equal(&total, &zs, vars, &mut clauses);
+        clauses.push(Clause::from(vec![eq]));
+    }
+    clauses
+}
+
+/// Encode that x < y, or x <= y if not 'strict'.
+fn compare(x: &IntVar, y: &IntVar, strict: bool, vars: &mut VarAllocator) -> Vec<Clause> {
+    let mut clauses = Vec::new();
+    if x.both_one_hot(y) {
+        // rule out every pair of values in the wrong order.
+        for (i, a) in x.lits.iter().enumerate() {
+            for (j, b) in y.lits.iter().enumerate() {
+                if i > j || (strict && i == j) {
+                    clauses.push(Clause::from(vec![a.negate(), b.negate()]));
+                }
+            }
+        }
+    } else {
+        let xs = x.bits(vars, &mut clauses);
+        let ys = y.bits(vars, &mut clauses);
+        let holds = if strict {
+            circuit::less_than(&xs, &ys, vars, &mut clauses)
+        } else {
+            circuit::less_or_equal(&xs, &ys, vars, &mut clauses)
+        };
+        clauses.push(Clause::from(vec![holds]));
+    }
+    clauses
+}
+
+/// The number of bits needed for values up to 'max'.
+fn width(max: u64) -> u32 {
+    u64::BITS - max.leading_zeros()
+}
+
+/// Encode that a binary number is at most a constant: whenever it has
+/// a 1 where the constant has a 0, it must have a 0 at some higher bit
+/// where the constant has a 1.
+fn at_most_constant(bits: &[Literal], max: u64) -> Vec<Clause> {
+    let mut clauses = Vec::new();
+    for (i, bit) in bits.iter().enumerate() {
+        if max & (1 << i) != 0 {
+            continue;
+        }
+        let mut clause = vec![bit.negate()];
+        for (j, higher) in bits.iter().enumerate().skip(i + 1) {
+            if max & (1 << j) != 0 {
+                clause.push(higher.negate());
+            }
+        }
+        clauses.push(Clause::from(clause));
+    }
+    clauses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cnf::*;
+
+    #[test]
+    fn encodes_integer_constraints() {
+        let encodings = [IntEncoding::OneHot, IntEncoding::Binary];
+        for (ex, ey, ez) in encodings
+            .iter()
+            .flat_map(|ex| encodings.iter().map(move |ey| (*ex, *ey)))
+            .flat_map(|(ex, ey)| encodings.iter().map(move |ez| (ex, ey, *ez)))
+        {
+            let mut vars = VarAllocator::new();
+            let mut clauses = Vec::new();
+            let x = IntVar::new(3, ex, &mut vars, &mut clauses);
+            let y = IntVar::new(5, ey, &mut vars, &mut clauses);
+            let z = IntVar::new(6, ez, &mut vars, &mut clauses);
+            clauses.extend(less_than(&x, &y, &mut vars));
+            clauses.extend(sum(&x, &y, &z, &mut vars));
+
+            for a in 0..=4 {
+                for b in 0..=6 {
+                    let mut formula = Formula::new(clauses.clone());
+                    formula.extend(x.fix(a));
+                    formula.extend(y.fix(b));
+                    let holds = a <= 3 && b <= 5 && a < b && a + b <= 6;
+                    match crate::sat_assignment(formula) {
+                        Some(model) => {
+                            assert!(holds, "{:?} {:?} {:?} {} {}", ex, ey, ez, a, b);
+                            assert_eq!(Some(a), x.value(&model));
+                            assert_eq!(Some(b), y.value(&model));
+                            assert_eq!(Some(a + b), z.value(&model));
+                        }
+                        None => assert!(!holds, "{:?} {:?} {:?} {} {}", ex, ey, ez, a, b),
+                    }
+                }
+            }
+
+            // equality and ordering agree.
+            let mut formula = Formula::new(clauses.clone());
+            formula.extend(equal(&x, &z, &mut vars));
+            formula.extend(less_or_equal(&y, &x, &mut vars));
+            assert!(!crate::sat(formula));
+        }
+    }
+}
@@ -0,0 +1,315 @@
+//! Linear pseudo-Boolean constraints: a weighted sum of literals, each
+//! counting as 1 if true and 0 if false, is at most a bound.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::cnf::encodings::circuit::{full_adder, half_adder};
+use crate::cnf::{Clause, Literal, Var, VarAllocator};
+
+/// A constraint that the total weight of the true literals is at most
+/// the bound.
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub struct PbConstraint {
+    /// Weighted literals.  A literal can occur more than once.
+    pub terms: Vec<(u64, Literal)>,
+    pub bound: u64,
+}
+
+/// Ways of encoding a pseudo-Boolean constraint.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub enum PbEncoding {
+    /// A decision diagram over the literals, where each node is the
+    /// constraint on the remaining literals given the weight so far:
+    /// propagates completely, but can be exponentially big.
+    #[default]
+    Bdd,
+    /// Add up the weights in binary with a network of adders, and
+    /// compare the sum to the bound: always small, but propagates
+    /// less.
+    Adder,
+}
+
+/// A node of a decision diagram.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Node {
+    True,
+    False,
+    Lit(Literal),
+}
+
+impl PbConstraint {
+    /// Construct a constraint.
+    pub fn new(terms: Vec<(u64, Literal)>, bound: u64) -> PbConstraint {
+        PbConstraint { terms, bound }
+    }
+
+    /// Check if the constraint holds, given the truth of each literal.
+    pub fn holds<F: Fn(Literal) -> bool>(&self, value: F) -> bool {
+        // the total can be more than fits in a u64.
+        let total: u128 = self
+            .terms
+            .iter()
+            .filter(|(_, lit)| value(*lit))
+            .map(|(weight, _)| u128::from(*weight))
+            .sum();
+        total <= u128::from(self.bound)
+    }
+
+    /// Encode the constraint as clauses.  New atoms are taken from
+    /// 'vars', after reserving the atoms of the literals, and their
+    /// range is returned with the clauses.
+    pub fn encode(
+        &self,
+        encoding: PbEncoding,
+        vars: &mut VarAllocator,
+    ) -> (Vec<Clause>, Range<Var>) {
+        for (_, lit) in &self.terms {
+            vars.reserve(lit.var());
+        }
+        let first = vars.next_var();
+        // literals heavier than the bound must be false.
+        let mut clauses: Vec<Clause> = self
+            .terms
+            .iter()
+            .filter(|(weight, _)| *weight > self.bound)
+            .map(|(_, lit)| Clause::from(vec![lit.negate()]))
+            .collect();
+        let terms: Vec<(u64, Literal)> = self
+            .terms
+            .iter()
+            .copied()
+            .filter(|(weight, _)| *weight > 0 && *weight <= self.bound)
+            .collect();
+        match encoding {
+            PbEncoding::Bdd => bdd(&terms, self.bound, vars, &mut clauses),
+            PbEncoding::Adder => adder(&terms, self.bound, vars, &mut clauses),
+        }
+        (clauses, first..vars.next_var())
+    }
+}
+
+/// Encode with a decision diagram.
+fn bdd(terms: &[(u64, Literal)], bound: u64, vars: &mut VarAllocator, clauses: &mut Vec<Clause>) {
+    // the heaviest first keeps the diagram small.
+    let mut terms = terms.to_vec();
+    terms.sort_by(|a, b| b.cmp(a));
+    let mut suffix_sums = vec![0; terms.len() + 1];
+    for i in (0..terms.len()).rev() {
+        suffix_sums[i] = suffix_sums[i + 1] + u128::from(terms[i].0);
+    }
+
+    let mut nodes = HashMap::new();
+    match node(&terms, &suffix_sums, 0, bound, vars, clauses, &mut nodes) {
+        Node::True => (),
+        Node::False => clauses.push(Clause::from(Vec::new())),
+        Node::Lit(root) => clauses.push(Clause::from(vec![root])),
+    }
+}
+
+/// The node for the constraint that the terms from 'i' weigh at most
+/// 'budget': its literal implies the constraint.
+fn node(
+    terms: &[(u64, Literal)],
+    suffix_sums: &[u128],
+    i: usize,
+    budget: u64,
+    vars: &mut VarAllocator,
+    clauses: &mut Vec<Clause>,
+    nodes: &mut HashMap<(usize, u64), Node>,
+) -> Node {
+    if suffix_sums[i] <= u128::from(budget) {
+        return Node::True;
+    }
+    // paths which reach the same literal with the same budget left
+    // share a node.
+    if let Some(node) = nodes.get(&(i, budget)) {
+        return *node;
+    }
+
+    let (weight, lit) = terms[i];
+    let hi = if weight > budget {
+        Node::False
+    } else {
+        node(
+            terms,
+            suffix_sums,
+            i + 1,
+            budget - weight,
+            vars,
+            clauses,
+            nodes,
+        )
+    };
+    let lo = node(terms, suffix_sums, i + 1, budget, vars, clauses, nodes);
+    let n = vars.fresh().positive();
+    match hi {
+        Node::True => (),
+        Node::False => clauses.push(Clause::from(vec![n.negate(), lit.negate()])),
+        Node::Lit(hi) => clauses.push(Clause::from(vec![n.negate(), lit.negate(), hi])),
+    }
+    match lo {
+        Node::True => (),
+        Node::False => clauses.push(Clause::from(vec![n.negate()])),
+        Node::Lit(lo) => clauses.push(Clause::from(vec![n.negate(), lo])),
+    }
+    nodes.insert((i, budget), Node::Lit(n));
+    Node::Lit(n)
+}
+
+/// Encode with an adder network.
+fn adder(terms: &[(u64, Literal)], bound: u64, vars: &mut VarAllocator, clauses: &mut Vec<Clause>) {
+    // the literals to add at each bit position.
+    let mut buckets: Vec<Vec<Literal>> = vec![Vec::new(); 64];
+    for (weight, lit) in terms {
+        for (bit, bucket) in buckets.iter_mut().enumerate() {
+            if weight & (1 << bit) != 0 {
+                bucket.push(*lit);
+            }
+        }
+    }
+
+    // add up each position, carrying into the next, until there's
+    // at most one literal left: the bit of the sum.
+    let mut sum = Vec::new();
+    let mut bit = 0;
+    while bit < buckets.len() {
+        while buckets[bit].len() >= 2 {
+            let (s, carry) = if buckets[bit].len() >= 3 {
+                let c = buckets[bit].pop().unwrap();
+                let b = buckets[bit].pop().unwrap();
+                let a = buckets[bit].pop().unwrap();
+                full_adder(a, b, c, vars, clauses)
+            } else {
+                let b = buckets[bit].pop().unwrap();
+                let a = buckets[bit].pop().unwrap();
+                half_adder(a, b, vars, clauses)
+            };
+            buckets[bit].insert(0, s);
+            if bit + 1 == buckets.len() {
+                buckets.push(Vec::new());
+            }
+            buckets[bit + 1].push(carry);
+        }
+        sum.push(buckets[bit].pop());
+        bit += 1;
+    }
+
+    // the sum is more than the bound if, at the highest bit where
+    // they differ, the sum has a 1: rule that out for every bit where
+    // the bound has a 0.  A missing bit of the sum is a constant 0,
+    // and the bits of the bound past the 64th are all 0.
+    let bound_bit = |i: usize| i < 64 && bound & (1 << i) != 0;
+    'bits: for (i, s) in sum.iter().enumerate() {
+        let s = match s {
+            Some(s) if !bound_bit(i) => *s,
+            _ => continue,
+        };
+        let mut clause = vec![s.negate()];
+        for (j, t) in sum.iter().enumerate().skip(i + 1) {
+            if bound_bit(j) {
+                match t {
+                    Some(t) => clause.push(t.negate()),
+                    // the sum is less than the bound at this bit
+                    // already, so it can't be more at a lower one.
+                    None => continue 'bits,
+                }
+            }
+        }
+        clauses.push(Clause::from(clause));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cnf::*;
+
+    #[test]
+    fn encodes_pseudo_boolean_constraints() {
+        let lit = |l: isize| Literal::new(l);
+        let constraints = [
+            PbConstraint::new(vec![(3, lit(1)), (2, lit(2)), (2, lit(3)), (1, lit(4))], 4),
+            PbConstraint::new(vec![(5, lit(1)), (5, lit(-2)), (1, lit(3)), (7, lit(4))], 6),
+            PbConstraint::new(vec![(1, lit(1)), (1, lit(2)), (1, lit(3)), (1, lit(1))], 1),
+            PbConstraint::new(vec![(9, lit(1)), (0, lit(2)), (4, lit(3))], 3),
+            PbConstraint::new(vec![(2, lit(1)), (2, lit(2))], 0),
+            PbConstraint::new(vec![(2, lit(1)), (3, lit(2)), (6, lit(4))], 11),
+            PbConstraint::new(vec![(1, lit(1))], 2),
+            PbConstraint::new(vec![(1, lit(1)), (4, lit(-2))], 2),
+            PbConstraint::new(vec![(4, lit(1)), (2, lit(-2)), (1, lit(3))], 11),
+            PbConstraint::new(vec![(u64::MAX, lit(1)), (1, lit(2))], u64::MAX),
+            PbConstraint::new(
+                vec![(1 << 63, lit(1)), (1 << 63, lit(2)), (1, lit(3))],
+                1 << 63,
+            ),
+        ];
+        for constraint in &constraints {
+            for encoding in [PbEncoding::Bdd, PbEncoding::Adder] {
+                // not every constraint mentions all four atoms.
+                let mut vars = VarAllocator::new();
+                vars.reserve(Var::new(4));
+                let (clauses, aux) = constraint.encode(encoding, &mut vars);
+                assert_eq!(Var::new(5)..vars.next_var(), aux);
+                for bits in 0..1u32 << 4 {
+                    let value =
+                        |lit: Literal| (bits & (1 << (lit.get_id() - 1)) != 0) != lit.is_negated();
+                    let mut formula = Formula::new(clauses.clone());
+                    for id in 1..=4 {
+                        let lit = lit(id);
+                        formula.insert_clause(Clause::from(vec![if value(lit) {
+                            lit
+                        } else {
+                            lit.negate()
+                        }]));
+                    }
+                    assert_eq!(
+                        constraint.holds(value),
+                        crate::sat(formula),
+                        "{:?} {:?} {:b}",
+                        encoding,
+                        constraint,
+                        bits
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn encodes_all_small_pseudo_boolean_constraints() {
+        let lits = [Literal::new(1), Literal::new(-2), Literal::new(3)];
+        for weights in 0..5 * 5 * 5 {
+            let terms: Vec<(u64, Literal)> = (0..3)
+                .map(|i| (weights / 5u64.pow(i as u32) % 5, lits[i]))
+                .collect();
+            for bound in 0..=13 {
+                let constraint = PbConstraint::new(terms.clone(), bound);
+                for encoding in [PbEncoding::Bdd, PbEncoding::Adder] {
+                    let mut vars = VarAllocator::new();
+                    vars.reserve(Var::new(3));
+                    let (clauses, _) = constraint.encode(encoding, &mut vars);
+                    for bits in 0..1u32 << 3 {
+                        let value = |lit: Literal| {
+                            (bits & (1 << (lit.get_id() - 1)) != 0) != lit.is_negated()
+                        };
+                        let mut formula = Formula::new(clauses.clone());
+                        for id in 1..=3 {
+                            let lit = Literal::new(id);
+                            let lit = if value(lit) { lit } else { lit.negate() };
+                            formula.insert_clause(Clause::from(vec![lit]));
+                        }
+                        assert_eq!(
+                            constraint.holds(value),
+                            crate::sat(formula),
+                            "{:?} {:?} {:b}",
+                            encoding,
+                            constraint,
+                            bits
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
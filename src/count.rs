@@ -0,0 +1,236 @@
+//! Approximate model counting, as in ApproxMC.  A random xor
+//! constraint over the atoms of a formula is satisfied by about half of
+//! its models, so adding m of them leaves about 1/2^m of the models.
+//! If m is chosen so that few enough are left to count by enumeration,
+//! that count times 2^m estimates the total.  Taking the median of
+//! many estimates, from independent constraints, gives a count which
+//! is probably close to the true one.
+
+use crate::cnf::{Clause, Formula, Literal, VarAllocator};
+use crate::dpll::SolveResult;
+use crate::rng::Rng;
+use crate::solver::Solver;
+use crate::theory::empty::Empty;
+
+/// The default tolerance.
+const DEFAULT_EPSILON: f64 = 0.8;
+
+/// The default confidence.
+const DEFAULT_DELTA: f64 = 0.2;
+
+/// Options for approximate model counting.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct ApproxMc {
+    /// The tolerance: the count is within a factor of 1 + epsilon of
+    /// the true count, with probability at least 1 - delta.
+    pub epsilon: f64,
+    /// The confidence, from 0 to 1: the chance of the count being
+    /// outside the tolerance is at most this.
+    pub delta: f64,
+    /// The seed for the random xor constraints.
+    pub seed: u64,
+}
+
+impl Default for ApproxMc {
+    fn default() -> ApproxMc {
+        ApproxMc {
+            epsilon: DEFAULT_EPSILON,
+            delta: DEFAULT_DELTA,
+            seed: 0,
+        }
+    }
+}
+
+impl ApproxMc {
+    /// Estimate the number of models of a formula, as assignments to
+    /// the atoms which occur in it.  If there are few enough, they are
+    /// counted exactly.
+    pub fn count(&self, formula: &Formula) -> u128 {
+        let mut atoms: Vec<Literal> = formula
+            .iter()
+            .flatten()
+            .map(|lit| Literal::new(lit.get_id()))
+            .collect();
+        atoms.sort();
+        atoms.dedup();
+        let vars = VarAllocator::above(formula);
+        let threshold = self.threshold();
+        let mut solver = Solver::new(Empty::new(), formula.clone());
+
+        let exact = count_up_to(&mut solver, &atoms, threshold);
+        if exact < threshold {
+            return exact as u128;
+        }
+
+        let iterations = (17.0 * (3.0 / self.delta).log2()).ceil() as usize;
+        let mut rng = Rng::new(self.seed);
+        let mut estimates = Vec::with_capacity(iterations);
+        let mut m = 1;
+        for _ in 0..iterations {
+            let xors: Vec<(Vec<Literal>, bool)> = (0..atoms.len())
+                .map(|_| {
+                    let lits = atoms.iter().copied().filter(|_| rng.next_bool()).collect();
+                    (lits, rng.next_bool())
+                })
+                .collect();
+            let mut cell = |m: usize| {
+                solver.push();
+                let mut vars = vars;
+                for (lits, parity) in &xors[..m] {
+                    for clause in xor_clauses(lits, *parity, &mut vars) {
+                        solver.add_clause(clause);
+                    }
+                }
+                let count = count_up_to(&mut solver, &atoms, threshold);
+                solver.pop();
+                count
+            };
+
+            // start from the number of constraints which worked last
+            // time, as it probably works again.
+            m = m.min(atoms.len());
+            let mut count = cell(m);
+            if count < threshold {
+                while m > 1 {
+                    let fewer = cell(m - 1);
+                    if fewer >= threshold {
+                        break;
+                    }
+                    m -= 1;
+                    count = fewer;
+                }
+            } else {
+                while count >= threshold && m < atoms.len() {
+                    m += 1;
+                    count = cell(m);
+                }
+            }
+            if count < threshold {
+                let scale = 1u128.checked_shl(m as u32).unwrap_or(u128::MAX);
+                estimates.push((count as u128).saturating_mul(scale));
+            }
+        }
+
+        if estimates.is_empty() {
+            return 0;
+        }
+        estimates.sort_unstable();
+        estimates[estimates.len() / 2]
+    }
+
+    /// The most models in a cell which are counted by enumeration.
+    fn threshold(&self) -> usize {
+        let epsilon = self.epsilon;
+        let bound = 1.0
+            + 9.84
+                * (1.0 + epsilon / (1.0 + epsilon))
+                * (1.0 + 1.0 / epsilon)
+                * (1.0 + 1.0 / epsilon);
+        bound.ceil() as usize
+    }
+}
+
+/// Count the models of the solver's formula, as assignments to the
+/// given atoms, stopping at 'limit'.  The clauses ruling out each
+/// model are removed afterwards.
+fn count_up_to(solver: &mut Solver<Empty>, atoms: &[Literal], limit: usize) -> usize {
+    solver.push();
+    let mut count = 0;
+    while count < limit {
+        match solver.solve() {
+            Ok(SolveResult::Sat(model)) => {
+                let blocking: Clause = model
+                    .into_iter()
+                    .filter(|lit| atoms.binary_search(&Literal::new(lit.get_id())).is_ok())
+                    .map(|lit| lit.negate())
+                    .collect();
+                solver.add_clause(blocking);
+                count += 1;
+            }
+            // the empty theory never fails.
+            _ => break,
+        }
+    }
+    solver.pop();
+    count
+}
+
+/// Encode that an odd number of the literals are true, if 'parity' is
+/// true, or an even number if not, using new atoms from 'vars' for
+/// the intermediate results.
+fn xor_clauses(lits: &[Literal], parity: bool, vars: &mut VarAllocator) -> Vec<Clause> {
+    let (first, rest) = match lits.split_first() {
+        Some(split) => split,
+        None if parity => return vec![Clause::from(Vec::new())],
+        None => return Vec::new(),
+    };
+
+    let mut clauses = Vec::new();
+    let mut acc = *first;
+    for lit in rest {
+        // t = acc ^ lit
+        let t = vars.fresh().positive();
+        clauses.push(Clause::from(vec![t.negate(), acc, *lit]));
+        clauses.push(Clause::from(vec![t.negate(), acc.negate(), lit.negate()]));
+        clauses.push(Clause::from(vec![t, acc.negate(), *lit]));
+        clauses.push(Clause::from(vec![t, acc, lit.negate()]));
+        acc = t;
+    }
+    clauses.push(Clause::from(vec![if parity { acc } else { acc.negate() }]));
+    clauses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cnf::*;
+
+    #[test]
+    fn counts_small_formulae_exactly() {
+        let approxmc = ApproxMc::default();
+        assert_eq!(
+            7,
+            approxmc.count(&Formula::new(vec![Clause::new(vec![1, 2, 3])]))
+        );
+        assert_eq!(
+            0,
+            approxmc.count(&Formula::new(vec![
+                Clause::new(vec![1]),
+                Clause::new(vec![-1])
+            ]))
+        );
+    }
+
+    #[test]
+    fn estimates_large_counts() {
+        // 2^10 - 1 models.
+        let formula = Formula::new(vec![Clause::new((1..=10).collect())]);
+        let approxmc = ApproxMc {
+            delta: 0.5,
+            ..ApproxMc::default()
+        };
+        let count = approxmc.count(&formula) as f64;
+        let exact = 1023.0;
+        assert!(count >= exact / (1.0 + approxmc.epsilon));
+        assert!(count <= exact * (1.0 + approxmc.epsilon));
+    }
+
+    #[test]
+    fn encodes_xors() {
+        for parity in [false, true] {
+            let lits: Vec<Literal> = (1..=3).map(Literal::new).collect();
+            let mut vars = VarAllocator::new();
+            vars.reserve(Var::new(3));
+            let formula = Formula::new(xor_clauses(&lits, parity, &mut vars));
+            let mut models: Vec<Vec<Literal>> = crate::all_models(formula)
+                .map(|model| model.into_iter().filter(|lit| lit.get_id() <= 3).collect())
+                .collect();
+            models.sort();
+            assert_eq!(4, models.len());
+            for model in models {
+                let odd = model.iter().filter(|lit| !lit.is_negated()).count() % 2 == 1;
+                assert_eq!(parity, odd);
+            }
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//! Cube and conquer: split a formula into cubes, partial assignments
+//! which between them cover every model, and then solve the formula
+//! under each cube in turn.  The splitting is done by lookahead,
+//! which is slow but makes good choices, so the cubes are much easier
+//! than the whole formula.  This helps on very hard combinatorial
+//! problems, where a single search gets lost.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::cnf::{Formula, Literal};
+use crate::dpll::{search, Config, Control, Outcome, Snapshot, SolveResult, SolverError};
+use crate::lookahead::Lookahead;
+use crate::theory::Theory;
+
+/// The default number of decisions in a cube.
+const DEFAULT_DEPTH: usize = 6;
+
+/// Options for cube and conquer.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct CubeAndConquer {
+    /// The most decisions in a cube, so there are at most 2^depth
+    /// cubes.
+    pub depth: usize,
+    /// The number of threads to solve cubes on.  With one thread, the
+    /// cubes are solved in order.
+    pub threads: usize,
+    /// The configuration of the search of each cube.
+    pub config: Config,
+}
+
+impl Default for CubeAndConquer {
+    fn default() -> CubeAndConquer {
+        CubeAndConquer {
+            depth: DEFAULT_DEPTH,
+            threads: 1,
+            config: Config::default(),
+        }
+    }
+}
+
+impl CubeAndConquer {
+    /// Split a formula into cubes.  Every model of the formula
+    /// satisfies at least one of the cubes, and cubes which unit
+    /// propagation refutes are left out, so if there are none the
+    /// formula is unsatisfiable.
+    pub fn cubes(&self, formula: &Formula) -> Vec<Vec<Literal>> {
+        let mut lookahead = Lookahead::new(formula);
+        let mut cubes = Vec::new();
+        if lookahead.assign_units() {
+            split(&mut lookahead, self.depth, &mut Vec::new(), &mut cubes);
+        }
+        cubes
+    }
+
+    /// Find a model which satisfies the formula, if one exists, by
+    /// solving it under each cube until one is satisfiable.  If a cube
+    /// runs out of budget and none are satisfiable, the result is
+    /// unknown.
+    pub fn solve<T: Theory + Clone + Send>(
+        &self,
+        theory: &T,
+        formula: &Formula,
+    ) -> Result<SolveResult, SolverError> {
+        let cubes = self.cubes(formula);
+        let next = AtomicUsize::new(0);
+        let interrupt = AtomicBool::new(false);
+        let unknown = AtomicBool::new(false);
+        let answer = Mutex::new(None);
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads.max(1) {
+                let mut theory = theory.clone();
+                let (cubes, next, interrupt, unknown, answer) =
+                    (&cubes, &next, &interrupt, &unknown, &answer);
+
+                scope.spawn(move || {
+                    while let Some(cube) = cubes.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        let control = Control {
+                            interrupt: Some(interrupt),
+                            ..Control::default()
+                        };
+                        let result = match search(
+                            &mut theory,
+                            formula.clone(),
+                            Snapshot::default(),
+                            cube,
+                            &self.config,
+                            control,
+                        )
+                        .outcome
+                        {
+                            Outcome::Sat(model) => Ok(SolveResult::Sat(model.get_assignments())),
+                            Outcome::Failed(e) => Err(e),
+                            Outcome::Unsat => continue,
+                            Outcome::OutOfBudget => {
+                                unknown.store(true, Ordering::Relaxed);
+                                continue;
+                            }
+                            // another cube is satisfiable.
+                            Outcome::Interrupted => return,
+                        };
+
+                        answer.lock().unwrap().get_or_insert(result);
+                        interrupt.store(true, Ordering::Relaxed);
+                        return;
+                    }
+                });
+            }
+        });
+
+        match answer.into_inner().unwrap() {
+            Some(result) => result,
+            None if unknown.into_inner() => Ok(SolveResult::Unknown),
+            None => Ok(SolveResult::Unsat),
+        }
+    }
+}
+
+/// Split on the best atom until the cube has 'depth' decisions or
+/// there's nothing left to split on, collecting the cubes which
+/// aren't refuted.
+fn split(
+    lookahead: &mut Lookahead,
+    depth: usize,
+    cube: &mut Vec<Literal>,
+    cubes: &mut Vec<Vec<Literal>>,
+) {
+    let len = lookahead.len();
+    let branch = if depth == 0 {
+        Some(None)
+    } else {
+        lookahead.look_ahead()
+    };
+
+    if let Some(branch) = branch {
+        match branch {
+            None => cubes.push(cube.clone()),
+            Some(lit) => {
+                for lit in [lit, lit.negate()] {
+                    let before = lookahead.len();
+                    if lookahead.assign(lit) {
+                        cube.push(lit);
+                        split(lookahead, depth - 1, cube, cubes);
+                        cube.pop();
+                    }
+                    lookahead.undo(before);
+                }
+            }
+        }
+    }
+
+    lookahead.undo(len);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cnf::*;
+    use crate::dpll::dpll;
+    use crate::rng::Rng;
+    use crate::testing::{is_model, random_formula};
+    use crate::theory::empty::Empty;
+
+    fn pigeonhole(n: isize) -> Formula {
+        // n+1 pigeons in n holes: pigeon p is in hole h if p*n+h.
+        let mut clauses = Vec::new();
+        for p in 0..=n {
+            clauses.push(Clause::new((1..=n).map(|h| p * n + h).collect()));
+        }
+        for h in 1..=n {
+            for p in 0..=n {
+                for q in p + 1..=n {
+                    clauses.push(Clause::new(vec![-(p * n + h), -(q * n + h)]));
+                }
+            }
+        }
+        Formula::new(clauses)
+    }
+
+    #[test]
+    fn solves_pigeonhole() {
+        let mut cnc = CubeAndConquer::default();
+        assert!(cnc.cubes(&pigeonhole(5)).len() > 1);
+        assert_eq!(
+            Ok(SolveResult::Unsat),
+            cnc.solve(&Empty::new(), &pigeonhole(5))
+        );
+
+        cnc.threads = 4;
+        assert_eq!(
+            Ok(SolveResult::Unsat),
+            cnc.solve(&Empty::new(), &pigeonhole(5))
+        );
+    }
+
+    #[test]
+    fn cubes_cover_every_model() {
+        let mut rng = Rng::new(0xda94_2042_e4dd_58b5);
+
+        let cnc = CubeAndConquer {
+            depth: 3,
+            ..CubeAndConquer::default()
+        };
+        for _ in 0..200 {
+            let num_vars = 1 + rng.below(8);
+            let formula = random_formula(&mut rng, num_vars, 3 * num_vars, 3);
+
+            let cubes = cnc.cubes(&formula);
+            for bits in 0..1 << num_vars {
+                let truth =
+                    |lit: &Literal| ((bits >> (lit.get_id() - 1)) & 1 == 1) != lit.is_negated();
+                if formula.iter().all(|lits| lits.iter().any(truth)) {
+                    assert!(cubes.iter().any(|cube| cube.iter().all(truth)));
+                }
+            }
+
+            let expected = dpll(&mut Empty::new(), formula.clone()).unwrap();
+            match cnc.solve(&Empty::new(), &formula).unwrap() {
+                SolveResult::Sat(lits) => {
+                    assert!(expected.is_sat());
+                    assert!(is_model(&formula, &lits));
+                }
+                SolveResult::Unsat => assert!(expected.is_unsat()),
+                SolveResult::Unknown => unreachable!(),
+            }
+        }
+    }
+}
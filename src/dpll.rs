@@ -1,21 +1,40 @@
 //! A basic SAT solver based on the paper "Abstract DPLL and Abstract
-//! DPLL Modulo Theories"
+//! DPLL Modulo Theories", extended with conflict analysis and clause
+//! learning.
+
+pub mod clauses;
+pub mod conflict;
+pub mod heuristic;
+pub mod restart;
+pub mod trace;
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 use crate::cnf::*;
-use crate::theory::Theory;
+use crate::dpll::clauses::ClauseDb;
+use crate::dpll::heuristic::{Heuristic, Vmtf};
+use crate::dpll::restart::{RestartPolicy, Restarts};
+use crate::dpll::trace::Transition;
+use crate::proof::Proof;
+use crate::rng::Rng;
+use crate::sls::WalkSat;
+use crate::theory::{Theory, TheoryError};
+use crate::verify;
 
 impl Literal {
     /// A literal is true in a model if it's a member of the set.
     /// This will return 'None' if the model doesn't have an
     /// assignment of truth for the literal or its negation.
     pub fn is_true_in(self, model: &Model) -> Option<bool> {
-        if model.contains(self) {
-            Some(true)
-        } else if model.contains(self.negate()) {
-            Some(false)
-        } else {
-            None
-        }
+        model
+            .value(self.var())
+            .map(|value| value != self.is_negated())
     }
 }
 
@@ -24,23 +43,28 @@ impl Clause {
     /// the model.  This will return 'None' if none of the literals
     /// have their truth decided by the model.
     pub fn is_true_in(&self, model: &Model) -> Option<bool> {
-        let Clause(lits) = self;
-        let mut all_false = true;
+        is_true_in(self.literals(), model)
+    }
+}
 
-        for lit in lits {
-            match lit.is_true_in(model) {
-                Some(true) => return Some(true),
-                Some(false) => continue,
-                None => all_false = false,
-            }
-        }
+/// Check if the literals of a clause make it true in a model, as with
+/// 'Clause::is_true_in'.
+fn is_true_in(lits: &[Literal], model: &Model) -> Option<bool> {
+    let mut all_false = true;
 
-        if all_false {
-            Some(false)
-        } else {
-            None
+    for lit in lits {
+        match lit.is_true_in(model) {
+            Some(true) => return Some(true),
+            Some(false) => continue,
+            None => all_false = false,
         }
     }
+
+    if all_false {
+        Some(false)
+    } else {
+        None
+    }
 }
 
 impl Formula {
@@ -48,10 +72,8 @@ impl Formula {
     /// the model.  This will return 'None' if at least one of the
     /// clauses doesn't have its truth determined by the model.
     pub fn is_true_in(&self, model: &Model) -> Option<bool> {
-        let Formula(clauses) = self;
-
-        for clause in clauses {
-            match clause.is_true_in(model) {
+        for lits in self.iter() {
+            match is_true_in(lits, model) {
                 Some(true) => continue,
                 Some(false) => return Some(false),
                 None => return None,
@@ -63,187 +85,1769 @@ impl Formula {
 }
 
 /// A model, or partial truth assignment, is a set of literals which
-/// are true.  Implemented as a vec because the DPLL algorithm makes
-/// use of the order of assignments when backtracking.
-#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
-pub struct Model(Vec<(Literal, Provenance)>);
+/// are true.  Implemented as a trail of assignments, in order,
+/// because the DPLL algorithm makes use of the order of assignments
+/// when backtracking, along with the truth of each atom, so looking
+/// up a literal takes constant time.
+#[derive(Clone, Debug, Default)]
+pub struct Model {
+    trail: Vec<Assignment>,
+    /// The truth of each atom, indexed by atom.
+    values: Vec<Option<bool>>,
+}
+
+// the values are determined by the trail, so only compare that.
+impl PartialEq for Model {
+    fn eq(&self, other: &Model) -> bool {
+        self.trail == other.trail
+    }
+}
+
+impl Eq for Model {}
+
+impl PartialOrd for Model {
+    fn partial_cmp(&self, other: &Model) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Model {
+    fn cmp(&self, other: &Model) -> std::cmp::Ordering {
+        self.trail.cmp(&other.trail)
+    }
+}
+
+/// A literal in a model, why it was assigned, and the decision level
+/// it was assigned at: the number of decisions up to and including
+/// it.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord)]
+struct Assignment {
+    lit: Literal,
+    provenance: Provenance,
+    level: usize,
+}
 
 /// Literals in a model track where they've come from: this is because
 /// backtracking is done in terms of literals arising from decisions.
-#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+/// Unit propagation records the index of the clause which became
+/// unit, for analysing conflicts.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord)]
 enum Provenance {
-    UnitPropagation,
+    UnitPropagation(usize),
     TheoryPropagation,
+    PureLiteral,
     Decision,
-    Backjump,
 }
 
 impl Model {
     /// Construct a new empty model.
-    fn new() -> Model {
-        Model(vec![])
+    pub(crate) fn new() -> Model {
+        Model::default()
     }
 
-    /// Append a literal to a model.
-    fn append(&mut self, lit: Literal, provenance: Provenance) {
-        let Model(lits) = self;
-        lits.push((lit, provenance));
+    /// Construct a new empty model with room for the atoms 1 to
+    /// 'num_atoms'.
+    fn with_atoms(num_atoms: usize) -> Model {
+        Model {
+            trail: Vec::with_capacity(num_atoms),
+            values: vec![None; num_atoms + 1],
+        }
     }
 
-    /// Check if the model contains a literal.
-    fn contains(&self, lit: Literal) -> bool {
-        let Model(lits) = self;
+    /// Render the model as lines of text, one per literal, so that a
+    /// search can be saved and resumed later.
+    pub(crate) fn to_lines(&self) -> Vec<String> {
+        self.trail
+            .iter()
+            .map(
+                |Assignment {
+                     lit, provenance, ..
+                 }| match provenance {
+                    Provenance::UnitPropagation(reason) => format!("u {} {}", lit, reason),
+                    Provenance::TheoryPropagation => format!("t {}", lit),
+                    Provenance::PureLiteral => format!("p {}", lit),
+                    Provenance::Decision => format!("d {}", lit),
+                },
+            )
+            .collect()
+    }
 
-        for (l, _) in lits {
-            if *l == lit {
-                return true;
+    /// Parse a model from lines of text, as produced by 'to_lines'.
+    /// Returns the first line which couldn't be parsed on failure.
+    pub(crate) fn from_lines<'a, I>(lines: I) -> Result<Model, String>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut model = Model::new();
+        for line in lines {
+            let mut words = line.split_ascii_whitespace();
+            let tag = words.next();
+            let lit = match words.next().map(|w| w.parse::<isize>()) {
+                Some(Ok(atom)) if atom != 0 => Literal::new(atom),
+                _ => return Err(line.to_string()),
+            };
+            let provenance = match (tag, words.next().map(|w| w.parse::<usize>())) {
+                (Some("u"), Some(Ok(reason))) => Provenance::UnitPropagation(reason),
+                (Some("t"), None) => Provenance::TheoryPropagation,
+                (Some("p"), None) => Provenance::PureLiteral,
+                (Some("d"), None) => Provenance::Decision,
+                _ => return Err(line.to_string()),
+            };
+            if words.next().is_some() {
+                return Err(line.to_string());
             }
+            model.append(lit, provenance);
         }
+        Ok(model)
+    }
 
-        false
+    /// The number of decisions in the model.
+    fn decision_level(&self) -> usize {
+        self.trail.last().map_or(0, |a| a.level)
+    }
+
+    /// Append a literal to a model.  Decisions start a new level.
+    fn append(&mut self, lit: Literal, provenance: Provenance) {
+        let level = if provenance == Provenance::Decision {
+            self.decision_level() + 1
+        } else {
+            self.decision_level()
+        };
+        let id = lit.get_id() as usize;
+        if id >= self.values.len() {
+            self.values.resize(id + 1, None);
+        }
+        self.values[id] = Some(!lit.is_negated());
+        self.trail.push(Assignment {
+            lit,
+            provenance,
+            level,
+        });
+    }
+
+    /// Remove the last literal from a model.
+    fn pop(&mut self) -> Option<Assignment> {
+        let a = self.trail.pop()?;
+        self.values[a.lit.get_id() as usize] = None;
+        Some(a)
+    }
+
+    /// The literals assigned before any decision, other than pure
+    /// literals: these are implied by the formula and the theory.
+    pub(crate) fn fixed(&self) -> impl Iterator<Item = Literal> + '_ {
+        self.trail
+            .iter()
+            .take_while(|a| a.level == 0)
+            .filter(|a| a.provenance != Provenance::PureLiteral)
+            .map(|a| a.lit)
+    }
+
+    /// Get the truth of a variable in the model, if it's assigned.
+    pub fn value(&self, var: Var) -> Option<bool> {
+        self.values.get(var.index()).copied().flatten()
+    }
+
+    /// Iterate over the true literals in the model, in the order they
+    /// were assigned.
+    pub fn iter(&self) -> impl Iterator<Item = Literal> + '_ {
+        self.trail.iter().map(|a| a.lit)
     }
 
     /// Get the true literals from the model, discarding the
     /// provenance information.
     pub fn get_assignments(&self) -> Vec<Literal> {
-        let Model(lits) = self;
-        lits.iter().map(|(l, _)| *l).collect()
+        self.iter().collect()
+    }
+
+    /// Get the truth of each named atom in the model.
+    pub fn to_names(&self, vars: &VarMap) -> BTreeMap<String, bool> {
+        vars.assignment(&self.get_assignments())
+    }
+
+    /// Get a total assignment of the atoms 1 to 'num_vars', in order,
+    /// as true literals.  Atoms the model doesn't assign are false.
+    /// Atoms above 'num_vars' which the model does assign are kept.
+    pub fn complete(&self, num_vars: usize) -> Vec<Literal> {
+        let max = self
+            .iter()
+            .map(|l| l.get_id() as usize)
+            .fold(num_vars, usize::max);
+        let mut values = vec![None; max + 1];
+        for l in self.iter() {
+            values[l.get_id() as usize] = Some(l);
+        }
+        (1..=max)
+            .filter_map(|id| match values[id] {
+                Some(lit) => Some(lit),
+                None if id <= num_vars => Some(Literal::new(-(id as isize))),
+                None => None,
+            })
+            .collect()
+    }
+}
+
+/// The index of a literal in tables with an entry for each literal.
+pub(crate) fn lit_index(lit: Literal) -> usize {
+    let id = lit.get_id() as usize;
+    if lit.is_negated() {
+        2 * id + 1
+    } else {
+        2 * id
     }
 }
 
-fn do_backjump(model: &mut Model) -> bool {
-    // this is a simpler version than in the paper (what it calls
-    // "standard backtracking") because I'm not sure how to implement
-    // `F |= C \/ l'`
-    let Model(lits) = model;
+/// The state of a search in progress.
+///
+/// Unit propagation uses two watched literals: the first two
+/// literals of every clause (of length two or more) are watched, and
+/// a clause only needs looking at when one of its watched literals
+/// becomes false.  Then either another literal can be watched
+/// instead, or the clause is unit (or false) in the model.
+struct Search<'a, T: ?Sized> {
+    theory: &'a mut T,
+    /// The clause database: the original clauses followed by the
+    /// learned clauses.
+    clauses: ClauseDb,
+    num_original: usize,
+    model: Model,
+    /// Whether each atom occurs in the formula, indexed by atom.
+    occurs: Vec<bool>,
+    /// Where in the model each atom was assigned, indexed by atom.
+    positions: Vec<usize>,
+    /// The last truth value each atom had, indexed by atom.  When an
+    /// atom is decided, it gets its saved phase again, so the search
+    /// stays close to the assignments it was exploring before a
+    /// backjump.
+    phases: Vec<bool>,
+    /// The clauses watching each literal, indexed by 'lit_index'.
+    watches: Vec<Vec<usize>>,
+    /// How much of the model has been unit propagated.
+    propagated: usize,
+    /// Literals which are decided, in order, before any others.
+    assumptions: Vec<Literal>,
+    /// The assumptions which made the formula unsatisfiable, if it's
+    /// only unsatisfiable with them.
+    core: Vec<Literal>,
+    heuristic: Heuristic,
+    /// The queue for the VMTF heuristic, if it's being used.
+    vmtf: Option<Vmtf>,
+    /// Which atoms are gate outputs, indexed by atom, if the inputs
+    /// heuristic is being used.
+    outputs: Vec<bool>,
+    /// The fraction of decisions to make at random.
+    random_decisions: f64,
+    rng: Rng,
+    /// The proof being recorded, if any.
+    proof: Option<Proof>,
+    /// The number in the proof of each clause, indexed by clause.
+    proof_ids: Vec<usize>,
+    /// The number in the proof of the unit clause of each atom
+    /// assigned at level 0, if it's been derived, indexed by atom.
+    unit_proof_ids: Vec<Option<usize>>,
+    /// The transitions made so far, if they're being recorded.
+    trace: Option<Vec<Transition>>,
+    /// The first error from the theory, which ends the search.
+    error: Option<TheoryError>,
+    /// The clause learned from each theory explanation, by its sorted
+    /// literals, so it's only learned once.
+    lemmas: HashMap<Vec<Literal>, usize>,
+    /// Whether pure literals can be assigned.  They aren't implied by
+    /// the formula, so clauses learned from them can't be used by
+    /// another search.
+    pure_literals: bool,
+    stats: Stats,
+}
+
+impl<'a, T: Theory + ?Sized> Search<'a, T> {
+    /// Set up the clause database and watches, and replay the model
+    /// being resumed from.
+    fn new(
+        theory: &'a mut T,
+        formula: Formula,
+        start: Snapshot,
+        assumptions: &[Literal],
+        config: &Config,
+    ) -> Search<'a, T> {
+        // the initial phases and the gates only depend on the
+        // original clauses.
+        let walked = match &config.walk {
+            Some(walksat) if start.phases.is_empty() => Some(walksat.walk(&formula).0),
+            _ => None,
+        };
+        let gates = if config.heuristic == Heuristic::Inputs {
+            formula.gates()
+        } else {
+            Vec::new()
+        };
+
+        let num_original = formula.len();
+        let num_atoms = formula
+            .iter()
+            .chain(start.learned.iter().map(Clause::literals))
+            .flatten()
+            .map(|lit| lit.get_id() as usize)
+            .chain(assumptions.iter().map(|lit| lit.get_id() as usize))
+            .max()
+            .unwrap_or(0);
+        let num_lits = formula
+            .iter()
+            .chain(start.learned.iter().map(Clause::literals))
+            .map(<[Literal]>::len)
+            .sum();
+
+        let mut search = Search {
+            theory,
+            clauses: ClauseDb::with_capacity(num_original + start.learned.len(), num_lits),
+            num_original,
+            model: Model::with_atoms(num_atoms),
+            occurs: vec![false; num_atoms + 1],
+            positions: vec![0; num_atoms + 1],
+            phases: vec![true; num_atoms + 1],
+            watches: vec![Vec::new(); 2 * num_atoms + 2],
+            propagated: 0,
+            assumptions: assumptions.to_vec(),
+            core: Vec::new(),
+            heuristic: config.heuristic,
+            vmtf: None,
+            outputs: Vec::new(),
+            random_decisions: config.random_decisions,
+            rng: Rng::new(config.seed),
+            proof: None,
+            proof_ids: Vec::new(),
+            unit_proof_ids: vec![None; num_atoms + 1],
+            trace: None,
+            error: None,
+            lemmas: HashMap::new(),
+            pure_literals: true,
+            stats: Stats::default(),
+        };
+
+        for clause in formula
+            .iter()
+            .chain(start.learned.iter().map(Clause::literals))
+        {
+            // watching the same literal twice would break propagation
+            let mut lits = clause.to_vec();
+            lits.sort();
+            lits.dedup();
+            for lit in &lits {
+                search.occurs[lit.get_id() as usize] = true;
+            }
+            search.add_clause(&lits);
+        }
+        for lit in assumptions {
+            search.occurs[lit.get_id() as usize] = true;
+        }
+
+        if let Some(values) = walked {
+            for (phase, value) in search.phases.iter_mut().zip(values) {
+                *phase = value;
+            }
+        }
+        for (phase, saved) in search.phases.iter_mut().zip(start.phases) {
+            *phase = saved;
+        }
+        if config.heuristic == Heuristic::Vmtf {
+            search.vmtf = Some(match start.vmtf {
+                Some(mut vmtf) => {
+                    vmtf.extend(&search.occurs);
+                    vmtf
+                }
+                None => Vmtf::new(&search.occurs),
+            });
+        }
+        if config.heuristic == Heuristic::Inputs {
+            search.outputs = vec![false; search.occurs.len()];
+            for gate in gates {
+                search.outputs[gate.output().get_id() as usize] = true;
+            }
+        }
+
+        for Assignment {
+            lit, provenance, ..
+        } in start.model.trail
+        {
+            search.assign(lit, provenance);
+        }
+
+        search
+    }
+
+    /// Add a clause to the database, watching its first two literals.
+    fn add_clause(&mut self, lits: &[Literal]) -> usize {
+        let i = self.clauses.push(lits);
+        if let [first, second, ..] = lits[..] {
+            self.watches[lit_index(first)].push(i);
+            self.watches[lit_index(second)].push(i);
+        }
+        i
+    }
 
-    while let Some((lit, provenance)) = lits.pop() {
+    /// The assignment of an atom in the model.  The atom must be
+    /// assigned.
+    fn assignment(&self, atom: usize) -> Assignment {
+        self.model.trail[self.positions[atom]]
+    }
+
+    /// The truth of a literal in the model.
+    fn value(&self, lit: Literal) -> Option<bool> {
+        lit.is_true_in(&self.model)
+    }
+
+    /// Add a literal to the model and the theory.
+    fn assign(&mut self, lit: Literal, provenance: Provenance) {
+        self.positions[lit.get_id() as usize] = self.model.trail.len();
+        if let Provenance::UnitPropagation(_) | Provenance::TheoryPropagation = provenance {
+            self.stats.propagations += 1;
+        }
+        #[cfg(feature = "log")]
+        match provenance {
+            Provenance::Decision => log::trace!(
+                "decide {} at level {}",
+                lit,
+                self.model.decision_level() + 1
+            ),
+            Provenance::TheoryPropagation => log::trace!("theory propagate {}", lit),
+            _ => (),
+        }
+        if let Some(trace) = &mut self.trace {
+            trace.push(match provenance {
+                Provenance::UnitPropagation(reason) => Transition::UnitPropagate {
+                    lit,
+                    clause: self.clauses.clause(reason),
+                },
+                Provenance::TheoryPropagation => Transition::TheoryPropagate(lit),
+                Provenance::PureLiteral => Transition::PureLiteral(lit),
+                Provenance::Decision => Transition::Decide(lit),
+            });
+        }
+        self.model.append(lit, provenance);
         if provenance == Provenance::Decision {
-            model.append(lit.negate(), Provenance::Backjump);
-            return true;
+            self.theory.push();
+        }
+        if let Err(e) = self.theory.incorporate(lit) {
+            self.error.get_or_insert(e);
         }
     }
 
-    false
-}
+    /// Ask the theory for the truth of a literal.  If the theory
+    /// fails, the error is kept and the literal is left undecided.
+    fn theory_value(&mut self, lit: Literal) -> Option<bool> {
+        match self.theory.decide(lit) {
+            Ok(value) => value,
+            Err(e) => {
+                self.error.get_or_insert(e);
+                None
+            }
+        }
+    }
+
+    /// Assign a literal implied by a clause, unless the theory says
+    /// it's false, in which case the theory's choice is assigned and
+    /// the clause is returned as a conflict.
+    fn imply(&mut self, lit: Literal, reason: usize) -> Option<usize> {
+        if self.theory_value(lit) == Some(false) {
+            self.theory_imply(lit.negate());
+            Some(reason)
+        } else {
+            self.assign(lit, Provenance::UnitPropagation(reason));
+            None
+        }
+    }
+
+    /// Assign the literals of unit clauses, and check for empty
+    /// clauses.  Returns a conflicting clause, if there is one.
+    fn assign_units(&mut self) -> Option<usize> {
+        for i in 0..self.clauses.len() {
+            match self.clauses[i] {
+                [] => return Some(i),
+                [lit] => match self.value(lit) {
+                    Some(true) => (),
+                    Some(false) => return Some(i),
+                    None => {
+                        if let Some(conflict) = self.imply(lit, i) {
+                            return Some(conflict);
+                        }
+                    }
+                },
+                _ => (),
+            }
+        }
+
+        None
+    }
 
-fn do_unit_propagation(model: &mut Model, formula: &Formula) -> Option<Literal> {
-    let Formula(clauses) = formula;
+    /// Propagate the consequences of the model, by unit propagation
+    /// and by asking the theory, until nothing changes.  Returns a
+    /// conflicting clause, if there is one.
+    fn propagate(&mut self) -> Option<usize> {
+        loop {
+            while self.propagated < self.model.trail.len() {
+                let lit = self.model.trail[self.propagated].lit;
+                self.propagated += 1;
+                if let Some(conflict) = self.propagate_literal(lit) {
+                    return Some(conflict);
+                }
+            }
+
+            if !self.propagate_theory() {
+                return None;
+            }
+        }
+    }
+
+    /// Visit the clauses watching the negation of a newly true
+    /// literal.
+    fn propagate_literal(&mut self, lit: Literal) -> Option<usize> {
+        let false_lit = lit.negate();
+        let mut watchers = mem::take(&mut self.watches[lit_index(false_lit)]);
+        let mut conflict = None;
+        let mut i = 0;
 
-    for clause in clauses {
-        if clause.is_true_in(model) == None {
-            let Clause(lits) = clause;
+        while i < watchers.len() {
+            let c = watchers[i];
 
-            for lit in lits {
-                if lit.is_true_in(model) == None {
-                    let lits_without_lit = lits.iter().filter(|l| *l != lit).copied().collect();
+            // keep the false literal in the second position, and find
+            // a replacement for it if there is one.
+            let lits = &mut self.clauses[c];
+            if lits[0] == false_lit {
+                lits.swap(0, 1);
+            }
+            let other = lits[0];
+            if self.model.values[other.get_id() as usize].map(|v| v != other.is_negated())
+                == Some(true)
+            {
+                i += 1;
+                continue;
+            }
+            let values = &self.model.values;
+            let replacement = lits.iter().skip(2).position(|l| {
+                values[l.get_id() as usize].map(|v| v != l.is_negated()) != Some(false)
+            });
+            if let Some(k) = replacement {
+                lits.swap(1, k + 2);
+                let new_watch = lits[1];
+                self.watches[lit_index(new_watch)].push(c);
+                watchers.swap_remove(i);
+                continue;
+            }
 
-                    if Clause(lits_without_lit).is_true_in(model) == Some(false) {
-                        return Some(*lit);
+            // no replacement: the clause is unit or false.
+            i += 1;
+            if self.value(other) == Some(false) {
+                conflict = Some(c);
+                break;
+            }
+            if let Some(c) = self.imply(other, c) {
+                conflict = Some(c);
+                break;
+            }
+        }
+
+        self.watches[lit_index(false_lit)] = watchers;
+        conflict
+    }
+
+    /// Ask the theory about every unassigned atom.  Returns true if
+    /// any were assigned.
+    fn propagate_theory(&mut self) -> bool {
+        let mut changed = false;
+
+        for id in 1..self.model.values.len() {
+            if !self.occurs[id] || self.model.values[id].is_some() {
+                continue;
+            }
+            let lit = Literal::new(id as isize);
+            match self.theory_value(lit) {
+                Some(true) => self.theory_imply(lit),
+                Some(false) => self.theory_imply(lit.negate()),
+                None => continue,
+            }
+            changed = true;
+        }
+
+        changed
+    }
+
+    /// Assign a literal the theory implies.  If the theory can explain
+    /// it, the explanation is learned as a clause, which is given as
+    /// the reason, so the propositional search can make the same
+    /// inference itself, and its contrapositive, from then on.
+    fn theory_imply(&mut self, lit: Literal) {
+        let explanation = match self.theory.explain(lit) {
+            Some(explanation)
+                if explanation
+                    .iter()
+                    .all(|l| l.var() != lit.var() && self.value(*l) == Some(true)) =>
+            {
+                explanation
+            }
+            _ => return self.assign(lit, Provenance::TheoryPropagation),
+        };
+
+        // watch the implied literal, and the last to become false.
+        let mut lits: Vec<Literal> = explanation.iter().map(|l| l.negate()).collect();
+        lits.sort_by_key(|l| Reverse(self.positions[l.get_id() as usize]));
+        lits.dedup();
+        lits.insert(0, lit);
+        let mut key = lits.clone();
+        key.sort();
+        let reason = match self.lemmas.get(&key) {
+            Some(i) => *i,
+            None => {
+                self.record(|_| Transition::Learn(Clause::unnormalized(lits.clone())));
+                let i = self.add_clause(&lits);
+                self.lemmas.insert(key, i);
+                self.stats.theory_lemmas += 1;
+                i
+            }
+        };
+        self.assign(lit, Provenance::UnitPropagation(reason));
+    }
+
+    /// Assign the literals which are pure in the original clauses not
+    /// yet satisfied.  This is only done with no decisions in the
+    /// model, and only if the theory is propositional: otherwise a
+    /// pure literal may still be constrained by the theory.
+    fn assign_pure_literals(&mut self) {
+        // pure literals aren't implied by the formula, so can't be
+        // used in a proof.
+        if self.model.decision_level() != 0
+            || !self.theory.is_propositional()
+            || self.proof.is_some()
+            || !self.pure_literals
+        {
+            return;
+        }
+
+        // making a literal true satisfies more clauses, which can make
+        // other literals pure, so repeat until nothing changes.
+        loop {
+            // assumptions act like unit clauses.
+            let mut occurrences = vec![false; self.watches.len()];
+            for lit in &self.assumptions {
+                occurrences[lit_index(*lit)] = true;
+            }
+            for lits in self.clauses.iter().take(self.num_original) {
+                if lits.iter().any(|lit| self.value(*lit) == Some(true)) {
+                    continue;
+                }
+                for lit in lits {
+                    occurrences[lit_index(*lit)] = true;
+                }
+            }
+
+            let mut changed = false;
+            for id in 1..self.model.values.len() {
+                if self.model.values[id].is_some() {
+                    continue;
+                }
+                let lit = Literal::new(id as isize);
+                let pure = match (
+                    occurrences[lit_index(lit)],
+                    occurrences[lit_index(lit.negate())],
+                ) {
+                    (true, false) => lit,
+                    (false, true) => lit.negate(),
+                    _ => continue,
+                };
+                self.assign(pure, Provenance::PureLiteral);
+                self.stats.pure_literals += 1;
+                changed = true;
+            }
+            if !changed {
+                return;
+            }
+        }
+    }
+
+    /// Check if any assumption is false in the model.
+    fn assumption_failed(&self) -> bool {
+        self.assumptions
+            .iter()
+            .any(|lit| self.value(*lit) == Some(false))
+    }
+
+    /// Find the assumptions which a false assumption follows from, by
+    /// tracing back through the reasons for its negation.  Assumptions
+    /// are decided before anything else, so every decision is one.
+    /// Theory propagations the theory can't explain depend on every
+    /// earlier decision.
+    fn failed_assumptions(&self) -> Vec<Literal> {
+        let failed = match self
+            .assumptions
+            .iter()
+            .find(|lit| self.value(**lit) == Some(false))
+        {
+            Some(lit) => *lit,
+            None => return Vec::new(),
+        };
+
+        let mut seen = vec![false; self.model.values.len()];
+        seen[failed.get_id() as usize] = true;
+        let mut core = vec![failed];
+        let mut theory_level = 0;
+        for a in self.model.trail.iter().rev() {
+            let id = a.lit.get_id() as usize;
+            if a.level <= theory_level && a.provenance == Provenance::Decision {
+                seen[id] = true;
+            }
+            if !seen[id] {
+                continue;
+            }
+            match a.provenance {
+                Provenance::UnitPropagation(r) => {
+                    for lit in &self.clauses[r] {
+                        seen[lit.get_id() as usize] = true;
+                    }
+                }
+                Provenance::TheoryPropagation => match self.theory_reason(self.positions[id]) {
+                    Some(explanation) => {
+                        for lit in explanation {
+                            seen[lit.get_id() as usize] = true;
+                        }
+                    }
+                    None => theory_level = theory_level.max(a.level),
+                },
+                Provenance::Decision => {
+                    if a.lit != failed {
+                        core.push(a.lit);
                     }
                 }
+                Provenance::PureLiteral => (),
             }
         }
+
+        core.sort_by_key(|lit| self.assumptions.iter().position(|a| a == lit));
+        core.dedup();
+        core
     }
 
-    None
-}
+    /// Decide the first unassigned assumption, or else a literal
+    /// chosen at random (some of the time), or by the heuristic, or
+    /// failing that the lowest unassigned atom, giving it its saved
+    /// phase.  Returns false if everything is assigned.
+    fn decide(&mut self) -> bool {
+        if let Some(lit) = self
+            .assumptions
+            .iter()
+            .copied()
+            .find(|lit| self.value(*lit).is_none())
+        {
+            self.assign(lit, Provenance::Decision);
+            self.stats.decisions += 1;
+            return true;
+        }
+
+        if self.random_decisions > 0.0 && self.rng.next_f64() < self.random_decisions {
+            let unassigned: Vec<usize> = (1..self.model.values.len())
+                .filter(|id| self.occurs[*id] && self.model.values[*id].is_none())
+                .collect();
+            if unassigned.is_empty() {
+                return false;
+            }
+            let id = unassigned[self.rng.below(unassigned.len())];
+            let lit = Literal::new(id as isize);
+            let lit = if self.rng.next_bool() {
+                lit
+            } else {
+                lit.negate()
+            };
+            self.assign(lit, Provenance::Decision);
+            self.stats.decisions += 1;
+            return true;
+        }
+
+        let num_atoms = self.model.values.len() - 1;
+        let values = &self.model.values;
+        let phases = &self.phases;
+        let occurs = &self.occurs;
+        let outputs = &self.outputs;
+        let value = |lit: Literal| values[lit.get_id() as usize].map(|v| v != lit.is_negated());
+        let chosen = match self.heuristic {
+            Heuristic::Ordered => None,
+            Heuristic::JeroslowWang => heuristic::jeroslow_wang(&self.clauses, value, num_atoms),
+            Heuristic::Dlis => heuristic::dlis(&self.clauses, value, num_atoms),
+            Heuristic::Vmtf => self
+                .vmtf
+                .as_mut()
+                .and_then(|vmtf| vmtf.pick(values))
+                .map(|id| {
+                    let lit = Literal::new(id as isize);
+                    if phases[id] {
+                        lit
+                    } else {
+                        lit.negate()
+                    }
+                }),
+            Heuristic::Inputs => (1..=num_atoms)
+                .find(|id| occurs[*id] && !outputs[*id] && values[*id].is_none())
+                .map(|id| {
+                    let lit = Literal::new(id as isize);
+                    if phases[id] {
+                        lit
+                    } else {
+                        lit.negate()
+                    }
+                }),
+        };
+        if let Some(lit) = chosen {
+            self.assign(lit, Provenance::Decision);
+            self.stats.decisions += 1;
+            return true;
+        }
+
+        for id in 1..self.model.values.len() {
+            if self.occurs[id] && self.model.values[id].is_none() {
+                let lit = Literal::new(id as isize);
+                let lit = if self.phases[id] { lit } else { lit.negate() };
+                self.assign(lit, Provenance::Decision);
+                self.stats.decisions += 1;
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Derive a learned clause from a conflict, by resolving the
+    /// conflicting clause with the reasons of literals assigned at
+    /// the current decision level until only one such literal
+    /// remains (the first unique implication point).  The learned
+    /// clause is false in the model, but becomes unit once the model
+    /// is backjumped to the returned level: the highest level of any
+    /// literal in the clause other than the implication point.
+    ///
+    /// The implication point is the first literal of the learned
+    /// clause, and a literal from the backjump level the second, so
+    /// they can be watched.
+    ///
+    /// Literals propagated by the theory don't have a reason clause,
+    /// so the theory is asked to explain them.  If it can't, they are
+    /// explained by the decisions made before them: the theory only
+    /// knows about literals which those decisions imply.
+    fn analyze(&self, conflict: usize) -> Analysis {
+        let trail = &self.model.trail;
+        let clauses = &self.clauses;
+        let current_level = self.model.decision_level();
+
+        let mut seen = vec![false; trail.len()];
+        let mut learned = vec![Literal::new(1)];
+        let mut backjump_level = 0;
+        let mut pending = 0;
+        let mut reason = clauses[conflict].to_vec();
+        let mut resolved = None;
+        let mut i = trail.len();
+        let mut antecedents = vec![conflict];
+        let mut units = Vec::new();
+
+        loop {
+            for lit in reason {
+                if Some(lit) == resolved {
+                    continue;
+                }
+                let pos = self.positions[lit.get_id() as usize];
+                let Assignment {
+                    provenance, level, ..
+                } = trail[pos];
+                // literals assigned at level 0 follow from the formula,
+                // so can be left out, but pure literals don't: they
+                // could stop being pure if more clauses are added.
+                if seen[pos] {
+                    continue;
+                }
+                if level == 0 && provenance != Provenance::PureLiteral {
+                    seen[pos] = true;
+                    units.push(lit.get_id() as usize);
+                    continue;
+                }
+                seen[pos] = true;
+                if level == current_level {
+                    pending += 1;
+                } else {
+                    learned.push(lit);
+                    if level > backjump_level {
+                        backjump_level = level;
+                        let last = learned.len() - 1;
+                        learned.swap(1, last);
+                    }
+                }
+            }
 
-fn do_theory_propagation<T: Theory>(
-    theory: &T,
-    model: &mut Model,
-    formula: &Formula,
-) -> Option<Literal> {
-    let Formula(clauses) = formula;
+            // find the most recently assigned literal still to resolve.
+            loop {
+                i -= 1;
+                if seen[i] {
+                    break;
+                }
+            }
+            let Assignment {
+                lit, provenance, ..
+            } = trail[i];
+            pending -= 1;
+            if pending == 0 {
+                learned[0] = lit.negate();
+                break;
+            }
 
-    for clause in clauses {
-        let Clause(lits) = clause;
-        for lit in lits {
-            if lit.is_true_in(model) == None {
-                match theory.decide(*lit) {
-                    Some(true) => {
-                        return Some(*lit);
+            resolved = Some(lit);
+            reason = match provenance {
+                Provenance::UnitPropagation(r) => {
+                    antecedents.push(r);
+                    clauses[r].to_vec()
+                }
+                Provenance::TheoryPropagation => match self.theory_reason(i) {
+                    Some(explanation) => {
+                        let mut lits = vec![lit];
+                        lits.extend(explanation.iter().map(|l| l.negate()));
+                        lits
                     }
-                    Some(false) => {
-                        return Some(lit.negate());
+                    None => {
+                        let mut lits = vec![lit];
+                        for a in &trail[..i] {
+                            if a.provenance == Provenance::Decision {
+                                lits.push(a.lit.negate());
+                            }
+                        }
+                        lits
                     }
-                    None => continue,
+                },
+                Provenance::PureLiteral | Provenance::Decision => {
+                    unreachable!("resolved past the decision of the current level")
                 }
+            };
+        }
+
+        let mut analysis = Analysis {
+            learned,
+            level: backjump_level,
+            antecedents,
+            units,
+            minimized: 0,
+        };
+        self.minimize(&mut analysis);
+        analysis
+    }
+
+    /// The theory's explanation of the literal at a position of the
+    /// trail, if it has one which only uses literals true before it.
+    fn theory_reason(&self, pos: usize) -> Option<Vec<Literal>> {
+        let explanation = self.theory.explain(self.model.trail[pos].lit)?;
+        let valid = explanation.iter().all(|lit| {
+            self.value(*lit) == Some(true) && self.positions[lit.get_id() as usize] < pos
+        });
+        if valid {
+            Some(explanation)
+        } else {
+            None
+        }
+    }
+
+    /// Remove the literals of a learned clause which are implied by
+    /// the others: those whose reason clauses only contain literals of
+    /// the learned clause, literals assigned at level 0, and other
+    /// literals which are implied in the same way.  Resolving with
+    /// those reason clauses, latest first, derives the smaller clause.
+    fn minimize(&self, analysis: &mut Analysis) {
+        let learned = &mut analysis.learned;
+        let mut in_clause = vec![false; self.model.values.len()];
+        for lit in learned.iter() {
+            in_clause[lit.get_id() as usize] = true;
+        }
+
+        let mut implied = vec![None; self.model.values.len()];
+        let mut removed = Vec::new();
+        let mut i = 1;
+        while i < learned.len() {
+            let atom = learned[i].get_id() as usize;
+            if self.is_implied(atom, &in_clause, &mut implied) {
+                removed.push(atom);
+                learned.swap_remove(i);
+            } else {
+                i += 1;
+            }
+        }
+        if removed.is_empty() {
+            return;
+        }
+
+        analysis.minimized = removed.len();
+        analysis.level = 0;
+        for i in 1..learned.len() {
+            let level = self.assignment(learned[i].get_id() as usize).level;
+            if level > analysis.level {
+                analysis.level = level;
+                learned.swap(1, i);
+            }
+        }
+
+        if self.proof.is_none() {
+            return;
+        }
+        for atom in &removed {
+            in_clause[*atom] = false;
+        }
+        let mut in_units = vec![false; self.model.values.len()];
+        for atom in &analysis.units {
+            in_units[*atom] = true;
+        }
+        let mut resolved = Vec::new();
+        let mut seen = vec![false; self.model.values.len()];
+        while let Some(atom) = removed.pop() {
+            if seen[atom] {
+                continue;
+            }
+            seen[atom] = true;
+            resolved.push(atom);
+            let reason = match self.assignment(atom).provenance {
+                Provenance::UnitPropagation(r) => r,
+                _ => unreachable!("removed a literal without a reason clause"),
+            };
+            for lit in &self.clauses[reason] {
+                let b = lit.get_id() as usize;
+                if b == atom || in_clause[b] || seen[b] {
+                    continue;
+                }
+                if self.assignment(b).level == 0 {
+                    if !in_units[b] {
+                        in_units[b] = true;
+                        analysis.units.push(b);
+                    }
+                } else {
+                    removed.push(b);
+                }
+            }
+        }
+        resolved.sort_by_key(|atom| Reverse(self.positions[*atom]));
+        for atom in resolved {
+            if let Provenance::UnitPropagation(r) = self.assignment(atom).provenance {
+                analysis.antecedents.push(r);
             }
         }
     }
 
-    None
-}
+    /// Check if an atom of a learned clause is implied by the atoms
+    /// in the clause, for 'minimize'.  Results are remembered in
+    /// 'implied', indexed by atom.
+    fn is_implied(&self, atom: usize, in_clause: &[bool], implied: &mut [Option<bool>]) -> bool {
+        // the atoms being checked, and how many of the literals of
+        // their reason clauses have been checked so far.
+        let mut stack = vec![(atom, 0)];
+        while let Some((a, i)) = stack.pop() {
+            let lits = match self.assignment(a).provenance {
+                Provenance::UnitPropagation(r) => &self.clauses[r],
+                _ => {
+                    implied[a] = Some(false);
+                    for (b, _) in stack {
+                        implied[b] = Some(false);
+                    }
+                    return false;
+                }
+            };
+            let b = match lits.get(i) {
+                Some(lit) => lit.get_id() as usize,
+                None => {
+                    implied[a] = Some(true);
+                    continue;
+                }
+            };
+            stack.push((a, i + 1));
 
-fn do_decision(model: &mut Model, formula: &Formula) -> Option<Literal> {
-    let Formula(clauses) = formula;
+            let Assignment {
+                provenance, level, ..
+            } = self.assignment(b);
+            let fact = level == 0 && provenance != Provenance::PureLiteral;
+            if b == a || in_clause[b] || fact || implied[b] == Some(true) {
+                continue;
+            }
+            if implied[b] == Some(false) {
+                for (c, _) in stack {
+                    implied[c] = Some(false);
+                }
+                return false;
+            }
+            stack.push((b, 0));
+        }
+        true
+    }
 
-    for clause in clauses {
-        if clause.is_true_in(model) == None {
-            let Clause(lits) = clause;
+    /// Start recording a proof, with the current clauses as premises.
+    fn start_proof(&mut self) {
+        self.proof = Some(Proof::new(self.clauses.clauses(0..self.clauses.len())));
+        self.proof_ids = (0..self.clauses.len()).collect();
+    }
 
-            for lit in lits {
-                if lit.is_true_in(model) == None {
-                    // make the lit positive
-                    return Some(Literal::new(lit.get_id()));
+    /// Record the derivation of a learned clause, which is about to
+    /// be added to the database.
+    fn prove_learned(&mut self, analysis: &Analysis) {
+        if self.proof.is_none() {
+            return;
+        }
+        let mut antecedents: Vec<usize> = analysis
+            .antecedents
+            .iter()
+            .map(|c| self.proof_ids[*c])
+            .collect();
+        for atom in &analysis.units {
+            antecedents.push(self.prove_unit(*atom));
+        }
+        if let Some(proof) = &mut self.proof {
+            let learned = Clause::unnormalized(analysis.learned.clone());
+            let id = proof.derive(learned, antecedents);
+            self.proof_ids.push(id);
+        }
+    }
+
+    /// Record the derivation of the empty clause from a clause which
+    /// is false at level 0.
+    fn prove_empty(&mut self, conflict: usize) {
+        if self.proof.is_none() {
+            return;
+        }
+        let mut antecedents = vec![self.proof_ids[conflict]];
+        let clause = self.clauses.clause(conflict);
+        for lit in clause.literals() {
+            antecedents.push(self.prove_unit(lit.get_id() as usize));
+        }
+        if let Some(proof) = &mut self.proof {
+            proof.derive(Clause::from(Vec::new()), antecedents);
+        }
+    }
+
+    /// Get the number in the proof of the unit clause of an atom
+    /// assigned at level 0, deriving it (and the units it depends on)
+    /// from its reason if need be.
+    fn prove_unit(&mut self, atom: usize) -> usize {
+        let mut todo = vec![atom];
+        while let Some(&next) = todo.last() {
+            if self.unit_proof_ids[next].is_some() {
+                todo.pop();
+                continue;
+            }
+
+            let Assignment {
+                lit, provenance, ..
+            } = self.assignment(next);
+            let reason = match provenance {
+                Provenance::UnitPropagation(r) => r,
+                _ => unreachable!("atom at level 0 without a reason clause"),
+            };
+            let lits = &self.clauses[reason];
+            let missing: Vec<usize> = lits
+                .iter()
+                .map(|l| l.get_id() as usize)
+                .filter(|a| *a != next && self.unit_proof_ids[*a].is_none())
+                .collect();
+            if !missing.is_empty() {
+                todo.extend(missing);
+                continue;
+            }
+
+            let id = if lits.len() == 1 {
+                self.proof_ids[reason]
+            } else {
+                let mut antecedents = vec![self.proof_ids[reason]];
+                for l in lits {
+                    if l.get_id() as usize != next {
+                        antecedents.extend(self.unit_proof_ids[l.get_id() as usize]);
+                    }
+                }
+                let unit = Clause::from(vec![lit]);
+                match &mut self.proof {
+                    Some(proof) => proof.derive(unit, antecedents),
+                    None => unreachable!("proving a unit without a proof"),
+                }
+            };
+            self.unit_proof_ids[next] = Some(id);
+            todo.pop();
+        }
+
+        self.unit_proof_ids[atom].unwrap_or(0)
+    }
+
+    /// The literal block distance of a clause: the number of distinct
+    /// decision levels among its literals.  Clauses with a low LBD
+    /// connect few parts of the search, and tend to be more useful.
+    fn lbd(&self, lits: &[Literal]) -> usize {
+        let mut levels: Vec<usize> = lits
+            .iter()
+            .map(|lit| self.assignment(lit.get_id() as usize).level)
+            .collect();
+        levels.sort_unstable();
+        levels.dedup();
+        levels.len()
+    }
+
+    /// Undo every assignment made after the given decision level.
+    fn backjump(&mut self, level: usize) {
+        let levels = self.model.decision_level().saturating_sub(level);
+        while self.model.decision_level() > level {
+            let a = self.model.pop().unwrap();
+            let id = a.lit.get_id() as usize;
+            self.phases[id] = !a.lit.is_negated();
+            if let Some(vmtf) = &mut self.vmtf {
+                vmtf.unassign(id);
+            }
+        }
+
+        self.propagated = self.propagated.min(self.model.trail.len());
+        self.theory.pop(levels);
+    }
+
+    /// Add a learned clause to the database and assign its first
+    /// literal.  Returns a conflicting clause, if there is one.
+    fn learn(&mut self, clause: Clause) -> Option<usize> {
+        let lit = clause.literals()[0];
+        self.record(|_| Transition::Learn(clause.clone()));
+        let i = self.add_clause(clause.literals());
+        self.imply(lit, i)
+    }
+
+    /// Add clauses learned by another search of the same formula.
+    /// This must be done at level 0, where false literals can be left
+    /// out and satisfied clauses skipped.  Returns a conflicting
+    /// clause, if there is one.
+    fn import(&mut self, clauses: Vec<Clause>) -> Option<usize> {
+        for clause in clauses {
+            let mut lits = clause.into_literals();
+            if lits.iter().any(|lit| self.value(*lit) == Some(true)) {
+                continue;
+            }
+            lits.retain(|lit| self.value(*lit).is_none());
+            lits.sort();
+            lits.dedup();
+
+            self.record(|_| Transition::Learn(Clause::from(lits.clone())));
+            let i = self.add_clause(&lits);
+            match self.clauses[i] {
+                [] => return Some(i),
+                [lit] => {
+                    if let Some(conflict) = self.imply(lit, i) {
+                        return Some(conflict);
+                    }
                 }
+                _ => (),
             }
         }
+
+        None
     }
 
-    None
+    /// Record a transition, if the trace is being recorded.
+    fn record<F: FnOnce(&Self) -> Transition>(&mut self, transition: F) {
+        if let Some(mut trace) = self.trace.take() {
+            trace.push(transition(self));
+            self.trace = Some(trace);
+        }
+    }
+
+    /// End the search.
+    fn finish(mut self, outcome: Outcome) -> Finished {
+        // the theory may have been wrong since it failed.
+        let outcome = match self.error.take() {
+            Some(e) => Outcome::Failed(e.into()),
+            None => outcome,
+        };
+        let proof = match outcome {
+            Outcome::Unsat => self.proof.clone(),
+            _ => None,
+        };
+        Finished {
+            outcome,
+            stats: self.stats,
+            state: self.snapshot(Model::new()),
+            fixed: self.model.fixed().collect(),
+            core: mem::take(&mut self.core),
+            proof,
+            trace: self.trace.unwrap_or_default(),
+        }
+    }
+
+    /// Take a snapshot with a model: the current one, for
+    /// checkpointing, or an empty one, for starting another search of
+    /// the same formula, or of one with more clauses.
+    fn snapshot(&self, model: Model) -> Snapshot {
+        Snapshot {
+            learned: self.clauses.clauses(self.num_original..self.clauses.len()),
+            model,
+            phases: self.phases.clone(),
+            vmtf: self.vmtf.clone(),
+        }
+    }
 }
 
-fn reset_theory<T: Theory>(theory: &mut T, model: &Model) {
-    let Model(lits) = model;
+/// The result of analysing a conflict.
+struct Analysis {
+    /// The literals of the clause learned, with the one to assign
+    /// first, and the one with the backjump level second.
+    learned: Vec<Literal>,
+    /// The level to backjump to.
+    level: usize,
+    /// The clauses resolved to derive the learned clause, in order.
+    antecedents: Vec<usize>,
+    /// The atoms assigned at level 0 which were left out of the
+    /// learned clause: resolving with their unit clauses completes
+    /// the derivation.
+    units: Vec<usize>,
+    /// The number of literals removed from the learned clause by
+    /// minimization.
+    minimized: usize,
+}
 
-    theory.forget();
+/// Statistics about a search.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd, Eq, Ord)]
+pub struct Stats {
+    /// The number of decisions made.
+    pub decisions: usize,
+    /// The number of literals assigned by unit or theory propagation.
+    pub propagations: usize,
+    /// The number of pure literals assigned.
+    pub pure_literals: usize,
+    /// The number of conflicts.
+    pub conflicts: usize,
+    /// The number of restarts.
+    pub restarts: usize,
+    /// The number of clauses learned.
+    pub learned_clauses: usize,
+    /// The total number of literals in learned clauses.
+    pub learned_literals: usize,
+    /// The number of literals removed from learned clauses because
+    /// the other literals imply them.
+    pub minimized_literals: usize,
+    /// The total literal block distance (LBD) of learned clauses.
+    pub learned_lbd: usize,
+    /// The number of learned clauses with an LBD of at most 2, which
+    /// are the most useful.
+    pub glue_clauses: usize,
+    /// The number of clauses learned from the theory's explanations of
+    /// its propagations.
+    pub theory_lemmas: usize,
+}
 
-    for (lit, _) in lits.iter() {
-        theory.incorporate(*lit);
+impl Stats {
+    /// The average LBD of learned clauses.
+    pub fn average_lbd(&self) -> f64 {
+        if self.learned_clauses == 0 {
+            0.0
+        } else {
+            self.learned_lbd as f64 / self.learned_clauses as f64
+        }
+    }
+}
+
+/// Options for the search.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct Config {
+    /// When to restart the search.
+    pub restart_policy: RestartPolicy,
+    /// How to choose decisions.
+    pub heuristic: Heuristic,
+    /// The fraction of decisions, from 0 to 1, to make at random
+    /// instead: a random unassigned atom, with a random truth value.
+    pub random_decisions: f64,
+    /// The seed for random decisions.  Searches with the same seed
+    /// (and the same everything else) make the same decisions.
+    pub seed: u64,
+    /// Backtrack chronologically, undoing only the last decision,
+    /// when backjumping would undo more than this many levels.  This
+    /// keeps more of the model, at the cost of some implications
+    /// being assigned at a later level than they could be.
+    pub chronological_backtracking: Option<usize>,
+    /// Give up after this many conflicts.
+    pub max_conflicts: Option<usize>,
+    /// Give up after this many decisions.
+    pub max_decisions: Option<usize>,
+    /// Give up at this time.
+    pub deadline: Option<Instant>,
+    /// Make the search depend only on its input: the formula, the
+    /// starting state, and this configuration.  The deadline is
+    /// ignored, so repeated searches make exactly the same decisions,
+    /// in the same order, and produce the same trace and result.
+    /// Searches always use 'seed' for their random choices, so only
+    /// the clock needs to be turned off.
+    pub deterministic: bool,
+    /// Before a new search, look for a model by local search, and
+    /// start with the phases of the best assignment found.  This is
+    /// cheap, and helps a lot on satisfiable problems.
+    pub walk: Option<WalkSat>,
+}
+
+impl Config {
+    /// Check if a search has used up its budget.
+    fn out_of_budget(&self, stats: &Stats) -> bool {
+        self.max_conflicts.is_some_and(|max| stats.conflicts >= max)
+            || self.max_decisions.is_some_and(|max| stats.decisions >= max)
+            || (!self.deterministic
+                && self
+                    .deadline
+                    .is_some_and(|deadline| Instant::now() >= deadline))
+    }
+}
+
+/// The result of solving a formula: a model, by default as a list of
+/// true literals, or nothing.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub enum SolveResult<M = Vec<Literal>> {
+    /// The formula is satisfiable, and this is a model.
+    Sat(M),
+    /// The formula is unsatisfiable.
+    Unsat,
+    /// The search ran out of budget before finding out.
+    Unknown,
+}
+
+impl<M> SolveResult<M> {
+    /// Check if the formula is satisfiable.
+    pub fn is_sat(&self) -> bool {
+        matches!(self, SolveResult::Sat(_))
+    }
+
+    /// Check if the formula is unsatisfiable.
+    pub fn is_unsat(&self) -> bool {
+        matches!(self, SolveResult::Unsat)
+    }
+
+    /// Get the model, if the formula is satisfiable.
+    pub fn into_model(self) -> Option<M> {
+        match self {
+            SolveResult::Sat(model) => Some(model),
+            SolveResult::Unsat | SolveResult::Unknown => None,
+        }
     }
+
+    /// Transform the model, if the formula is satisfiable.
+    pub fn map<N, F: FnOnce(M) -> N>(self, f: F) -> SolveResult<N> {
+        match self {
+            SolveResult::Sat(model) => SolveResult::Sat(f(model)),
+            SolveResult::Unsat => SolveResult::Unsat,
+            SolveResult::Unknown => SolveResult::Unknown,
+        }
+    }
+}
+
+/// Why a search couldn't finish.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub enum SolverError {
+    /// The theory failed.
+    Theory(TheoryError),
+}
+
+impl fmt::Display for SolverError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SolverError::Theory(e) => write!(f, "theory error: {}", e),
+        }
+    }
+}
+
+impl Error for SolverError {}
+
+impl From<TheoryError> for SolverError {
+    fn from(e: TheoryError) -> SolverError {
+        SolverError::Theory(e)
+    }
+}
+
+/// The outcome of a search which can be interrupted.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub(crate) enum Outcome {
+    Sat(Model),
+    Unsat,
+    Interrupted,
+    OutOfBudget,
+    Failed(SolverError),
+}
+
+impl Outcome {
+    /// Convert the outcome of a search without an interrupt flag.
+    pub(crate) fn into_result(self) -> Result<SolveResult<Model>, SolverError> {
+        match self {
+            Outcome::Sat(model) => Ok(SolveResult::Sat(model)),
+            Outcome::Unsat => Ok(SolveResult::Unsat),
+            Outcome::Interrupted => unreachable!("search interrupted without an interrupt flag"),
+            Outcome::OutOfBudget => Ok(SolveResult::Unknown),
+            Outcome::Failed(e) => Err(e),
+        }
+    }
+}
+
+/// The state of a search, which can be saved and resumed later: the
+/// clauses learned so far, the current model, and the state of the
+/// branching heuristic.  The heuristic state is an optimisation, so
+/// it can be left out.
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd, Eq, Ord)]
+pub(crate) struct Snapshot {
+    pub(crate) learned: Vec<Clause>,
+    pub(crate) model: Model,
+    pub(crate) phases: Vec<bool>,
+    pub(crate) vmtf: Option<Vmtf>,
+}
+
+/// A function to save the state of a search.
+pub(crate) type SaveSnapshot<'a> = &'a mut dyn FnMut(&Snapshot);
+
+/// A function to inspect a conflict: the model falsifies the clause
+/// with the given index.
+pub(crate) type OnConflict<'a> = &'a mut dyn FnMut(&ClauseDb, &Model, usize);
+
+/// Something which happened in a search, for reporting progress.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub enum SearchEvent<'a> {
+    /// A conflict was analysed.  These are the statistics so far.
+    Conflict(&'a Stats),
+    /// A clause was learned, with its LBD.
+    Learned(&'a Clause, usize),
+    /// The search restarted.  These are the statistics so far.
+    Restart(&'a Stats),
+}
+
+/// A function to report the progress of a search.
+pub(crate) type OnEvent<'a> = &'a mut dyn FnMut(&SearchEvent);
+
+/// A function to pass on a learned clause, with its LBD.
+pub(crate) type ExportClause<'a> = &'a mut dyn FnMut(&Clause, usize);
+
+/// A function to get clauses learned elsewhere.
+pub(crate) type ImportClauses<'a> = &'a mut dyn FnMut() -> Vec<Clause>;
+
+/// How a search ended, and what it found out along the way.
+#[derive(Clone, Debug)]
+pub(crate) struct Finished {
+    pub(crate) outcome: Outcome,
+    pub(crate) stats: Stats,
+    /// The final state, without the model.
+    pub(crate) state: Snapshot,
+    /// The literals fixed at decision level 0 in the final model.
+    pub(crate) fixed: Vec<Literal>,
+    /// The assumptions which made the formula unsatisfiable, if it's
+    /// only unsatisfiable with them.
+    pub(crate) core: Vec<Literal>,
+    /// A proof of unsatisfiability, if one was asked for and the
+    /// formula is unsatisfiable.
+    pub(crate) proof: Option<Proof>,
+    /// The transitions made, if they were asked for.
+    pub(crate) trace: Vec<Transition>,
+}
+
+/// Hooks for controlling a search from the outside.
+#[derive(Default)]
+pub(crate) struct Control<'a> {
+    /// Give up if this flag is set.
+    pub(crate) interrupt: Option<&'a AtomicBool>,
+    /// Call the function with the current state, at most this often.
+    pub(crate) checkpoint: Option<(Duration, SaveSnapshot<'a>)>,
+    /// Call the function on every conflict.
+    pub(crate) conflict: Option<OnConflict<'a>>,
+    /// Call the function on conflicts, learned clauses, and restarts.
+    pub(crate) event: Option<OnEvent<'a>>,
+    /// Record a proof of unsatisfiability.  This is only possible if
+    /// the theory is propositional.
+    pub(crate) proof: bool,
+    /// Record the transitions made.
+    pub(crate) trace: bool,
+    /// Call the function on every learned clause, to pass it on to
+    /// other searches of the same formula.
+    pub(crate) export: Option<ExportClause<'a>>,
+    /// Call the function on every restart, to get clauses learned by
+    /// other searches of the same formula.  This rules out a proof.
+    pub(crate) import: Option<ImportClauses<'a>>,
 }
 
 /// Given a formula, find a model which satisfies it if one exists.
-pub fn dpll<T: Theory>(theory: &mut T, formula: Formula) -> Option<Model> {
-    let mut model = Model::new();
+pub fn dpll<T: Theory + ?Sized>(
+    theory: &mut T,
+    formula: Formula,
+) -> Result<SolveResult<Model>, SolverError> {
+    dpll_with_assumptions(theory, formula, &[])
+}
+
+/// Like 'dpll', but only find models in which the assumed literals
+/// are true.  Clauses learned along the way follow from the formula
+/// alone, not from the assumptions.
+pub fn dpll_with_assumptions<T: Theory + ?Sized>(
+    theory: &mut T,
+    formula: Formula,
+    assumptions: &[Literal],
+) -> Result<SolveResult<Model>, SolverError> {
+    let config = Config::default();
+    let finished = search(
+        theory,
+        formula,
+        Snapshot::default(),
+        assumptions,
+        &config,
+        Control::default(),
+    );
+    finished.outcome.into_result()
+}
+
+/// Like 'dpll_with_assumptions', but resuming from a saved state,
+/// with the given options, and under the control of some hooks.
+/// Returns the statistics of the search, and its final state without
+/// the model.
+pub(crate) fn search<T: Theory + ?Sized>(
+    theory: &mut T,
+    formula: Formula,
+    start: Snapshot,
+    assumptions: &[Literal],
+    config: &Config,
+    mut control: Control,
+) -> Finished {
+    // the clock is only read if it's needed, as there isn't one on
+    // every platform.
+    let mut last_checkpoint = control.checkpoint.as_ref().map(|_| Instant::now());
+    let mut restarts = Restarts::new(config.restart_policy);
+    // the theory may remember a previous search.
+    theory.forget();
+
+    let mut search = Search::new(theory, formula, start, assumptions, config);
+    // imported clauses have no derivation.
+    if control.proof && control.import.is_none() && search.theory.is_propositional() {
+        search.start_proof();
+    }
+    search.pure_literals = control.export.is_none();
+    if control.trace {
+        search.trace = Some(Vec::new());
+    }
+    let mut conflict = search.assign_units();
+    if conflict.is_none() {
+        search.assign_pure_literals();
+    }
 
     loop {
-        match formula.is_true_in(&model) {
-            Some(true) => break,
-            Some(false) => {
-                if do_backjump(&mut model) {
-                    reset_theory(theory, &model);
-                    continue;
+        if search.error.is_some() {
+            return search.finish(Outcome::Unsat);
+        }
+        if let Some(flag) = control.interrupt {
+            if flag.load(Ordering::Relaxed) {
+                return search.finish(Outcome::Interrupted);
+            }
+        }
+        if config.out_of_budget(&search.stats) {
+            return search.finish(Outcome::OutOfBudget);
+        }
+        if let Some((interval, save)) = &mut control.checkpoint {
+            if last_checkpoint.is_some_and(|last| last.elapsed() >= *interval) {
+                save(&search.snapshot(search.model.clone()));
+                last_checkpoint = Some(Instant::now());
+            }
+        }
+
+        if conflict.is_none() {
+            conflict = search.propagate();
+        }
+
+        match conflict.take() {
+            Some(c) => {
+                if let Some(on_conflict) = &mut control.conflict {
+                    on_conflict(&search.clauses, &search.model, c);
                 }
 
-                return None;
-            }
-            None => {
-                // need to eagerly apply constraints required by the
-                // theory, or unit propagation might pick a literal
-                // which the theory would forbid.
-                if let Some(lit) = do_theory_propagation(theory, &mut model, &formula) {
-                    model.append(lit, Provenance::TheoryPropagation);
-                    theory.incorporate(lit);
-                    continue;
+                if search.model.decision_level() == 0 {
+                    search.record(|s| Transition::Fail(Some(s.clauses.clause(c))));
+                    search.prove_empty(c);
+                    return search.finish(Outcome::Unsat);
                 }
-                if let Some(lit) = do_unit_propagation(&mut model, &formula) {
-                    model.append(lit, Provenance::UnitPropagation);
-                    theory.incorporate(lit);
-                    continue;
+
+                let analysis = search.analyze(c);
+                search.prove_learned(&analysis);
+                let Analysis {
+                    learned,
+                    level,
+                    minimized,
+                    ..
+                } = analysis;
+                let learned = Clause::unnormalized(learned);
+                let lbd = search.lbd(learned.literals());
+                search.stats.conflicts += 1;
+                search.stats.learned_clauses += 1;
+                search.stats.learned_literals += learned.len();
+                search.stats.minimized_literals += minimized;
+                search.stats.learned_lbd += lbd;
+                if lbd <= 2 {
+                    search.stats.glue_clauses += 1;
                 }
-                if let Some(lit) = do_decision(&mut model, &formula) {
-                    model.append(lit, Provenance::Decision);
-                    theory.incorporate(lit);
-                    continue;
+                if let Some(on_event) = &mut control.event {
+                    on_event(&SearchEvent::Conflict(&search.stats));
+                    on_event(&SearchEvent::Learned(&learned, lbd));
+                }
+                if let Some(export) = &mut control.export {
+                    export(&learned, lbd);
                 }
 
-                panic!("failed to do either propagation or decision in an incomplete model");
+                if let Some(vmtf) = &mut search.vmtf {
+                    vmtf.bump(&learned);
+                }
+
+                // a learned unit clause isn't watched, so it has to be
+                // assigned at level 0 to stay assigned.
+                let current_level = search.model.decision_level();
+                let level = match config.chronological_backtracking {
+                    Some(threshold) if learned.len() > 1 && current_level - level > threshold => {
+                        current_level - 1
+                    }
+                    _ => level,
+                };
+
+                let restart = restarts.conflict(lbd);
+                #[cfg(feature = "log")]
+                log::debug!(
+                    "conflict {}: learned {} (lbd {}), backjumping to level {}",
+                    search.stats.conflicts,
+                    learned,
+                    lbd,
+                    level
+                );
+                search.record(|s| Transition::Backjump {
+                    conflict: s.clauses.clause(c),
+                    level,
+                });
+                search.backjump(level);
+                conflict = search.learn(learned);
+                if restart && conflict.is_none() {
+                    search.record(|_| Transition::Restart);
+                    search.backjump(0);
+                    if let Some(import) = &mut control.import {
+                        conflict = search.import(import());
+                    }
+                    if conflict.is_none() {
+                        search.assign_pure_literals();
+                    }
+                    search.stats.restarts += 1;
+                    #[cfg(feature = "log")]
+                    log::debug!(
+                        "restart {} after {} conflicts",
+                        search.stats.restarts,
+                        search.stats.conflicts
+                    );
+                    if let Some(on_event) = &mut control.event {
+                        on_event(&SearchEvent::Restart(&search.stats));
+                    }
+                }
+            }
+            None => {
+                if search.assumption_failed() {
+                    // there's no refutation of the formula itself.
+                    search.proof = None;
+                    search.record(|_| Transition::Fail(None));
+                    search.core = search.failed_assumptions();
+                    return search.finish(Outcome::Unsat);
+                }
+                if !search.decide() {
+                    break;
+                }
             }
         }
     }
 
-    Some(model)
+    // a self-check, in debug builds: the original clauses all hold.
+    if cfg!(debug_assertions) {
+        let lits: Vec<Literal> = search.model.trail.iter().map(|a| a.lit).collect();
+        let violated = verify::violated(search.clauses.iter().take(search.num_original), &lits);
+        debug_assert!(violated.is_empty(), "model violates clauses {:?}", violated);
+    }
+
+    // the model is still needed for the fixed literals.
+    let model = search.model.clone();
+    search.finish(Outcome::Sat(model))
 }
@@ -0,0 +1,118 @@
+//! The clause database of a search, stored flat: the literals of
+//! every clause are in one buffer, so adding a clause doesn't need an
+//! allocation of its own, and clauses added together are next to each
+//! other in memory.
+//!
+//! This is the layout of 'Formula' too, but the search also reorders
+//! the literals of a clause in place, to keep its watched literals at
+//! the front, which a formula doesn't allow.
+
+use std::ops::{Index, IndexMut, Range};
+
+use crate::cnf::{Clause, Formula, Literal};
+
+/// Clauses, numbered in the order they were added.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub(crate) struct ClauseDb {
+    /// The literals of all the clauses, one after another.
+    lits: Vec<Literal>,
+    /// Where the literals of each clause start in 'lits', followed by
+    /// the end of the last clause.
+    starts: Vec<usize>,
+}
+
+impl Default for ClauseDb {
+    fn default() -> ClauseDb {
+        ClauseDb::with_capacity(0, 0)
+    }
+}
+
+impl ClauseDb {
+    /// Construct an empty database with room for some clauses and
+    /// literals.
+    pub(crate) fn with_capacity(clauses: usize, lits: usize) -> ClauseDb {
+        let mut starts = Vec::with_capacity(clauses + 1);
+        starts.push(0);
+        ClauseDb {
+            lits: Vec::with_capacity(lits),
+            starts,
+        }
+    }
+
+    /// The number of clauses.
+    pub(crate) fn len(&self) -> usize {
+        self.starts.len() - 1
+    }
+
+    /// Add a clause, returning its number.
+    pub(crate) fn push(&mut self, lits: &[Literal]) -> usize {
+        self.lits.extend_from_slice(lits);
+        self.starts.push(self.lits.len());
+        self.len() - 1
+    }
+
+    /// Copy a clause out of the database.
+    pub(crate) fn clause(&self, i: usize) -> Clause {
+        self[i].iter().copied().collect()
+    }
+
+    /// Copy a range of clauses out of the database.
+    pub(crate) fn clauses(&self, range: Range<usize>) -> Vec<Clause> {
+        range.map(|i| self.clause(i)).collect()
+    }
+
+    /// Iterate over the literals of each clause, in order.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &[Literal]> + '_ {
+        self.starts
+            .windows(2)
+            .map(move |bounds| &self.lits[bounds[0]..bounds[1]])
+    }
+}
+
+impl Index<usize> for ClauseDb {
+    type Output = [Literal];
+
+    fn index(&self, i: usize) -> &[Literal] {
+        &self.lits[self.starts[i]..self.starts[i + 1]]
+    }
+}
+
+impl IndexMut<usize> for ClauseDb {
+    fn index_mut(&mut self, i: usize) -> &mut [Literal] {
+        &mut self.lits[self.starts[i]..self.starts[i + 1]]
+    }
+}
+
+impl From<&Formula> for ClauseDb {
+    fn from(formula: &Formula) -> ClauseDb {
+        let num_lits = formula.iter().map(<[Literal]>::len).sum();
+        let mut db = ClauseDb::with_capacity(formula.len(), num_lits);
+        for lits in formula.iter() {
+            db.push(lits);
+        }
+        db
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stores_clauses_flat() {
+        let mut db = ClauseDb::from(&cnf![[1, -2], [], [3]]);
+        assert_eq!(3, db.len());
+        assert_eq!(3, db.push(&[Literal::new(-1), Literal::new(2)]));
+        db[0].swap(0, 1);
+
+        assert_eq!(
+            Clause::from(vec![Literal::new(-2), Literal::new(1)]),
+            db.clause(0)
+        );
+        assert_eq!(vec![clause![], clause![3]], db.clauses(1..3));
+        assert_eq!(
+            vec![2, 0, 1, 2],
+            db.iter().map(<[Literal]>::len).collect::<Vec<_>>()
+        );
+    }
+}
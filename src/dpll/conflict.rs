@@ -0,0 +1,148 @@
+//! Implication graphs of conflicts, for visualising why a search
+//! failed.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::cnf::Literal;
+use crate::dpll::clauses::ClauseDb;
+use crate::dpll::{Assignment, Model, Provenance};
+use crate::graph::Graph;
+
+/// A node in a conflict graph.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub enum ConflictNode {
+    /// A literal which was assigned at the given decision level.
+    Literal {
+        literal: Literal,
+        level: usize,
+        decision: bool,
+    },
+    /// The conflict itself.
+    Conflict,
+}
+
+/// The part of the implication graph which led to a conflict.  Edges
+/// go from the negations of the other literals of a clause to the
+/// literal that clause implied (or to the conflict node, for the
+/// conflicting clause), and are labelled with the clause index.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub struct ConflictGraph {
+    clause: usize,
+    nodes: Vec<ConflictNode>,
+    edges: Vec<(usize, usize, usize)>,
+}
+
+impl ConflictGraph {
+    /// Construct the conflict graph of a model which falsifies the
+    /// given clause.
+    pub(crate) fn new(clauses: &ClauseDb, model: &Model, conflict: usize) -> ConflictGraph {
+        let trail = &model.trail;
+
+        let positions: BTreeMap<Literal, usize> =
+            trail.iter().enumerate().map(|(i, a)| (a.lit, i)).collect();
+
+        // walk backwards from the conflict, finding the antecedents of
+        // each literal, and remembering the edges.
+        let mut seen = BTreeSet::new();
+        let mut edges = Vec::new();
+        let mut todo = Vec::new();
+        let mut antecedents = |lits: &[Literal],
+                               skip: Option<Literal>,
+                               to: Option<usize>,
+                               reason: usize,
+                               todo: &mut Vec<usize>| {
+            for lit in lits {
+                if Some(*lit) == skip {
+                    continue;
+                }
+                if let Some(&from) = positions.get(&lit.negate()) {
+                    edges.push((from, to, reason));
+                    if seen.insert(from) {
+                        todo.push(from);
+                    }
+                }
+            }
+        };
+
+        antecedents(&clauses[conflict], None, None, conflict, &mut todo);
+        while let Some(i) = todo.pop() {
+            let Assignment {
+                lit, provenance, ..
+            } = trail[i];
+            if let Provenance::UnitPropagation(reason) = provenance {
+                antecedents(&clauses[reason], Some(lit), Some(i), reason, &mut todo);
+            }
+        }
+
+        // number the nodes in trail order, with the conflict last.
+        let mut indices = BTreeMap::new();
+        let mut nodes = Vec::with_capacity(seen.len() + 1);
+        for &i in &seen {
+            let Assignment {
+                lit,
+                provenance,
+                level,
+            } = trail[i];
+            indices.insert(i, nodes.len());
+            nodes.push(ConflictNode::Literal {
+                literal: lit,
+                level,
+                decision: provenance == Provenance::Decision,
+            });
+        }
+        let conflict_node = nodes.len();
+        nodes.push(ConflictNode::Conflict);
+
+        let mut edges: Vec<(usize, usize, usize)> = edges
+            .into_iter()
+            .map(|(from, to, reason)| {
+                let to = to.map_or(conflict_node, |t| indices[&t]);
+                (indices[&from], to, reason)
+            })
+            .collect();
+        edges.sort();
+        edges.dedup();
+
+        ConflictGraph {
+            clause: conflict,
+            nodes,
+            edges,
+        }
+    }
+
+    /// The index of the clause which was falsified.
+    pub fn clause(&self) -> usize {
+        self.clause
+    }
+
+    /// The nodes of the graph.
+    pub fn nodes(&self) -> &[ConflictNode] {
+        &self.nodes
+    }
+
+    /// The edges of the graph, as (from, to, clause index) triples.
+    pub fn edges(&self) -> &[(usize, usize, usize)] {
+        &self.edges
+    }
+
+    /// Convert to a labelled graph, for exporting.
+    pub fn to_graph(&self) -> Graph {
+        let mut graph = Graph::directed();
+        for node in &self.nodes {
+            let label = match node {
+                ConflictNode::Literal {
+                    literal,
+                    level,
+                    decision: true,
+                } => format!("{} @ {} (decision)", literal, level),
+                ConflictNode::Literal { literal, level, .. } => format!("{} @ {}", literal, level),
+                ConflictNode::Conflict => format!("conflict (clause {})", self.clause),
+            };
+            graph.add_node(label);
+        }
+        for (from, to, reason) in &self.edges {
+            graph.add_edge(*from, *to, Some(format!("clause {}", reason)));
+        }
+        graph
+    }
+}
@@ -0,0 +1,326 @@
+//! Branching heuristics, which choose the literal to decide next.
+
+use crate::cnf::{Clause, Literal};
+use crate::dpll::clauses::ClauseDb;
+use crate::dpll::lit_index;
+
+/// How to choose the next decision.  Whatever the heuristic, atoms
+/// which only occur in satisfied clauses are decided last, in order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd, Eq, Ord)]
+pub enum Heuristic {
+    /// The lowest unassigned atom, with its saved phase.
+    #[default]
+    Ordered,
+    /// Jeroslow-Wang: the atom whose literals occur most in the
+    /// unsatisfied clauses, weighting each occurrence by 2^-n for a
+    /// clause of length n, so short clauses count for more.  The
+    /// literal with the higher weight is made true.
+    JeroslowWang,
+    /// Dynamic largest individual sum (DLIS): the literal which
+    /// occurs in the most unsatisfied clauses.
+    Dlis,
+    /// Variable move-to-front (VMTF): atoms are kept in a queue, and
+    /// the atoms of each learned clause are moved to the front.  The
+    /// unassigned atom nearest the front is decided, with its saved
+    /// phase.
+    Vmtf,
+    /// The lowest unassigned atom which isn't the output of a gate
+    /// defined by the clauses, with its saved phase.  Gate outputs
+    /// follow from their inputs by propagation, so deciding the
+    /// inputs of an encoded circuit is usually better.
+    Inputs,
+}
+
+/// Pick a literal by the Jeroslow-Wang heuristic.  Returns 'None' if
+/// every clause is satisfied.
+pub(crate) fn jeroslow_wang<F>(clauses: &ClauseDb, value: F, num_atoms: usize) -> Option<Literal>
+where
+    F: Fn(Literal) -> Option<bool>,
+{
+    let scores = scores(clauses, value, num_atoms, |len| (-(len as f64)).exp2());
+
+    let mut best = None;
+    let mut best_score = 0.0;
+    for id in 1..=num_atoms {
+        let pos = Literal::new(id as isize);
+        let neg = pos.negate();
+        let score = scores[lit_index(pos)] + scores[lit_index(neg)];
+        if score > best_score {
+            best_score = score;
+            best = Some(if scores[lit_index(neg)] > scores[lit_index(pos)] {
+                neg
+            } else {
+                pos
+            });
+        }
+    }
+    best
+}
+
+/// Pick a literal by the DLIS heuristic.  Returns 'None' if every
+/// clause is satisfied.
+pub(crate) fn dlis<F>(clauses: &ClauseDb, value: F, num_atoms: usize) -> Option<Literal>
+where
+    F: Fn(Literal) -> Option<bool>,
+{
+    let scores = scores(clauses, value, num_atoms, |_| 1.0);
+
+    let mut best = None;
+    let mut best_score = 0.0;
+    for id in 1..=num_atoms {
+        for lit in [Literal::new(id as isize), Literal::new(-(id as isize))] {
+            if scores[lit_index(lit)] > best_score {
+                best_score = scores[lit_index(lit)];
+                best = Some(lit);
+            }
+        }
+    }
+    best
+}
+
+/// The queue of atoms for the VMTF heuristic, as a doubly-linked
+/// list threaded through vectors indexed by atom, with 0 as the null
+/// atom.  Each atom is stamped with when it was last moved to the
+/// front, so the queue is in descending order of stamp.
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd, Eq, Ord)]
+pub(crate) struct Vmtf {
+    /// The next atom towards the back of the queue.
+    next: Vec<usize>,
+    /// The next atom towards the front of the queue.
+    prev: Vec<usize>,
+    stamps: Vec<u64>,
+    front: usize,
+    /// Every atom in front of this one is assigned.
+    search: usize,
+    counter: u64,
+}
+
+impl Vmtf {
+    /// Construct a queue of the atoms which occur, with lower atoms
+    /// nearer the front.
+    pub(crate) fn new(occurs: &[bool]) -> Vmtf {
+        let mut vmtf = Vmtf::default();
+        vmtf.extend(occurs);
+        vmtf
+    }
+
+    /// Construct a queue with the given atoms, front first, for atoms
+    /// up to 'len - 1'.
+    pub(crate) fn from_order(order: &[usize], len: usize) -> Vmtf {
+        let mut vmtf = Vmtf {
+            next: vec![0; len],
+            prev: vec![0; len],
+            stamps: vec![0; len],
+            ..Vmtf::default()
+        };
+        for id in order.iter().rev() {
+            vmtf.push_front(*id);
+        }
+        vmtf.search = vmtf.front;
+        vmtf
+    }
+
+    /// The atoms in the queue, front first.
+    pub(crate) fn order(&self) -> Vec<usize> {
+        let mut order = Vec::new();
+        let mut id = self.front;
+        while id != 0 {
+            order.push(id);
+            id = self.next[id];
+        }
+        order
+    }
+
+    /// Add the atoms which occur but aren't in the queue yet to the
+    /// front, with lower atoms nearer the front.
+    pub(crate) fn extend(&mut self, occurs: &[bool]) {
+        if self.stamps.len() < occurs.len() {
+            self.next.resize(occurs.len(), 0);
+            self.prev.resize(occurs.len(), 0);
+            self.stamps.resize(occurs.len(), 0);
+        }
+        for id in (1..occurs.len()).rev() {
+            // every atom in the queue has a stamp of at least 1.
+            if occurs[id] && self.stamps[id] == 0 {
+                self.push_front(id);
+            }
+        }
+        self.search = self.front;
+    }
+
+    /// Move the atoms of a clause to the front of the queue, keeping
+    /// their relative order.
+    pub(crate) fn bump(&mut self, clause: &Clause) {
+        let mut ids: Vec<usize> = clause
+            .literals()
+            .iter()
+            .map(|lit| lit.get_id() as usize)
+            .collect();
+        ids.sort_by_key(|id| self.stamps[*id]);
+        for id in ids {
+            if id != self.front {
+                self.unlink(id);
+                self.push_front(id);
+            }
+        }
+        self.search = self.front;
+    }
+
+    /// Note that an atom has become unassigned.
+    pub(crate) fn unassign(&mut self, id: usize) {
+        if self.stamps[id] > self.stamps[self.search] {
+            self.search = id;
+        }
+    }
+
+    /// Find the unassigned atom nearest the front of the queue.
+    pub(crate) fn pick(&mut self, values: &[Option<bool>]) -> Option<usize> {
+        let mut id = self.search;
+        while id != 0 && values[id].is_some() {
+            id = self.next[id];
+        }
+        if id == 0 {
+            None
+        } else {
+            self.search = id;
+            Some(id)
+        }
+    }
+
+    fn unlink(&mut self, id: usize) {
+        let (prev, next) = (self.prev[id], self.next[id]);
+        if prev == 0 {
+            self.front = next;
+        } else {
+            self.next[prev] = next;
+        }
+        if next != 0 {
+            self.prev[next] = prev;
+        }
+    }
+
+    fn push_front(&mut self, id: usize) {
+        self.counter += 1;
+        self.stamps[id] = self.counter;
+        self.prev[id] = 0;
+        self.next[id] = self.front;
+        if self.front != 0 {
+            self.prev[self.front] = id;
+        }
+        self.front = id;
+    }
+}
+
+/// Sum the weights of the unsatisfied clauses each unassigned literal
+/// occurs in, indexed by 'lit_index'.
+fn scores<F, W>(clauses: &ClauseDb, value: F, num_atoms: usize, weight: W) -> Vec<f64>
+where
+    F: Fn(Literal) -> Option<bool>,
+    W: Fn(usize) -> f64,
+{
+    let mut scores = vec![0.0; 2 * num_atoms + 2];
+
+    for lits in clauses.iter() {
+        if lits.iter().any(|lit| value(*lit) == Some(true)) {
+            continue;
+        }
+        let w = weight(lits.len());
+        for lit in lits {
+            if value(*lit).is_none() {
+                scores[lit_index(*lit)] += w;
+            }
+        }
+    }
+
+    scores
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cnf::*;
+    use crate::dpll::Config;
+    use crate::solver::Solver;
+    use crate::testing::*;
+    use crate::theory::empty::Empty;
+
+    #[test]
+    fn jeroslow_wang_prefers_short_clauses() {
+        let formula = Formula::new(vec![
+            Clause::new(vec![1, 2, 3]),
+            Clause::new(vec![1, 3, 4]),
+            Clause::new(vec![-2, 5]),
+            Clause::new(vec![-2, -4]),
+        ]);
+        // 1 is in two clauses of length 3 (1/4), but -2 is in two
+        // clauses of length 2 (1/2).
+        assert_eq!(
+            Some(Literal::new(-2)),
+            jeroslow_wang(&ClauseDb::from(&formula), |_| None, 5)
+        );
+    }
+
+    #[test]
+    fn dlis_ignores_satisfied_clauses() {
+        let formula = Formula::new(vec![
+            Clause::new(vec![1, 2]),
+            Clause::new(vec![1, 3]),
+            Clause::new(vec![-3, 2]),
+        ]);
+        let clauses = ClauseDb::from(&formula);
+        assert_eq!(Some(Literal::new(1)), dlis(&clauses, |_| None, 3));
+
+        let value = |lit: Literal| {
+            if lit.get_id() == 1 {
+                Some(!lit.is_negated())
+            } else {
+                None
+            }
+        };
+        assert_eq!(Some(Literal::new(2)), dlis(&clauses, value, 3));
+    }
+
+    #[test]
+    fn vmtf_picks_recently_bumped_atoms() {
+        let mut vmtf = Vmtf::new(&[false, true, true, true, true]);
+        let mut values = vec![None; 5];
+        assert_eq!(Some(1), vmtf.pick(&values));
+
+        vmtf.bump(&Clause::new(vec![-4, 3]));
+        assert_eq!(Some(3), vmtf.pick(&values));
+
+        values[3] = Some(true);
+        assert_eq!(Some(4), vmtf.pick(&values));
+        values[4] = Some(false);
+        assert_eq!(Some(1), vmtf.pick(&values));
+
+        values[3] = None;
+        vmtf.unassign(3);
+        assert_eq!(Some(3), vmtf.pick(&values));
+    }
+
+    #[test]
+    fn every_heuristic_agrees_with_brute_force() {
+        for (num_vars, formula) in random_formulae(0x2545_f491_4f6c_dd1d, 300) {
+            let brute_force = brute_force_sat(num_vars, &formula, &[]);
+            for heuristic in [
+                Heuristic::JeroslowWang,
+                Heuristic::Dlis,
+                Heuristic::Vmtf,
+                Heuristic::Inputs,
+            ] {
+                let mut solver = Solver::new(Empty::new(), formula.clone());
+                solver.set_config(Config {
+                    heuristic,
+                    ..Config::default()
+                });
+                assert_eq!(
+                    brute_force,
+                    solver.solve().unwrap().is_sat(),
+                    "{:?}",
+                    heuristic
+                );
+            }
+        }
+    }
+}
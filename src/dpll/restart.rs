@@ -0,0 +1,159 @@
+//! Restart schedules.  A restart undoes every decision, keeping the
+//! learned clauses and saved phases, which lets the search escape
+//! from unproductive parts of the search space.
+
+use std::collections::VecDeque;
+
+/// When to restart, in terms of the number of conflicts since the
+/// last restart.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum RestartPolicy {
+    /// Never restart.
+    Never,
+    /// Restart after `unit * luby(n)` conflicts, where `luby` is the
+    /// sequence 1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8, ...
+    Luby { unit: usize },
+    /// Restart after `first` conflicts, then `first * factor`, then
+    /// `first * factor * factor`, and so on.
+    Geometric { first: usize, factor: f64 },
+    /// Restart when the clauses being learned are getting worse, as
+    /// in Glucose: when the average literal block distance (LBD) of
+    /// the last `window` learned clauses, scaled by `k`, exceeds the
+    /// average LBD of all learned clauses.
+    Glucose { window: usize, k: f64 },
+}
+
+impl Default for RestartPolicy {
+    fn default() -> RestartPolicy {
+        RestartPolicy::Luby { unit: 100 }
+    }
+}
+
+/// Keeps track of when the next restart is due.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub(crate) struct Restarts {
+    policy: RestartPolicy,
+    restarts: usize,
+    conflicts: usize,
+    limit: f64,
+    recent_lbds: VecDeque<usize>,
+    recent_lbd_sum: usize,
+    total_lbd_sum: usize,
+    total_conflicts: usize,
+}
+
+impl Restarts {
+    pub(crate) fn new(policy: RestartPolicy) -> Restarts {
+        let mut restarts = Restarts {
+            policy,
+            restarts: 0,
+            conflicts: 0,
+            limit: 0.0,
+            recent_lbds: VecDeque::new(),
+            recent_lbd_sum: 0,
+            total_lbd_sum: 0,
+            total_conflicts: 0,
+        };
+        restarts.limit = restarts.next_limit();
+        restarts
+    }
+
+    /// Record a conflict, and the LBD of the clause learned from it,
+    /// returning true if it's time to restart.
+    pub(crate) fn conflict(&mut self, lbd: usize) -> bool {
+        self.conflicts += 1;
+        self.total_conflicts += 1;
+        self.total_lbd_sum += lbd;
+
+        let due = match self.policy {
+            RestartPolicy::Never => false,
+            RestartPolicy::Glucose { window, k } => {
+                self.recent_lbds.push_back(lbd);
+                self.recent_lbd_sum += lbd;
+                if self.recent_lbds.len() > window {
+                    self.recent_lbd_sum -= self.recent_lbds.pop_front().unwrap_or(0);
+                }
+                let recent_average = self.recent_lbd_sum as f64 / window as f64;
+                let total_average = self.total_lbd_sum as f64 / self.total_conflicts as f64;
+                self.recent_lbds.len() == window && recent_average * k > total_average
+            }
+            _ => self.conflicts as f64 >= self.limit,
+        };
+        if !due {
+            return false;
+        }
+
+        self.restarts += 1;
+        self.conflicts = 0;
+        self.limit = self.next_limit();
+        // glucose restarts need a full window of new clauses before
+        // the next one.
+        self.recent_lbds.clear();
+        self.recent_lbd_sum = 0;
+        true
+    }
+
+    /// The number of conflicts before the next restart.
+    fn next_limit(&self) -> f64 {
+        match self.policy {
+            RestartPolicy::Never | RestartPolicy::Glucose { .. } => f64::INFINITY,
+            RestartPolicy::Luby { unit } => (unit * luby(self.restarts + 1)) as f64,
+            RestartPolicy::Geometric { first, factor } => {
+                first as f64 * factor.powi(self.restarts as i32)
+            }
+        }
+    }
+}
+
+/// The nth element of the Luby sequence, counting from 1.
+fn luby(mut n: usize) -> usize {
+    loop {
+        let mut k = 1;
+        while (1 << k) - 1 < n {
+            k += 1;
+        }
+        if (1 << k) - 1 == n {
+            return 1 << (k - 1);
+        }
+        n -= (1 << (k - 1)) - 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn luby_sequence() {
+        let sequence: Vec<usize> = (1..=15).map(luby).collect();
+        assert_eq!(vec![1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8], sequence);
+    }
+
+    #[test]
+    fn geometric_schedule() {
+        let mut restarts = Restarts::new(RestartPolicy::Geometric {
+            first: 2,
+            factor: 2.0,
+        });
+        let schedule: Vec<bool> = (0..14).map(|_| restarts.conflict(1)).collect();
+        let restart_after: Vec<usize> = schedule
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| **r)
+            .map(|(i, _)| i + 1)
+            .collect();
+        assert_eq!(vec![2, 6, 14], restart_after);
+    }
+
+    #[test]
+    fn glucose_restarts_when_lbd_rises() {
+        let mut restarts = Restarts::new(RestartPolicy::Glucose { window: 3, k: 0.8 });
+        for _ in 0..10 {
+            assert!(!restarts.conflict(2));
+        }
+        assert!(!restarts.conflict(4));
+        assert!(restarts.conflict(4));
+        // the window is cleared by a restart
+        assert!(!restarts.conflict(8));
+    }
+}
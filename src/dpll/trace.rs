@@ -0,0 +1,72 @@
+//! Traces of a search, as the sequence of transitions it makes.  The
+//! transitions are those of the abstract DPLL(T) system in the paper,
+//! extended with the restarts and pure literals of this solver.
+
+use crate::cnf::{Clause, Literal};
+
+/// A step of the search.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub enum Transition {
+    /// A literal was assigned because every other literal of the
+    /// clause is false.
+    UnitPropagate { lit: Literal, clause: Clause },
+    /// A literal was assigned because the theory implies it.
+    TheoryPropagate(Literal),
+    /// A literal was assigned because its negation doesn't occur in
+    /// any clause which isn't satisfied yet.
+    PureLiteral(Literal),
+    /// A literal was assigned by choice, starting a new level.
+    Decide(Literal),
+    /// The clause is false with no decisions in the model, so the
+    /// formula is unsatisfiable.  If there's no clause, an assumption
+    /// is false instead, so the formula is unsatisfiable under the
+    /// assumptions.
+    Fail(Option<Clause>),
+    /// The clause is false, and the search backjumps to an earlier
+    /// level.  A clause is learned next, whose first literal is then
+    /// unit propagated.
+    Backjump { conflict: Clause, level: usize },
+    /// A clause was added to the clause database.
+    Learn(Clause),
+    /// Every decision was undone.
+    Restart,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cnf::*;
+    use crate::solver::Solver;
+    use crate::theory::empty::Empty;
+
+    #[test]
+    fn records_transitions() {
+        let mut solver = Solver::new(
+            Empty::new(),
+            Formula::new(vec![
+                Clause::new(vec![1, 2]),
+                Clause::new(vec![1, -2]),
+                Clause::new(vec![-1, 2]),
+                Clause::new(vec![-1, -2]),
+            ]),
+        );
+        solver.record_trace(true);
+        solver.solve().unwrap();
+
+        // deciding 1 forces 2 and -2, so -1 is learned, which forces
+        // both 2 and -2 again with no decisions left to undo.
+        let trace = solver.trace();
+        let (one, two) = (Literal::new(1), Literal::new(2));
+        assert_eq!(7, trace.len());
+        assert_eq!(Transition::Decide(one), trace[0]);
+        assert!(matches!(trace[1], Transition::UnitPropagate { lit, .. } if lit == two));
+        assert!(matches!(trace[2], Transition::Backjump { level: 0, .. }));
+        assert_eq!(
+            Transition::Learn(Clause::from(vec![one.negate()])),
+            trace[3]
+        );
+        assert!(matches!(trace[4], Transition::UnitPropagate { lit, .. } if lit == one.negate()));
+        assert!(matches!(trace[5], Transition::UnitPropagate { lit, .. } if lit == two));
+        assert!(matches!(trace[6], Transition::Fail(Some(_))));
+    }
+}
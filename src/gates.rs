@@ -0,0 +1,348 @@
+//! Gates defined by the clauses of a formula.  Encoding a circuit as
+//! CNF (the Tseitin transformation) introduces an atom for each gate,
+//! with clauses saying it equals the gate applied to its inputs.
+//! Recognising those clauses recovers some of the circuit, which is
+//! useful for simplification and for choosing decisions: the output
+//! of a gate follows from its inputs by propagation, so it's better
+//! to decide the inputs.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::cnf::{Formula, Literal};
+
+/// A gate: the output literal is equivalent to a function of the
+/// input literals.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub enum Gate {
+    /// The output is true if every input is.
+    And {
+        output: Literal,
+        inputs: Vec<Literal>,
+    },
+    /// The output is true if some input is.
+    Or {
+        output: Literal,
+        inputs: Vec<Literal>,
+    },
+    /// The output is true if exactly one input is.
+    Xor {
+        output: Literal,
+        inputs: (Literal, Literal),
+    },
+    /// The output is the 'then' input if the condition is true, and
+    /// the 'otherwise' input if not.
+    Ite {
+        output: Literal,
+        condition: Literal,
+        then: Literal,
+        otherwise: Literal,
+    },
+}
+
+impl Gate {
+    /// The output of the gate.
+    pub fn output(&self) -> Literal {
+        match self {
+            Gate::And { output, .. }
+            | Gate::Or { output, .. }
+            | Gate::Xor { output, .. }
+            | Gate::Ite { output, .. } => *output,
+        }
+    }
+
+    /// The inputs of the gate.
+    pub fn inputs(&self) -> Vec<Literal> {
+        match self {
+            Gate::And { inputs, .. } | Gate::Or { inputs, .. } => inputs.clone(),
+            Gate::Xor { inputs, .. } => vec![inputs.0, inputs.1],
+            Gate::Ite {
+                condition,
+                then,
+                otherwise,
+                ..
+            } => vec![*condition, *then, *otherwise],
+        }
+    }
+}
+
+impl Formula {
+    /// Find the gates defined by the clauses of the formula, at most
+    /// one for each atom, in order of their output atoms.  And and or
+    /// gates are preferred, then xor, then if-then-else.  The clauses
+    /// of a gate may be used by others, so the gates can define some
+    /// atoms in terms of each other.
+    pub fn gates(&self) -> Vec<Gate> {
+        let mut normalised: Vec<Vec<Literal>> = Vec::new();
+        for lits in self.iter() {
+            let mut lits = lits.to_vec();
+            lits.sort();
+            lits.dedup();
+            if !lits
+                .iter()
+                .any(|lit| lits.binary_search(&lit.negate()).is_ok())
+            {
+                normalised.push(lits);
+            }
+        }
+
+        let mut occurrences: HashMap<Literal, Vec<usize>> = HashMap::new();
+        for (i, lits) in normalised.iter().enumerate() {
+            for lit in lits {
+                occurrences.entry(*lit).or_default().push(i);
+            }
+        }
+        let ternary: HashSet<&[Literal]> = normalised
+            .iter()
+            .filter(|lits| lits.len() == 3)
+            .map(Vec::as_slice)
+            .collect();
+        let finder = Finder {
+            clauses: &normalised,
+            occurrences: &occurrences,
+            ternary: &ternary,
+        };
+
+        let atoms: BTreeSet<isize> = normalised
+            .iter()
+            .flatten()
+            .map(|lit| lit.get_id())
+            .collect();
+        atoms
+            .into_iter()
+            .filter_map(|id| {
+                let pos = Literal::new(id);
+                finder
+                    .and(pos)
+                    .or_else(|| finder.and(pos.negate()))
+                    .or_else(|| finder.xor(pos))
+                    .or_else(|| finder.ite(pos))
+                    .or_else(|| finder.ite(pos.negate()))
+            })
+            .collect()
+    }
+}
+
+/// Indexes of sorted, deduplicated, non-tautological clauses, for
+/// finding gates.
+struct Finder<'a> {
+    clauses: &'a [Vec<Literal>],
+    occurrences: &'a HashMap<Literal, Vec<usize>>,
+    ternary: &'a HashSet<&'a [Literal]>,
+}
+
+impl Finder<'_> {
+    /// The clauses a literal occurs in.
+    fn containing(&self, lit: Literal) -> impl Iterator<Item = &[Literal]> + '_ {
+        self.occurrences
+            .get(&lit)
+            .into_iter()
+            .flatten()
+            .map(move |i| self.clauses[*i].as_slice())
+    }
+
+    /// Check if there's a clause of three literals.
+    fn has_ternary(&self, lits: [Literal; 3]) -> bool {
+        let mut lits = lits;
+        lits.sort();
+        self.ternary.contains(&lits[..])
+    }
+
+    /// Find an and gate defining a literal: o = a && b && ... is the
+    /// clauses (-o a), (-o b), ..., and (o -a -b ...).  If the literal
+    /// is negative, this is an or gate defining its atom.
+    fn and(&self, output: Literal) -> Option<Gate> {
+        let implied: HashSet<Literal> = self
+            .containing(output.negate())
+            .filter(|lits| lits.len() == 2)
+            .flat_map(|lits| lits.iter().copied().filter(|lit| *lit != output.negate()))
+            .collect();
+
+        let inputs: Vec<Literal> = self
+            .containing(output)
+            .filter(|lits| lits.len() > 2)
+            .map(|lits| {
+                lits.iter()
+                    .filter(|lit| **lit != output)
+                    .map(|lit| lit.negate())
+                    .collect::<Vec<Literal>>()
+            })
+            .find(|inputs| inputs.iter().all(|input| implied.contains(input)))?;
+
+        Some(if output.is_negated() {
+            let mut inputs: Vec<Literal> = inputs.iter().map(|lit| lit.negate()).collect();
+            inputs.sort();
+            Gate::Or {
+                output: output.negate(),
+                inputs,
+            }
+        } else {
+            let mut inputs = inputs;
+            inputs.sort();
+            Gate::And { output, inputs }
+        })
+    }
+
+    /// Find an xor gate defining an atom: o = a ^ b is the four
+    /// clauses over o, a, and b which each have an odd number of
+    /// negative literals.  With an even number, it defines -o.  The
+    /// atom must be the highest of the three.
+    fn xor(&self, output: Literal) -> Option<Gate> {
+        for lit in [output, output.negate()] {
+            for lits in self.containing(lit).filter(|lits| lits.len() == 3) {
+                let mut others = lits.iter().filter(|l| l.get_id() != output.get_id());
+                let (a, b) = match (others.next(), others.next()) {
+                    (Some(a), Some(b)) => (Literal::new(a.get_id()), Literal::new(b.get_id())),
+                    _ => continue,
+                };
+                // the clauses define each atom as the xor of the
+                // others, so only take the highest as the output.
+                if a.get_id() > output.get_id() || b.get_id() > output.get_id() {
+                    continue;
+                }
+                let odd = lits.iter().filter(|l| l.is_negated()).count() % 2 == 1;
+
+                let mut all = true;
+                for negated in 0..8 {
+                    let signs = [negated & 1 == 1, negated & 2 == 2, negated & 4 == 4];
+                    if (signs.iter().filter(|s| **s).count() % 2 == 1) != odd {
+                        continue;
+                    }
+                    let clause = [output, a, b];
+                    let clause = [0, 1, 2].map(|i| {
+                        if signs[i] {
+                            clause[i].negate()
+                        } else {
+                            clause[i]
+                        }
+                    });
+                    all &= self.has_ternary(clause);
+                }
+                if all {
+                    return Some(Gate::Xor {
+                        output: if odd { output } else { output.negate() },
+                        inputs: (a, b),
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    /// Find an if-then-else gate defining a literal: o = c ? t : e is
+    /// the clauses (-o -c t), (-o c e), (o -c -t), and (o c -e).  If
+    /// the literal is negative, this defines its atom as c ? -t : -e.
+    fn ite(&self, output: Literal) -> Option<Gate> {
+        for lits in self
+            .containing(output.negate())
+            .filter(|lits| lits.len() == 3)
+        {
+            let others: Vec<Literal> = lits
+                .iter()
+                .copied()
+                .filter(|lit| *lit != output.negate())
+                .collect();
+            for (p, then) in [(others[0], others[1]), (others[1], others[0])] {
+                let condition = p.negate();
+                for lits in self
+                    .containing(output.negate())
+                    .filter(|lits| lits.len() == 3 && lits.contains(&condition))
+                {
+                    let otherwise = match lits
+                        .iter()
+                        .find(|lit| **lit != output.negate() && **lit != condition)
+                    {
+                        Some(lit) => *lit,
+                        None => continue,
+                    };
+                    // with then and otherwise on the same atom, this is an xor.
+                    if otherwise.get_id() == then.get_id()
+                        || otherwise.get_id() == condition.get_id()
+                    {
+                        continue;
+                    }
+                    if self.has_ternary([output, condition.negate(), then.negate()])
+                        && self.has_ternary([output, condition, otherwise.negate()])
+                    {
+                        return Some(if output.is_negated() {
+                            Gate::Ite {
+                                output: output.negate(),
+                                condition,
+                                then: then.negate(),
+                                otherwise: otherwise.negate(),
+                            }
+                        } else {
+                            Gate::Ite {
+                                output,
+                                condition,
+                                then,
+                                otherwise,
+                            }
+                        });
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cnf::*;
+
+    fn lit(l: isize) -> Literal {
+        Literal::new(l)
+    }
+
+    #[test]
+    fn finds_tseitin_gates() {
+        let formula = Formula::new(vec![
+            // 5 = 1 && 2
+            Clause::new(vec![-5, 1]),
+            Clause::new(vec![-5, 2]),
+            Clause::new(vec![5, -1, -2]),
+            // 6 = 3 || -4
+            Clause::new(vec![6, -3]),
+            Clause::new(vec![6, 4]),
+            Clause::new(vec![-6, 3, -4]),
+            // 7 = 1 ^ 3
+            Clause::new(vec![-7, 1, 3]),
+            Clause::new(vec![-7, -1, -3]),
+            Clause::new(vec![7, -1, 3]),
+            Clause::new(vec![7, 1, -3]),
+            // 8 = 5 ? 6 : 7
+            Clause::new(vec![-8, -5, 6]),
+            Clause::new(vec![-8, 5, 7]),
+            Clause::new(vec![8, -5, -6]),
+            Clause::new(vec![8, 5, -7]),
+            Clause::new(vec![8]),
+        ]);
+
+        let gates = formula.gates();
+        let outputs: Vec<Literal> = gates.iter().map(Gate::output).collect();
+        assert_eq!(vec![lit(5), lit(6), lit(7), lit(8)], outputs);
+        assert_eq!(
+            Gate::And {
+                output: lit(5),
+                inputs: vec![lit(1), lit(2)]
+            },
+            gates[0]
+        );
+        assert_eq!(
+            Gate::Or {
+                output: lit(6),
+                inputs: vec![lit(-4), lit(3)]
+            },
+            gates[1]
+        );
+        assert_eq!(
+            Gate::Xor {
+                output: lit(7),
+                inputs: (lit(1), lit(3))
+            },
+            gates[2]
+        );
+        assert_eq!(vec![lit(5), lit(6), lit(7)], gates[3].inputs());
+    }
+}
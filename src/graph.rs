@@ -0,0 +1,376 @@
+//! Simple labelled graphs, for exporting things to standard graph
+//! tooling in DOT or GraphML format, and the variable interaction
+//! graph of a formula.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+
+use crate::cnf::{Formula, Var};
+
+/// A graph with labelled nodes and (optionally) labelled edges.
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd, Eq, Ord)]
+pub struct Graph {
+    directed: bool,
+    nodes: Vec<String>,
+    edges: Vec<(usize, usize, Option<String>)>,
+}
+
+impl Graph {
+    /// Construct a new empty directed graph.
+    pub fn directed() -> Graph {
+        Graph {
+            directed: true,
+            ..Graph::default()
+        }
+    }
+
+    /// Construct a new empty undirected graph.
+    pub fn undirected() -> Graph {
+        Graph::default()
+    }
+
+    /// Add a node, returning its index.
+    pub fn add_node(&mut self, label: String) -> usize {
+        self.nodes.push(label);
+        self.nodes.len() - 1
+    }
+
+    /// Add an edge between two nodes.
+    pub fn add_edge(&mut self, from: usize, to: usize, label: Option<String>) {
+        self.edges.push((from, to, label));
+    }
+
+    /// Check if the graph is directed.
+    pub fn is_directed(&self) -> bool {
+        self.directed
+    }
+
+    /// Get the node labels, in index order.
+    pub fn nodes(&self) -> &[String] {
+        &self.nodes
+    }
+
+    /// Get the edges.
+    pub fn edges(&self) -> &[(usize, usize, Option<String>)] {
+        &self.edges
+    }
+
+    /// Write the graph in DOT format.
+    pub fn write_dot<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let (keyword, arrow) = if self.directed {
+            ("digraph", "->")
+        } else {
+            ("graph", "--")
+        };
+
+        writeln!(writer, "{} {{", keyword)?;
+        for (i, label) in self.nodes.iter().enumerate() {
+            writeln!(writer, "  n{} [label=\"{}\"];", i, escape_dot(label))?;
+        }
+        for (from, to, olabel) in &self.edges {
+            match olabel {
+                Some(label) => writeln!(
+                    writer,
+                    "  n{} {} n{} [label=\"{}\"];",
+                    from,
+                    arrow,
+                    to,
+                    escape_dot(label)
+                )?,
+                None => writeln!(writer, "  n{} {} n{};", from, arrow, to)?,
+            }
+        }
+        writeln!(writer, "}}")?;
+        writer.flush()
+    }
+
+    /// Write the graph in GraphML format.
+    pub fn write_graphml<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let edgedefault = if self.directed {
+            "directed"
+        } else {
+            "undirected"
+        };
+
+        writeln!(writer, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(
+            writer,
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">"
+        )?;
+        writeln!(
+            writer,
+            "  <key id=\"label\" for=\"all\" attr.name=\"label\" attr.type=\"string\"/>"
+        )?;
+        writeln!(writer, "  <graph id=\"G\" edgedefault=\"{}\">", edgedefault)?;
+        for (i, label) in self.nodes.iter().enumerate() {
+            writeln!(
+                writer,
+                "    <node id=\"n{}\"><data key=\"label\">{}</data></node>",
+                i,
+                escape_xml(label)
+            )?;
+        }
+        for (from, to, olabel) in &self.edges {
+            match olabel {
+                Some(label) => writeln!(
+                    writer,
+                    "    <edge source=\"n{}\" target=\"n{}\"><data key=\"label\">{}</data></edge>",
+                    from,
+                    to,
+                    escape_xml(label)
+                )?,
+                None => writeln!(
+                    writer,
+                    "    <edge source=\"n{}\" target=\"n{}\"/>",
+                    from, to
+                )?,
+            }
+        }
+        writeln!(writer, "  </graph>")?;
+        writeln!(writer, "</graphml>")?;
+        writer.flush()
+    }
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The variable interaction graph of a formula: variables are
+/// connected if they occur together in some clause.
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd, Eq, Ord)]
+pub struct InteractionGraph {
+    adjacency: BTreeMap<Var, BTreeSet<Var>>,
+}
+
+impl Formula {
+    /// Compute the variable interaction graph.  A clause of length k
+    /// contributes up to k*(k-1)/2 edges, so this can be large for
+    /// formulae with long clauses.
+    pub fn interaction_graph(&self) -> InteractionGraph {
+        let mut adjacency: BTreeMap<Var, BTreeSet<Var>> = BTreeMap::new();
+
+        for lits in self.iter() {
+            for lit in lits {
+                let neighbours = adjacency.entry(lit.var()).or_default();
+                for other in lits {
+                    if other.var() != lit.var() {
+                        neighbours.insert(other.var());
+                    }
+                }
+            }
+        }
+
+        InteractionGraph { adjacency }
+    }
+
+    /// Split a formula into components which share no variables, in
+    /// the order of their first clauses.  The formula is satisfiable
+    /// if and only if every component is, and a model is the union of
+    /// models of the components.  An empty clause is a component by
+    /// itself.
+    pub fn components(&self) -> Vec<Formula> {
+        let num_atoms = self
+            .iter()
+            .flatten()
+            .map(|lit| lit.get_id() as usize)
+            .max()
+            .unwrap_or(0);
+
+        // union-find over atoms, with path halving.
+        let mut parents: Vec<usize> = (0..=num_atoms).collect();
+        fn find(parents: &mut [usize], mut atom: usize) -> usize {
+            while parents[atom] != atom {
+                parents[atom] = parents[parents[atom]];
+                atom = parents[atom];
+            }
+            atom
+        }
+        for lits in self.iter() {
+            if let Some((first, rest)) = lits.split_first() {
+                let root = find(&mut parents, first.get_id() as usize);
+                for lit in rest {
+                    let other = find(&mut parents, lit.get_id() as usize);
+                    parents[other] = root;
+                }
+            }
+        }
+
+        let mut components: Vec<Formula> = Vec::new();
+        let mut indices: BTreeMap<usize, usize> = BTreeMap::new();
+        for (i, lits) in self.iter().enumerate() {
+            let index = match lits.first() {
+                Some(lit) => *indices
+                    .entry(find(&mut parents, lit.get_id() as usize))
+                    .or_insert(components.len()),
+                None => components.len(),
+            };
+            if index == components.len() {
+                components.push(Formula::default());
+            }
+            components[index].insert_clause(self.clause(i));
+        }
+        components
+    }
+}
+
+impl InteractionGraph {
+    /// The variables in the graph, in ascending order.
+    pub fn variables(&self) -> impl Iterator<Item = Var> + '_ {
+        self.adjacency.keys().copied()
+    }
+
+    /// The variables which share a clause with the given one.
+    pub fn neighbours(&self, var: Var) -> impl Iterator<Item = Var> + '_ {
+        self.adjacency.get(&var).into_iter().flatten().copied()
+    }
+
+    /// The number of variables which share a clause with the given
+    /// one.
+    pub fn degree(&self, var: Var) -> usize {
+        self.adjacency.get(&var).map_or(0, BTreeSet::len)
+    }
+
+    /// The number of variables.
+    pub fn num_variables(&self) -> usize {
+        self.adjacency.len()
+    }
+
+    /// The number of edges.
+    pub fn num_edges(&self) -> usize {
+        self.adjacency.values().map(BTreeSet::len).sum::<usize>() / 2
+    }
+
+    /// A map from degree to the number of variables with that degree.
+    pub fn degree_distribution(&self) -> BTreeMap<usize, usize> {
+        let mut distribution = BTreeMap::new();
+        for neighbours in self.adjacency.values() {
+            *distribution.entry(neighbours.len()).or_insert(0) += 1;
+        }
+        distribution
+    }
+
+    /// Partition the variables into communities by label propagation:
+    /// each variable repeatedly adopts the most common community of
+    /// its neighbours (breaking ties towards the smallest), until
+    /// nothing changes.  This is deterministic, but not necessarily
+    /// optimal.
+    pub fn communities(&self) -> Vec<BTreeSet<Var>> {
+        const MAX_ROUNDS: usize = 100;
+
+        let mut labels: BTreeMap<Var, Var> = self.variables().map(|v| (v, v)).collect();
+        for _ in 0..MAX_ROUNDS {
+            let mut changed = false;
+            for (var, neighbours) in &self.adjacency {
+                let mut counts: BTreeMap<Var, usize> = BTreeMap::new();
+                for n in neighbours {
+                    *counts.entry(labels[n]).or_insert(0) += 1;
+                }
+                let best = counts
+                    .iter()
+                    .max_by(|(l1, c1), (l2, c2)| c1.cmp(c2).then(l2.cmp(l1)))
+                    .map(|(l, _)| *l);
+                if let Some(label) = best {
+                    if labels[var] != label {
+                        labels.insert(*var, label);
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        let mut communities: BTreeMap<Var, BTreeSet<Var>> = BTreeMap::new();
+        for (var, label) in labels {
+            communities.entry(label).or_default().insert(var);
+        }
+        communities.into_values().collect()
+    }
+
+    /// The modularity of the communities found by 'communities', from
+    /// -0.5 (worse than random) to 1 (strong community structure).
+    pub fn modularity(&self) -> f64 {
+        let edges = self.num_edges() as f64;
+        if edges == 0.0 {
+            return 0.0;
+        }
+
+        let mut modularity = 0.0;
+        for community in self.communities() {
+            let mut internal_degree = 0;
+            let mut total_degree = 0;
+            for var in &community {
+                total_degree += self.degree(*var);
+                internal_degree += self
+                    .neighbours(*var)
+                    .filter(|n| community.contains(n))
+                    .count();
+            }
+            let internal_edges = internal_degree as f64 / 2.0;
+            let share = total_degree as f64 / (2.0 * edges);
+            modularity += internal_edges / edges - share * share;
+        }
+        modularity
+    }
+
+    /// Convert to a labelled graph, for exporting.
+    pub fn to_graph(&self) -> Graph {
+        let mut graph = Graph::undirected();
+        let mut indices = BTreeMap::new();
+        for var in self.variables() {
+            indices.insert(var, graph.add_node(var.to_string()));
+        }
+        for (var, neighbours) in &self.adjacency {
+            for n in neighbours.range(Var(var.index() + 1)..) {
+                graph.add_edge(indices[var], indices[n], None);
+            }
+        }
+        graph
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cnf::*;
+
+    #[test]
+    fn interaction_graph_connects_clause_mates() {
+        let formula = cnf![[1, -2, 3], [3, 4]];
+        let graph = formula.interaction_graph();
+
+        assert_eq!(4, graph.num_variables());
+        assert_eq!(4, graph.num_edges());
+        assert_eq!(3, graph.degree(Var::new(3)));
+        assert_eq!(
+            vec![Var::new(1), Var::new(2), Var::new(4)],
+            graph.neighbours(Var::new(3)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn splits_into_components() {
+        let formula = cnf![[1, 2], [3, 4], [], [-4, 5], [2, -6]];
+        assert_eq!(
+            vec![cnf![[1, 2], [2, -6]], cnf![[3, 4], [-4, 5]], cnf![[]]],
+            formula.components()
+        );
+    }
+
+    #[test]
+    fn disjoint_cliques_are_separate_communities() {
+        let formula = cnf![[1, 2, 3], [4, 5, 6]];
+        let graph = formula.interaction_graph();
+
+        assert_eq!(2, graph.communities().len());
+        assert!((graph.modularity() - 0.5).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,106 @@
+//! Solving Horn formulae, where every clause has at most one positive
+//! literal.  Such a clause is an implication: the conjunction of its
+//! negated atoms implies its positive atom, or false if there isn't
+//! one.  Starting with every atom false, and only making atoms true
+//! when an implication forces them to be, finds the model with the
+//! fewest true atoms, if there is a model at all.
+
+use crate::cnf::{Formula, Literal};
+
+/// Find the least model of a Horn formula, which assigns every atom up
+/// to the largest in the formula.  Returns 'None' if the formula is
+/// unsatisfiable.  The formula must be Horn.
+pub fn solve(formula: &Formula) -> Option<Vec<Literal>> {
+    let num_atoms = formula
+        .iter()
+        .flatten()
+        .map(|lit| lit.get_id() as usize)
+        .max()
+        .unwrap_or(0);
+
+    // each clause fires when every atom of its negative literals is
+    // true, so count how many aren't yet.
+    let mut premises: Vec<Vec<usize>> = vec![Vec::new(); num_atoms + 1];
+    let mut waiting: Vec<usize> = vec![0; formula.len()];
+    let mut values = vec![false; num_atoms + 1];
+    let mut todo = Vec::new();
+    for (i, lits) in formula.iter().enumerate() {
+        debug_assert!(
+            formula.clause(i).is_horn(),
+            "{} is not Horn",
+            formula.clause(i)
+        );
+        for lit in lits.iter().filter(|lit| lit.is_negated()) {
+            premises[lit.get_id() as usize].push(i);
+            waiting[i] += 1;
+        }
+        if waiting[i] == 0 {
+            todo.push(i);
+        }
+    }
+
+    while let Some(i) = todo.pop() {
+        let lits = &formula[i];
+        let conclusion = match lits.iter().find(|lit| !lit.is_negated()) {
+            Some(lit) => lit.get_id() as usize,
+            None => return None,
+        };
+        if values[conclusion] {
+            continue;
+        }
+        values[conclusion] = true;
+        for j in &premises[conclusion] {
+            // an atom may occur more than once in a clause.
+            waiting[*j] -= 1;
+            if waiting[*j] == 0 {
+                todo.push(*j);
+            }
+        }
+    }
+
+    let model = (1..=num_atoms)
+        .map(|id| {
+            let lit = Literal::new(id as isize);
+            if values[id] {
+                lit
+            } else {
+                lit.negate()
+            }
+        })
+        .collect();
+    Some(model)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cnf::*;
+
+    #[test]
+    fn finds_least_models() {
+        // 1, 1 -> 2, 2 & 3 -> 4, 4 -> false
+        let formula = Formula::new(vec![
+            Clause::new(vec![1]),
+            Clause::new(vec![-1, 2]),
+            Clause::new(vec![-2, -3, 4]),
+            Clause::new(vec![-4]),
+        ]);
+        assert!(formula.is_horn());
+        assert_eq!(
+            Some(vec![
+                Literal::new(1),
+                Literal::new(2),
+                Literal::new(-3),
+                Literal::new(-4)
+            ]),
+            solve(&formula)
+        );
+
+        let mut formula = formula;
+        formula.insert_clause(Clause::new(vec![3]));
+        assert_eq!(None, solve(&formula));
+
+        formula.insert_clause(Clause::new(vec![3, 4]));
+        assert!(!formula.is_horn());
+    }
+}
@@ -0,0 +1,183 @@
+//! The IPASIR interface to incremental SAT solvers, so this solver can
+//! be linked into tools written against it.  Clauses are added a
+//! literal at a time, terminated by 0; assumptions only apply to the
+//! next call to 'ipasir_solve'.
+
+use std::collections::BTreeSet;
+use std::mem;
+use std::os::raw::{c_char, c_int, c_void};
+
+use crate::cnf::{Clause, Formula, Literal};
+use crate::dpll::SolveResult;
+use crate::solver::Solver;
+use crate::theory::empty::Empty;
+
+/// The result of 'ipasir_solve' when the formula is satisfiable.
+const SAT: c_int = 10;
+
+/// The result of 'ipasir_solve' when the formula is unsatisfiable.
+const UNSAT: c_int = 20;
+
+/// The result of 'ipasir_solve' when the search gave up.
+const UNKNOWN: c_int = 0;
+
+/// A solver, with the clause being added and the assumptions for the
+/// next search.
+#[derive(Debug)]
+struct State {
+    solver: Solver<Empty>,
+    clause: Vec<isize>,
+    assumptions: Vec<Literal>,
+    /// The model found by the last search, if it was satisfiable.
+    model: BTreeSet<Literal>,
+    /// The failed assumptions of the last search, if it wasn't.
+    failed: BTreeSet<Literal>,
+}
+
+/// The name and version of the solver.
+#[no_mangle]
+pub extern "C" fn ipasir_signature() -> *const c_char {
+    concat!("sat-", env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char
+}
+
+/// Construct a new solver, with no clauses.
+#[no_mangle]
+pub extern "C" fn ipasir_init() -> *mut c_void {
+    let state = State {
+        solver: Solver::new(Empty::new(), Formula::new(Vec::new())),
+        clause: Vec::new(),
+        assumptions: Vec::new(),
+        model: BTreeSet::new(),
+        failed: BTreeSet::new(),
+    };
+    Box::into_raw(Box::new(state)) as *mut c_void
+}
+
+/// Free a solver.
+///
+/// # Safety
+///
+/// 'solver' must have come from 'ipasir_init', and not be used again.
+#[no_mangle]
+pub unsafe extern "C" fn ipasir_release(solver: *mut c_void) {
+    drop(Box::from_raw(solver as *mut State));
+}
+
+/// Add a literal to the clause being built, or finish it if 'lit' is
+/// 0.
+///
+/// # Safety
+///
+/// 'solver' must have come from 'ipasir_init'.
+#[no_mangle]
+pub unsafe extern "C" fn ipasir_add(solver: *mut c_void, lit: i32) {
+    let state = &mut *(solver as *mut State);
+    if lit == 0 {
+        let lits = mem::take(&mut state.clause);
+        state.solver.add_clause(Clause::new(lits));
+    } else {
+        state.clause.push(lit as isize);
+    }
+}
+
+/// Assume a literal in the next search.
+///
+/// # Safety
+///
+/// 'solver' must have come from 'ipasir_init'.
+#[no_mangle]
+pub unsafe extern "C" fn ipasir_assume(solver: *mut c_void, lit: i32) {
+    let state = &mut *(solver as *mut State);
+    state.assumptions.push(Literal::new(lit as isize));
+}
+
+/// Search for a model in which the assumptions hold, and then forget
+/// them.  Returns 10 if there is one, 20 if there isn't, and 0 if the
+/// search gave up.
+///
+/// # Safety
+///
+/// 'solver' must have come from 'ipasir_init'.
+#[no_mangle]
+pub unsafe extern "C" fn ipasir_solve(solver: *mut c_void) -> c_int {
+    let state = &mut *(solver as *mut State);
+    let assumptions = mem::take(&mut state.assumptions);
+    state.model.clear();
+    state.failed.clear();
+    // the empty theory never fails.
+    match state.solver.solve_with_assumptions(&assumptions) {
+        Ok(SolveResult::Sat(model)) => {
+            state.model = model.into_iter().collect();
+            SAT
+        }
+        Ok(SolveResult::Unsat) => {
+            state.failed = state.solver.failed_assumptions().iter().copied().collect();
+            UNSAT
+        }
+        Ok(SolveResult::Unknown) | Err(_) => UNKNOWN,
+    }
+}
+
+/// After a satisfiable search, the value of a literal's atom in the
+/// model: 'lit' if it's true, '-lit' if it's false, and 0 if either
+/// will do.
+///
+/// # Safety
+///
+/// 'solver' must have come from 'ipasir_init'.
+#[no_mangle]
+pub unsafe extern "C" fn ipasir_val(solver: *mut c_void, lit: i32) -> i32 {
+    let state = &*(solver as *const State);
+    let Literal(atom) = Literal::new(lit as isize);
+    if state.model.contains(&Literal(atom)) {
+        lit
+    } else if state.model.contains(&Literal(-atom)) {
+        -lit
+    } else {
+        0
+    }
+}
+
+/// After an unsatisfiable search, whether an assumption was needed to
+/// show it: 1 if so, 0 if not.
+///
+/// # Safety
+///
+/// 'solver' must have come from 'ipasir_init'.
+#[no_mangle]
+pub unsafe extern "C" fn ipasir_failed(solver: *mut c_void, lit: i32) -> c_int {
+    let state = &*(solver as *const State);
+    state.failed.contains(&Literal::new(lit as isize)) as c_int
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solves_incrementally() {
+        unsafe {
+            let solver = ipasir_init();
+            for lit in &[1, 2, 0, -1, 3, 0, -2, 3, 0] {
+                ipasir_add(solver, *lit);
+            }
+            assert_eq!(SAT, ipasir_solve(solver));
+            assert_eq!(3, ipasir_val(solver, 3));
+            assert_eq!(3, ipasir_val(solver, -3));
+
+            ipasir_assume(solver, -3);
+            ipasir_assume(solver, 4);
+            assert_eq!(UNSAT, ipasir_solve(solver));
+            assert_eq!(1, ipasir_failed(solver, -3));
+            assert_eq!(0, ipasir_failed(solver, 4));
+
+            // the assumptions are forgotten after each search.
+            assert_eq!(SAT, ipasir_solve(solver));
+
+            ipasir_add(solver, -3);
+            ipasir_add(solver, 0);
+            assert_eq!(UNSAT, ipasir_solve(solver));
+            ipasir_release(solver);
+        }
+    }
+}
@@ -17,103 +17,589 @@
     unused_qualifications
 )]
 
+pub mod ast;
+pub mod bdd;
+pub mod cache;
+#[macro_use]
 pub mod cnf;
+pub mod count;
+pub mod cube;
 pub mod dpll;
+pub mod gates;
+pub mod graph;
+pub mod horn;
+#[cfg(feature = "ipasir")]
+pub mod ipasir;
+pub mod lookahead;
+pub mod maxsat;
+pub mod mus;
+pub mod output;
+pub mod parallel;
 pub mod parse;
+pub mod preprocess;
+pub mod proof;
+mod rng;
+pub mod shrink;
+pub mod sls;
+pub mod solver;
+pub mod symmetry;
+#[cfg(test)]
+mod testing;
 pub mod theory;
+pub mod verify;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+use std::iter;
 
-use crate::cnf::{Formula, Literal};
-use crate::dpll::dpll;
+use crate::cnf::{Clause, Formula, Literal};
+use crate::dpll::{dpll, dpll_with_assumptions, SolveResult, SolverError};
+use crate::sls::WalkSat;
+use crate::solver::Solver;
 use crate::theory::empty::Empty;
 use crate::theory::Theory;
 
+/// A way of searching for a model.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd, Eq, Ord)]
+pub enum Engine {
+    /// Conflict-driven clause learning.
+    #[default]
+    Cdcl,
+    /// Lookahead, which is better on some random and crafted problems.
+    Lookahead,
+}
+
 pub fn sat(formula: Formula) -> bool {
-    smt(&mut Empty::new(), formula)
+    sat_assignment(formula).is_some()
 }
 
-pub fn sat_assignment(formula: Formula) -> Option<Vec<Literal>> {
+/// Find a satisfying assignment.  Components of the formula which
+/// share no variables are solved separately.  Horn formulae are
+/// solved directly, without a search.  Otherwise, variables are
+/// eliminated from the formula before solving, and assigned
+/// afterwards.
+pub fn sat_assignment(mut formula: Formula) -> Option<Vec<Literal>> {
+    let components = formula.components();
+    if components.len() > 1 {
+        let mut model = Vec::new();
+        for component in components {
+            model.extend(sat_assignment(component)?);
+        }
+        return Some(model);
+    }
+    if formula.is_horn() {
+        return horn::solve(&formula);
+    }
+    let reconstruction = preprocess::eliminate_variables(&mut formula);
+    // the empty theory never fails.
     smt_assignment(&mut Empty::new(), formula)
+        .ok()?
+        .into_model()
+        .map(|lits| reconstruction.extend(&lits))
 }
 
-pub fn smt<T: Theory>(theory: &mut T, formula: Formula) -> bool {
-    dpll(theory, formula).is_some()
+/// Like 'sat_assignment', but with a choice of engine.
+pub fn sat_assignment_with_engine(mut formula: Formula, engine: Engine) -> Option<Vec<Literal>> {
+    match engine {
+        Engine::Cdcl => sat_assignment(formula),
+        Engine::Lookahead => {
+            let reconstruction = preprocess::eliminate_variables(&mut formula);
+            lookahead::lookahead(&formula).map(|lits| reconstruction.extend(&lits))
+        }
+    }
+}
+
+/// Like 'sat_assignment', but first try to find a model by local
+/// search, which is often much faster if there is one, falling back
+/// to a complete search if it gives up.
+pub fn sat_assignment_with_local_search(
+    mut formula: Formula,
+    walksat: &WalkSat,
+) -> Option<Vec<Literal>> {
+    let reconstruction = preprocess::eliminate_variables(&mut formula);
+    let model = match walksat.solve(&formula) {
+        Some(lits) => lits,
+        None => smt_assignment(&mut Empty::new(), formula)
+            .ok()?
+            .into_model()?,
+    };
+    Some(reconstruction.extend(&model))
 }
 
-pub fn smt_assignment<T: Theory>(theory: &mut T, formula: Formula) -> Option<Vec<Literal>> {
-    dpll(theory, formula).map(|model| model.get_assignments())
+/// Iterate over every model of a formula, each assigning all of its
+/// atoms, in order of atom.  After each model is found, a clause
+/// ruling it out is added, and the search goes on from there, keeping
+/// what it has learned.
+pub fn all_models(formula: Formula) -> impl Iterator<Item = Vec<Literal>> {
+    let mut solver = Solver::new(Empty::new(), formula);
+    let mut done = false;
+    iter::from_fn(move || {
+        if done {
+            return None;
+        }
+        match solver.solve() {
+            Ok(SolveResult::Sat(mut model)) => {
+                model.sort_by_key(|lit| lit.get_id());
+                solver.add_clause(model.iter().map(|lit| lit.negate()).collect());
+                Some(model)
+            }
+            // the empty theory never fails.
+            _ => {
+                done = true;
+                None
+            }
+        }
+    })
+}
+
+/// Find the backbone of a formula: the literals which are true in
+/// every model, in order of atom.  Each literal of a model is checked
+/// by searching for a model with it false, and any such model rules
+/// out the other literals it disagrees with too.  If the formula is
+/// unsatisfiable, this is empty.
+pub fn backbone(formula: Formula) -> Vec<Literal> {
+    let mut solver = Solver::new(Empty::new(), formula);
+    // the empty theory never fails.
+    let mut candidates = match solver.solve() {
+        Ok(SolveResult::Sat(model)) => model,
+        _ => return Vec::new(),
+    };
+    candidates.sort_by_key(|lit| lit.get_id());
+
+    let mut backbone = Vec::new();
+    while let Some(lit) = candidates.pop() {
+        if solver.fixed_literals().contains(&lit) {
+            backbone.push(lit);
+            continue;
+        }
+        match solver.solve_with_assumptions(&[lit.negate()]) {
+            Ok(SolveResult::Sat(model)) => candidates.retain(|c| model.contains(c)),
+            _ => {
+                // it's implied, so add it to help the other checks.
+                solver.add_clause(Clause::from(vec![lit]));
+                backbone.push(lit);
+            }
+        }
+    }
+    backbone.reverse();
+    backbone
+}
+
+pub fn smt<T: Theory + ?Sized>(theory: &mut T, formula: Formula) -> Result<bool, SolverError> {
+    dpll(theory, formula).map(|result| result.is_sat())
+}
+
+pub fn smt_assignment<T: Theory + ?Sized>(
+    theory: &mut T,
+    formula: Formula,
+) -> Result<SolveResult, SolverError> {
+    dpll(theory, formula).map(|result| result.map(|model| model.get_assignments()))
+}
+
+/// Find a satisfying assignment in which the assumed literals are
+/// true.  The formula itself is not changed.
+pub fn smt_assignment_with_assumptions<T: Theory + ?Sized>(
+    theory: &mut T,
+    formula: Formula,
+    assumptions: &[Literal],
+) -> Result<SolveResult, SolverError> {
+    dpll_with_assumptions(theory, formula, assumptions)
+        .map(|result| result.map(|model| model.get_assignments()))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::cnf::*;
+    use crate::dpll::trace::Transition;
+    use crate::rng::Rng;
+    use crate::testing::*;
+    use crate::theory::arrays::*;
+    use crate::theory::composite::*;
     use crate::theory::euf::*;
+    use crate::theory::idl::*;
+    use crate::theory::lia::*;
 
     #[test]
     fn simple_sat_1() {
-        assert!(sat(Formula::new(vec![Clause::new(vec![1])])));
+        assert!(sat(cnf![[1]]));
     }
 
     #[test]
     fn simple_sat_2() {
-        assert!(sat(Formula::new(vec![Clause::new(vec![1, 2])])));
+        assert!(sat(cnf![[1, 2]]));
     }
 
     #[test]
     fn simple_sat_2b() {
-        assert!(sat(Formula::new(vec![
-            Clause::new(vec![-1]),
-            Clause::new(vec![1, -2]),
-        ])));
+        assert!(sat(cnf![[-1], [1, -2]]));
     }
 
     #[test]
     fn simple_sat_3() {
-        assert!(sat(Formula::new(vec![
-            Clause::new(vec![1, 2]),
-            Clause::new(vec![3])
-        ])));
+        assert!(sat(cnf![[1, 2], [3]]));
     }
 
     #[test]
     fn simple_unsat_1() {
-        assert!(!sat(Formula::new(vec![
-            Clause::new(vec![1]),
-            Clause::new(vec![-1])
-        ])));
+        assert!(!sat(cnf![[1], [-1]]));
     }
 
     #[test]
     fn simple_unsat_2() {
-        assert!(!sat(Formula::new(vec![
-            Clause::new(vec![1]),
-            Clause::new(vec![2]),
-            Clause::new(vec![-1, -2]),
-        ])));
+        assert!(!sat(cnf![[1], [2], [-1, -2]]));
     }
 
     #[test]
     fn complex_sat_7() {
-        assert!(sat(Formula::new(vec![
-            Clause::new(vec![-3, 4]),
-            Clause::new(vec![-1, -3, -5]),
-            Clause::new(vec![-2, -4, -5]),
-            Clause::new(vec![-2, 3, 5, -6]),
-            Clause::new(vec![-1, 2]),
-            Clause::new(vec![-1, 3, -5, -6]),
-            Clause::new(vec![1, -6]),
-            Clause::new(vec![1, 7]),
-        ])));
+        assert!(sat(cnf![
+            [-3, 4],
+            [-1, -3, -5],
+            [-2, -4, -5],
+            [-2, 3, 5, -6],
+            [-1, 2],
+            [-1, 3, -5, -6],
+            [1, -6],
+            [1, 7]
+        ]));
     }
 
     #[test]
-    fn euf_unsat_atoms() {
-        let formula = Formula::new(vec![
-            Clause::new(vec![1]),
-            Clause::new(vec![2]),
-            Clause::new(vec![3]),
-            Clause::new(vec![-4]),
+    fn removes_duplicates_and_tautologies() {
+        // unnormalized, as the constructors would normalize them.
+        let raw =
+            |lits: &[isize]| Clause::unnormalized(lits.iter().copied().map(Literal::new).collect());
+        let mut formula = Formula::new(vec![
+            raw(&[2, 1, 2]),
+            raw(&[1, -3, 3]),
+            raw(&[1, 2]),
+            raw(&[-1]),
         ]);
+        let stats = formula.simplify();
+        assert_eq!(Formula::new(vec![raw(&[2, 1]), raw(&[-1])]), formula);
+        assert_eq!(1, stats.duplicate_clauses);
+        assert_eq!(1, stats.duplicate_literals);
+        assert_eq!(1, stats.tautologies);
+    }
+
+    #[test]
+    fn conditions_formulae() {
+        let mut formula = cnf![[1, 2], [-1, 3, 4], [-2, -4]];
+        formula.condition(&[Literal::new(1), Literal::new(-3)]);
+        assert_eq!(cnf![[4], [-2, -4]], formula);
+
+        assert_eq!(
+            vec![Literal::new(4), Literal::new(-2)],
+            formula.propagate_units()
+        );
+        assert_eq!(Formula::new(Vec::new()), formula);
+
+        let mut formula = cnf![[1], [-1, 2], [-2, -1]];
+        formula.propagate_units();
+        assert!(formula.iter().any(<[Literal]>::is_empty));
+    }
+
+    #[test]
+    fn allocates_fresh_variables() {
+        let formula = cnf![[1, -4], [2]];
+        let mut vars = VarAllocator::above(&formula);
+        assert_eq!(Var::new(5), vars.fresh());
+        vars.reserve(Var::new(7));
+        assert_eq!(Var::new(8), vars.fresh());
+        vars.reserve(Var::new(3));
+        assert_eq!(Var::new(9), vars.next_var());
+    }
+
+    #[test]
+    fn collects_clauses_and_formulae() {
+        let mut formula: Formula = (1..=3)
+            .map(|id| (id..=3).map(Literal::new).collect::<Clause>())
+            .collect();
+        let mut clause: Clause = iter::once(Literal::new(-1)).collect();
+        clause.extend(vec![Literal::new(-2)]);
+        formula.extend(iter::once(clause));
+        assert_eq!(cnf![[1, 2, 3], [2, 3], [3], [-1, -2]], formula);
+    }
+
+    #[test]
+    fn normalizes_clauses() {
+        let lits = vec![3, -1, 3, 1].into_iter().map(Literal::new).collect();
+        let mut clause = Clause::unnormalized(lits);
+        assert!(!clause.is_normalized());
+        assert!(clause.is_tautology());
+        assert!(clause.normalize());
+        assert_eq!(clause![-1, 1, 3], clause);
+        assert!(clause.is_normalized());
+
+        let clause = Clause::new(vec![4, -2, 1]);
+        assert_eq!(clause![1, -2, 4], clause);
+        assert!(!clause.is_tautology());
+        assert!(clause![-2, 4].subsumes(&clause));
+        assert!(clause![].subsumes(&clause));
+        assert!(!clause![2, 4].subsumes(&clause));
+        assert!(!clause.subsumes(&clause![-2, 4]));
+
+        let mut clause = clause![3, 1];
+        clause.insert_literal(Literal::new(-2));
+        clause.extend(vec![Literal::new(3), Literal::new(2)]);
+        assert!(clause.is_normalized());
+        assert_eq!(clause![1, -2, 2, 3], clause);
+        assert_eq!(
+            &[
+                Literal::new(1),
+                Literal::new(-2),
+                Literal::new(2),
+                Literal::new(3)
+            ],
+            clause.literals()
+        );
+    }
+
+    #[test]
+    fn stores_formulae_flat() {
+        let mut formula = cnf![[1, -2], [], [3]];
+        assert_eq!(3, formula.len());
+        assert_eq!(&[Literal::new(1), Literal::new(-2)], &formula[0]);
+        assert!(formula[1].is_empty());
+        assert_eq!(clause![3], formula.clause(2));
+        assert_eq!(
+            vec![2, 0, 1],
+            formula.iter().map(<[Literal]>::len).collect::<Vec<_>>()
+        );
+
+        formula.insert_clause(clause![-3, 4]);
+        assert_eq!(cnf![[1, -2], [], [3], [-3, 4]], formula);
+        formula.truncate(2);
+        assert_eq!(cnf![[1, -2], []], formula);
+        assert_eq!(formula, Formula::new(formula.clauses()));
+        formula.truncate(0);
+        assert!(formula.is_empty());
+        assert_eq!(Formula::default(), formula);
+    }
+
+    #[test]
+    fn shuffles_formulae() {
+        let original = cnf![[1, -2, 3], [-1, 4], [2, -3, -4], [5], [-5, 1]];
+        let mut formula = original.clone();
+        let renaming = formula.shuffle(7);
+        assert_ne!(original, formula);
+        assert_eq!(5, renaming.len());
+
+        // renaming the original gives the same clauses, in some order.
+        let renamed: Formula = original
+            .iter()
+            .map(|lits| {
+                lits.iter()
+                    .map(|lit| renaming[&lit.var()].literal(!lit.is_negated()))
+                    .collect::<Clause>()
+            })
+            .collect();
+        assert_eq!(renamed.canonical(), formula.canonical());
+
+        let mut again = original;
+        assert_eq!(renaming, again.shuffle(7));
+        assert_eq!(formula, again);
+    }
+
+    #[test]
+    fn formula_stats() {
+        let stats = cnf![[1, -2], [-1, 3, 4], [2, 3], [-4]].stats();
+        assert_eq!(4, stats.num_vars);
+        assert_eq!(4, stats.num_clauses);
+        assert_eq!(
+            vec![(&1, &1), (&2, &2), (&3, &1)],
+            stats.clause_lengths.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(5, stats.positive_literals);
+        assert_eq!(3, stats.negative_literals);
+        assert_eq!(2, stats.horn_clauses);
+        assert!(!stats.is_horn());
+    }
+
+    #[test]
+    fn named_variables() {
+        let mut vars = VarMap::new();
+        let formula = Formula::from_names(&[&["rain", "-wet"], &["wet"]], &mut vars);
+        assert_eq!(cnf![[1, -2], [2]], formula);
+        assert_eq!(Some(Var::new(2)), vars.id("wet"));
+        assert_eq!(Some("rain"), vars.name(Var::new(1)));
+        assert_eq!(None, vars.name(Var::new(3)));
+        assert_eq!("(rain || -wet) && (wet)", formula.to_names(&vars));
+
+        let model = sat_assignment(formula).unwrap();
+        let names = vars.assignment(&model);
+        assert_eq!(Some(&true), names.get("rain"));
+        assert_eq!(Some(&true), names.get("wet"));
+    }
+
+    #[test]
+    fn assumptions() {
+        let formula = cnf![[1, 2], [-1, 3]];
+        let mut theory = Empty::new();
+
+        let lits = smt_assignment_with_assumptions(
+            &mut theory,
+            formula.clone(),
+            &[Literal::new(-2), Literal::new(4)],
+        )
+        .unwrap()
+        .into_model()
+        .unwrap();
+        for lit in &[1, -2, 3, 4] {
+            assert!(lits.contains(&Literal::new(*lit)));
+        }
+
+        assert_eq!(
+            Ok(SolveResult::Unsat),
+            smt_assignment_with_assumptions(
+                &mut theory,
+                formula.clone(),
+                &[Literal::new(-2), Literal::new(-3)]
+            )
+        );
+        assert!(
+            smt_assignment_with_assumptions(&mut theory, formula, &[Literal::new(-3)])
+                .unwrap()
+                .is_sat()
+        );
+    }
+
+    #[test]
+    fn querying_models() {
+        let formula = cnf![[2], [-2, 5]];
+        let model = dpll(&mut Empty::new(), formula)
+            .unwrap()
+            .into_model()
+            .unwrap();
+        let expected: Vec<Literal> = [-1, 2, -3, -4, 5]
+            .iter()
+            .map(|l| Literal::new(*l))
+            .collect();
+        assert_eq!(expected, model.complete(4));
+
+        assert_eq!(Some(true), model.value(Var::new(2)));
+        assert_eq!(Some(true), model.value(Var::new(5)));
+        assert_eq!(None, model.value(Var::new(1)));
+        assert_eq!(Some(true), model.value(Literal::new(-5).var()));
+        assert_eq!(None, model.value(Var::new(100)));
+        assert_eq!(Some(false), Literal::new(-2).is_true_in(&model));
+        assert_eq!(None, Literal::new(-3).is_true_in(&model));
+        assert_eq!(model.get_assignments(), model.iter().collect::<Vec<_>>());
+    }
+
+    /// Generate pseudo-random 3-SAT formulae around the phase
+    /// transition, and check the solver agrees with brute force.
+    #[test]
+    fn random_formulae_agree_with_brute_force() {
+        for (num_vars, formula) in random_formulae(0x2545_f491_4f6c_dd1d, 300) {
+            let brute_force = brute_force_sat(num_vars, &formula, &[]);
+            match sat_assignment(formula.clone()) {
+                Some(lits) => {
+                    assert!(brute_force, "{} is unsat but got {:?}", formula, lits);
+                    assert!(is_model(&formula, &lits));
+                    for lits in formula.iter() {
+                        for lit in lits {
+                            assert!(lits.iter().any(|l| l.get_id() == lit.get_id()));
+                        }
+                    }
+                }
+                None => assert!(!brute_force, "{} is sat", formula),
+            }
+        }
+    }
+
+    #[test]
+    fn backbone_agrees_with_brute_force() {
+        for (num_vars, formula) in random_formulae(0x2545_f491_4f6c_dd1d, 300) {
+            let is_sat = brute_force_sat(num_vars, &formula, &[]);
+            let forced = backbone(formula.clone());
+            for var in (1..=num_vars).map(Var::new) {
+                for lit in [var.positive(), var.negative()] {
+                    let is_forced = is_sat && !brute_force_sat(num_vars, &formula, &[lit.negate()]);
+                    let occurs = formula
+                        .iter()
+                        .any(|lits| lits.iter().any(|l| l.var() == var));
+                    assert_eq!(
+                        is_forced && occurs,
+                        forced.contains(&lit),
+                        "{} {}",
+                        formula,
+                        lit
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn assumptions_agree_with_brute_force() {
+        let mut rng = Rng::new(0x853c_49e6_748f_ea9b);
+        for (num_vars, formula) in random_formulae(0x2545_f491_4f6c_dd1d, 300) {
+            let assumption = Var::new(1 + rng.below(num_vars)).literal(rng.next_bool());
+            assert_eq!(
+                brute_force_sat(num_vars, &formula, &[assumption]),
+                smt_assignment_with_assumptions(&mut Empty::new(), formula.clone(), &[assumption])
+                    .unwrap()
+                    .is_sat(),
+                "assuming {} in {}",
+                assumption,
+                formula
+            );
+        }
+    }
+
+    #[test]
+    fn pure_literals() {
+        let formula = cnf![[1, -2], [-1, 3], [-2, -3]];
+        assert_eq!(vec![Literal::new(-2)], formula.pure_literals());
+    }
+
+    #[test]
+    fn enumerates_all_models() {
+        let count = |clauses: Vec<Clause>| {
+            let models: Vec<Vec<Literal>> = all_models(Formula::new(clauses.clone())).collect();
+            for (i, model) in models.iter().enumerate() {
+                assert!(!models[..i].contains(model));
+                for lits in clauses.iter().map(Clause::literals) {
+                    assert!(lits.iter().any(|lit| model.contains(lit)));
+                }
+            }
+            models.len()
+        };
+
+        assert_eq!(7, count(vec![Clause::new(vec![1, 2, 3])]));
+        assert_eq!(
+            2,
+            count(vec![Clause::new(vec![1, 2]), Clause::new(vec![-1, -2])])
+        );
+        assert_eq!(0, count(vec![Clause::new(vec![1]), Clause::new(vec![-1])]));
+        assert_eq!(1, count(vec![]));
+        assert_eq!(
+            vec![vec![Literal::new(1), Literal::new(-2)]],
+            all_models(cnf![[1], [-2]]).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn finds_backbones() {
+        // 1 and -2 are forced, 3 and 4 are not.
+        let formula = cnf![[1, 2], [1, -2], [-2, 3], [-2, -1], [3, 4]];
+        assert_eq!(
+            vec![Literal::new(1), Literal::new(-2)],
+            backbone(formula.clone())
+        );
+        for lit in backbone(formula.clone()) {
+            let mut negated = formula.clone();
+            negated.insert_clause(Clause::from(vec![lit.negate()]));
+            assert!(!sat(negated));
+        }
+
+        assert!(backbone(cnf![[1], [-1]]).is_empty());
+    }
+
+    #[test]
+    fn euf_unsat_atoms() {
+        let formula = cnf![[1], [2], [3], [-4]];
         let mut euf = EUF::new(vec![
             EUFLiteral::new(
                 EUFTerm::ap(1, vec![EUFTerm::atom(1), EUFTerm::atom(2)]),
@@ -137,17 +623,12 @@ mod tests {
         ]);
 
         assert!(sat(formula.clone()));
-        assert!(!smt(&mut euf, formula));
+        assert_eq!(Ok(false), smt(&mut euf, formula));
     }
 
     #[test]
     fn euf_unsat_functions() {
-        let formula = Formula::new(vec![
-            Clause::new(vec![1]),
-            Clause::new(vec![2]),
-            Clause::new(vec![3]),
-            Clause::new(vec![-4]),
-        ]);
+        let formula = cnf![[1], [2], [3], [-4]];
         let mut euf = EUF::new(vec![
             EUFLiteral::new(
                 EUFTerm::ap(1, vec![EUFTerm::atom(1), EUFTerm::atom(2)]),
@@ -174,17 +655,128 @@ mod tests {
         ]);
 
         assert!(sat(formula.clone()));
-        assert!(!smt(&mut euf, formula));
+        assert_eq!(Ok(false), smt(&mut euf, formula));
     }
 
     #[test]
-    fn euf_sat_functions() {
-        let formula = Formula::new(vec![
-            Clause::new(vec![1]),
-            Clause::new(vec![2]),
-            Clause::new(vec![3]),
-            Clause::new(vec![-4, 4]),
+    fn euf_explains_decisions() {
+        let (a, b, c, d, e) = (1, 2, 3, 4, 5);
+        let mut euf = EUF::new(vec![
+            EUFLiteral::new(EUFTerm::atom(a), EUFTerm::atom(b)),
+            EUFLiteral::new(EUFTerm::atom(b), EUFTerm::atom(c)),
+            EUFLiteral::new(
+                EUFTerm::ap(1, vec![EUFTerm::atom(a)]),
+                EUFTerm::ap(1, vec![EUFTerm::atom(c)]),
+            ),
+            EUFLiteral::new(EUFTerm::atom(c), EUFTerm::atom(d)),
+            EUFLiteral::new(EUFTerm::atom(a), EUFTerm::atom(e)),
+            EUFLiteral::new(EUFTerm::atom(b), EUFTerm::atom(e)),
         ]);
+        for lit in [4, 1, 2, -5] {
+            euf.incorporate(Literal::new(lit)).unwrap();
+        }
+
+        // f(a) = f(c) by congruence, and c = d doesn't matter.
+        assert_eq!(Ok(Some(true)), euf.decide(Literal::new(3)));
+        assert_eq!(
+            Some(vec![Literal::new(1), Literal::new(2)]),
+            euf.explain(Literal::new(3))
+        );
+
+        // b != e as a = b and a != e.
+        assert_eq!(Ok(Some(false)), euf.decide(Literal::new(6)));
+        assert_eq!(
+            Some(vec![Literal::new(-5), Literal::new(1)]),
+            euf.explain(Literal::new(-6))
+        );
+        assert_eq!(None, euf.explain(Literal::new(5)));
+    }
+
+    #[test]
+    fn euf_backtracks() {
+        let mut euf = EUF::new(vec![
+            EUFLiteral::new(EUFTerm::atom(1), EUFTerm::atom(2)),
+            EUFLiteral::new(EUFTerm::atom(2), EUFTerm::atom(3)),
+            EUFLiteral::new(EUFTerm::atom(1), EUFTerm::atom(3)),
+            EUFLiteral::new(
+                EUFTerm::ap(1, vec![EUFTerm::atom(1)]),
+                EUFTerm::ap(1, vec![EUFTerm::atom(3)]),
+            ),
+        ]);
+        euf.incorporate(Literal::new(1)).unwrap();
+        euf.push();
+        euf.incorporate(Literal::new(2)).unwrap();
+        euf.push();
+        euf.incorporate(Literal::new(-4)).unwrap();
+        assert_eq!(Ok(Some(true)), euf.decide(Literal::new(3)));
+        assert!(euf.decide(Literal::new(4)).is_err());
+
+        euf.pop(1);
+        assert_eq!(Ok(Some(true)), euf.decide(Literal::new(4)));
+        euf.pop(1);
+        assert_eq!(Ok(None), euf.decide(Literal::new(3)));
+        assert_eq!(Ok(None), euf.decide(Literal::new(4)));
+
+        // popping past the first point is the same as popping to it.
+        euf.push();
+        euf.incorporate(Literal::new(-2)).unwrap();
+        euf.pop(5);
+        assert_eq!(Ok(None), euf.decide(Literal::new(2)));
+        assert_eq!(Ok(Some(true)), euf.decide(Literal::new(1)));
+    }
+
+    #[test]
+    fn euf_learns_lemmas() {
+        let euf = EUF::new(vec![
+            EUFLiteral::new(EUFTerm::atom(1), EUFTerm::atom(2)),
+            EUFLiteral::new(EUFTerm::atom(2), EUFTerm::atom(3)),
+            EUFLiteral::new(EUFTerm::atom(1), EUFTerm::atom(3)),
+            EUFLiteral::new(EUFTerm::atom(4), EUFTerm::atom(5)),
+            EUFLiteral::new(EUFTerm::atom(5), EUFTerm::atom(6)),
+        ]);
+        let mut solver = Solver::new(euf, cnf![[1, 4], [2, 4], [-3, 4], [-4, 5], [-4, -5]]);
+        solver.record_trace(true);
+        assert_eq!(Ok(SolveResult::Unsat), solver.solve());
+        assert!(solver.stats().theory_lemmas > 0);
+
+        // each lemma is an instance of transitivity.
+        let lemmas: Vec<Clause> = solver
+            .trace()
+            .iter()
+            .filter_map(|t| match t {
+                Transition::Learn(clause) => Some(clause.clone()),
+                _ => None,
+            })
+            .filter(|clause| clause.literals().iter().all(|l| l.get_id() <= 3))
+            .collect();
+        assert!(!lemmas.is_empty());
+        for lits in lemmas.iter().map(Clause::literals) {
+            let mut lits = lits.to_vec();
+            lits.sort();
+            assert!([vec![-3, -2, 1], vec![-3, -1, 2], vec![-2, -1, 3]]
+                .iter()
+                .any(|ids| {
+                    let mut expected: Vec<Literal> = ids.iter().map(|i| Literal::new(*i)).collect();
+                    expected.sort();
+                    expected == lits
+                }));
+        }
+    }
+
+    #[test]
+    fn euf_unknown_atom() {
+        let formula = cnf![[1], [2]];
+        let mut euf = EUF::new(vec![EUFLiteral::new(EUFTerm::atom(1), EUFTerm::atom(2))]);
+
+        match smt(&mut euf, formula) {
+            Err(SolverError::Theory(_)) => (),
+            result => panic!("expected a theory error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn euf_sat_functions() {
+        let formula = cnf![[1], [2], [3], [-4, 4]];
         let mut euf = EUF::new(vec![
             EUFLiteral::new(
                 EUFTerm::ap(1, vec![EUFTerm::atom(1), EUFTerm::atom(2)]),
@@ -211,6 +803,332 @@ mod tests {
         ]);
 
         assert!(sat(formula.clone()));
-        assert!(smt(&mut euf, formula));
+        assert_eq!(Ok(true), smt(&mut euf, formula));
+    }
+
+    #[test]
+    fn euf_distinct() {
+        // three different terms can't each be equal to x or y.
+        let (a, b, c, x, y) = (1, 2, 3, 4, 5);
+        let mut lits = vec![EUFLiteral::distinct(vec![
+            EUFTerm::atom(a),
+            EUFTerm::atom(b),
+            EUFTerm::atom(c),
+        ])];
+        for term in [a, b, c] {
+            for value in [x, y] {
+                lits.push(EUFLiteral::new(EUFTerm::atom(term), EUFTerm::atom(value)));
+            }
+        }
+        let formula = cnf![[1], [2, 3], [4, 5], [6, 7]];
+        assert!(sat(formula.clone()));
+        assert_eq!(Ok(false), smt(&mut EUF::new(lits.clone()), formula.clone()));
+        assert_eq!(
+            Ok(true),
+            smt(&mut EUF::new(lits.clone()), cnf![[1], [2, 3], [4, 5]])
+        );
+
+        // distinct(a, b, c) makes a = x and b = x unequal, and its
+        // negation makes a = c when both are different to b: a = x,
+        // b != x, b = y, and c != y.
+        let mut euf = EUF::new(lits);
+        euf.push();
+        euf.incorporate(Literal::new(1)).unwrap();
+        euf.incorporate(Literal::new(2)).unwrap();
+        assert_eq!(Ok(Some(false)), euf.decide(Literal::new(4)));
+        assert_eq!(
+            Some(vec![Literal::new(1), Literal::new(2)]),
+            euf.explain(Literal::new(-4))
+        );
+        euf.pop(1);
+        euf.incorporate(Literal::new(-1)).unwrap();
+        euf.incorporate(Literal::new(2)).unwrap();
+        euf.incorporate(Literal::new(-4)).unwrap();
+        assert_eq!(Ok(None), euf.decide(Literal::new(6)));
+        euf.incorporate(Literal::new(5)).unwrap();
+        euf.incorporate(Literal::new(-7)).unwrap();
+        assert_eq!(Ok(Some(true)), euf.decide(Literal::new(6)));
+        assert_eq!(None, euf.explain(Literal::new(6)));
+    }
+
+    #[test]
+    fn euf_distinct_congruence() {
+        // a3 = f(a3) makes f(f(a3)) = f(a2) into a3 = f(a2), which the
+        // second 'distinct' rules out, so the first is false.
+        let f = |term: EUFTerm| EUFTerm::ap(9, vec![term]);
+        let (a1, a2, a3) = (EUFTerm::atom(1), EUFTerm::atom(2), EUFTerm::atom(3));
+        let mut euf = EUF::new(vec![
+            EUFLiteral::distinct(vec![f(a1.clone()), a1, a2.clone()]),
+            EUFLiteral::new(f(f(a3.clone())), f(a2.clone())),
+            EUFLiteral::distinct(vec![a2.clone(), f(a2), a3.clone()]),
+            EUFLiteral::new(a3.clone(), f(a3.clone())),
+            EUFLiteral::new(a3.clone(), f(a3)),
+        ]);
+        let formula = cnf![[2, -2, 4], [3, -5], [1], [-3, 4]];
+        assert_eq!(Ok(true), smt(&mut euf, formula));
+
+        // f(a), f(b), and f(c) are all different, so a, b, and c are
+        // too, even though that's not a literal.
+        let (a, b, c) = (EUFTerm::atom(1), EUFTerm::atom(2), EUFTerm::atom(3));
+        let mut euf = EUF::new(vec![
+            EUFLiteral::distinct(vec![f(a.clone()), f(b.clone()), f(c.clone())]),
+            EUFLiteral::distinct(vec![a, b, c]),
+        ]);
+        let formula = cnf![[1], [-2]];
+        assert!(sat(formula.clone()));
+        assert_eq!(Ok(false), smt(&mut euf, formula));
+    }
+
+    #[test]
+    fn idl_unsat_cycle() {
+        // x - y <= 3, y - z <= -1, and z - x <= -3 sum to 0 <= -1.
+        let formula = cnf![[1], [2], [3, 4], [-4]];
+        let mut idl = IDL::new(vec![
+            IDLLiteral::new(1, 2, 3),
+            IDLLiteral::new(2, 3, -1),
+            IDLLiteral::new(3, 1, -3),
+            IDLLiteral::new(1, 3, 5),
+        ]);
+
+        assert!(sat(formula.clone()));
+        assert_eq!(Ok(false), smt(&mut idl, formula));
+    }
+
+    #[test]
+    fn idl_sat_values() {
+        let lits = vec![
+            IDLLiteral::new(1, 2, 3),
+            IDLLiteral::new(2, 3, -1),
+            IDLLiteral::new(3, 1, -3),
+            IDLLiteral::new(1, 3, 0),
+        ];
+        let formula = cnf![[1], [2], [3, 4]];
+        let mut model = match smt_assignment(&mut IDL::new(lits.clone()), formula) {
+            Ok(SolveResult::Sat(model)) => model,
+            result => panic!("expected a model, got {:?}", result),
+        };
+        model.sort_by_key(|lit| lit.get_id());
+        assert!(model.contains(&Literal::new(-3)));
+
+        // the values satisfy every bound of the model.
+        let mut idl = IDL::new(lits);
+        for lit in &model {
+            idl.incorporate(*lit).unwrap();
+        }
+        let values = idl.values();
+        for (lit, (x, y, c)) in model
+            .iter()
+            .zip([(1, 2, 3), (2, 3, -1), (3, 1, -3), (1, 3, 0)])
+        {
+            let difference = values[x] - values[y];
+            assert_eq!(!lit.is_negated(), difference <= c);
+        }
+    }
+
+    #[test]
+    fn idl_explains_and_backtracks() {
+        let mut idl = IDL::new(vec![
+            IDLLiteral::new(1, 2, 3),
+            IDLLiteral::new(2, 3, -1),
+            IDLLiteral::new(1, 3, 2),
+            IDLLiteral::new(3, 1, -3),
+            IDLLiteral::new(4, 1, 0),
+        ]);
+        idl.incorporate(Literal::new(1)).unwrap();
+        idl.push();
+        idl.incorporate(Literal::new(2)).unwrap();
+        idl.incorporate(Literal::new(5)).unwrap();
+
+        // x - z <= 2 by the path, and so not z - x <= -3.
+        assert_eq!(Ok(Some(true)), idl.decide(Literal::new(3)));
+        assert_eq!(Ok(Some(false)), idl.decide(Literal::new(4)));
+        assert_eq!(
+            Some(vec![Literal::new(1), Literal::new(2)]),
+            idl.explain(Literal::new(-4))
+        );
+        assert!(idl.incorporate(Literal::new(4)).is_err());
+
+        idl.pop(1);
+        assert_eq!(Ok(None), idl.decide(Literal::new(3)));
+        assert_eq!(None, idl.explain(Literal::new(3)));
+        idl.incorporate(Literal::new(4)).unwrap();
+        assert_eq!(Ok(Some(false)), idl.decide(Literal::new(2)));
+    }
+
+    #[test]
+    fn lia_unsat_fractional() {
+        // x + y = 1 and x = y only have the solution x = y = 1/2.
+        let formula = cnf![[1], [2], [3], [4]];
+        let lits = vec![
+            LIALiteral::new(vec![(1, 1), (2, 1)], 1),
+            LIALiteral::new(vec![(1, -1), (2, -1)], -1),
+            LIALiteral::new(vec![(1, 1), (2, -1)], 0),
+            LIALiteral::new(vec![(1, -1), (2, 1)], 0),
+        ];
+
+        assert!(sat(formula.clone()));
+        assert_eq!(Ok(false), smt(&mut LIA::new(lits.clone()), formula.clone()));
+
+        // but x + y = 2 and x = y do have one.
+        let mut lits = lits;
+        lits[0] = LIALiteral::new(vec![(1, 1), (2, 1)], 2);
+        lits[1] = LIALiteral::new(vec![(1, -1), (2, -1)], -2);
+        assert_eq!(Ok(true), smt(&mut LIA::new(lits), formula));
+    }
+
+    #[test]
+    fn lia_tightens_bounds() {
+        // 2x <= 1 and 2x >= 1 are x <= 0 and x >= 1, and 3x + 3y <= 2
+        // is x + y <= 0.
+        let mut lia = LIA::new(vec![
+            LIALiteral::new(vec![(1, 2)], 1),
+            LIALiteral::new(vec![(1, -2)], -1),
+            LIALiteral::new(vec![(1, 3), (2, 3)], 2),
+            LIALiteral::new(vec![(1, 1), (2, 1)], 0),
+        ]);
+        lia.incorporate(Literal::new(1)).unwrap();
+        assert_eq!(Ok(Some(false)), lia.decide(Literal::new(2)));
+
+        lia.push();
+        lia.incorporate(Literal::new(3)).unwrap();
+        assert_eq!(Ok(Some(true)), lia.decide(Literal::new(4)));
+        lia.pop(1);
+        assert_eq!(Ok(None), lia.decide(Literal::new(4)));
+    }
+
+    #[test]
+    fn arrays_read_over_write() {
+        let (select, store, f) = (1, 2, 3);
+        let (a, i, v, j, x, y) = (1, 2, 3, 4, 5, 6);
+        let write = || {
+            EUFTerm::ap(
+                store,
+                vec![
+                    EUFTerm::atom(a),
+                    EUFTerm::ap(f, vec![EUFTerm::atom(x)]),
+                    EUFTerm::atom(v),
+                ],
+            )
+        };
+        let index = || EUFTerm::ap(f, vec![EUFTerm::atom(y)]);
+        let lits = vec![
+            EUFLiteral::new(
+                EUFTerm::ap(select, vec![write(), index()]),
+                EUFTerm::atom(v),
+            ),
+            EUFLiteral::new(
+                EUFTerm::ap(select, vec![write(), index()]),
+                EUFTerm::ap(select, vec![EUFTerm::atom(a), index()]),
+            ),
+            EUFLiteral::new(EUFTerm::atom(x), EUFTerm::atom(y)),
+            EUFLiteral::new(EUFTerm::atom(i), EUFTerm::atom(j)),
+        ];
+
+        // the read is either of the value written or of the old array,
+        // and of the value written if the indices are equal.
+        assert_eq!(
+            Ok(false),
+            smt(
+                &mut Arrays::new(select, store, lits.clone()),
+                cnf![[-1], [-2], [4]]
+            )
+        );
+        assert_eq!(
+            Ok(false),
+            smt(
+                &mut Arrays::new(select, store, lits.clone()),
+                cnf![[-1], [3], [4]]
+            )
+        );
+        assert_eq!(
+            Ok(true),
+            smt(
+                &mut Arrays::new(select, store, lits.clone()),
+                cnf![[-1], [4]]
+            )
+        );
+
+        let mut arrays = Arrays::new(select, store, lits);
+        assert_eq!(Ok(None), arrays.decide(Literal::new(1)));
+        arrays.push();
+        arrays.incorporate(Literal::new(-2)).unwrap();
+        assert_eq!(Ok(Some(true)), arrays.decide(Literal::new(1)));
+        assert_eq!(Ok(None), arrays.decide(Literal::new(3)));
+        arrays.pop(1);
+        assert_eq!(Ok(None), arrays.decide(Literal::new(1)));
+    }
+
+    #[test]
+    fn arrays_extensionality() {
+        let (select, store) = (1, 2);
+        let (a, b, i, v) = (1, 2, 3, 4);
+        let write = |array| {
+            EUFTerm::ap(
+                store,
+                vec![EUFTerm::atom(array), EUFTerm::atom(i), EUFTerm::atom(v)],
+            )
+        };
+        let read = |array| EUFTerm::ap(select, vec![EUFTerm::atom(array), EUFTerm::atom(i)]);
+        let lits = vec![
+            EUFLiteral::new(
+                EUFTerm::ap(store, vec![EUFTerm::atom(a), EUFTerm::atom(i), read(a)]),
+                EUFTerm::atom(a),
+            ),
+            EUFLiteral::new(EUFTerm::atom(a), EUFTerm::atom(b)),
+            EUFLiteral::new(write(a), write(b)),
+            EUFLiteral::new(read(a), read(b)),
+        ];
+
+        // writing what's already there doesn't change an array.
+        assert_eq!(
+            Ok(false),
+            smt(&mut Arrays::new(select, store, lits.clone()), cnf![[-1]])
+        );
+
+        // arrays which differ somewhere other than i still do after
+        // writing to i, and otherwise they differ at i.
+        assert_eq!(
+            Ok(false),
+            smt(
+                &mut Arrays::new(select, store, lits.clone()),
+                cnf![[-2], [3], [4]]
+            )
+        );
+        assert_eq!(
+            Ok(true),
+            smt(
+                &mut Arrays::new(select, store, lits.clone()),
+                cnf![[-2], [3]]
+            )
+        );
+        assert_eq!(
+            Ok(true),
+            smt(&mut Arrays::new(select, store, lits), cnf![[-2], [4]])
+        );
+    }
+
+    #[test]
+    fn composite_of_theories() {
+        let (a, b, c) = (1, 2, 3);
+        let euf = EUF::new(vec![
+            EUFLiteral::new(EUFTerm::atom(a), EUFTerm::atom(b)),
+            EUFLiteral::new(EUFTerm::atom(a), EUFTerm::atom(c)),
+            EUFLiteral::new(EUFTerm::atom(b), EUFTerm::atom(c)),
+        ]);
+        let idl = IDL::new(vec![IDLLiteral::new(1, 2, -1), IDLLiteral::new(2, 1, -1)]);
+        let mut composite = Composite::new();
+        composite.add(Box::new(idl), vec![3, 4]).unwrap();
+        composite.add(Box::new(euf), vec![1, 2, 6]).unwrap();
+        assert!(composite.add(Box::new(Empty::new()), vec![5, 2]).is_err());
+
+        // y < x rules out x < y, so a = b, so a = c.
+        let mut theory: Box<dyn Theory> = Box::new(composite);
+        assert!(sat(cnf![[1, 3], [-2], [4], [5], [6]]));
+        assert_eq!(
+            Ok(false),
+            smt(&mut theory, cnf![[1, 3], [-2], [4], [5], [6]])
+        );
+        assert_eq!(Ok(true), smt(&mut theory, cnf![[1, 3], [-2], [5], [6]]));
+        assert_eq!(Ok(None), theory.decide(Literal::new(5)));
     }
 }
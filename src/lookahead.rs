@@ -0,0 +1,296 @@
+//! A lookahead solver, in the style of march.  Before each decision,
+//! both literals of every candidate atom are tried out by unit
+//! propagation.  This finds literals which are implied because the
+//! other literal causes a conflict, and picks the decision which
+//! simplifies the formula the most.  Lookahead is expensive, but on
+//! random and crafted problems it's often better than CDCL.
+
+use crate::cnf::{Formula, Literal};
+use crate::dpll::lit_index;
+
+/// Find a model which satisfies the formula, if one exists, by
+/// lookahead.  The model assigns every atom up to the largest in the
+/// formula.
+pub fn lookahead(formula: &Formula) -> Option<Vec<Literal>> {
+    let mut lookahead = Lookahead::new(formula);
+    if lookahead.assign_units() && lookahead.solve() {
+        Some(lookahead.model())
+    } else {
+        None
+    }
+}
+
+/// A partial assignment with unit propagation, for trying out
+/// literals before deciding which to split on.
+#[derive(Debug)]
+pub(crate) struct Lookahead<'a> {
+    formula: &'a Formula,
+    /// The clauses each literal occurs in, indexed by 'lit_index'.
+    occurrences: Vec<Vec<usize>>,
+    /// The truth of each atom, indexed by atom.
+    values: Vec<Option<bool>>,
+    /// The assigned literals, in order.
+    trail: Vec<Literal>,
+}
+
+impl<'a> Lookahead<'a> {
+    /// Start with nothing assigned.
+    pub(crate) fn new(formula: &'a Formula) -> Lookahead<'a> {
+        let num_atoms = formula
+            .iter()
+            .flatten()
+            .map(|lit| lit.get_id() as usize)
+            .max()
+            .unwrap_or(0);
+
+        let mut occurrences = vec![Vec::new(); 2 * num_atoms + 2];
+        for (i, lits) in formula.iter().enumerate() {
+            for lit in lits {
+                occurrences[lit_index(*lit)].push(i);
+            }
+        }
+
+        Lookahead {
+            formula,
+            occurrences,
+            values: vec![None; num_atoms + 1],
+            trail: Vec::new(),
+        }
+    }
+
+    /// The number of literals assigned.
+    pub(crate) fn len(&self) -> usize {
+        self.trail.len()
+    }
+
+    fn value(&self, lit: Literal) -> Option<bool> {
+        self.values[lit.get_id() as usize].map(|v| v != lit.is_negated())
+    }
+
+    /// Assign the unit clauses.  Returns false if there's a conflict.
+    pub(crate) fn assign_units(&mut self) -> bool {
+        for lits in self.formula.iter() {
+            match lits[..] {
+                [] => return false,
+                [lit] => match self.value(lit) {
+                    Some(true) => (),
+                    Some(false) => return false,
+                    None => {
+                        if !self.assign(lit) {
+                            return false;
+                        }
+                    }
+                },
+                _ => (),
+            }
+        }
+        true
+    }
+
+    /// Assign a literal and propagate its consequences.  Returns false
+    /// if there's a conflict, leaving the assignments made so far in
+    /// place to be undone.
+    pub(crate) fn assign(&mut self, lit: Literal) -> bool {
+        let formula = self.formula;
+        let mut next = self.trail.len();
+        self.values[lit.get_id() as usize] = Some(!lit.is_negated());
+        self.trail.push(lit);
+
+        while next < self.trail.len() {
+            let falsified = lit_index(self.trail[next].negate());
+            next += 1;
+            for k in 0..self.occurrences[falsified].len() {
+                let lits = &formula[self.occurrences[falsified][k]];
+                let mut unassigned = None;
+                let mut num_unassigned = 0;
+                if lits.iter().any(|lit| self.value(*lit) == Some(true)) {
+                    continue;
+                }
+                for lit in lits {
+                    if self.value(*lit).is_none() {
+                        unassigned = Some(*lit);
+                        num_unassigned += 1;
+                    }
+                }
+                match (num_unassigned, unassigned) {
+                    (0, _) => return false,
+                    (1, Some(unit)) => {
+                        self.values[unit.get_id() as usize] = Some(!unit.is_negated());
+                        self.trail.push(unit);
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Undo every assignment after the first 'len'.
+    pub(crate) fn undo(&mut self, len: usize) {
+        for lit in self.trail.drain(len..) {
+            self.values[lit.get_id() as usize] = None;
+        }
+    }
+
+    /// Assign a literal, count how many literals it assigns in all,
+    /// and undo it.  Returns 'None' if there's a conflict.
+    fn probe(&mut self, lit: Literal) -> Option<usize> {
+        let len = self.trail.len();
+        let ok = self.assign(lit);
+        let assigned = self.trail.len() - len;
+        self.undo(len);
+        ok.then_some(assigned)
+    }
+
+    /// The unassigned atoms of the clauses which aren't satisfied.
+    fn candidates(&self) -> Vec<usize> {
+        let mut candidate = vec![false; self.values.len()];
+        for lits in self.formula.iter() {
+            if lits.iter().any(|lit| self.value(*lit) == Some(true)) {
+                continue;
+            }
+            for lit in lits {
+                if self.value(*lit).is_none() {
+                    candidate[lit.get_id() as usize] = true;
+                }
+            }
+        }
+        (1..candidate.len()).filter(|id| candidate[*id]).collect()
+    }
+
+    /// Try both literals of every candidate atom, and pick the one
+    /// whose literals assign the most, weighing both the same.  If
+    /// only one literal of an atom causes a conflict, the other is
+    /// implied, so it's assigned and the lookahead starts again.
+    /// Returns 'None' if there's a conflict either way, and 'Some(None)'
+    /// if there's nothing left to split on.
+    pub(crate) fn look_ahead(&mut self) -> Option<Option<Literal>> {
+        'restart: loop {
+            let mut best = None;
+            let mut best_score = 0;
+            for id in self.candidates() {
+                let pos = Literal::new(id as isize);
+                let neg = pos.negate();
+                match (self.probe(pos), self.probe(neg)) {
+                    (None, None) => return None,
+                    (None, Some(_)) => {
+                        if !self.assign(neg) {
+                            return None;
+                        }
+                        continue 'restart;
+                    }
+                    (Some(_), None) => {
+                        if !self.assign(pos) {
+                            return None;
+                        }
+                        continue 'restart;
+                    }
+                    (Some(t), Some(f)) => {
+                        let score = t * f;
+                        if score > best_score {
+                            best_score = score;
+                            best = Some(if f > t { neg } else { pos });
+                        }
+                    }
+                }
+            }
+            return Some(best);
+        }
+    }
+
+    /// Search for a model by splitting on the best atom, and trying
+    /// both of its literals.  The model is left assigned if there is
+    /// one.
+    fn solve(&mut self) -> bool {
+        let len = self.trail.len();
+        if let Some(branch) = self.look_ahead() {
+            match branch {
+                // every clause is satisfied.
+                None => return true,
+                Some(lit) => {
+                    for lit in [lit, lit.negate()] {
+                        let before = self.trail.len();
+                        if self.assign(lit) && self.solve() {
+                            return true;
+                        }
+                        self.undo(before);
+                    }
+                }
+            }
+        }
+
+        self.undo(len);
+        false
+    }
+
+    /// Every atom as a true literal, in order.  Atoms which aren't
+    /// assigned are false.
+    fn model(&self) -> Vec<Literal> {
+        (1..self.values.len())
+            .map(|id| {
+                let lit = Literal::new(id as isize);
+                if self.values[id] == Some(true) {
+                    lit
+                } else {
+                    lit.negate()
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cnf::*;
+    use crate::testing::*;
+    use crate::{sat_assignment_with_engine, Engine};
+
+    #[test]
+    fn refutes_pigeonhole() {
+        // 4 pigeons in 3 holes: pigeon p is in hole h if 3p+h.
+        let mut clauses = Vec::new();
+        for p in 0..4 {
+            clauses.push(Clause::new((1..=3).map(|h| 3 * p + h).collect()));
+        }
+        for h in 1..=3 {
+            for p in 0..4 {
+                for q in p + 1..4 {
+                    clauses.push(Clause::new(vec![-(3 * p + h), -(3 * q + h)]));
+                }
+            }
+        }
+        assert_eq!(None, lookahead(&Formula::new(clauses)));
+    }
+
+    #[test]
+    fn finds_implied_literals() {
+        // 1 fails, since 2 and -2 follow from it, so -1 is implied
+        // without any decisions, and then 3.
+        let formula = Formula::new(vec![
+            Clause::new(vec![-1, 2]),
+            Clause::new(vec![-1, -2]),
+            Clause::new(vec![1, 3]),
+            Clause::new(vec![3, 4, 5]),
+        ]);
+        let mut lookahead = Lookahead::new(&formula);
+        assert!(lookahead.assign_units());
+        assert_eq!(Some(None), lookahead.look_ahead());
+        assert_eq!(vec![Literal::new(-1), Literal::new(3)], lookahead.trail);
+    }
+
+    #[test]
+    fn agrees_with_brute_force() {
+        for (num_vars, formula) in random_formulae(0x2545_f491_4f6c_dd1d, 300) {
+            let brute_force = brute_force_sat(num_vars, &formula, &[]);
+            match sat_assignment_with_engine(formula.clone(), Engine::Lookahead) {
+                Some(lits) => {
+                    assert!(brute_force, "{} is unsat but got {:?}", formula, lits);
+                    assert!(is_model(&formula, &lits));
+                }
+                None => assert!(!brute_force, "{} is sat", formula),
+            }
+        }
+    }
+}
@@ -1,43 +1,469 @@
 extern crate sat;
 
 use sat::cnf::*;
+use sat::dpll::heuristic::Heuristic;
+use sat::dpll::{Config, SolveResult};
+use sat::graph::Graph;
+use sat::maxsat;
+use sat::mus;
+use sat::output;
+use sat::parse::aiger;
+use sat::parse::bv;
+use sat::parse::dimacs::ParseOptions;
 use sat::parse::empty;
 use sat::parse::euf;
+use sat::parse::gcnf::{self, Gcnf};
+use sat::parse::icnf::{self, Step};
+use sat::parse::idl;
+use sat::parse::lia;
+use sat::parse::wcnf::{self, Wcnf};
+use sat::proof::Proof;
+use sat::sls::WalkSat;
+use sat::solver::{Checkpoint, ConflictGraphs, Solver};
+use sat::theory::bv::BV;
+use sat::theory::empty::Empty;
 use sat::theory::Theory;
 
 use std::env;
 use std::fmt::Display;
-use std::io::{self, Read};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read};
 use std::process::exit;
+use std::time::{Duration, Instant};
 
 const EXIT_SAT: i32 = 0;
 const EXIT_UNSAT: i32 = 1;
+const EXIT_UNKNOWN: i32 = 2;
 const EXIT_ERROR: i32 = 254;
 
+const DEFAULT_CHECKPOINT_INTERVAL: u64 = 600;
+
+/// Command-line options.
+struct Options {
+    theory_name: String,
+    checkpoint: Option<String>,
+    checkpoint_interval: Duration,
+    resume: Option<String>,
+    conflict_graph: Option<String>,
+    proof: Option<String>,
+    heuristic: Heuristic,
+    random_decisions: f64,
+    seed: u64,
+    chronological_backtracking: Option<usize>,
+    walk: Option<usize>,
+    max_conflicts: Option<usize>,
+    max_decisions: Option<usize>,
+    timeout: Option<Duration>,
+    deterministic: bool,
+    lenient: bool,
+    competition: bool,
+}
+
 fn main() {
-    let default_theory = "sat".to_string();
-    let theory_name = env::args().nth(1).unwrap_or(default_theory);
-
-    if theory_name == "sat" {
-        let (mut theory, formula) = parse_from_stdin(empty::from_string);
-        smt_main(&mut theory, formula);
-    } else if theory_name == "euf" {
-        let (mut theory, formula) = parse_from_stdin(euf::from_string);
-        smt_main(&mut theory, formula);
+    let options = parse_args(env::args().skip(1));
+
+    if let Some((theory, formula)) = parse_theory(&options) {
+        smt_main(&options, theory, formula);
+    } else if options.theory_name == "bv" {
+        let (bv, formula) = parse_from_stdin(bv::from_string);
+        bv_main(&options, &bv, formula);
+    } else if options.theory_name == "icnf" {
+        let steps = match icnf::from_reader(io::stdin().lock()) {
+            Ok(steps) => steps,
+            Err(e) => die("Failed to parse input:", e, None),
+        };
+        icnf_main(&options, steps);
+    } else if options.theory_name == "maxsat" {
+        let wcnf = match wcnf::from_reader(io::stdin().lock()) {
+            Ok(wcnf) => wcnf,
+            Err(e) => die("Failed to parse input:", e, None),
+        };
+        maxsat_main(wcnf);
+    } else if options.theory_name == "mus" {
+        let gcnf = match gcnf::from_reader(io::stdin().lock()) {
+            Ok(gcnf) => gcnf,
+            Err(e) => die("Failed to parse input:", e, None),
+        };
+        mus_main(gcnf);
     } else {
-        die("Unknown theory:", theory_name, Some("Expected 'sat'"))
+        die(
+            "Unknown theory:",
+            &options.theory_name,
+            Some("Expected 'sat', 'aiger', 'bv', 'euf', 'icnf', 'idl', 'lia', 'maxsat', or 'mus'"),
+        )
+    }
+}
+
+/// Parse a problem from stdin, if the mode is one solved by
+/// 'smt_main', and box its theory.
+fn parse_theory(options: &Options) -> Option<(Box<dyn Theory>, Formula)> {
+    fn boxed<T: Theory + 'static>((theory, formula): (T, Formula)) -> (Box<dyn Theory>, Formula) {
+        (Box::new(theory), formula)
+    }
+
+    match options.theory_name.as_str() {
+        "sat" => {
+            let parse_options = ParseOptions {
+                lenient: options.lenient,
+            };
+            match empty::from_reader_with_options(io::stdin().lock(), parse_options) {
+                Ok(parsed) => Some(boxed(parsed)),
+                Err(e) => die("Failed to parse input:", e, None),
+            }
+        }
+        "aiger" => match aiger::from_reader(io::stdin().lock()) {
+            Ok(aig) => Some(boxed((Empty::new(), aig.to_cnf()))),
+            Err(e) => die("Failed to parse input:", e, None),
+        },
+        "euf" => Some(boxed(parse_from_stdin(euf::from_string))),
+        "idl" => Some(boxed(parse_from_stdin(idl::from_string))),
+        "lia" => Some(boxed(parse_from_stdin(lia::from_string))),
+        _ => None,
+    }
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Options {
+    let mut options = Options {
+        theory_name: "sat".to_string(),
+        checkpoint: None,
+        checkpoint_interval: Duration::from_secs(DEFAULT_CHECKPOINT_INTERVAL),
+        resume: None,
+        conflict_graph: None,
+        proof: None,
+        heuristic: Heuristic::default(),
+        random_decisions: 0.0,
+        seed: 0,
+        chronological_backtracking: None,
+        walk: None,
+        max_conflicts: None,
+        max_decisions: None,
+        timeout: None,
+        deterministic: false,
+        lenient: false,
+        competition: false,
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--checkpoint" => options.checkpoint = Some(flag_value(&arg, args.next())),
+            "--checkpoint-interval" => match flag_value(&arg, args.next()).parse::<u64>() {
+                Ok(secs) => options.checkpoint_interval = Duration::from_secs(secs),
+                Err(e) => die("Bad checkpoint interval:", e, Some("Expected seconds")),
+            },
+            "--resume" => options.resume = Some(flag_value(&arg, args.next())),
+            "--conflict-graph" => options.conflict_graph = Some(flag_value(&arg, args.next())),
+            "--proof" => options.proof = Some(flag_value(&arg, args.next())),
+            "--heuristic" => match flag_value(&arg, args.next()).as_str() {
+                "ordered" => options.heuristic = Heuristic::Ordered,
+                "jw" => options.heuristic = Heuristic::JeroslowWang,
+                "dlis" => options.heuristic = Heuristic::Dlis,
+                "vmtf" => options.heuristic = Heuristic::Vmtf,
+                "inputs" => options.heuristic = Heuristic::Inputs,
+                h => die(
+                    "Unknown heuristic:",
+                    h,
+                    Some("Expected 'ordered', 'jw', 'dlis', 'vmtf', or 'inputs'"),
+                ),
+            },
+            "--random-decisions" => match flag_value(&arg, args.next()).parse::<f64>() {
+                Ok(f) if (0.0..=1.0).contains(&f) => options.random_decisions = f,
+                Ok(f) => die("Bad random decision fraction:", f, Some("Expected 0 to 1")),
+                Err(e) => die("Bad random decision fraction:", e, Some("Expected 0 to 1")),
+            },
+            "--seed" => match flag_value(&arg, args.next()).parse::<u64>() {
+                Ok(seed) => options.seed = seed,
+                Err(e) => die("Bad seed:", e, None),
+            },
+            "--chronological-backtracking" => {
+                match flag_value(&arg, args.next()).parse::<usize>() {
+                    Ok(n) => options.chronological_backtracking = Some(n),
+                    Err(e) => die(
+                        "Bad backtracking threshold:",
+                        e,
+                        Some("Expected a number of levels"),
+                    ),
+                }
+            }
+            "--walk" => match flag_value(&arg, args.next()).parse::<usize>() {
+                Ok(n) => options.walk = Some(n),
+                Err(e) => die("Bad flip limit:", e, Some("Expected a number of flips")),
+            },
+            "--max-conflicts" => match flag_value(&arg, args.next()).parse::<usize>() {
+                Ok(n) => options.max_conflicts = Some(n),
+                Err(e) => die("Bad conflict limit:", e, None),
+            },
+            "--max-decisions" => match flag_value(&arg, args.next()).parse::<usize>() {
+                Ok(n) => options.max_decisions = Some(n),
+                Err(e) => die("Bad decision limit:", e, None),
+            },
+            "--timeout" => match flag_value(&arg, args.next()).parse::<u64>() {
+                Ok(secs) => options.timeout = Some(Duration::from_secs(secs)),
+                Err(e) => die("Bad timeout:", e, Some("Expected seconds")),
+            },
+            "--deterministic" => options.deterministic = true,
+            "--lenient" => options.lenient = true,
+            "--output" => match flag_value(&arg, args.next()).as_str() {
+                "plain" => options.competition = false,
+                "competition" => options.competition = true,
+                o => die(
+                    "Unknown output format:",
+                    o,
+                    Some("Expected 'plain' or 'competition'"),
+                ),
+            },
+            _ if arg.starts_with("--") => die("Unknown flag:", arg, None),
+            _ => options.theory_name = arg,
+        }
+    }
+
+    if options.deterministic && options.timeout.is_some() {
+        die(
+            "Conflicting flags:",
+            "--deterministic --timeout",
+            Some("A timeout depends on the clock"),
+        );
+    }
+
+    options
+}
+
+fn flag_value(flag: &str, value: Option<String>) -> String {
+    match value {
+        Some(v) => v,
+        None => die("Missing value for flag:", flag, None),
     }
 }
 
-fn smt_main<T: Theory>(theory: &mut T, formula: Formula) {
-    if let Some(lits) = sat::smt_assignment(theory, formula) {
-        for lit in lits {
-            println!("{}", lit);
+fn smt_main(options: &Options, theory: Box<dyn Theory>, formula: Formula) {
+    let mut solver = Solver::new(theory, formula);
+    solver.set_config(solver_config(options));
+
+    if let Some(path) = &options.resume {
+        let checkpoint = match File::open(path) {
+            Ok(file) => Checkpoint::read(BufReader::new(file)),
+            Err(e) => die("Failed to open checkpoint:", e, None),
+        };
+        match checkpoint.and_then(|c| solver.resume(c)) {
+            Ok(()) => (),
+            Err(e) => die("Failed to resume from checkpoint:", e, None),
+        }
+    }
+
+    if options.conflict_graph.is_some() {
+        solver.record_conflict_graphs(ConflictGraphs::Final);
+    }
+    if options.proof.is_some() {
+        solver.record_proof(true);
+    }
+
+    let result = if let Some(path) = &options.checkpoint {
+        solver.solve_with_checkpoints(options.checkpoint_interval, |checkpoint| {
+            if let Err(e) = save_checkpoint(path, checkpoint) {
+                eprintln!("Failed to save checkpoint: {}", e);
+            }
+        })
+    } else {
+        solver.solve()
+    };
+
+    if let Some(path) = &options.conflict_graph {
+        if let Some((_, graph)) = solver.conflict_graphs().last() {
+            if let Err(e) = save_graph(path, &graph.to_graph()) {
+                die("Failed to save conflict graph:", e, None);
+            }
         }
-        exit(EXIT_SAT);
+    }
+
+    if let Some(path) = &options.proof {
+        if let Some(proof) = solver.proof() {
+            if let Err(e) = save_proof(path, proof) {
+                die("Failed to save proof:", e, None);
+            }
+        }
+    }
+
+    if options.competition {
+        let result = match result {
+            Ok(result) => result,
+            Err(e) => die("Failed to solve:", e, None),
+        };
+        if let Err(e) = output::write(&result, io::stdout().lock()) {
+            die("Failed to write answer:", e, None);
+        }
+        exit(output::exit_code(&result));
+    }
+
+    match result {
+        Ok(SolveResult::Sat(lits)) => {
+            for lit in lits {
+                println!("{}", lit);
+            }
+            exit(EXIT_SAT);
+        }
+        Ok(SolveResult::Unsat) => {
+            println!("Unsatisfiable!");
+            exit(EXIT_UNSAT);
+        }
+        Ok(SolveResult::Unknown) => {
+            println!("Unknown!");
+            exit(EXIT_UNKNOWN);
+        }
+        Err(e) => die("Failed to solve:", e, None),
+    }
+}
+
+/// Bit-blast and solve a bit-vector problem, printing the model of
+/// the original atoms and then the value of each variable.
+fn bv_main(options: &Options, bv: &BV, formula: Formula) {
+    let blasted = bv.to_cnf(&formula);
+    let mut solver = Solver::new(Empty::new(), blasted.formula.clone());
+    solver.set_config(solver_config(options));
+
+    match solver.solve() {
+        Ok(SolveResult::Sat(lits)) => {
+            for lit in &lits {
+                if lit.get_id() as usize <= blasted.num_atoms {
+                    println!("{}", lit);
+                }
+            }
+            for var in blasted.bits.keys() {
+                if let Some(value) = blasted.value(*var, &lits) {
+                    println!("{} = {}", var, value);
+                }
+            }
+            exit(EXIT_SAT);
+        }
+        Ok(SolveResult::Unsat) => {
+            println!("Unsatisfiable!");
+            exit(EXIT_UNSAT);
+        }
+        Ok(SolveResult::Unknown) => {
+            println!("Unknown!");
+            exit(EXIT_UNKNOWN);
+        }
+        Err(e) => die("Failed to solve:", e, None),
+    }
+}
+
+/// Replay an incremental problem, printing the answer to each query
+/// on a line: the model, 'Unsatisfiable!', or 'Unknown!'.  The exit
+/// status is that of the last query.
+fn icnf_main(options: &Options, steps: Vec<Step>) {
+    let mut solver = Solver::new(Empty::new(), Formula::new(Vec::new()));
+    solver.set_config(solver_config(options));
+
+    let mut status = EXIT_SAT;
+    for step in steps {
+        let assumptions = match step {
+            Step::AddClause(clause) => {
+                solver.add_clause(clause);
+                continue;
+            }
+            Step::Solve(assumptions) => assumptions,
+        };
+        status = match solver.solve_with_assumptions(&assumptions) {
+            Ok(SolveResult::Sat(mut lits)) => {
+                lits.sort_by_key(|lit| lit.var());
+                let lits: Vec<String> = lits.iter().map(|lit| lit.to_string()).collect();
+                println!("{}", lits.join(" "));
+                EXIT_SAT
+            }
+            Ok(SolveResult::Unsat) => {
+                println!("Unsatisfiable!");
+                EXIT_UNSAT
+            }
+            Ok(SolveResult::Unknown) => {
+                println!("Unknown!");
+                EXIT_UNKNOWN
+            }
+            Err(e) => die("Failed to solve:", e, None),
+        };
+    }
+    exit(status);
+}
+
+/// The solver configuration given by the flags.
+fn solver_config(options: &Options) -> Config {
+    Config {
+        heuristic: options.heuristic,
+        random_decisions: options.random_decisions,
+        seed: options.seed,
+        chronological_backtracking: options.chronological_backtracking,
+        walk: options.walk.map(|max_flips| WalkSat {
+            max_flips,
+            seed: options.seed,
+            ..WalkSat::default()
+        }),
+        max_conflicts: options.max_conflicts,
+        max_decisions: options.max_decisions,
+        deadline: options.timeout.map(|timeout| Instant::now() + timeout),
+        deterministic: options.deterministic,
+        ..Config::default()
+    }
+}
+
+/// Print the cost of an optimal model, and the model.
+fn maxsat_main(wcnf: Wcnf) {
+    match maxsat::solve_weighted(&wcnf.hard, &wcnf.soft) {
+        Some(mut solution) => {
+            solution.model.sort_by_key(|lit| lit.var());
+            println!("Cost: {}", solution.cost);
+            for lit in solution.model {
+                println!("{}", lit);
+            }
+            exit(EXIT_SAT);
+        }
+        None => {
+            println!("Unsatisfiable!");
+            exit(EXIT_UNSAT);
+        }
+    }
+}
+
+/// Print the groups of a minimal unsatisfiable subset, or
+/// 'Satisfiable!' if there isn't one.
+fn mus_main(gcnf: Gcnf) {
+    match mus::group_mus(&gcnf.hard, &gcnf.groups) {
+        Some(groups) => {
+            for group in groups {
+                println!("{}", group);
+            }
+            exit(EXIT_UNSAT);
+        }
+        None => {
+            println!("Satisfiable!");
+            exit(EXIT_SAT);
+        }
+    }
+}
+
+/// Write a checkpoint to a temporary file and then move it into
+/// place, so a crash mid-write doesn't clobber the last checkpoint.
+fn save_checkpoint(path: &str, checkpoint: &Checkpoint) -> io::Result<()> {
+    let tmp_path = format!("{}.tmp", path);
+    checkpoint.write(BufWriter::new(File::create(&tmp_path)?))?;
+    fs::rename(tmp_path, path)
+}
+
+/// Write a graph in GraphML format if the path ends in '.graphml',
+/// and in DOT format otherwise.
+fn save_graph(path: &str, graph: &Graph) -> io::Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+    if path.ends_with(".graphml") {
+        graph.write_graphml(writer)
+    } else {
+        graph.write_dot(writer)
+    }
+}
+
+/// Write a proof in LRAT format if the path ends in '.lrat', and in
+/// DRAT format otherwise.
+fn save_proof(path: &str, proof: &Proof) -> io::Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+    if path.ends_with(".lrat") {
+        proof.write_lrat(writer)
     } else {
-        println!("Unsatisfiable!");
-        exit(EXIT_UNSAT);
+        proof.write_drat(writer)
     }
 }
 
@@ -56,7 +482,7 @@ fn die<T: Display>(msg: &str, e: T, ohint: Option<&str>) -> ! {
     eprintln!("{}", msg);
     eprintln!("    {}", e);
     if let Some(hint) = ohint {
-        eprintln!("");
+        eprintln!();
         eprintln!("{}", hint);
     }
 
@@ -0,0 +1,234 @@
+//! Maximum satisfiability: find a model of some hard clauses which
+//! falsifies as few soft clauses as possible, or as little weight of
+//! them.
+//!
+//! The unweighted solver gives each soft clause a new relaxation atom,
+//! which can be made true to satisfy it, and looks for models with
+//! fewer and fewer true relaxation atoms, until there are none: the
+//! last model found is optimal.
+//!
+//! The weighted solver works upwards from below instead, as in
+//! Fu-Malik: it assumes every soft clause is satisfied, and when that
+//! fails, the unsatisfiable core of assumptions says which soft
+//! clauses conflict, so one of them has to be given up.
+
+use crate::cnf::encodings::{self, Cardinality};
+use crate::cnf::{Clause, Formula, Literal, VarAllocator};
+use crate::dpll::SolveResult;
+use crate::solver::Solver;
+use crate::theory::empty::Empty;
+
+/// An optimal model.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub struct Solution {
+    /// The number of soft clauses the model falsifies, or their total
+    /// weight.
+    pub cost: usize,
+    /// The model, which satisfies every hard clause.
+    pub model: Vec<Literal>,
+}
+
+/// Find a model of the hard clauses which falsifies as few of the soft
+/// clauses as possible.  Returns 'None' if the hard clauses are
+/// unsatisfiable.
+pub fn solve(hard: &Formula, soft: &Formula) -> Option<Solution> {
+    let mut vars = VarAllocator::above(hard);
+    vars.reserve_formula(soft);
+    let first_relaxation = vars.next_var();
+
+    let mut solver = Solver::new(Empty::new(), hard.clone());
+    let mut relaxations = Vec::with_capacity(soft.len());
+    for mut clause in soft.clauses() {
+        let relaxation = vars.fresh().positive();
+        clause.insert_literal(relaxation);
+        solver.add_clause(clause);
+        relaxations.push(relaxation);
+    }
+
+    let mut best = None;
+    // the empty theory never fails.
+    while let Ok(SolveResult::Sat(model)) = solver.solve() {
+        let model: Vec<Literal> = model
+            .into_iter()
+            .filter(|lit| lit.var() < first_relaxation)
+            .collect();
+        let cost = soft
+            .iter()
+            .filter(|lits| !lits.iter().any(|lit| model.contains(lit)))
+            .count();
+        best = Some(Solution { cost, model });
+        if cost == 0 {
+            break;
+        }
+        for clause in at_most(&relaxations, cost - 1, &mut vars) {
+            solver.add_clause(clause);
+        }
+    }
+    best
+}
+
+/// Find a model of the hard clauses which minimises the total weight
+/// of the soft clauses it falsifies.  Returns 'None' if the hard
+/// clauses are unsatisfiable.
+pub fn solve_weighted(hard: &Formula, soft: &[(Clause, usize)]) -> Option<Solution> {
+    let mut vars = VarAllocator::above(hard);
+    for (clause, _) in soft {
+        for lit in clause.literals() {
+            vars.reserve(lit.var());
+        }
+    }
+    let first_relaxation = vars.next_var();
+    let mut solver = Solver::new(Empty::new(), hard.clone());
+
+    // the soft clauses, with their relaxation atoms, the assumption
+    // which enforces them, and their remaining weight.
+    let mut clauses: Vec<(Vec<Literal>, Literal, usize)> = Vec::with_capacity(soft.len());
+    for (clause, weight) in soft {
+        if *weight > 0 {
+            let lits = clause.literals().to_vec();
+            let assumption = block(&mut solver, lits.clone(), &mut vars);
+            clauses.push((lits, assumption, *weight));
+        }
+    }
+
+    loop {
+        let assumptions: Vec<Literal> = clauses.iter().map(|(_, a, _)| *a).collect();
+        // the empty theory never fails.
+        match solver.solve_with_assumptions(&assumptions).ok()? {
+            SolveResult::Sat(model) => {
+                let model: Vec<Literal> = model
+                    .into_iter()
+                    .filter(|lit| lit.var() < first_relaxation)
+                    .collect();
+                let cost = soft
+                    .iter()
+                    .filter(|(clause, _)| !clause.literals().iter().any(|lit| model.contains(lit)))
+                    .map(|(_, weight)| weight)
+                    .sum();
+                return Some(Solution { cost, model });
+            }
+            SolveResult::Unsat => (),
+            SolveResult::Unknown => return None,
+        }
+
+        let core = solver.failed_assumptions().to_vec();
+        if core.is_empty() {
+            return None;
+        }
+        let in_core: Vec<usize> = (0..clauses.len())
+            .filter(|i| core.contains(&clauses[*i].1))
+            .collect();
+        let min_weight = in_core.iter().map(|i| clauses[*i].2).min().unwrap();
+
+        // relax a copy of each clause in the core, with the minimum
+        // weight: exactly one of the copies can be given up.  What's
+        // left of the weight stays with the original.
+        let mut relaxations = Vec::with_capacity(in_core.len());
+        for i in in_core.iter().rev() {
+            let relaxation = vars.fresh().positive();
+            relaxations.push(relaxation);
+
+            let mut lits = clauses[*i].0.clone();
+            lits.push(relaxation);
+            let assumption = block(&mut solver, lits.clone(), &mut vars);
+            if clauses[*i].2 == min_weight {
+                clauses.remove(*i);
+            } else {
+                clauses[*i].2 -= min_weight;
+            }
+            clauses.push((lits, assumption, min_weight));
+        }
+        solver.add_clause(Clause::from(relaxations.clone()));
+        for clause in at_most(&relaxations, 1, &mut vars) {
+            solver.add_clause(clause);
+        }
+    }
+}
+
+/// Add a soft clause to the solver with a new blocking atom, so that
+/// it's only enforced when the returned literal is assumed.
+fn block(solver: &mut Solver<Empty>, mut lits: Vec<Literal>, vars: &mut VarAllocator) -> Literal {
+    let blocking = vars.fresh().positive();
+    lits.push(blocking);
+    solver.add_clause(Clause::from(lits));
+    blocking.negate()
+}
+
+/// Encode that at most 'k' of the literals are true.
+fn at_most(lits: &[Literal], k: usize, vars: &mut VarAllocator) -> Vec<Clause> {
+    encodings::at_most_k(lits, k, Cardinality::SequentialCounter, vars).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cnf::*;
+    use crate::rng::Rng;
+    use crate::testing::random_clause;
+
+    #[test]
+    fn minimises_falsified_soft_clauses() {
+        // at most one of 1, 2, and 3, but each is wanted.
+        let hard = Formula::new(vec![
+            Clause::new(vec![-1, -2]),
+            Clause::new(vec![-1, -3]),
+            Clause::new(vec![-2, -3]),
+        ]);
+        let soft = Formula::new(vec![
+            Clause::new(vec![1]),
+            Clause::new(vec![2]),
+            Clause::new(vec![3]),
+            Clause::new(vec![-4]),
+        ]);
+        let solution = solve(&hard, &soft).unwrap();
+        assert_eq!(2, solution.cost);
+        assert!(solution.model.contains(&Literal::new(-4)));
+        for lits in hard.iter() {
+            assert!(lits.iter().any(|lit| solution.model.contains(lit)));
+        }
+
+        let unsat = Formula::new(vec![Clause::new(vec![1]), Clause::new(vec![-1])]);
+        assert_eq!(None, solve(&unsat, &soft));
+    }
+
+    #[test]
+    fn minimises_falsified_weight() {
+        let mut rng = Rng::new(0x2545_f491_4f6c_dd1d);
+        for _ in 0..100 {
+            let hard = Formula::new((0..3).map(|_| random_clause(&mut rng, 6, 2)).collect());
+            let soft: Vec<(Clause, usize)> = (0..8)
+                .map(|i| (random_clause(&mut rng, 6, 1 + i % 2), 1 + i % 4))
+                .collect();
+
+            let falsified = |truth: &dyn Fn(&Literal) -> bool| {
+                soft.iter()
+                    .filter(|(clause, _)| !clause.literals().iter().any(truth))
+                    .map(|(_, weight)| weight)
+                    .sum::<usize>()
+            };
+            let optimum = (0..1u32 << 6)
+                .filter_map(|bits| {
+                    let truth =
+                        |lit: &Literal| (bits & (1 << (lit.get_id() - 1)) != 0) != lit.is_negated();
+                    if hard.iter().all(|lits| lits.iter().any(truth)) {
+                        Some(falsified(&truth))
+                    } else {
+                        None
+                    }
+                })
+                .min();
+
+            match solve_weighted(&hard, &soft) {
+                Some(solution) => {
+                    assert_eq!(optimum, Some(solution.cost));
+                    let truth = |lit: &Literal| solution.model.contains(lit);
+                    assert_eq!(solution.cost, falsified(&truth));
+                    for lits in hard.iter() {
+                        assert!(lits.iter().any(truth));
+                    }
+                }
+                None => assert_eq!(None, optimum),
+            }
+        }
+    }
+}
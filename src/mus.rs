@@ -0,0 +1,121 @@
+//! Minimal unsatisfiable subsets: a smallest-by-inclusion set of
+//! clauses, or groups of clauses, which is unsatisfiable by itself, so
+//! dropping any one of them makes it satisfiable.
+//!
+//! Each group gets a new selector atom, which is added negated to its
+//! clauses, so assuming the selector turns the group on.  Groups are
+//! then dropped one at a time: if the rest are still unsatisfiable,
+//! only the groups in their core of failed assumptions are kept, and
+//! otherwise the dropped group is in the MUS.
+
+use std::collections::BTreeMap;
+
+use crate::cnf::{Formula, Literal, VarAllocator};
+use crate::dpll::SolveResult;
+use crate::solver::Solver;
+use crate::theory::empty::Empty;
+
+/// Find a MUS of a formula, as the indices of its clauses.  Returns
+/// 'None' if the formula is satisfiable.
+pub fn mus(formula: &Formula) -> Option<Vec<usize>> {
+    let groups = (0..formula.len())
+        .map(|i| (i, Formula::new(vec![formula.clause(i)])))
+        .collect();
+    group_mus(&Formula::new(Vec::new()), &groups)
+}
+
+/// Find a MUS of groups, as their numbers, given the hard clauses
+/// which are always on.  Returns 'None' if the hard clauses and all
+/// the groups together are satisfiable, and no groups if the hard
+/// clauses are unsatisfiable by themselves.
+pub fn group_mus(hard: &Formula, groups: &BTreeMap<usize, Formula>) -> Option<Vec<usize>> {
+    let mut vars = VarAllocator::above(hard);
+    for formula in groups.values() {
+        vars.reserve_formula(formula);
+    }
+
+    let mut solver = Solver::new(Empty::new(), hard.clone());
+    let mut selectors: Vec<(usize, Literal)> = Vec::with_capacity(groups.len());
+    for (group, formula) in groups {
+        let selector = vars.fresh().positive();
+        for mut clause in formula.clauses() {
+            clause.insert_literal(selector.negate());
+            solver.add_clause(clause);
+        }
+        selectors.push((*group, selector));
+    }
+
+    // the groups which might be in the MUS, and those which must be.
+    let mut unknown = selectors;
+    let mut needed = Vec::new();
+    if !refine(&mut solver, &mut unknown, &needed)? {
+        return None;
+    }
+    while let Some(candidate) = unknown.pop() {
+        if !refine(&mut solver, &mut unknown, &needed)? {
+            needed.push(candidate);
+        }
+    }
+
+    let mut mus: Vec<usize> = needed.into_iter().map(|(group, _)| group).collect();
+    mus.sort_unstable();
+    Some(mus)
+}
+
+/// Check if the needed and unknown groups are unsatisfiable together
+/// and, if so, drop the unknown groups which aren't in the core.
+/// Returns 'None' if the search gives up.
+fn refine(
+    solver: &mut Solver<Empty>,
+    unknown: &mut Vec<(usize, Literal)>,
+    needed: &[(usize, Literal)],
+) -> Option<bool> {
+    let assumptions: Vec<Literal> = needed
+        .iter()
+        .chain(unknown.iter())
+        .map(|(_, selector)| *selector)
+        .collect();
+    // the empty theory never fails.
+    match solver.solve_with_assumptions(&assumptions).ok()? {
+        SolveResult::Sat(_) => Some(false),
+        SolveResult::Unsat => {
+            let core = solver.failed_assumptions();
+            unknown.retain(|(_, selector)| core.contains(selector));
+            Some(true)
+        }
+        SolveResult::Unknown => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cnf::*;
+
+    #[test]
+    fn finds_minimal_subsets() {
+        // 1 and -1 conflict; so do 2, -2 or 3, and -3.
+        let formula = cnf![[1], [2], [-1], [4, 5], [-2, 3], [-3]];
+        let found = mus(&formula).unwrap();
+        assert!(found == vec![0, 2] || found == vec![1, 4, 5]);
+        assert!(!crate::sat(Formula::new(
+            found.iter().map(|i| formula.clause(*i)).collect()
+        )));
+
+        assert_eq!(None, mus(&cnf![[1, 2], [-1]]));
+    }
+
+    #[test]
+    fn finds_minimal_groups() {
+        let hard = cnf![[1, 2]];
+        let mut groups = BTreeMap::new();
+        groups.insert(1, cnf![[-1], [3]]);
+        groups.insert(2, cnf![[4]]);
+        groups.insert(5, cnf![[-2, -3]]);
+        assert_eq!(Some(vec![1, 5]), group_mus(&hard, &groups));
+
+        groups.remove(&5);
+        assert_eq!(None, group_mus(&hard, &groups));
+        assert_eq!(Some(Vec::new()), group_mus(&cnf![[1], [-1]], &groups));
+    }
+}
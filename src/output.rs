@@ -0,0 +1,101 @@
+//! Answers in the format of the SAT competition, which harnesses and
+//! fuzzers expect: an 's' line with the answer and, for a satisfiable
+//! problem, the model in 'v' lines ending with a 0.
+
+use std::io::{self, Write};
+
+use crate::cnf::Literal;
+use crate::dpll::SolveResult;
+
+/// The exit status for a satisfiable problem.
+pub const EXIT_SAT: i32 = 10;
+
+/// The exit status for an unsatisfiable problem.
+pub const EXIT_UNSAT: i32 = 20;
+
+/// The exit status when the answer is unknown.
+pub const EXIT_UNKNOWN: i32 = 0;
+
+/// The longest a 'v' line gets, unless a single literal is longer.
+const LINE_WIDTH: usize = 78;
+
+/// Write an answer.  The model is written in order of atom.
+pub fn write<W: Write>(result: &SolveResult, mut writer: W) -> io::Result<()> {
+    match result {
+        SolveResult::Sat(lits) => {
+            writeln!(writer, "s SATISFIABLE")?;
+            write_values(&mut writer, lits)?;
+        }
+        SolveResult::Unsat => writeln!(writer, "s UNSATISFIABLE")?,
+        SolveResult::Unknown => writeln!(writer, "s UNKNOWN")?,
+    }
+    writer.flush()
+}
+
+/// Write a model in 'v' lines, wrapped at 'LINE_WIDTH'.
+fn write_values<W: Write>(writer: &mut W, lits: &[Literal]) -> io::Result<()> {
+    let mut lits = lits.to_vec();
+    lits.sort_by_key(|lit| lit.var());
+    let words = lits
+        .iter()
+        .map(|lit| lit.to_string())
+        .chain(Some("0".to_string()));
+
+    let mut line = String::from("v");
+    for word in words {
+        if line.len() > 1 && line.len() + 1 + word.len() > LINE_WIDTH {
+            writeln!(writer, "{}", line)?;
+            line.truncate(1);
+        }
+        line.push(' ');
+        line.push_str(&word);
+    }
+    writeln!(writer, "{}", line)
+}
+
+/// The exit status for an answer.
+pub fn exit_code(result: &SolveResult) -> i32 {
+    match result {
+        SolveResult::Sat(_) => EXIT_SAT,
+        SolveResult::Unsat => EXIT_UNSAT,
+        SolveResult::Unknown => EXIT_UNKNOWN,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn written(result: &SolveResult) -> String {
+        let mut out = Vec::new();
+        write(result, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn writes_answers() {
+        let model = vec![Literal::new(2), Literal::new(-1), Literal::new(3)];
+        assert_eq!(
+            "s SATISFIABLE\nv -1 2 3 0\n",
+            written(&SolveResult::Sat(model))
+        );
+        assert_eq!("s UNSATISFIABLE\n", written(&SolveResult::Unsat));
+        assert_eq!("s UNKNOWN\n", written(&SolveResult::Unknown));
+        assert_eq!(EXIT_UNSAT, exit_code(&SolveResult::Unsat));
+    }
+
+    #[test]
+    fn wraps_value_lines() {
+        let model = (1..=100).map(|i| Literal::new(-i)).collect();
+        let out = written(&SolveResult::Sat(model));
+        let lines: Vec<&str> = out.lines().skip(1).collect();
+        assert!(lines.len() > 1);
+        assert!(lines
+            .iter()
+            .all(|l| l.starts_with("v ") && l.len() <= LINE_WIDTH));
+        assert!(lines.last().unwrap().ends_with(" -100 0"));
+
+        let words: Vec<&str> = lines.iter().flat_map(|l| l.split(' ').skip(1)).collect();
+        assert_eq!(101, words.len());
+    }
+}
@@ -0,0 +1,200 @@
+//! Solving with several searches at once, each on its own thread,
+//! taking the answer of whichever finishes first.  Searches of the
+//! same formula with different heuristics or seeds can take wildly
+//! different times, so a portfolio of them is more robust than any
+//! one of them.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::cnf::{Clause, Formula};
+use crate::dpll::heuristic::Heuristic;
+use crate::dpll::{search, Config, Control, Outcome, Snapshot, SolveResult, SolverError};
+use crate::theory::Theory;
+
+/// The longest learned clause shared by default.
+const DEFAULT_SHARE_CLAUSES: usize = 8;
+
+/// A portfolio of search configurations.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub struct Portfolio {
+    /// The configuration of each search.
+    pub configs: Vec<Config>,
+    /// Share learned clauses with at most this many literals between
+    /// the searches.  Each search adds the clauses learned by the
+    /// others when it restarts.
+    pub share_clauses: Option<usize>,
+}
+
+impl Portfolio {
+    /// A portfolio of searches which differ in their heuristic and
+    /// seed, sharing short learned clauses.
+    pub fn new(threads: usize) -> Portfolio {
+        let configs = (0..threads)
+            .map(|i| Config {
+                heuristic: if i % 2 == 0 {
+                    Heuristic::Ordered
+                } else {
+                    Heuristic::Vmtf
+                },
+                random_decisions: if i < 2 { 0.0 } else { 0.05 },
+                seed: i as u64,
+                ..Config::default()
+            })
+            .collect();
+
+        Portfolio {
+            configs,
+            share_clauses: Some(DEFAULT_SHARE_CLAUSES),
+        }
+    }
+
+    /// Find a model which satisfies the formula, if one exists.  Each
+    /// search has its own copy of the theory.  If every search runs
+    /// out of budget, the result is unknown.
+    pub fn solve<T: Theory + Clone + Send>(
+        &self,
+        theory: &T,
+        formula: &Formula,
+    ) -> Result<SolveResult, SolverError> {
+        let interrupt = AtomicBool::new(false);
+        let answer = Mutex::new(None);
+        // every clause shared so far, with the search which learned it.
+        let shared: Mutex<Vec<(usize, Clause)>> = Mutex::new(Vec::new());
+
+        thread::scope(|scope| {
+            for (worker, config) in self.configs.iter().enumerate() {
+                let mut theory = theory.clone();
+                let formula = formula.clone();
+                let (interrupt, answer, shared) = (&interrupt, &answer, &shared);
+                let share_clauses = self.share_clauses;
+
+                scope.spawn(move || {
+                    let mut export = |clause: &Clause, _lbd: usize| {
+                        if share_clauses.is_some_and(|max| clause.len() <= max) {
+                            shared.lock().unwrap().push((worker, clause.clone()));
+                        }
+                    };
+                    let mut imported = 0;
+                    let mut import = || {
+                        let shared = shared.lock().unwrap();
+                        let clauses = shared[imported..]
+                            .iter()
+                            .filter(|(from, _)| *from != worker)
+                            .map(|(_, clause)| clause.clone())
+                            .collect();
+                        imported = shared.len();
+                        clauses
+                    };
+                    let sharing = share_clauses.is_some();
+                    let control = Control {
+                        interrupt: Some(interrupt),
+                        export: if sharing { Some(&mut export) } else { None },
+                        import: if sharing { Some(&mut import) } else { None },
+                        ..Control::default()
+                    };
+
+                    let outcome = search(
+                        &mut theory,
+                        formula,
+                        Snapshot::default(),
+                        &[],
+                        config,
+                        control,
+                    )
+                    .outcome;
+                    let result = match outcome {
+                        Outcome::Sat(model) => Ok(SolveResult::Sat(model.get_assignments())),
+                        Outcome::Unsat => Ok(SolveResult::Unsat),
+                        Outcome::Failed(e) => Err(e),
+                        // another search finished first, or this one
+                        // gave up, which shouldn't stop the others.
+                        Outcome::Interrupted | Outcome::OutOfBudget => return,
+                    };
+
+                    answer.lock().unwrap().get_or_insert(result);
+                    interrupt.store(true, Ordering::Relaxed);
+                });
+            }
+        });
+
+        answer
+            .into_inner()
+            .unwrap()
+            .unwrap_or(Ok(SolveResult::Unknown))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cnf::*;
+    use crate::dpll::dpll;
+    use crate::rng::Rng;
+    use crate::testing::{is_model, random_formula};
+    use crate::theory::empty::Empty;
+
+    fn pigeonhole(n: isize) -> Formula {
+        // n+1 pigeons in n holes: pigeon p is in hole h if p*n+h.
+        let mut clauses = Vec::new();
+        for p in 0..=n {
+            clauses.push(Clause::new((1..=n).map(|h| p * n + h).collect()));
+        }
+        for h in 1..=n {
+            for p in 0..=n {
+                for q in p + 1..=n {
+                    clauses.push(Clause::new(vec![-(p * n + h), -(q * n + h)]));
+                }
+            }
+        }
+        Formula::new(clauses)
+    }
+
+    #[test]
+    fn solves_pigeonhole() {
+        let mut portfolio = Portfolio::new(4);
+        assert_eq!(
+            Ok(SolveResult::Unsat),
+            portfolio.solve(&Empty::new(), &pigeonhole(6))
+        );
+
+        portfolio.share_clauses = None;
+        assert_eq!(
+            Ok(SolveResult::Unsat),
+            portfolio.solve(&Empty::new(), &pigeonhole(5))
+        );
+    }
+
+    #[test]
+    fn agrees_with_one_search() {
+        let mut rng = Rng::new(0x853c_49e6_748f_ea9b);
+        let portfolio = Portfolio::new(3);
+        for _ in 0..100 {
+            let num_vars = 1 + rng.below(12);
+            let formula = random_formula(&mut rng, num_vars, 4 * num_vars, 3);
+
+            let expected = dpll(&mut Empty::new(), formula.clone()).unwrap();
+            match portfolio.solve(&Empty::new(), &formula).unwrap() {
+                SolveResult::Sat(lits) => {
+                    assert!(expected.is_sat());
+                    assert!(is_model(&formula, &lits));
+                }
+                SolveResult::Unsat => assert!(expected.is_unsat()),
+                SolveResult::Unknown => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn gives_up_when_every_search_does() {
+        let mut portfolio = Portfolio::new(2);
+        for config in &mut portfolio.configs {
+            config.max_conflicts = Some(1);
+        }
+        assert_eq!(
+            Ok(SolveResult::Unknown),
+            portfolio.solve(&Empty::new(), &pigeonhole(5))
+        );
+    }
+}
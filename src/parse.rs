@@ -1,6 +1,63 @@
 //! THeory parsers
 
+use std::fmt;
+
+pub mod aiger;
+pub mod binary;
+pub mod bv;
 pub mod combinators;
 pub mod dimacs;
 pub mod empty;
 pub mod euf;
+pub mod gcnf;
+pub mod icnf;
+pub mod idl;
+pub mod lia;
+pub mod wcnf;
+
+/// The most characters of a line to show in an error.
+const SNIPPET_LENGTH: usize = 20;
+
+/// Where in the input an error is.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub struct Position {
+    /// The line, from 1.
+    pub line: usize,
+    /// The column, in characters from 1.
+    pub column: usize,
+    /// The text of the line from the column on, cut short if it is
+    /// long.
+    pub snippet: String,
+}
+
+impl Position {
+    /// The position of a byte offset into a line.
+    pub(crate) fn new(line: usize, text: &str, offset: usize) -> Position {
+        let rest = text[offset..].trim_end();
+        let mut snippet: String = rest.chars().take(SNIPPET_LENGTH).collect();
+        if snippet.len() < rest.len() {
+            snippet.push_str("...");
+        }
+        Position {
+            line,
+            column: text[..offset].chars().count() + 1,
+            snippet,
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: '{}'",
+            self.line, self.column, self.snippet
+        )
+    }
+}
+
+/// The byte offset of a word in a line, where the word is a slice of
+/// the line.
+pub(crate) fn offset(line: &str, word: &str) -> usize {
+    word.as_ptr() as usize - line.as_ptr() as usize
+}
@@ -0,0 +1,294 @@
+//! Parser for AIGER, the and-inverter graph format of hardware model
+//! checkers, in both its ASCII ('aag') and binary ('aig') forms, and
+//! conversion of the graph to CNF.
+//!
+//! An AIGER literal is twice a variable, plus one if it's negated.
+//! Variable 0 is the constant false, so literal 1 is true.  Symbol
+//! tables and comments, after the gates, are ignored.
+
+use std::fmt;
+use std::io::{BufRead, Read};
+
+use crate::cnf::*;
+
+/// An and-inverter graph.
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub struct Aig {
+    /// The highest variable.
+    pub max_var: usize,
+    /// The input literals.
+    pub inputs: Vec<usize>,
+    /// Each latch literal, with the literal of its next state.
+    pub latches: Vec<(usize, usize)>,
+    /// The output literals.
+    pub outputs: Vec<usize>,
+    /// Each and gate: its literal, and the literals of its inputs.
+    pub ands: Vec<(usize, usize, usize)>,
+}
+
+impl Aig {
+    /// Convert the graph to CNF with the Tseitin encoding, asserting
+    /// that every output is true.  Variable v is atom v, and latches
+    /// are free, as inputs, so this is one step of the circuit.
+    pub fn to_cnf(&self) -> Formula {
+        let mut clauses = Vec::new();
+        for &(lhs, rhs0, rhs1) in &self.ands {
+            let gate = Literal::new((lhs / 2) as isize);
+            let inputs = [literal(rhs0), literal(rhs1)];
+
+            // the gate implies each input.
+            for input in &inputs {
+                match input {
+                    Ok(lit) => clauses.push(vec![gate.negate(), *lit].into_iter().collect()),
+                    Err(true) => (),
+                    Err(false) => clauses.push(Clause::from(vec![gate.negate()])),
+                }
+            }
+            // the inputs imply the gate.
+            if !inputs.contains(&Err(false)) {
+                let mut lits = vec![gate];
+                lits.extend(
+                    inputs
+                        .iter()
+                        .filter_map(|input| input.ok())
+                        .map(Literal::negate),
+                );
+                clauses.push(lits.into_iter().collect());
+            }
+        }
+        for output in &self.outputs {
+            match literal(*output) {
+                Ok(lit) => clauses.push(Clause::from(vec![lit])),
+                Err(true) => (),
+                Err(false) => clauses.push(Clause::from(Vec::new())),
+            }
+        }
+        Formula::new(clauses)
+    }
+}
+
+/// The CNF literal of an AIGER literal, or its value if it's a
+/// constant.
+fn literal(lit: usize) -> Result<Literal, bool> {
+    match lit / 2 {
+        0 => Err(lit == 1),
+        var => Ok(Var::new(var).literal(lit.is_multiple_of(2))),
+    }
+}
+
+/// Parse a string in the ASCII AIGER format.
+pub fn from_string(aag: String) -> Result<Aig, ParseError> {
+    from_reader(aag.as_bytes())
+}
+
+/// Parse either AIGER format from a reader, going by the header.
+pub fn from_reader<R: BufRead>(mut reader: R) -> Result<Aig, ParseError> {
+    let header = read_line(&mut reader)?;
+    let mut words = header.split_ascii_whitespace();
+    let binary = match words.next() {
+        Some("aag") => false,
+        Some("aig") => true,
+        Some(fmt) => return Err(ParseError::UnexpectedFormat(fmt.to_string())),
+        None => return Err(ParseError::CannotParseHeader(header)),
+    };
+    let numbers: Result<Vec<usize>, _> = words.map(|w| w.parse::<usize>()).collect();
+    let (max_var, num_inputs, num_latches, num_outputs, num_ands) = match numbers.as_deref() {
+        Ok([m, i, l, o, a]) => (*m, *i, *l, *o, *a),
+        _ => return Err(ParseError::CannotParseHeader(header)),
+    };
+
+    let mut aig = Aig {
+        max_var,
+        ..Aig::default()
+    };
+    let max_lit = 2 * max_var + 1;
+    let check = |lit: usize| {
+        if lit > max_lit {
+            Err(ParseError::LiteralOutOfRange(lit))
+        } else {
+            Ok(lit)
+        }
+    };
+
+    for i in 0..num_inputs {
+        aig.inputs.push(if binary {
+            2 * (i + 1)
+        } else {
+            check(read_literals(&mut reader, 1, 1)?[0])?
+        });
+    }
+    for i in 0..num_latches {
+        // the optional initial value doesn't matter for one step.
+        let (lit, next) = if binary {
+            let lits = read_literals(&mut reader, 1, 2)?;
+            (2 * (num_inputs + i + 1), lits[0])
+        } else {
+            let lits = read_literals(&mut reader, 2, 3)?;
+            (lits[0], lits[1])
+        };
+        aig.latches.push((check(lit)?, check(next)?));
+    }
+    for _ in 0..num_outputs {
+        aig.outputs
+            .push(check(read_literals(&mut reader, 1, 1)?[0])?);
+    }
+    for i in 0..num_ands {
+        let (lhs, rhs0, rhs1) = if binary {
+            let lhs = 2 * (num_inputs + num_latches + i + 1);
+            let rhs0 = lhs
+                .checked_sub(read_number(&mut reader)?)
+                .ok_or(ParseError::LiteralOutOfRange(lhs))?;
+            let rhs1 = rhs0
+                .checked_sub(read_number(&mut reader)?)
+                .ok_or(ParseError::LiteralOutOfRange(rhs0))?;
+            (lhs, rhs0, rhs1)
+        } else {
+            let lits = read_literals(&mut reader, 3, 3)?;
+            (lits[0], lits[1], lits[2])
+        };
+        aig.ands.push((check(lhs)?, check(rhs0)?, check(rhs1)?));
+    }
+
+    for lit in aig
+        .inputs
+        .iter()
+        .chain(aig.latches.iter().map(|(lit, _)| lit))
+    {
+        if !lit.is_multiple_of(2) || *lit == 0 {
+            return Err(ParseError::NotAVariable(*lit));
+        }
+    }
+    for (lhs, _, _) in &aig.ands {
+        if !lhs.is_multiple_of(2) || *lhs == 0 {
+            return Err(ParseError::NotAVariable(*lhs));
+        }
+    }
+    Ok(aig)
+}
+
+/// Read a line, without the newline.
+fn read_line<R: BufRead>(reader: &mut R) -> Result<String, ParseError> {
+    let mut line = String::new();
+    match reader.read_line(&mut line) {
+        Ok(0) => Err(ParseError::UnexpectedEnd),
+        Ok(_) => Ok(line.trim_end().to_string()),
+        Err(e) => Err(ParseError::ReadError(e.to_string())),
+    }
+}
+
+/// Read a line of 'min' to 'max' literals.
+fn read_literals<R: BufRead>(
+    reader: &mut R,
+    min: usize,
+    max: usize,
+) -> Result<Vec<usize>, ParseError> {
+    let line = read_line(reader)?;
+    let lits: Result<Vec<usize>, _> = line
+        .split_ascii_whitespace()
+        .map(|w| w.parse::<usize>())
+        .collect();
+    match lits {
+        Ok(lits) if (min..=max).contains(&lits.len()) => Ok(lits),
+        _ => Err(ParseError::CannotParseLine(line)),
+    }
+}
+
+/// Read a number of the binary format, seven bits at a time, least
+/// significant first, with the top bit of each byte set if more
+/// follow.
+fn read_number<R: BufRead>(reader: &mut R) -> Result<usize, ParseError> {
+    let mut n = 0;
+    let mut shift = 0;
+    for byte in reader.bytes() {
+        let byte = byte.map_err(|e| ParseError::ReadError(e.to_string()))?;
+        if shift >= usize::BITS {
+            return Err(ParseError::LiteralOutOfRange(n));
+        }
+        n |= usize::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(n);
+        }
+        shift += 7;
+    }
+    Err(ParseError::UnexpectedEnd)
+}
+
+/// A parser error.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub enum ParseError {
+    CannotParseHeader(String),
+    CannotParseLine(String),
+    UnexpectedFormat(String),
+    LiteralOutOfRange(usize),
+    /// An input, latch, or gate is a negated or constant literal.
+    NotAVariable(usize),
+    UnexpectedEnd,
+    ReadError(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::CannotParseHeader(s) => write!(f, "cannot parse header: {}", s),
+            ParseError::CannotParseLine(s) => write!(f, "cannot parse line: {}", s),
+            ParseError::UnexpectedFormat(s) => {
+                write!(f, "expected 'aag' or 'aig' format, got '{}'", s)
+            }
+            ParseError::LiteralOutOfRange(lit) => write!(f, "literal {} out of range", lit),
+            ParseError::NotAVariable(lit) => {
+                write!(f, "literal {} is negated or constant", lit)
+            }
+            ParseError::UnexpectedEnd => write!(f, "unexpected end of input"),
+            ParseError::ReadError(s) => write!(f, "cannot read input: {}", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The same circuit in both formats: an and gate of two inputs
+    /// and a latch, and its negation as the output.
+    const AAG: &str = "aag 4 2 1 1 1\n2\n4\n6 8\n9\n8 6 4\ni0 x\nc\ncomment\n";
+    const AIG: &[u8] = b"aig 4 2 1 1 1\n8\n9\n\x02\x02i0 x\nc\ncomment\n";
+
+    #[test]
+    fn parses_both_formats() {
+        let expected = Aig {
+            max_var: 4,
+            inputs: vec![2, 4],
+            latches: vec![(6, 8)],
+            outputs: vec![9],
+            ands: vec![(8, 6, 4)],
+        };
+        assert_eq!(Ok(expected.clone()), from_string(AAG.to_string()));
+        assert_eq!(Ok(expected), from_reader(AIG));
+    }
+
+    #[test]
+    fn converts_to_cnf() {
+        let aig = from_string(AAG.to_string()).unwrap();
+        assert_eq!(cnf![[-4, 3], [-4, 2], [4, -3, -2], [-4]], aig.to_cnf());
+        assert!(crate::sat(aig.to_cnf()));
+
+        // x and not x.
+        let aig = from_string("aag 2 1 0 1 1\n2\n4\n4 2 3\n".to_string()).unwrap();
+        assert!(!crate::sat(aig.to_cnf()));
+
+        // constants: the gate is false, and so the output.
+        let aig = from_string("aag 2 1 0 1 1\n2\n4\n4 2 0\n".to_string()).unwrap();
+        assert_eq!(cnf![[-2, 1], [-2], [2]], aig.to_cnf());
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert!(from_string("p cnf 1 1\n".to_string()).is_err());
+        assert!(from_string("aag 1 1 0 0 0\n".to_string()).is_err());
+        assert!(from_string("aag 1 1 0 0 0\n4\n".to_string()).is_err());
+        assert!(from_string("aag 1 1 0 0 0\n3\n".to_string()).is_err());
+        assert!(from_string("aag 1 1 0 0 0\n2 2\n".to_string()).is_err());
+        assert!(from_reader(&b"aig 2 1 0 0 1\n\x80"[..]).is_err());
+        assert!(from_reader(&b"aig 2 1 0 0 1\n\x05\x00"[..]).is_err());
+    }
+}
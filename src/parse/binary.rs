@@ -0,0 +1,156 @@
+//! Reader and writer for the binary clause format of drat-trim.  Each
+//! clause is the byte 'a', then each literal as a variable-length
+//! number, then a 0 byte.  A literal l is the number 2l if positive,
+//! and 2|l| + 1 if negative, written seven bits at a time, least
+//! significant first, with the top bit of each byte set if more
+//! follow.
+//!
+//! This is much smaller than DIMACS for big formulae, and much faster
+//! to parse.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use crate::cnf::*;
+
+/// The byte which starts an added clause.
+const ADD: u8 = b'a';
+
+/// The byte which starts a deleted clause, in a proof.
+const DELETE: u8 = b'd';
+
+/// Write a formula in binary format.
+pub fn write<W: Write>(formula: &Formula, mut writer: W) -> io::Result<()> {
+    for lits in formula.iter() {
+        write_clause(&mut writer, lits)?;
+    }
+    writer.flush()
+}
+
+/// Write the literals of a single clause in binary format.
+pub fn write_clause<W: Write>(writer: &mut W, lits: &[Literal]) -> io::Result<()> {
+    let mut bytes = vec![ADD];
+    for lit in lits {
+        let id = lit.get_id() as u64;
+        let mut n = 2 * id + u64::from(lit.is_negated());
+        while n > 0x7f {
+            bytes.push((n & 0x7f) as u8 | 0x80);
+            n >>= 7;
+        }
+        bytes.push(n as u8);
+    }
+    bytes.push(0);
+    writer.write_all(&bytes)
+}
+
+/// Parse a formula in binary format.
+pub fn from_reader<R: BufRead>(reader: R) -> Result<Formula, ParseError> {
+    let mut clauses = Vec::new();
+    let mut clause: Option<Vec<Literal>> = None;
+    // the literal being read, and how far it has been shifted.
+    let mut n: u64 = 0;
+    let mut shift = 0;
+
+    for byte in reader.bytes() {
+        let byte = byte.map_err(|e| ParseError::ReadError(e.to_string()))?;
+        let lits = match &mut clause {
+            Some(lits) => lits,
+            None => match byte {
+                ADD => {
+                    clause = Some(Vec::new());
+                    continue;
+                }
+                _ => return Err(ParseError::UnexpectedByte(byte)),
+            },
+        };
+
+        if shift >= 63 {
+            return Err(ParseError::LiteralOutOfRange);
+        }
+        n |= u64::from(byte & 0x7f) << shift;
+        shift += 7;
+        if byte & 0x80 != 0 {
+            continue;
+        }
+        if n == 0 {
+            // proofs in this format put the pivot of a RAT lemma
+            // first, so the literals are kept in the order written.
+            clauses.push(Clause::unnormalized(clause.take().unwrap()));
+        } else {
+            let id = isize::try_from(n / 2).map_err(|_| ParseError::LiteralOutOfRange)?;
+            if id == 0 {
+                return Err(ParseError::LiteralOutOfRange);
+            }
+            lits.push(Literal::new(if n % 2 == 1 { -id } else { id }));
+        }
+        n = 0;
+        shift = 0;
+    }
+
+    if clause.is_some() {
+        Err(ParseError::UnterminatedClause)
+    } else {
+        Ok(Formula::new(clauses))
+    }
+}
+
+/// A parser error.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub enum ParseError {
+    UnexpectedByte(u8),
+    LiteralOutOfRange,
+    UnterminatedClause,
+    ReadError(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedByte(DELETE) => write!(f, "unexpected deleted clause"),
+            ParseError::UnexpectedByte(b) => write!(f, "expected 'a' but got byte {:#04x}", b),
+            ParseError::LiteralOutOfRange => write!(f, "literal out of range"),
+            ParseError::UnterminatedClause => write!(f, "last clause has no terminating 0"),
+            ParseError::ReadError(s) => write!(f, "cannot read input: {}", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        let formula = cnf![[1, -2], [], [63, -64, 100_000]];
+        let mut bytes = Vec::new();
+        write(&formula, &mut bytes).unwrap();
+
+        // 63 is 126, in one byte, and -64 is 129, in two.
+        assert_eq!(
+            &[b'a', 2, 5, 0, b'a', 0, b'a', 126, 0x81, 0x01],
+            &bytes[..10]
+        );
+        assert_eq!(Ok(formula), from_reader(&bytes[..]));
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert_eq!(
+            Err(ParseError::UnexpectedByte(b'd')),
+            from_reader(&b"d\x02\x00"[..])
+        );
+        assert_eq!(
+            Err(ParseError::UnterminatedClause),
+            from_reader(&b"a\x02"[..])
+        );
+        assert_eq!(
+            Err(ParseError::UnterminatedClause),
+            from_reader(&b"a\x82"[..])
+        );
+        assert_eq!(
+            Err(ParseError::LiteralOutOfRange),
+            from_reader(&b"a\x01\x00"[..])
+        );
+    }
+}
@@ -0,0 +1,257 @@
+//! Parse BV formulae.
+
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::cnf::Formula;
+use crate::parse::combinators::*;
+use crate::parse::dimacs;
+use crate::parse::Position;
+use crate::theory::bv::*;
+
+/// Parse a BV theory and formula represented as a string.  The
+/// representation is as follows:
+///
+///    width integer
+///    bv_lit
+///    bv_lit
+///    [...]
+///    --
+///    <dimacs>
+///
+/// Where a bv_lit is a comparison and two bv_terms, and a comparison
+/// is one of '==', '/=', '<', '<=', '>', or '>=' (unsigned).
+///
+/// Where a bv_term is one of:
+///   - integer (a variable)
+///   - #integer (a constant)
+///   - ~(bv_term)
+///   - op(bv_term bv_term)
+///
+/// And an op is one of '+', '-', '*', '&', '|', or '^'.
+pub fn from_string(input: String) -> Result<(BV, Formula), ParseError> {
+    let mut lines = input.lines();
+    let mut lits = Vec::new();
+
+    let text = lines.next().unwrap_or("");
+    let line = Line { number: 1, text };
+    let width = parse_width(&mut text.chars().peekable(), line)?;
+
+    let mut skipped = 1;
+    for text in lines.by_ref() {
+        skipped += 1;
+        if text == "--" {
+            break;
+        }
+        let line = Line {
+            number: skipped,
+            text,
+        };
+        lits.push(parse_lit(&mut text.chars().peekable(), line)?);
+    }
+
+    match dimacs::from_lines_after(lines, skipped, dimacs::ParseOptions::default()) {
+        Ok(formula) => Ok((BV::new(width, lits), formula)),
+        Err(e) => Err(ParseError::DIMACSError(e)),
+    }
+}
+
+/// A line of the input, for the positions of errors.
+#[derive(Clone, Copy, Debug)]
+struct Line<'a> {
+    number: usize,
+    text: &'a str,
+}
+
+impl<'a> Line<'a> {
+    /// The position of the next character.
+    fn at(&self, chars: &Peekable<Chars<'a>>) -> Position {
+        let rest: usize = chars.clone().map(char::len_utf8).sum();
+        Position::new(self.number, self.text, self.text.len() - rest)
+    }
+}
+
+/// Parse the width line: width integer
+fn parse_width<'a>(chars: &mut Peekable<Chars<'a>>, line: Line<'a>) -> Result<usize, ParseError> {
+    let at = line.at(chars);
+    if !"width ".chars().all(|c| chars.next_if_eq(&c).is_some()) {
+        return Err(ParseError::CannotParseWidth(at));
+    }
+    eat_whitespace(chars);
+    let width = parse_usize(chars).ok_or(ParseError::CannotParseWidth(at.clone()))?;
+    eat_whitespace(chars);
+    match chars.peek() {
+        None => Ok(width),
+        Some(_) => Err(ParseError::CannotParseWidth(at)),
+    }
+}
+
+/// Parse a lit: comparison bv_term bv_term
+fn parse_lit<'a>(chars: &mut Peekable<Chars<'a>>, line: Line<'a>) -> Result<BVLiteral, ParseError> {
+    let at = line.at(chars);
+    let mut symbol = String::new();
+    while let Some(c) = chars.next_if(|c| "=/<>".contains(*c)) {
+        symbol.push(c);
+    }
+
+    let left = parse_term(chars, line)?;
+    let right = parse_term(chars, line)?;
+    eat_whitespace(chars);
+    if chars.peek().is_some() {
+        return Err(ParseError::UnexpectedInput(line.at(chars)));
+    }
+
+    match symbol.as_str() {
+        "==" => Ok(BVLiteral::new(BVComparison::Equal, left, right)),
+        "/=" => Ok(BVLiteral::new(BVComparison::Equal, left, right).negate()),
+        "<" => Ok(BVLiteral::new(BVComparison::Less, left, right)),
+        "<=" => Ok(BVLiteral::new(BVComparison::LessOrEqual, left, right)),
+        ">" => Ok(BVLiteral::new(BVComparison::Less, right, left)),
+        ">=" => Ok(BVLiteral::new(BVComparison::LessOrEqual, right, left)),
+        _ => Err(ParseError::CannotParseComparison(at)),
+    }
+}
+
+/// Parse a term, one of:
+///   - integer
+///   - #integer
+///   - ~(bv_term)
+///   - op(bv_term bv_term)
+fn parse_term<'a>(chars: &mut Peekable<Chars<'a>>, line: Line<'a>) -> Result<BVTerm, ParseError> {
+    eat_whitespace(chars);
+    let at = line.at(chars);
+
+    if chars.next_if_eq(&'#').is_some() {
+        return match parse_usize(chars) {
+            Some(value) => Ok(BVTerm::constant(value as u64)),
+            None => Err(ParseError::CannotParseTerm(at)),
+        };
+    }
+    if let Some(var) = parse_usize(chars) {
+        return Ok(BVTerm::var(var));
+    }
+
+    let op = match chars.next() {
+        Some('~') => None,
+        Some('+') => Some(BVOp::Add),
+        Some('-') => Some(BVOp::Sub),
+        Some('*') => Some(BVOp::Mul),
+        Some('&') => Some(BVOp::And),
+        Some('|') => Some(BVOp::Or),
+        Some('^') => Some(BVOp::Xor),
+        _ => return Err(ParseError::CannotParseTerm(at)),
+    };
+    parse_symbol(chars, line, '(')?;
+    let term = match op {
+        None => BVTerm::complement(parse_term(chars, line)?),
+        Some(op) => {
+            let left = parse_term(chars, line)?;
+            let right = parse_term(chars, line)?;
+            BVTerm::op(op, left, right)
+        }
+    };
+    parse_symbol(chars, line, ')')?;
+    Ok(term)
+}
+
+/// Parse a symbol, like '(' or ')'
+fn parse_symbol<'a>(
+    chars: &mut Peekable<Chars<'a>>,
+    line: Line<'a>,
+    symbol: char,
+) -> Result<(), ParseError> {
+    eat_whitespace(chars);
+    let at = line.at(chars);
+    match chars.next_if_eq(&symbol) {
+        Some(_) => Ok(()),
+        None => Err(ParseError::ExpectedSymbol { symbol, at }),
+    }
+}
+
+/// A parser error.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub enum ParseError {
+    CannotParseWidth(Position),
+    CannotParseComparison(Position),
+    CannotParseTerm(Position),
+    ExpectedSymbol { symbol: char, at: Position },
+    UnexpectedInput(Position),
+    DIMACSError(dimacs::ParseError),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::CannotParseWidth(at) => {
+                write!(f, "cannot parse width at {}, expected 'width N'", at)
+            }
+            ParseError::CannotParseComparison(at) => write!(
+                f,
+                "cannot parse comparison at {}, expected '==', '/=', '<', '<=', '>', or '>='",
+                at
+            ),
+            ParseError::CannotParseTerm(at) => write!(f, "cannot parse term at {}", at),
+            ParseError::ExpectedSymbol { symbol, at } => {
+                write!(f, "expected '{}' at {}", symbol, at)
+            }
+            ParseError::UnexpectedInput(at) => write!(f, "unexpected input at {}", at),
+            ParseError::DIMACSError(e) => write!(f, "cannot parse DIMACS: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_terms() {
+        let input = "width 4\n== *(1 ~(2)) #12\n> 2 ^(1 #3)\n--\np cnf 2 1\n1 -2 0\n";
+        let expected = BV::new(
+            4,
+            vec![
+                BVLiteral::new(
+                    BVComparison::Equal,
+                    BVTerm::op(
+                        BVOp::Mul,
+                        BVTerm::var(1),
+                        BVTerm::complement(BVTerm::var(2)),
+                    ),
+                    BVTerm::constant(12),
+                ),
+                BVLiteral::new(
+                    BVComparison::Less,
+                    BVTerm::op(BVOp::Xor, BVTerm::var(1), BVTerm::constant(3)),
+                    BVTerm::var(2),
+                ),
+            ],
+        );
+        assert_eq!(
+            Ok((expected, cnf![[1, -2]])),
+            from_string(input.to_string())
+        );
+    }
+
+    #[test]
+    fn reports_positions() {
+        let input = "width 4\n== +(1 2 #3\n--\np cnf 1 1\n1 0\n";
+        assert_eq!(
+            Err(ParseError::ExpectedSymbol {
+                symbol: ')',
+                at: Position {
+                    line: 2,
+                    column: 10,
+                    snippet: "#3".to_string(),
+                },
+            }),
+            from_string(input.to_string()).map(|_| ())
+        );
+
+        let input = "== 1 2\n--\np cnf 1 1\n1 0\n";
+        assert!(matches!(
+            from_string(input.to_string()),
+            Err(ParseError::CannotParseWidth(_))
+        ));
+    }
+}
@@ -31,3 +31,14 @@ pub fn parse_usize<'a>(chars: &mut Peekable<Chars<'a>>) -> Option<usize> {
         Some(out)
     }
 }
+
+/// Parse a nonempty isize, with an optional minus sign.
+pub fn parse_isize<'a>(chars: &mut Peekable<Chars<'a>>) -> Option<isize> {
+    let negative = chars.next_if_eq(&'-').is_some();
+    let n = parse_usize(chars)? as isize;
+    if negative {
+        Some(-n)
+    } else {
+        Some(n)
+    }
+}
@@ -1,103 +1,393 @@
 //! Parser for DIMACS CNF format
 
+use std::collections::BTreeMap;
 use std::fmt;
+#[cfg(any(feature = "gzip", feature = "xz"))]
+use std::io::BufReader;
+use std::io::{self, BufRead, Write};
+use std::mem;
 
 use crate::cnf::*;
+use crate::parse::{offset, Position};
+
+/// Options for parsing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub struct ParseOptions {
+    /// Accept the mistakes found in real benchmark files: the wrong
+    /// number of variables or clauses in the 'p' line, comments and
+    /// blank lines among the clauses, no 0 after the last clause, and
+    /// a '%' line, or a lone 0 after the clauses, ending the input.
+    pub lenient: bool,
+}
+
+/// The comments of a DIMACS file, which many tools use to record
+/// where a formula came from and what its atoms mean.
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub struct Metadata {
+    /// The text of each comment, without the 'c', in order, apart
+    /// from those naming atoms.
+    pub comments: Vec<String>,
+    /// The names of atoms, from comments of the form 'c var <atom>
+    /// <name>'.
+    pub names: BTreeMap<Var, String>,
+}
+
+impl Metadata {
+    /// Record a comment, given the text after the 'c'.
+    fn comment(&mut self, text: &str) {
+        let text = text.strip_prefix(' ').unwrap_or(text).trim_end();
+        let mut words = text.splitn(3, ' ');
+        if let (Some("var"), Some(Ok(id)), Some(name)) = (
+            words.next(),
+            words.next().map(|w| w.parse::<usize>()),
+            words.next(),
+        ) {
+            if id > 0 {
+                self.names.insert(Var::new(id), name.to_string());
+                return;
+            }
+        }
+        self.comments.push(text.to_string());
+    }
+}
+
+/// Write a formula in DIMACS CNF format, starting with the comments
+/// and atom names of the metadata.
+pub fn write<W: Write>(formula: &Formula, metadata: &Metadata, mut writer: W) -> io::Result<()> {
+    for comment in &metadata.comments {
+        if comment.is_empty() {
+            writeln!(writer, "c")?;
+        } else {
+            writeln!(writer, "c {}", comment)?;
+        }
+    }
+    for (var, name) in &metadata.names {
+        writeln!(writer, "c var {} {}", var, name)?;
+    }
+    let num_vars = formula
+        .iter()
+        .flatten()
+        .map(|lit| lit.get_id())
+        .max()
+        .unwrap_or(0);
+    writeln!(writer, "p cnf {} {}", num_vars, formula.len())?;
+    for lits in formula.iter() {
+        for lit in lits {
+            write!(writer, "{} ", lit)?;
+        }
+        writeln!(writer, "0")?;
+    }
+    writer.flush()
+}
 
 /// Parse a string in DIMACS CNF format.
 pub fn from_string(dimacs: String) -> Result<Formula, ParseError> {
     from_lines(dimacs.lines())
 }
 
+/// Parse a string in DIMACS CNF format, with options.
+pub fn from_string_with_options(
+    dimacs: String,
+    options: ParseOptions,
+) -> Result<Formula, ParseError> {
+    from_lines_with_options(dimacs.lines(), options)
+}
+
+/// Parse a string in DIMACS CNF format, keeping the comments.
+pub fn from_string_with_metadata(
+    dimacs: String,
+    options: ParseOptions,
+) -> Result<(Formula, Metadata), ParseError> {
+    parse_lines(dimacs.lines(), 0, options)
+}
+
 pub fn from_lines<'a, I>(lines: I) -> Result<Formula, ParseError>
 where
     I: IntoIterator<Item = &'a str>,
 {
-    let mut in_prelude = true;
-    let mut expected_number_of_variables = 0;
-    let mut expected_number_of_clauses = 0;
-    let mut clause = Vec::new();
-    let mut clauses = Vec::new();
-    let mut variables = 0;
-
-    'outer: for line in lines {
+    from_lines_with_options(lines, ParseOptions::default())
+}
+
+pub fn from_lines_with_options<'a, I>(
+    lines: I,
+    options: ParseOptions,
+) -> Result<Formula, ParseError>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    from_lines_after(lines, 0, options)
+}
+
+/// Parse lines which come after some others, so errors have the right
+/// line numbers.
+pub(crate) fn from_lines_after<'a, I>(
+    lines: I,
+    skipped: usize,
+    options: ParseOptions,
+) -> Result<Formula, ParseError>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    parse_lines(lines, skipped, options).map(|(formula, _)| formula)
+}
+
+fn parse_lines<'a, I>(
+    lines: I,
+    skipped: usize,
+    options: ParseOptions,
+) -> Result<(Formula, Metadata), ParseError>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut parser = Parser::new(options);
+    parser.line_number = skipped;
+    for line in lines {
+        if parser.line(line)? {
+            break;
+        }
+    }
+    parser.finish()
+}
+
+/// Parse DIMACS CNF format from a reader, a line at a time, so the
+/// input never needs to be in memory all at once.  Reading stops after
+/// the last clause.
+///
+/// Input compressed with gzip or xz is decompressed as it's read, if
+/// the 'gzip' or 'xz' feature is enabled.
+pub fn from_reader<R: BufRead>(reader: R) -> Result<Formula, ParseError> {
+    from_reader_with_options(reader, ParseOptions::default())
+}
+
+/// Parse DIMACS CNF format from a reader, with options.
+pub fn from_reader_with_options<R: BufRead>(
+    reader: R,
+    options: ParseOptions,
+) -> Result<Formula, ParseError> {
+    from_reader_with_metadata(reader, options).map(|(formula, _)| formula)
+}
+
+/// Parse DIMACS CNF format from a reader, keeping the comments.
+pub fn from_reader_with_metadata<R: BufRead>(
+    mut reader: R,
+    options: ParseOptions,
+) -> Result<(Formula, Metadata), ParseError> {
+    let magic = match reader.fill_buf() {
+        Ok(buf) => buf,
+        Err(e) => return Err(ParseError::ReadError(e.to_string())),
+    };
+    if magic.starts_with(GZIP_MAGIC) {
+        from_gzip_reader(reader, options)
+    } else if magic.starts_with(XZ_MAGIC) {
+        from_xz_reader(reader, options)
+    } else {
+        from_uncompressed_reader(reader, options)
+    }
+}
+
+/// The first bytes of a gzip stream.
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// The first bytes of an xz stream.
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+
+#[cfg(feature = "gzip")]
+fn from_gzip_reader<R: BufRead>(
+    reader: R,
+    options: ParseOptions,
+) -> Result<(Formula, Metadata), ParseError> {
+    let decoder = flate2::bufread::MultiGzDecoder::new(reader);
+    from_uncompressed_reader(BufReader::new(decoder), options)
+}
+
+#[cfg(not(feature = "gzip"))]
+fn from_gzip_reader<R: BufRead>(
+    _reader: R,
+    _options: ParseOptions,
+) -> Result<(Formula, Metadata), ParseError> {
+    Err(ParseError::UnsupportedCompression("gzip".to_string()))
+}
+
+#[cfg(feature = "xz")]
+fn from_xz_reader<R: BufRead>(
+    reader: R,
+    options: ParseOptions,
+) -> Result<(Formula, Metadata), ParseError> {
+    let decoder = xz2::bufread::XzDecoder::new_multi_decoder(reader);
+    from_uncompressed_reader(BufReader::new(decoder), options)
+}
+
+#[cfg(not(feature = "xz"))]
+fn from_xz_reader<R: BufRead>(
+    _reader: R,
+    _options: ParseOptions,
+) -> Result<(Formula, Metadata), ParseError> {
+    Err(ParseError::UnsupportedCompression("xz".to_string()))
+}
+
+fn from_uncompressed_reader<R: BufRead>(
+    mut reader: R,
+    options: ParseOptions,
+) -> Result<(Formula, Metadata), ParseError> {
+    let mut parser = Parser::new(options);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => {
+                if parser.line(&line)? {
+                    break;
+                }
+            }
+            Err(e) => return Err(ParseError::ReadError(e.to_string())),
+        }
+    }
+    parser.finish()
+}
+
+/// The state of parsing, between lines.
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd, Eq, Ord)]
+struct Parser {
+    options: ParseOptions,
+    /// The number of the last line parsed, from 1.
+    line_number: usize,
+    seen_prelude: bool,
+    expected_number_of_variables: usize,
+    expected_number_of_clauses: usize,
+    clause: Vec<isize>,
+    clauses: Vec<Clause>,
+    variables: usize,
+    metadata: Metadata,
+}
+
+impl Parser {
+    fn new(options: ParseOptions) -> Parser {
+        Parser {
+            options,
+            ..Parser::default()
+        }
+    }
+
+    /// Parse a line.  Returns true if all the clauses have been seen,
+    /// so the rest of the input can be ignored.
+    fn line(&mut self, line: &str) -> Result<bool, ParseError> {
+        self.line_number += 1;
+        let line_number = self.line_number;
+        let at = |word: &str| Position::new(line_number, line, offset(line, word));
         let mut words = line.split_ascii_whitespace();
-        if in_prelude {
+        if self.options.lenient {
+            let terminator = match line.trim() {
+                "%" => true,
+                "0" => {
+                    self.clause.is_empty() && self.clauses.len() >= self.expected_number_of_clauses
+                }
+                _ => false,
+            };
+            if self.seen_prelude && terminator {
+                return Ok(true);
+            }
+            match words.clone().next() {
+                Some("c") => {
+                    self.metadata.comment(&line.trim_start()[1..]);
+                    return Ok(false);
+                }
+                None => return Ok(false),
+                _ => (),
+            }
+        }
+        if !self.seen_prelude {
             match words.next() {
-                Some("c") => continue,
+                Some("c") => self.metadata.comment(&line.trim_start()[1..]),
                 Some("p") => match words.next() {
                     Some("cnf") => match words.next().map(|w| w.parse::<usize>()) {
                         Some(Ok(num_vars)) => match words.next().map(|n| n.parse::<usize>()) {
                             Some(Ok(num_clauses)) => {
-                                expected_number_of_variables = num_vars;
-                                expected_number_of_clauses = num_clauses;
-                                in_prelude = false;
+                                self.expected_number_of_variables = num_vars;
+                                self.expected_number_of_clauses = num_clauses;
+                                self.seen_prelude = true;
                             }
-                            _ => return Err(ParseError::CannotParsePreludeLine(line.to_string())),
+                            _ => return Err(ParseError::CannotParsePreludeLine(at(line))),
                         },
-                        _ => return Err(ParseError::CannotParsePreludeLine(line.to_string())),
+                        _ => return Err(ParseError::CannotParsePreludeLine(at(line))),
                     },
-                    Some(fmt) => return Err(ParseError::UnexpectedFormat(fmt.to_string())),
-                    None => return Err(ParseError::CannotParsePreludeLine(line.to_string())),
+                    Some(fmt) => {
+                        return Err(ParseError::UnexpectedFormat(fmt.to_string(), at(fmt)))
+                    }
+                    None => return Err(ParseError::CannotParsePreludeLine(at(line))),
                 },
-                _ => return Err(ParseError::CannotParsePreludeLine(line.to_string())),
+                _ => return Err(ParseError::CannotParsePreludeLine(at(line))),
             }
         } else {
             for lit in words {
                 match lit.parse::<isize>() {
                     Ok(0) => {
-                        clauses.push(Clause::new(clause));
-                        if clauses.len() == expected_number_of_clauses {
-                            break 'outer;
+                        self.clauses.push(Clause::new(mem::take(&mut self.clause)));
+                        if self.clauses.len() == self.expected_number_of_clauses
+                            && !self.options.lenient
+                        {
+                            return Ok(true);
                         }
-                        clause = Vec::new();
                     }
                     Ok(n) => {
-                        let var = n.abs() as usize;
-                        if var > variables {
-                            variables = var;
+                        let var = n.unsigned_abs();
+                        if var > self.variables {
+                            self.variables = var;
                         }
-                        clause.push(n);
+                        self.clause.push(n);
                     }
-                    Err(_) => return Err(ParseError::CannotParseClauseLine(line.to_string())),
+                    Err(_) => return Err(ParseError::CannotParseClauseLine(at(lit))),
                 }
             }
         }
+        Ok(false)
     }
 
-    if variables == expected_number_of_variables {
-        if clauses.len() == expected_number_of_clauses {
-            Ok(Formula::new(clauses))
+    /// Check the number of variables and clauses.  In lenient mode,
+    /// only finish the last clause.
+    fn finish(mut self) -> Result<(Formula, Metadata), ParseError> {
+        if self.options.lenient {
+            if !self.clause.is_empty() {
+                self.clauses.push(Clause::new(self.clause));
+            }
+            return Ok((Formula::new(self.clauses), self.metadata));
+        }
+        if self.variables == self.expected_number_of_variables {
+            if self.clauses.len() == self.expected_number_of_clauses {
+                Ok((Formula::new(self.clauses), self.metadata))
+            } else {
+                Err(ParseError::WrongNumberOfClauses {
+                    expected: self.expected_number_of_clauses,
+                    actual: self.clauses.len(),
+                })
+            }
         } else {
-            Err(ParseError::WrongNumberOfClauses {
-                expected: expected_number_of_clauses,
-                actual: clauses.len(),
+            Err(ParseError::WrongNumberOfVariables {
+                expected: self.expected_number_of_variables,
+                actual: self.variables,
             })
         }
-    } else {
-        Err(ParseError::WrongNumberOfVariables {
-            expected: expected_number_of_variables,
-            actual: variables,
-        })
     }
 }
 
 /// A parser error.
 #[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
 pub enum ParseError {
-    CannotParsePreludeLine(String),
-    CannotParseClauseLine(String),
-    UnexpectedFormat(String),
+    CannotParsePreludeLine(Position),
+    CannotParseClauseLine(Position),
+    UnexpectedFormat(String, Position),
     WrongNumberOfVariables { expected: usize, actual: usize },
     WrongNumberOfClauses { expected: usize, actual: usize },
+    ReadError(String),
+    UnsupportedCompression(String),
 }
 
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            ParseError::CannotParsePreludeLine(s) => write!(f, "cannot parse prelude line '{}'", s),
-            ParseError::CannotParseClauseLine(s) => write!(f, "cannot parse clause line '{}'", s),
-            ParseError::UnexpectedFormat(s) => write!(f, "unexpected format '{}'", s),
+            ParseError::CannotParsePreludeLine(at) => write!(f, "cannot parse prelude at {}", at),
+            ParseError::CannotParseClauseLine(at) => write!(f, "cannot parse literal at {}", at),
+            ParseError::UnexpectedFormat(s, at) => write!(f, "unexpected format '{}' at {}", s, at),
             ParseError::WrongNumberOfVariables { expected, actual } => write!(
                 f,
                 "wrong number of variables, expected {} but got {}",
@@ -108,14 +398,19 @@ impl fmt::Display for ParseError {
                 "wrong number of clauses, expected {} but got {}",
                 expected, actual
             ),
+            ParseError::ReadError(s) => write!(f, "cannot read input: {}", s),
+            ParseError::UnsupportedCompression(s) => write!(
+                f,
+                "input is {}-compressed, but the '{}' feature is not enabled",
+                s, s
+            ),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::from_string;
-    use crate::cnf::*;
+    use super::*;
 
     #[test]
     fn works() {
@@ -198,4 +493,146 @@ mod tests {
 
         assert!(from_string(formula_str.to_string()).is_err());
     }
+
+    #[test]
+    fn reads_incrementally() {
+        let formula_str = "c hello world\n\
+                           p cnf 3 2\n\
+                           -3 1 0 2\n\
+                           -1 0\n\
+                           this is never read";
+
+        assert_eq!(
+            Ok(Formula::new(vec![
+                Clause::new(vec![-3, 1]),
+                Clause::new(vec![2, -1]),
+            ])),
+            from_reader(formula_str.as_bytes())
+        );
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn reads_gzip() {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(b"p cnf 2 2\n1 -2 0\n2 0\n").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        assert_eq!(
+            Ok(Formula::new(vec![
+                Clause::new(vec![1, -2]),
+                Clause::new(vec![2]),
+            ])),
+            from_reader(&compressed[..])
+        );
+    }
+
+    #[cfg(feature = "xz")]
+    #[test]
+    fn reads_xz() {
+        use std::io::Write;
+        use xz2::write::XzEncoder;
+
+        let mut encoder = XzEncoder::new(Vec::new(), 6);
+        encoder.write_all(b"p cnf 2 2\n1 -2 0\n2 0\n").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        assert_eq!(
+            Ok(Formula::new(vec![
+                Clause::new(vec![1, -2]),
+                Clause::new(vec![2]),
+            ])),
+            from_reader(&compressed[..])
+        );
+    }
+
+    #[cfg(not(feature = "gzip"))]
+    #[test]
+    fn rejects_gzip_without_feature() {
+        assert_eq!(
+            Err(ParseError::UnsupportedCompression("gzip".to_string())),
+            from_reader(&[0x1f, 0x8b, 0x08, 0x00][..])
+        );
+    }
+
+    #[test]
+    fn parses_leniently() {
+        let lenient = ParseOptions { lenient: true };
+        let formula_str = "c hello world\n\
+                           p cnf 5 1\n\
+                           \n\
+                           1 -2 0\n\
+                           c in the middle\n\
+                           2 3\n\
+                           %\n\
+                           0\n";
+
+        assert!(from_string(formula_str.to_string()).is_err());
+        assert_eq!(
+            Ok(Formula::new(vec![
+                Clause::new(vec![1, -2]),
+                Clause::new(vec![2, 3]),
+            ])),
+            from_string_with_options(formula_str.to_string(), lenient)
+        );
+
+        let formula_str = "p cnf 2 1\n1 -2 0\n0\n";
+        assert_eq!(
+            Ok(Formula::new(vec![Clause::new(vec![1, -2])])),
+            from_string_with_options(formula_str.to_string(), lenient)
+        );
+    }
+
+    #[test]
+    fn reports_positions() {
+        let formula_str = "c hello\np cnf 2 2\n1 -2 0\n  2 -x1 0\n";
+        assert_eq!(
+            Err(ParseError::CannotParseClauseLine(Position {
+                line: 4,
+                column: 5,
+                snippet: "-x1 0".to_string(),
+            })),
+            from_string(formula_str.to_string())
+        );
+
+        let formula_str = format!("p dnf {}\n", "1 ".repeat(20));
+        match from_reader(formula_str.as_bytes()) {
+            Err(ParseError::UnexpectedFormat(format, at)) => {
+                assert_eq!("dnf", format);
+                assert_eq!((1, 3), (at.line, at.column));
+                assert_eq!("dnf 1 1 1 1 1 1 1 1 ...", at.snippet);
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn keeps_metadata() {
+        let formula_str = "c made by hand\n\
+                           c\n\
+                           c var 1 x\n\
+                           c var 3 long name\n\
+                           p cnf 3 2\n\
+                           1 -3 0\n\
+                           2 0\n";
+
+        let (formula, metadata) =
+            from_string_with_metadata(formula_str.to_string(), ParseOptions::default()).unwrap();
+        assert_eq!(vec!["made by hand", ""], metadata.comments);
+        assert_eq!(
+            Some("long name"),
+            metadata.names.get(&Var::new(3)).map(String::as_str)
+        );
+
+        let mut written = Vec::new();
+        write(&formula, &metadata, &mut written).unwrap();
+        assert_eq!(formula_str, String::from_utf8(written.clone()).unwrap());
+        assert_eq!(
+            Ok((formula, metadata)),
+            from_reader_with_metadata(&written[..], ParseOptions::default())
+        );
+    }
 }
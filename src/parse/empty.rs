@@ -1,5 +1,7 @@
 //! Parse SAT formulae.
 
+use std::io::BufRead;
+
 use crate::cnf::Formula;
 use crate::parse::dimacs;
 use crate::theory::empty::Empty;
@@ -7,3 +9,14 @@ use crate::theory::empty::Empty;
 pub fn from_string(input: String) -> Result<(Empty, Formula), dimacs::ParseError> {
     dimacs::from_string(input).map(|formula| (Empty::new(), formula))
 }
+
+pub fn from_reader<R: BufRead>(reader: R) -> Result<(Empty, Formula), dimacs::ParseError> {
+    dimacs::from_reader(reader).map(|formula| (Empty::new(), formula))
+}
+
+pub fn from_reader_with_options<R: BufRead>(
+    reader: R,
+    options: dimacs::ParseOptions,
+) -> Result<(Empty, Formula), dimacs::ParseError> {
+    dimacs::from_reader_with_options(reader, options).map(|formula| (Empty::new(), formula))
+}
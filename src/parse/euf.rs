@@ -2,10 +2,12 @@
 
 use std::fmt;
 use std::iter::Peekable;
+use std::str::Chars;
 
 use crate::cnf::Formula;
 use crate::parse::combinators::*;
 use crate::parse::dimacs;
+use crate::parse::Position;
 use crate::theory::euf::*;
 
 /// Parse an EUF theory and formula represented as a string.  The
@@ -20,6 +22,7 @@ use crate::theory::euf::*;
 /// Where an euf_lit is one of:
 ///   - == euf_term euf_term
 ///   - /= euf_term euf_term
+///   - distinct euf_term...
 ///
 /// Where an euf_term is one of:
 ///   - integer
@@ -27,39 +30,76 @@ use crate::theory::euf::*;
 pub fn from_string(input: String) -> Result<(EUF, Formula), ParseError> {
     let mut lines = input.lines();
     let mut lits = Vec::new();
+    let mut skipped = 0;
 
-    while let Some(line) = lines.next() {
-        if line == "--" {
+    for text in lines.by_ref() {
+        skipped += 1;
+        if text == "--" {
             break;
         } else {
-            match parse_lit(&mut line.chars().peekable()) {
+            let line = Line {
+                number: skipped,
+                text,
+            };
+            match parse_lit(&mut text.chars().peekable(), line) {
                 Ok(lit) => lits.push(lit),
                 Err(e) => return Err(e),
             }
         }
     }
 
-    match dimacs::from_lines(lines) {
+    match dimacs::from_lines_after(lines, skipped, dimacs::ParseOptions::default()) {
         Ok(formula) => Ok((EUF::new(lits), formula)),
         Err(e) => Err(ParseError::DIMACSError(e)),
     }
 }
 
+/// A line of the input, for the positions of errors.
+#[derive(Clone, Copy, Debug)]
+struct Line<'a> {
+    number: usize,
+    text: &'a str,
+}
+
+impl<'a> Line<'a> {
+    /// The position of the next character.
+    fn at(&self, chars: &Peekable<Chars<'a>>) -> Position {
+        let rest: usize = chars.clone().map(char::len_utf8).sum();
+        Position::new(self.number, self.text, self.text.len() - rest)
+    }
+}
+
 /// Parse a lit, one of:
 ///   - == euf_term euf_term
 ///   - /= euf_term euf_term
-fn parse_lit<'a>(mut chars: &mut Peekable<std::str::Chars<'a>>) -> Result<EUFLiteral, ParseError> {
+///   - distinct euf_term...
+fn parse_lit<'a>(
+    chars: &mut Peekable<Chars<'a>>,
+    line: Line<'a>,
+) -> Result<EUFLiteral, ParseError> {
+    let at = line.at(chars);
+    if chars.clone().take(8).eq("distinct".chars()) {
+        chars.nth(7);
+        let mut terms = Vec::new();
+        eat_whitespace(chars);
+        while chars.peek().is_some() {
+            terms.push(parse_term(chars, line)?);
+            eat_whitespace(chars);
+        }
+        return Ok(EUFLiteral::distinct(terms));
+    }
+
     let c1 = chars.next();
     let c2 = chars.next();
 
     let is_equality = match (c1, c2) {
         (Some('='), Some('=')) => true,
         (Some('/'), Some('=')) => false,
-        _ => return Err(ParseError::CannotParseEqualitySymbol { c1, c2 }),
+        _ => return Err(ParseError::CannotParseEqualitySymbol { c1, c2, at }),
     };
 
-    let left = parse_term(&mut chars)?;
-    let right = parse_term(&mut chars)?;
+    let left = parse_term(chars, line)?;
+    let right = parse_term(chars, line)?;
 
     let lit = EUFLiteral::new(left, right);
     if is_equality {
@@ -72,13 +112,13 @@ fn parse_lit<'a>(mut chars: &mut Peekable<std::str::Chars<'a>>) -> Result<EUFLit
 /// Parse a term, is one of:
 ///   - integer
 ///   - integer(euf_term...)
-fn parse_term<'a>(mut chars: &mut Peekable<std::str::Chars<'a>>) -> Result<EUFTerm, ParseError> {
-    eat_whitespace(&mut chars);
+fn parse_term<'a>(chars: &mut Peekable<Chars<'a>>, line: Line<'a>) -> Result<EUFTerm, ParseError> {
+    eat_whitespace(chars);
 
-    let atom = parse_atom(&mut chars)?;
+    let atom = parse_atom(chars, line)?;
     let mut parameters = Vec::new();
 
-    eat_whitespace(&mut chars);
+    eat_whitespace(chars);
 
     match chars.peek() {
         Some('(') => {
@@ -89,12 +129,12 @@ fn parse_term<'a>(mut chars: &mut Peekable<std::str::Chars<'a>>) -> Result<EUFTe
                         chars.next();
                         break;
                     }
-                    None => return Err(ParseError::UnexpectedEndOfApTerm),
+                    None => return Err(ParseError::UnexpectedEndOfApTerm(line.at(chars))),
                     _ => (),
                 }
-                let term = parse_term(&mut chars)?;
+                let term = parse_term(chars, line)?;
                 parameters.push(term);
-                eat_whitespace(&mut chars);
+                eat_whitespace(chars);
             }
             Ok(EUFTerm::ap(atom, parameters))
         }
@@ -103,42 +143,88 @@ fn parse_term<'a>(mut chars: &mut Peekable<std::str::Chars<'a>>) -> Result<EUFTe
 }
 
 /// Parse an atom
-fn parse_atom<'a>(mut chars: &mut Peekable<std::str::Chars<'a>>) -> Result<usize, ParseError> {
-    if let Some(atom) = parse_usize(&mut chars) {
+fn parse_atom<'a>(chars: &mut Peekable<Chars<'a>>, line: Line<'a>) -> Result<usize, ParseError> {
+    let at = line.at(chars);
+    if let Some(atom) = parse_usize(chars) {
         Ok(atom)
     } else {
-        Err(ParseError::CannotParseAtom)
+        Err(ParseError::CannotParseAtom(at))
     }
 }
 
 /// A parser error.
 #[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
 pub enum ParseError {
-    CannotParseEqualitySymbol { c1: Option<char>, c2: Option<char> },
-    UnexpectedEndOfApTerm,
-    CannotParseAtom,
+    CannotParseEqualitySymbol {
+        c1: Option<char>,
+        c2: Option<char>,
+        at: Position,
+    },
+    UnexpectedEndOfApTerm(Position),
+    CannotParseAtom(Position),
     DIMACSError(dimacs::ParseError),
 }
 
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            ParseError::CannotParseEqualitySymbol { c1, c2 } => match (c1, c2) {
+            ParseError::CannotParseEqualitySymbol { c1, c2, at } => match (c1, c2) {
                 (Some(a), Some(b)) => write!(
                     f,
-                    "cannot parse equality symbol, expected '==' or '/=' but got '{}{}'",
-                    a, b
+                    "cannot parse equality symbol at {}, expected '==', '/=', or 'distinct' but got '{}{}'",
+                    at, a, b
                 ),
                 (Some(a), None) => write!(
                     f,
-                    "cannot parse equality symbol, expected '==' or '/=' but got '{}'",
-                    a
+                    "cannot parse equality symbol at {}, expected '==', '/=', or 'distinct' but got '{}'",
+                    at, a
                 ),
-                _ => write!(f, "unexpected empty line"),
+                _ => write!(f, "unexpected empty line {}", at.line),
             },
-            ParseError::UnexpectedEndOfApTerm => write!(f, "unexpected end of application term"),
-            ParseError::CannotParseAtom => write!(f, "cannot parse atom"),
+            ParseError::UnexpectedEndOfApTerm(at) => {
+                write!(f, "unexpected end of application term at {}", at)
+            }
+            ParseError::CannotParseAtom(at) => write!(f, "cannot parse atom at {}", at),
             ParseError::DIMACSError(e) => write!(f, "cannot parse DIMACS: {}", e),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_positions() {
+        let input = "== 1(1 2) 1\n== 1(x) 2\n--\np cnf 1 1\n1 0\n";
+        assert_eq!(
+            Err(ParseError::CannotParseAtom(Position {
+                line: 2,
+                column: 6,
+                snippet: "x) 2".to_string(),
+            })),
+            from_string(input.to_string()).map(|_| ())
+        );
+
+        let input = "== 1 2\n--\np cnf 1 1\n1 y 0\n";
+        match from_string(input.to_string()) {
+            Err(ParseError::DIMACSError(dimacs::ParseError::CannotParseClauseLine(at))) => {
+                assert_eq!((4, 3), (at.line, at.column));
+                assert_eq!("line 4, column 3: 'y 0'", at.to_string());
+            }
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn parses_distinct() {
+        let input = "distinct 1 2(1)  3\n--\np cnf 1 1\n-1 0\n";
+        let (euf, formula) = from_string(input.to_string()).unwrap();
+        let expected = EUF::new(vec![EUFLiteral::distinct(vec![
+            EUFTerm::atom(1),
+            EUFTerm::ap(2, vec![EUFTerm::atom(1)]),
+            EUFTerm::atom(3),
+        ])]);
+        assert_eq!((expected, cnf![[-1]]), (euf, formula));
+    }
+}
@@ -0,0 +1,289 @@
+//! Parser and writer for GCNF, the group-oriented format for MUS
+//! extraction: after a 'p gcnf' line giving the number of variables,
+//! clauses, and groups, each clause starts with its group in braces.
+//! Group 0 holds the hard clauses, which are in every MUS.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::mem;
+
+use crate::cnf::*;
+
+/// A formula with its clauses in groups.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub struct Gcnf {
+    /// The clauses of group 0.
+    pub hard: Formula,
+    /// The clauses of each other group which has any.
+    pub groups: BTreeMap<usize, Formula>,
+}
+
+impl Gcnf {
+    /// The highest atom in any clause.
+    pub fn num_vars(&self) -> usize {
+        self.clauses()
+            .flat_map(|(_, lits)| lits)
+            .map(|lit| lit.get_id() as usize)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The highest group number.
+    pub fn num_groups(&self) -> usize {
+        self.groups.keys().next_back().copied().unwrap_or(0)
+    }
+
+    /// Write in GCNF format.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(
+            writer,
+            "p gcnf {} {} {}",
+            self.num_vars(),
+            self.clauses().count(),
+            self.num_groups()
+        )?;
+        for (group, lits) in self.clauses() {
+            write!(writer, "{{{}}} ", group)?;
+            for lit in lits {
+                write!(writer, "{} ", lit)?;
+            }
+            writeln!(writer, "0")?;
+        }
+        writer.flush()
+    }
+
+    /// The literals of every clause, with its group.
+    fn clauses(&self) -> impl Iterator<Item = (usize, &[Literal])> + '_ {
+        self.hard.iter().map(|lits| (0, lits)).chain(
+            self.groups
+                .iter()
+                .flat_map(|(group, formula)| formula.iter().map(move |lits| (*group, lits))),
+        )
+    }
+}
+
+/// Parse a string in GCNF format.
+pub fn from_string(gcnf: String) -> Result<Gcnf, ParseError> {
+    let mut parser = Parser::default();
+    for line in gcnf.lines() {
+        parser.line(line)?;
+    }
+    parser.finish()
+}
+
+/// Parse GCNF format from a reader, a line at a time.
+pub fn from_reader<R: BufRead>(mut reader: R) -> Result<Gcnf, ParseError> {
+    let mut parser = Parser::default();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => parser.line(&line)?,
+            Err(e) => return Err(ParseError::ReadError(e.to_string())),
+        }
+    }
+    parser.finish()
+}
+
+/// The state of parsing, between lines.
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd, Eq, Ord)]
+struct Parser {
+    /// The variables, clauses, and groups of the 'p' line.
+    prelude: Option<(usize, usize, usize)>,
+    /// The group of the current clause, if in the middle of one.
+    group: Option<usize>,
+    clause: Vec<isize>,
+    hard: Vec<Clause>,
+    groups: BTreeMap<usize, Vec<Clause>>,
+    num_clauses: usize,
+    variables: usize,
+}
+
+impl Parser {
+    fn line(&mut self, line: &str) -> Result<(), ParseError> {
+        let mut words = line.split_ascii_whitespace().peekable();
+        if self.group.is_none() {
+            match words.peek() {
+                Some(&"c") | None => return Ok(()),
+                Some(&"p") if self.prelude.is_none() => return self.prelude(line, words.skip(1)),
+                _ => (),
+            }
+        }
+        let (_, _, num_groups) = match self.prelude {
+            Some(prelude) => prelude,
+            None => return Err(ParseError::MissingPrelude),
+        };
+
+        for word in words {
+            let group = match self.group {
+                Some(group) => group,
+                None => {
+                    let group = word
+                        .strip_prefix('{')
+                        .and_then(|w| w.strip_suffix('}'))
+                        .and_then(|w| w.parse::<usize>().ok())
+                        .ok_or_else(|| ParseError::CannotParseClauseLine(line.to_string()))?;
+                    if group > num_groups {
+                        return Err(ParseError::GroupOutOfRange {
+                            expected: num_groups,
+                            actual: group,
+                        });
+                    }
+                    self.group = Some(group);
+                    continue;
+                }
+            };
+            match word.parse::<isize>() {
+                Ok(0) => {
+                    let clause = Clause::new(mem::take(&mut self.clause));
+                    match group {
+                        0 => self.hard.push(clause),
+                        _ => self.groups.entry(group).or_default().push(clause),
+                    }
+                    self.num_clauses += 1;
+                    self.group = None;
+                }
+                Ok(n) => {
+                    self.variables = self.variables.max(n.unsigned_abs());
+                    self.clause.push(n);
+                }
+                Err(_) => return Err(ParseError::CannotParseClauseLine(line.to_string())),
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse the rest of a 'p' line.
+    fn prelude<'a, I: Iterator<Item = &'a str>>(
+        &mut self,
+        line: &str,
+        mut words: I,
+    ) -> Result<(), ParseError> {
+        match words.next() {
+            Some("gcnf") => (),
+            Some(fmt) => return Err(ParseError::UnexpectedFormat(fmt.to_string())),
+            None => return Err(ParseError::CannotParsePreludeLine(line.to_string())),
+        }
+        let numbers: Result<Vec<usize>, _> = words.map(|w| w.parse::<usize>()).collect();
+        self.prelude = match numbers.as_deref() {
+            Ok([num_vars, num_clauses, num_groups]) => Some((*num_vars, *num_clauses, *num_groups)),
+            _ => return Err(ParseError::CannotParsePreludeLine(line.to_string())),
+        };
+        Ok(())
+    }
+
+    /// Check the clause is finished, and the number of variables and
+    /// clauses.  A file can declare more variables than it uses, but
+    /// not fewer.
+    fn finish(self) -> Result<Gcnf, ParseError> {
+        let (num_vars, num_clauses, _) = match self.prelude {
+            Some(prelude) => prelude,
+            None => return Err(ParseError::MissingPrelude),
+        };
+        if self.group.is_some() {
+            return Err(ParseError::UnterminatedClause);
+        }
+        if self.variables > num_vars {
+            return Err(ParseError::WrongNumberOfVariables {
+                expected: num_vars,
+                actual: self.variables,
+            });
+        }
+        if self.num_clauses != num_clauses {
+            return Err(ParseError::WrongNumberOfClauses {
+                expected: num_clauses,
+                actual: self.num_clauses,
+            });
+        }
+        Ok(Gcnf {
+            hard: Formula::new(self.hard),
+            groups: self
+                .groups
+                .into_iter()
+                .map(|(group, clauses)| (group, Formula::new(clauses)))
+                .collect(),
+        })
+    }
+}
+
+/// A parser error.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub enum ParseError {
+    CannotParsePreludeLine(String),
+    CannotParseClauseLine(String),
+    UnexpectedFormat(String),
+    MissingPrelude,
+    UnterminatedClause,
+    GroupOutOfRange { expected: usize, actual: usize },
+    WrongNumberOfVariables { expected: usize, actual: usize },
+    WrongNumberOfClauses { expected: usize, actual: usize },
+    ReadError(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::CannotParsePreludeLine(s) => write!(f, "cannot parse prelude line: {}", s),
+            ParseError::CannotParseClauseLine(s) => write!(f, "cannot parse clause line: {}", s),
+            ParseError::UnexpectedFormat(s) => write!(f, "expected 'gcnf' format, got '{}'", s),
+            ParseError::MissingPrelude => write!(f, "missing 'p gcnf' line"),
+            ParseError::UnterminatedClause => write!(f, "last clause has no terminating 0"),
+            ParseError::GroupOutOfRange { expected, actual } => write!(
+                f,
+                "group out of range, expected at most {} but got {}",
+                expected, actual
+            ),
+            ParseError::WrongNumberOfVariables { expected, actual } => write!(
+                f,
+                "too many variables, expected at most {} but got {}",
+                expected, actual
+            ),
+            ParseError::WrongNumberOfClauses { expected, actual } => write!(
+                f,
+                "wrong number of clauses, expected {} but got {}",
+                expected, actual
+            ),
+            ParseError::ReadError(s) => write!(f, "cannot read input: {}", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_writes_groups() {
+        let gcnf = "c groups\n\
+                    p gcnf 3 4 3\n\
+                    {0} 1 2 0\n\
+                    {3} -1 0\n\
+                    {1} -2\n\
+                    3 0\n\
+                    {3} -3 0\n";
+        let mut groups = BTreeMap::new();
+        groups.insert(1, cnf![[-2, 3]]);
+        groups.insert(3, cnf![[-1], [-3]]);
+        let expected = Gcnf {
+            hard: cnf![[1, 2]],
+            groups,
+        };
+        assert_eq!(Ok(expected.clone()), from_string(gcnf.to_string()));
+
+        let mut written = Vec::new();
+        expected.write(&mut written).unwrap();
+        assert_eq!(Ok(expected), from_reader(&written[..]));
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert!(from_string("{0} 1 0\n".to_string()).is_err());
+        assert!(from_string("p cnf 1 1\n1 0\n".to_string()).is_err());
+        assert!(from_string("p gcnf 1 1 1\n1 0\n".to_string()).is_err());
+        assert!(from_string("p gcnf 1 1 1\n{2} 1 0\n".to_string()).is_err());
+        assert!(from_string("p gcnf 1 2 1\n{1} 1 0\n".to_string()).is_err());
+        assert!(from_string("p gcnf 1 1 1\n{1} 1\n".to_string()).is_err());
+    }
+}
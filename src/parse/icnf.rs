@@ -0,0 +1,195 @@
+//! Parser for iCNF, the incremental format: after a 'p inccnf' line,
+//! clause lines add clauses, and 'a' lines solve under assumptions,
+//! so a benchmark can be replayed against the incremental solver.
+
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::mem;
+
+use crate::cnf::*;
+
+/// A step of an incremental problem.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub enum Step {
+    /// Add a clause, which stays for all later queries.
+    AddClause(Clause),
+    /// Solve the clauses so far, assuming the literals are true.
+    Solve(Vec<Literal>),
+}
+
+/// Write steps in iCNF format.
+pub fn write<W: Write>(steps: &[Step], mut writer: W) -> io::Result<()> {
+    writeln!(writer, "p inccnf")?;
+    for step in steps {
+        let lits = match step {
+            Step::AddClause(clause) => clause.literals(),
+            Step::Solve(lits) => {
+                write!(writer, "a ")?;
+                lits
+            }
+        };
+        for lit in lits {
+            write!(writer, "{} ", lit)?;
+        }
+        writeln!(writer, "0")?;
+    }
+    writer.flush()
+}
+
+/// Parse a string in iCNF format.
+pub fn from_string(icnf: String) -> Result<Vec<Step>, ParseError> {
+    let mut parser = Parser::default();
+    for line in icnf.lines() {
+        parser.line(line)?;
+    }
+    parser.finish()
+}
+
+/// Parse iCNF format from a reader, a line at a time.
+pub fn from_reader<R: BufRead>(mut reader: R) -> Result<Vec<Step>, ParseError> {
+    let mut parser = Parser::default();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => parser.line(&line)?,
+            Err(e) => return Err(ParseError::ReadError(e.to_string())),
+        }
+    }
+    parser.finish()
+}
+
+/// The state of parsing, between lines.
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd, Eq, Ord)]
+struct Parser {
+    seen_prelude: bool,
+    /// Whether the current clause is an assumption line, if in the
+    /// middle of one.
+    assumptions: Option<bool>,
+    lits: Vec<isize>,
+    steps: Vec<Step>,
+}
+
+impl Parser {
+    fn line(&mut self, line: &str) -> Result<(), ParseError> {
+        let mut words = line.split_ascii_whitespace().peekable();
+        if !self.seen_prelude {
+            return match words.next() {
+                Some("c") | None => Ok(()),
+                Some("p") => match words.next() {
+                    Some("inccnf") => {
+                        self.seen_prelude = true;
+                        Ok(())
+                    }
+                    Some(fmt) => Err(ParseError::UnexpectedFormat(fmt.to_string())),
+                    None => Err(ParseError::CannotParsePreludeLine(line.to_string())),
+                },
+                _ => Err(ParseError::CannotParsePreludeLine(line.to_string())),
+            };
+        }
+
+        if self.assumptions.is_none() {
+            match words.peek() {
+                Some(&"c") | None => return Ok(()),
+                Some(&"a") => {
+                    words.next();
+                    self.assumptions = Some(true);
+                }
+                _ => (),
+            }
+        }
+
+        for word in words {
+            match word.parse::<isize>() {
+                Ok(0) => {
+                    let lits = mem::take(&mut self.lits);
+                    self.steps.push(if self.assumptions == Some(true) {
+                        Step::Solve(lits.into_iter().map(Literal::new).collect())
+                    } else {
+                        Step::AddClause(Clause::new(lits))
+                    });
+                    self.assumptions = None;
+                }
+                Ok(n) => {
+                    if self.assumptions.is_none() {
+                        self.assumptions = Some(false);
+                    }
+                    self.lits.push(n);
+                }
+                // an 'a' has to start a line.
+                Err(_) => return Err(ParseError::CannotParseClauseLine(line.to_string())),
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Vec<Step>, ParseError> {
+        if !self.seen_prelude {
+            Err(ParseError::MissingPrelude)
+        } else if self.assumptions.is_some() {
+            Err(ParseError::UnterminatedClause)
+        } else {
+            Ok(self.steps)
+        }
+    }
+}
+
+/// A parser error.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub enum ParseError {
+    CannotParsePreludeLine(String),
+    CannotParseClauseLine(String),
+    UnexpectedFormat(String),
+    MissingPrelude,
+    UnterminatedClause,
+    ReadError(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::CannotParsePreludeLine(s) => write!(f, "cannot parse prelude line: {}", s),
+            ParseError::CannotParseClauseLine(s) => write!(f, "cannot parse clause line: {}", s),
+            ParseError::UnexpectedFormat(s) => write!(f, "expected 'inccnf' format, got '{}'", s),
+            ParseError::MissingPrelude => write!(f, "missing 'p inccnf' line"),
+            ParseError::UnterminatedClause => write!(f, "last line has no terminating 0"),
+            ParseError::ReadError(s) => write!(f, "cannot read input: {}", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_writes_steps() {
+        let icnf = "c incremental\n\
+                    p inccnf\n\
+                    1 2 0\n\
+                    a -1 0\n\
+                    -2 3\n\
+                    0\n\
+                    a -1 -3 0\n";
+        let steps = vec![
+            Step::AddClause(Clause::new(vec![1, 2])),
+            Step::Solve(vec![Literal::new(-1)]),
+            Step::AddClause(Clause::new(vec![-2, 3])),
+            Step::Solve(vec![Literal::new(-1), Literal::new(-3)]),
+        ];
+        assert_eq!(Ok(steps.clone()), from_string(icnf.to_string()));
+
+        let mut written = Vec::new();
+        write(&steps, &mut written).unwrap();
+        assert_eq!(Ok(steps), from_reader(&written[..]));
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert!(from_string("1 2 0\n".to_string()).is_err());
+        assert!(from_string("p cnf 2 1\n1 2 0\n".to_string()).is_err());
+        assert!(from_string("p inccnf\na 1\n".to_string()).is_err());
+        assert!(from_string("p inccnf\n1 0 a 2 0\n".to_string()).is_err());
+    }
+}
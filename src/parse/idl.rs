@@ -0,0 +1,175 @@
+//! Parse IDL formulae.
+
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::cnf::Formula;
+use crate::parse::combinators::*;
+use crate::parse::dimacs;
+use crate::parse::Position;
+use crate::theory::idl::*;
+
+/// Parse an IDL theory and formula represented as a string.  The
+/// representation is as follows:
+///
+///    idl_lit
+///    idl_lit
+///    [...]
+///    --
+///    <dimacs>
+///
+/// Where an idl_lit is:
+///   - variable - variable <= integer
+///
+/// And a variable is a nonnegative integer.
+pub fn from_string(input: String) -> Result<(IDL, Formula), ParseError> {
+    let mut lines = input.lines();
+    let mut lits = Vec::new();
+    let mut skipped = 0;
+
+    for text in lines.by_ref() {
+        skipped += 1;
+        if text == "--" {
+            break;
+        }
+        let line = Line {
+            number: skipped,
+            text,
+        };
+        lits.push(parse_lit(&mut text.chars().peekable(), line)?);
+    }
+
+    match dimacs::from_lines_after(lines, skipped, dimacs::ParseOptions::default()) {
+        Ok(formula) => Ok((IDL::new(lits), formula)),
+        Err(e) => Err(ParseError::DIMACSError(e)),
+    }
+}
+
+/// A line of the input, for the positions of errors.
+#[derive(Clone, Copy, Debug)]
+struct Line<'a> {
+    number: usize,
+    text: &'a str,
+}
+
+impl<'a> Line<'a> {
+    /// The position of the next character.
+    fn at(&self, chars: &Peekable<Chars<'a>>) -> Position {
+        let rest: usize = chars.clone().map(char::len_utf8).sum();
+        Position::new(self.number, self.text, self.text.len() - rest)
+    }
+}
+
+/// Parse a lit: variable - variable <= integer
+fn parse_lit<'a>(
+    chars: &mut Peekable<Chars<'a>>,
+    line: Line<'a>,
+) -> Result<IDLLiteral, ParseError> {
+    let left = parse_variable(chars, line)?;
+    parse_symbol(chars, line, "-")?;
+    let right = parse_variable(chars, line)?;
+    parse_symbol(chars, line, "<=")?;
+
+    eat_whitespace(chars);
+    let at = line.at(chars);
+    let bound = parse_isize(chars).ok_or(ParseError::CannotParseBound(at))?;
+
+    eat_whitespace(chars);
+    match chars.peek() {
+        None => Ok(IDLLiteral::new(left, right, bound)),
+        Some(_) => Err(ParseError::UnexpectedInput(line.at(chars))),
+    }
+}
+
+/// Parse a variable
+fn parse_variable<'a>(
+    chars: &mut Peekable<Chars<'a>>,
+    line: Line<'a>,
+) -> Result<usize, ParseError> {
+    eat_whitespace(chars);
+    let at = line.at(chars);
+    parse_usize(chars).ok_or(ParseError::CannotParseVariable(at))
+}
+
+/// Parse a symbol, like '-' or '<='
+fn parse_symbol<'a>(
+    chars: &mut Peekable<Chars<'a>>,
+    line: Line<'a>,
+    symbol: &'static str,
+) -> Result<(), ParseError> {
+    eat_whitespace(chars);
+    let at = line.at(chars);
+    for expected in symbol.chars() {
+        if chars.next_if_eq(&expected).is_none() {
+            return Err(ParseError::ExpectedSymbol { symbol, at });
+        }
+    }
+    Ok(())
+}
+
+/// A parser error.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub enum ParseError {
+    ExpectedSymbol { symbol: &'static str, at: Position },
+    CannotParseVariable(Position),
+    CannotParseBound(Position),
+    UnexpectedInput(Position),
+    DIMACSError(dimacs::ParseError),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::ExpectedSymbol { symbol, at } => {
+                write!(f, "expected '{}' at {}", symbol, at)
+            }
+            ParseError::CannotParseVariable(at) => write!(f, "cannot parse variable at {}", at),
+            ParseError::CannotParseBound(at) => write!(f, "cannot parse bound at {}", at),
+            ParseError::UnexpectedInput(at) => write!(f, "unexpected input at {}", at),
+            ParseError::DIMACSError(e) => write!(f, "cannot parse DIMACS: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_bounds() {
+        let input = "1 - 2 <= 3\n2-1<=-4\n--\np cnf 2 1\n1 -2 0\n";
+        let (idl, formula) = from_string(input.to_string()).unwrap();
+        assert_eq!(
+            IDL::new(vec![IDLLiteral::new(1, 2, 3), IDLLiteral::new(2, 1, -4)]),
+            idl
+        );
+        assert_eq!(cnf![[1, -2]], formula);
+    }
+
+    #[test]
+    fn reports_positions() {
+        let input = "1 - 2 <= 3\n1 + 2 <= 3\n--\np cnf 1 1\n1 0\n";
+        assert_eq!(
+            Err(ParseError::ExpectedSymbol {
+                symbol: "-",
+                at: Position {
+                    line: 2,
+                    column: 3,
+                    snippet: "+ 2 <= 3".to_string(),
+                },
+            }),
+            from_string(input.to_string()).map(|_| ())
+        );
+
+        let input = "1 - 2 <= x\n--\np cnf 1 1\n1 0\n";
+        assert_eq!(
+            Err(ParseError::CannotParseBound(Position {
+                line: 1,
+                column: 10,
+                snippet: "x".to_string(),
+            })),
+            from_string(input.to_string()).map(|_| ())
+        );
+    }
+}
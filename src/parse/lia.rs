@@ -0,0 +1,204 @@
+//! Parse LIA formulae.
+
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::cnf::Formula;
+use crate::parse::combinators::*;
+use crate::parse::dimacs;
+use crate::parse::Position;
+use crate::theory::lia::*;
+
+/// Parse an LIA theory and formula represented as a string.  The
+/// representation is as follows:
+///
+///    lia_lit
+///    lia_lit
+///    [...]
+///    --
+///    <dimacs>
+///
+/// Where an lia_lit is one of:
+///   - lia_term + lia_term ... <= integer
+///   - lia_term + lia_term ... >= integer
+///
+/// Where a '+' can also be a '-', as can the start of the first
+/// lia_term, and an lia_term is one of:
+///   - variable
+///   - integer*variable
+///
+/// And a variable is a nonnegative integer.
+pub fn from_string(input: String) -> Result<(LIA, Formula), ParseError> {
+    let mut lines = input.lines();
+    let mut lits = Vec::new();
+    let mut skipped = 0;
+
+    for text in lines.by_ref() {
+        skipped += 1;
+        if text == "--" {
+            break;
+        }
+        let line = Line {
+            number: skipped,
+            text,
+        };
+        lits.push(parse_lit(&mut text.chars().peekable(), line)?);
+    }
+
+    match dimacs::from_lines_after(lines, skipped, dimacs::ParseOptions::default()) {
+        Ok(formula) => Ok((LIA::new(lits), formula)),
+        Err(e) => Err(ParseError::DIMACSError(e)),
+    }
+}
+
+/// A line of the input, for the positions of errors.
+#[derive(Clone, Copy, Debug)]
+struct Line<'a> {
+    number: usize,
+    text: &'a str,
+}
+
+impl<'a> Line<'a> {
+    /// The position of the next character.
+    fn at(&self, chars: &Peekable<Chars<'a>>) -> Position {
+        let rest: usize = chars.clone().map(char::len_utf8).sum();
+        Position::new(self.number, self.text, self.text.len() - rest)
+    }
+}
+
+/// Parse a lit, one of:
+///   - lia_term + lia_term ... <= integer
+///   - lia_term + lia_term ... >= integer
+fn parse_lit<'a>(
+    chars: &mut Peekable<Chars<'a>>,
+    line: Line<'a>,
+) -> Result<LIALiteral, ParseError> {
+    let mut coefficients = Vec::new();
+    eat_whitespace(chars);
+    let mut sign = if chars.next_if_eq(&'-').is_some() {
+        -1
+    } else {
+        1
+    };
+    loop {
+        let (var, a) = parse_term(chars, line)?;
+        coefficients.push((var, sign * a));
+        eat_whitespace(chars);
+        sign = match chars.peek() {
+            Some('+') => 1,
+            Some('-') => -1,
+            _ => break,
+        };
+        chars.next();
+    }
+
+    let at = line.at(chars);
+    let is_upper = match (chars.next(), chars.next()) {
+        (Some('<'), Some('=')) => true,
+        (Some('>'), Some('=')) => false,
+        _ => return Err(ParseError::CannotParseComparison(at)),
+    };
+
+    eat_whitespace(chars);
+    let at = line.at(chars);
+    let bound = parse_isize(chars).ok_or(ParseError::CannotParseBound(at))?;
+
+    eat_whitespace(chars);
+    if chars.peek().is_some() {
+        return Err(ParseError::UnexpectedInput(line.at(chars)));
+    }
+    if is_upper {
+        Ok(LIALiteral::new(coefficients, bound))
+    } else {
+        // t >= c is -t <= -c.
+        let coefficients = coefficients.into_iter().map(|(var, a)| (var, -a)).collect();
+        Ok(LIALiteral::new(coefficients, -bound))
+    }
+}
+
+/// Parse a term, one of:
+///   - variable
+///   - integer*variable
+fn parse_term<'a>(
+    chars: &mut Peekable<Chars<'a>>,
+    line: Line<'a>,
+) -> Result<(usize, isize), ParseError> {
+    eat_whitespace(chars);
+    let at = line.at(chars);
+    let n = parse_usize(chars).ok_or(ParseError::CannotParseTerm(at))?;
+    if chars.next_if_eq(&'*').is_none() {
+        return Ok((n, 1));
+    }
+    let at = line.at(chars);
+    let var = parse_usize(chars).ok_or(ParseError::CannotParseTerm(at))?;
+    Ok((var, n as isize))
+}
+
+/// A parser error.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub enum ParseError {
+    CannotParseTerm(Position),
+    CannotParseComparison(Position),
+    CannotParseBound(Position),
+    UnexpectedInput(Position),
+    DIMACSError(dimacs::ParseError),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::CannotParseTerm(at) => write!(f, "cannot parse term at {}", at),
+            ParseError::CannotParseComparison(at) => {
+                write!(
+                    f,
+                    "cannot parse comparison at {}, expected '<=' or '>='",
+                    at
+                )
+            }
+            ParseError::CannotParseBound(at) => write!(f, "cannot parse bound at {}", at),
+            ParseError::UnexpectedInput(at) => write!(f, "unexpected input at {}", at),
+            ParseError::DIMACSError(e) => write!(f, "cannot parse DIMACS: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_bounds() {
+        let input = "1 + 2 <= 3\n-2*1 - 2 >= -4\n3*2<=0\n--\np cnf 2 1\n1 -2 0\n";
+        let (lia, _) = from_string(input.to_string()).unwrap();
+        let expected = LIA::new(vec![
+            LIALiteral::new(vec![(1, 1), (2, 1)], 3),
+            LIALiteral::new(vec![(1, 2), (2, 1)], 4),
+            LIALiteral::new(vec![(2, 3)], 0),
+        ]);
+        assert_eq!(expected, lia);
+    }
+
+    #[test]
+    fn reports_positions() {
+        let input = "1 + 2 < 3\n--\np cnf 1 1\n1 0\n";
+        assert_eq!(
+            Err(ParseError::CannotParseComparison(Position {
+                line: 1,
+                column: 7,
+                snippet: "< 3".to_string(),
+            })),
+            from_string(input.to_string()).map(|_| ())
+        );
+
+        let input = "1 <= 3\n2*x >= 1\n--\np cnf 1 1\n1 0\n";
+        assert_eq!(
+            Err(ParseError::CannotParseTerm(Position {
+                line: 2,
+                column: 3,
+                snippet: "x >= 1".to_string(),
+            })),
+            from_string(input.to_string()).map(|_| ())
+        );
+    }
+}
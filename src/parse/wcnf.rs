@@ -0,0 +1,309 @@
+//! Parser and writer for WCNF, the MaxSAT format.  Both the classic
+//! format, with a 'p wcnf' line and hard clauses marked by the "top"
+//! weight, and the 2022 format, with no 'p' line and hard clauses
+//! marked 'h', are read.
+
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::mem;
+
+use crate::cnf::*;
+
+/// A MaxSAT problem: hard clauses, which must be satisfied, and soft
+/// clauses with weights, which should be.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub struct Wcnf {
+    pub hard: Formula,
+    pub soft: Vec<(Clause, usize)>,
+}
+
+impl Wcnf {
+    /// The highest atom in any clause.
+    pub fn num_vars(&self) -> usize {
+        self.hard
+            .iter()
+            .chain(self.soft.iter().map(|(clause, _)| clause.literals()))
+            .flatten()
+            .map(|lit| lit.get_id() as usize)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Write in the 2022 format.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for lits in self.hard.iter() {
+            write!(writer, "h ")?;
+            write_clause(&mut writer, lits)?;
+        }
+        for (clause, weight) in &self.soft {
+            write!(writer, "{} ", weight)?;
+            write_clause(&mut writer, clause.literals())?;
+        }
+        writer.flush()
+    }
+
+    /// Write in the classic format, with a top weight one more than
+    /// the total weight of the soft clauses.
+    pub fn write_classic<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let top = self
+            .soft
+            .iter()
+            .fold(1usize, |total, (_, weight)| total.saturating_add(*weight));
+        writeln!(
+            writer,
+            "p wcnf {} {} {}",
+            self.num_vars(),
+            self.hard.len() + self.soft.len(),
+            top
+        )?;
+        for lits in self.hard.iter() {
+            write!(writer, "{} ", top)?;
+            write_clause(&mut writer, lits)?;
+        }
+        for (clause, weight) in &self.soft {
+            write!(writer, "{} ", weight)?;
+            write_clause(&mut writer, clause.literals())?;
+        }
+        writer.flush()
+    }
+}
+
+/// Write the literals of a clause and the terminating 0.
+fn write_clause<W: Write>(writer: &mut W, lits: &[Literal]) -> io::Result<()> {
+    for lit in lits {
+        write!(writer, "{} ", lit)?;
+    }
+    writeln!(writer, "0")
+}
+
+/// Parse a string in WCNF format.
+pub fn from_string(wcnf: String) -> Result<Wcnf, ParseError> {
+    let mut parser = Parser::default();
+    for line in wcnf.lines() {
+        parser.line(line)?;
+    }
+    parser.finish()
+}
+
+/// Parse WCNF format from a reader, a line at a time.
+pub fn from_reader<R: BufRead>(mut reader: R) -> Result<Wcnf, ParseError> {
+    let mut parser = Parser::default();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => parser.line(&line)?,
+            Err(e) => return Err(ParseError::ReadError(e.to_string())),
+        }
+    }
+    parser.finish()
+}
+
+/// The state of parsing, between lines.
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd, Eq, Ord)]
+struct Parser {
+    /// The variables, clauses, and top weight of the 'p' line, if
+    /// this is the classic format.
+    prelude: Option<(usize, usize, Option<usize>)>,
+    /// Whether any clause has been seen.
+    seen_clause: bool,
+    /// The weight of the current clause, or 'None' if it is hard, if
+    /// in the middle of one.
+    weight: Option<Option<usize>>,
+    clause: Vec<isize>,
+    hard: Vec<Clause>,
+    soft: Vec<(Clause, usize)>,
+    variables: usize,
+}
+
+impl Parser {
+    fn line(&mut self, line: &str) -> Result<(), ParseError> {
+        let mut words = line.split_ascii_whitespace().peekable();
+        if self.weight.is_none() {
+            match words.peek() {
+                Some(&"c") | None => return Ok(()),
+                Some(&"p") if self.prelude.is_none() && !self.seen_clause => {
+                    return self.prelude(line, words.skip(1));
+                }
+                _ => (),
+            }
+        }
+
+        for word in words {
+            let weight = match self.weight {
+                Some(weight) => weight,
+                None => {
+                    self.seen_clause = true;
+                    self.weight = Some(self.parse_weight(word, line)?);
+                    continue;
+                }
+            };
+            match word.parse::<isize>() {
+                Ok(0) => {
+                    let clause = Clause::new(mem::take(&mut self.clause));
+                    match weight {
+                        Some(weight) => self.soft.push((clause, weight)),
+                        None => self.hard.push(clause),
+                    }
+                    self.weight = None;
+                }
+                Ok(n) => {
+                    self.variables = self.variables.max(n.unsigned_abs());
+                    self.clause.push(n);
+                }
+                Err(_) => return Err(ParseError::CannotParseClauseLine(line.to_string())),
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse the rest of a 'p' line.
+    fn prelude<'a, I: Iterator<Item = &'a str>>(
+        &mut self,
+        line: &str,
+        mut words: I,
+    ) -> Result<(), ParseError> {
+        match words.next() {
+            Some("wcnf") => (),
+            Some(fmt) => return Err(ParseError::UnexpectedFormat(fmt.to_string())),
+            None => return Err(ParseError::CannotParsePreludeLine(line.to_string())),
+        }
+        let numbers: Result<Vec<usize>, _> = words.map(|w| w.parse::<usize>()).collect();
+        self.prelude = match numbers.as_deref() {
+            Ok([num_vars, num_clauses]) => Some((*num_vars, *num_clauses, None)),
+            Ok([num_vars, num_clauses, top]) => Some((*num_vars, *num_clauses, Some(*top))),
+            _ => return Err(ParseError::CannotParsePreludeLine(line.to_string())),
+        };
+        Ok(())
+    }
+
+    /// Parse the weight at the start of a clause: 'Some' for a soft
+    /// clause and 'None' for a hard one.
+    fn parse_weight(&self, word: &str, line: &str) -> Result<Option<usize>, ParseError> {
+        match self.prelude {
+            Some((_, _, top)) => match word.parse::<usize>() {
+                Ok(weight) if top.is_some_and(|top| weight >= top) => Ok(None),
+                Ok(weight) => Ok(Some(weight)),
+                Err(_) => Err(ParseError::CannotParseClauseLine(line.to_string())),
+            },
+            None if word == "h" => Ok(None),
+            None => match word.parse::<usize>() {
+                Ok(weight) => Ok(Some(weight)),
+                Err(_) => Err(ParseError::CannotParseClauseLine(line.to_string())),
+            },
+        }
+    }
+
+    /// Check the clause is finished and, in the classic format, the
+    /// number of variables and clauses.  A classic file can declare
+    /// more variables than it uses, but not fewer.
+    fn finish(self) -> Result<Wcnf, ParseError> {
+        if self.weight.is_some() {
+            return Err(ParseError::UnterminatedClause);
+        }
+        if let Some((num_vars, num_clauses, _)) = self.prelude {
+            if self.variables > num_vars {
+                return Err(ParseError::WrongNumberOfVariables {
+                    expected: num_vars,
+                    actual: self.variables,
+                });
+            }
+            let actual = self.hard.len() + self.soft.len();
+            if actual != num_clauses {
+                return Err(ParseError::WrongNumberOfClauses {
+                    expected: num_clauses,
+                    actual,
+                });
+            }
+        }
+        Ok(Wcnf {
+            hard: Formula::new(self.hard),
+            soft: self.soft,
+        })
+    }
+}
+
+/// A parser error.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub enum ParseError {
+    CannotParsePreludeLine(String),
+    CannotParseClauseLine(String),
+    UnexpectedFormat(String),
+    UnterminatedClause,
+    WrongNumberOfVariables { expected: usize, actual: usize },
+    WrongNumberOfClauses { expected: usize, actual: usize },
+    ReadError(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::CannotParsePreludeLine(s) => write!(f, "cannot parse prelude line: {}", s),
+            ParseError::CannotParseClauseLine(s) => write!(f, "cannot parse clause line: {}", s),
+            ParseError::UnexpectedFormat(s) => write!(f, "expected 'wcnf' format, got '{}'", s),
+            ParseError::UnterminatedClause => write!(f, "last clause has no terminating 0"),
+            ParseError::WrongNumberOfVariables { expected, actual } => write!(
+                f,
+                "too many variables, expected at most {} but got {}",
+                expected, actual
+            ),
+            ParseError::WrongNumberOfClauses { expected, actual } => write!(
+                f,
+                "wrong number of clauses, expected {} but got {}",
+                expected, actual
+            ),
+            ParseError::ReadError(s) => write!(f, "cannot read input: {}", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> Wcnf {
+        Wcnf {
+            hard: cnf![[1, 2], [-1, -2]],
+            soft: vec![(Clause::new(vec![1]), 3), (Clause::new(vec![2, -3]), 5)],
+        }
+    }
+
+    #[test]
+    fn parses_both_formats() {
+        let classic = "c classic\n\
+                       p wcnf 3 4 9\n\
+                       9 1 2 0\n\
+                       9 -1 -2 0\n\
+                       3 1 0\n\
+                       5 2\n\
+                       -3 0\n";
+        let new = "c 2022\n\
+                   h 1 2 0\n\
+                   h -1 -2 0\n\
+                   3 1 0\n\
+                   5 2 -3 0\n";
+
+        assert_eq!(Ok(example()), from_string(classic.to_string()));
+        assert_eq!(Ok(example()), from_reader(new.as_bytes()));
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut classic = Vec::new();
+        example().write_classic(&mut classic).unwrap();
+        assert_eq!(Ok(example()), from_reader(&classic[..]));
+
+        let mut new = Vec::new();
+        example().write(&mut new).unwrap();
+        assert_eq!(Ok(example()), from_reader(&new[..]));
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert!(from_string("p cnf 1 1\n1 0\n".to_string()).is_err());
+        assert!(from_string("p wcnf 1 2 9\n9 1 0\n".to_string()).is_err());
+        assert!(from_string("h 1 2\n".to_string()).is_err());
+        assert!(from_string("x 1 0\n".to_string()).is_err());
+    }
+}
@@ -0,0 +1,976 @@
+//! Simplifications of a formula, to make before solving.
+//!
+//! Subsumption and self-subsuming resolution replace a formula with
+//! an equivalent one, so they are safe to use with any theory.
+//! Variable elimination, equivalent literal substitution, and
+//! variable addition only preserve satisfiability, and need any model
+//! found to be fixed up to match the original formula, so they are
+//! only suitable for SAT.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::mem;
+
+use crate::cnf::{Clause, Formula, Literal, Var};
+use crate::dpll::lit_index;
+
+/// What a simplification did.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd, Eq, Ord)]
+pub struct SimplificationStats {
+    /// The number of clauses removed because they contain a literal
+    /// and its negation.
+    pub tautologies: usize,
+    /// The number of clauses removed because another clause
+    /// subsumed them.
+    pub subsumed_clauses: usize,
+    /// The number of literals removed from clauses by
+    /// self-subsuming resolution.
+    pub strengthened_literals: usize,
+}
+
+/// Simplify a formula in place:
+///
+/// - A clause containing a literal and its negation is always true,
+///   so is removed.
+///
+/// - A clause C subsumes a clause D if every literal of C is in D.
+///   Any model of C is a model of D, so D is removed.
+///
+/// - If C = C' + l and D = D' + -l, where C' is a subset of D', then
+///   resolving them gives D', which subsumes D: so -l is removed from
+///   D.  This is self-subsuming resolution.
+///
+/// Strengthening a clause can let it subsume or strengthen others,
+/// so this is repeated until nothing changes.  The literals of each
+/// clause are sorted and deduplicated, and the surviving clauses stay
+/// in their original order.
+pub fn simplify(formula: &mut Formula) -> SimplificationStats {
+    let mut stats = SimplificationStats::default();
+
+    let mut database: Vec<Option<Vec<Literal>>> = formula
+        .iter()
+        .map(|lits| {
+            let mut lits = lits.to_vec();
+            lits.sort();
+            lits.dedup();
+            if lits
+                .iter()
+                .any(|lit| lits.binary_search(&lit.negate()).is_ok())
+            {
+                stats.tautologies += 1;
+                None
+            } else {
+                Some(lits)
+            }
+        })
+        .collect();
+
+    // occurrence lists are only ever added to, so they may mention
+    // clauses which no longer contain the literal.
+    let mut occurrences: HashMap<Literal, Vec<usize>> = HashMap::new();
+    for (i, lits) in database.iter().enumerate() {
+        for lit in lits.iter().flatten() {
+            occurrences.entry(*lit).or_default().push(i);
+        }
+    }
+
+    let mut queue: VecDeque<usize> = (0..database.len()).collect();
+    while let Some(i) = queue.pop_front() {
+        let lits = match &database[i] {
+            Some(lits) => lits.clone(),
+            None => continue,
+        };
+
+        // every clause C can subsume or strengthen mentions the atom
+        // of each literal of C, so pick the least common.
+        let count = |lit: &Literal| {
+            occurrences.get(lit).map_or(0, Vec::len)
+                + occurrences.get(&lit.negate()).map_or(0, Vec::len)
+        };
+        let pivot = match lits.iter().min_by_key(|lit| count(lit)) {
+            Some(lit) => *lit,
+            None => {
+                // the empty clause subsumes everything.
+                for (j, other) in database.iter_mut().enumerate() {
+                    if j != i && other.take().is_some() {
+                        stats.subsumed_clauses += 1;
+                    }
+                }
+                break;
+            }
+        };
+        let mut candidates: Vec<usize> = occurrences
+            .get(&pivot)
+            .into_iter()
+            .chain(occurrences.get(&pivot.negate()))
+            .flatten()
+            .copied()
+            .filter(|j| *j != i)
+            .collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        for j in candidates {
+            let other = match &mut database[j] {
+                Some(other) => other,
+                None => continue,
+            };
+            match subsumes(&lits, other) {
+                Some(None) => {
+                    database[j] = None;
+                    stats.subsumed_clauses += 1;
+                }
+                Some(Some(lit)) => {
+                    other.retain(|l| *l != lit);
+                    stats.strengthened_literals += 1;
+                    queue.push_back(j);
+                }
+                None => (),
+            }
+        }
+    }
+
+    *formula = database.into_iter().flatten().map(Clause::from).collect();
+    stats
+}
+
+/// Check if one sorted clause subsumes another.  Returns 'Some(None)'
+/// if it does, 'Some(Some(lit))' if it would after removing 'lit'
+/// from the other clause, and 'None' otherwise.
+fn subsumes(lits: &[Literal], other: &[Literal]) -> Option<Option<Literal>> {
+    if lits.len() > other.len() {
+        return None;
+    }
+
+    let mut flipped = None;
+    for lit in lits {
+        if other.binary_search(lit).is_ok() {
+            continue;
+        }
+        if flipped.is_none() && other.binary_search(&lit.negate()).is_ok() {
+            flipped = Some(lit.negate());
+            continue;
+        }
+        return None;
+    }
+    Some(flipped)
+}
+
+/// The clauses removed by variable elimination, and the variables
+/// added by variable addition, to turn a model of the simplified
+/// formula into one of the original formula.
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd, Eq, Ord)]
+pub struct Reconstruction {
+    variables: BTreeSet<Var>,
+    eliminated: Vec<(Var, Vec<Clause>)>,
+    added: BTreeSet<Var>,
+}
+
+impl Reconstruction {
+    /// The number of variables eliminated.
+    pub fn eliminated(&self) -> usize {
+        self.eliminated.len()
+    }
+
+    /// The number of variables added by variable addition.
+    pub fn added(&self) -> usize {
+        self.added.len()
+    }
+
+    /// Extend a model of the simplified formula to a model of the
+    /// original formula, by assigning the eliminated variables (and
+    /// any others which no longer occur), and unassigning the added
+    /// variables.
+    ///
+    /// The eliminated variables are assigned in the reverse of the
+    /// order they were eliminated in: a variable is made false if
+    /// one of the clauses it was removed from needs it to be, and
+    /// true otherwise.  As the resolvents of those clauses are true,
+    /// this satisfies all of them.
+    pub fn extend(&self, model: &[Literal]) -> Vec<Literal> {
+        let mut values: BTreeMap<Var, bool> = model
+            .iter()
+            .map(|lit| (lit.var(), !lit.is_negated()))
+            .collect();
+        let eliminated: BTreeSet<Var> = self.eliminated.iter().map(|(var, _)| *var).collect();
+
+        let mut extended = model.to_vec();
+        for var in &self.variables {
+            if !values.contains_key(var) && !eliminated.contains(var) {
+                values.insert(*var, false);
+                extended.push(var.negative());
+            }
+        }
+
+        for (var, clauses) in self.eliminated.iter().rev() {
+            let needs_false = clauses.iter().any(|clause| {
+                clause.literals().iter().all(|lit| {
+                    if lit.var() == *var {
+                        lit.is_negated()
+                    } else {
+                        values.get(&lit.var()) == Some(&lit.is_negated())
+                    }
+                })
+            });
+            values.insert(*var, !needs_false);
+            extended.push(var.literal(!needs_false));
+        }
+
+        extended.retain(|lit| !self.added.contains(&lit.var()));
+        extended
+    }
+}
+
+/// Eliminate variables by resolution, as in SatELite: a variable x is
+/// eliminated by replacing the clauses containing x or -x with every
+/// non-tautological resolvent on x, if there are no more resolvents
+/// than clauses replaced.  Variables are tried in order of how few
+/// resolvents they could produce, and this is repeated until nothing
+/// changes.
+///
+/// If x is the output of an and or or gate, defined by some of its
+/// clauses, then resolving two clauses which aren't part of the gate
+/// only gives resolvents implied by the others, so just the resolvents
+/// of gate clauses with non-gate clauses are needed.
+///
+/// The result is satisfiable if and only if the original formula is,
+/// and the returned 'Reconstruction' turns a model of the result into
+/// a model of the original.
+pub fn eliminate_variables(formula: &mut Formula) -> Reconstruction {
+    let mut reconstruction = Reconstruction::default();
+
+    // tautologies would resolve with themselves, so drop them: they
+    // are always true anyway.
+    let mut database: Vec<Option<Vec<Literal>>> = Vec::with_capacity(formula.len());
+    for lits in formula.iter() {
+        let mut lits = lits.to_vec();
+        lits.sort();
+        lits.dedup();
+        for lit in &lits {
+            reconstruction.variables.insert(lit.var());
+        }
+        if !lits
+            .iter()
+            .any(|lit| lits.binary_search(&lit.negate()).is_ok())
+        {
+            database.push(Some(lits));
+        }
+    }
+
+    // occurrence lists may mention clauses which have been removed.
+    let mut occurrences: HashMap<Literal, Vec<usize>> = HashMap::new();
+    for (i, lits) in database.iter().enumerate() {
+        for lit in lits.iter().flatten() {
+            occurrences.entry(*lit).or_default().push(i);
+        }
+    }
+
+    let mut eliminated = BTreeSet::new();
+    loop {
+        let mut candidates: Vec<(usize, Var)> = reconstruction
+            .variables
+            .iter()
+            .filter(|var| !eliminated.contains(*var))
+            .map(|var| {
+                let pos = live(&occurrences, &database, var.positive()).len();
+                let neg = live(&occurrences, &database, var.negative()).len();
+                (pos * neg, *var)
+            })
+            .collect();
+        candidates.sort_unstable();
+
+        let mut changed = false;
+        for (_, var) in candidates {
+            let pos = live(&occurrences, &database, var.positive());
+            let neg = live(&occurrences, &database, var.negative());
+            let limit = pos.len() + neg.len();
+            let gate = gate_clauses(&database, &pos, &neg, var.positive()).or_else(|| {
+                gate_clauses(&database, &neg, &pos, var.negative()).map(|(gn, gp)| (gp, gn))
+            });
+
+            let mut resolvents = Vec::new();
+            'resolve: for p in &pos {
+                for n in &neg {
+                    if gate
+                        .as_ref()
+                        .is_some_and(|(gp, gn)| gp.contains(p) == gn.contains(n))
+                    {
+                        continue;
+                    }
+                    if let Some(resolvent) = resolve(
+                        database[*p].as_ref().unwrap(),
+                        database[*n].as_ref().unwrap(),
+                        var,
+                    ) {
+                        resolvents.push(resolvent);
+                        if resolvents.len() > limit {
+                            break 'resolve;
+                        }
+                    }
+                }
+            }
+            if resolvents.len() > limit {
+                continue;
+            }
+
+            let removed = pos
+                .iter()
+                .chain(&neg)
+                .filter_map(|i| database[*i].take())
+                .map(Clause::from)
+                .collect();
+            reconstruction.eliminated.push((var, removed));
+            eliminated.insert(var);
+            for resolvent in resolvents {
+                for lit in &resolvent {
+                    occurrences.entry(*lit).or_default().push(database.len());
+                }
+                database.push(Some(resolvent));
+            }
+            changed = true;
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    *formula = database.into_iter().flatten().map(Clause::from).collect();
+    reconstruction
+}
+
+/// Substitute equivalent literals.  The binary clauses of a formula
+/// are implications: (a b) means -a implies b, and -b implies a.  The
+/// literals in a strongly connected component of these implications
+/// are all equivalent, so each is replaced by the one with the lowest
+/// atom.  If a literal is equivalent to its negation, the formula is
+/// unsatisfiable, and is replaced with the empty clause.
+///
+/// Substitution can shorten clauses to binary ones, making more
+/// literals equivalent, so this is repeated until nothing changes.
+/// The returned 'Reconstruction' turns a model of the result into a
+/// model of the original.
+pub fn substitute_equivalent_literals(formula: &mut Formula) -> Reconstruction {
+    let mut reconstruction = Reconstruction::default();
+    for lit in formula.iter().flatten() {
+        reconstruction.variables.insert(lit.var());
+    }
+    let num_atoms = reconstruction
+        .variables
+        .iter()
+        .next_back()
+        .map_or(0, |var| var.index());
+
+    loop {
+        let components = implication_components(formula, num_atoms);
+
+        // atoms are visited in order, so the first literal seen in a
+        // component has the lowest atom.
+        let mut representatives = vec![None; 2 * num_atoms + 2];
+        for id in 1..=num_atoms {
+            let pos = Literal::new(id as isize);
+            if components[lit_index(pos)] == components[lit_index(pos.negate())] {
+                *formula = Formula::new(vec![Clause::from(Vec::new())]);
+                return reconstruction;
+            }
+            for lit in [pos, pos.negate()] {
+                representatives[components[lit_index(lit)]].get_or_insert(lit);
+            }
+        }
+        let substitute = |lit: Literal| representatives[components[lit_index(lit)]].unwrap();
+
+        let mut changed = false;
+        for id in 1..=num_atoms {
+            let pos = Literal::new(id as isize);
+            let representative = substitute(pos);
+            if representative != pos {
+                // the eliminated variable has the same value as its
+                // representative.
+                let equivalence = vec![
+                    Clause::from(vec![pos.negate(), representative]),
+                    Clause::from(vec![pos, representative.negate()]),
+                ];
+                reconstruction.eliminated.push((pos.var(), equivalence));
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+
+        let mut substituted = Formula::default();
+        for lits in formula.iter() {
+            let mut lits: Vec<Literal> = lits.iter().copied().map(substitute).collect();
+            lits.sort();
+            lits.dedup();
+            if !lits
+                .iter()
+                .any(|lit| lits.binary_search(&lit.negate()).is_ok())
+            {
+                substituted.insert_clause(Clause::from(lits));
+            }
+        }
+        *formula = substituted;
+    }
+
+    reconstruction
+}
+
+/// Number the strongly connected components of the implications of
+/// the binary clauses, by Tarjan's algorithm.  Returns the component
+/// of each literal, indexed by 'lit_index'.
+fn implication_components(formula: &Formula, num_atoms: usize) -> Vec<usize> {
+    let num_nodes = 2 * num_atoms + 2;
+    let mut edges = vec![Vec::new(); num_nodes];
+    for lits in formula.iter() {
+        if let [a, b] = lits[..] {
+            edges[lit_index(a.negate())].push(lit_index(b));
+            edges[lit_index(b.negate())].push(lit_index(a));
+        }
+    }
+
+    let mut index = vec![usize::MAX; num_nodes];
+    let mut lowlink = vec![0; num_nodes];
+    let mut on_stack = vec![false; num_nodes];
+    let mut stack = Vec::new();
+    let mut components = vec![usize::MAX; num_nodes];
+    let mut next_index = 0;
+    let mut num_components = 0;
+
+    // the recursion is done with an explicit stack of nodes and the
+    // next edge to follow, as implication chains can be very long.
+    for root in 2..num_nodes {
+        if index[root] != usize::MAX {
+            continue;
+        }
+        let mut calls = vec![(root, 0)];
+        while let Some((node, mut edge)) = calls.pop() {
+            if edge == 0 {
+                index[node] = next_index;
+                lowlink[node] = next_index;
+                next_index += 1;
+                stack.push(node);
+                on_stack[node] = true;
+            } else {
+                let child = edges[node][edge - 1];
+                lowlink[node] = lowlink[node].min(lowlink[child]);
+            }
+
+            let mut recursed = false;
+            while edge < edges[node].len() {
+                let child = edges[node][edge];
+                edge += 1;
+                if index[child] == usize::MAX {
+                    calls.push((node, edge));
+                    calls.push((child, 0));
+                    recursed = true;
+                    break;
+                } else if on_stack[child] {
+                    lowlink[node] = lowlink[node].min(index[child]);
+                }
+            }
+            if recursed {
+                continue;
+            }
+
+            if lowlink[node] == index[node] {
+                while let Some(member) = stack.pop() {
+                    on_stack[member] = false;
+                    components[member] = num_components;
+                    if member == node {
+                        break;
+                    }
+                }
+                num_components += 1;
+            }
+        }
+    }
+
+    components
+}
+
+/// The clauses which still contain a literal.
+fn live(
+    occurrences: &HashMap<Literal, Vec<usize>>,
+    database: &[Option<Vec<Literal>>],
+    lit: Literal,
+) -> Vec<usize> {
+    occurrences.get(&lit).map_or_else(Vec::new, |is| {
+        is.iter()
+            .copied()
+            .filter(|i| database[*i].is_some())
+            .collect()
+    })
+}
+
+/// Add variables to factor out repeated patterns, as in bounded
+/// variable addition (BVA): if every clause (l_i C_j) is in the
+/// formula, for literals l_1 ... l_m and clauses C_1 ... C_n, they are
+/// replaced with (l_i x) and (-x C_j) for a new variable x.  This
+/// replaces m*n clauses with m+n, and resolving on x gives the
+/// originals back.  Patterns are grown greedily from each literal,
+/// adding whichever literal keeps the most clauses, while that
+/// removes more clauses, and this is repeated until nothing changes.
+///
+/// The new variables are numbered after the largest in the formula.
+/// The result is satisfiable if and only if the original formula is,
+/// and the returned 'Reconstruction' turns a model of the result into
+/// a model of the original, by dropping the new variables.
+pub fn add_variables(formula: &mut Formula) -> Reconstruction {
+    let mut reconstruction = Reconstruction::default();
+
+    let mut database: Vec<Option<Vec<Literal>>> = Vec::with_capacity(formula.len());
+    let mut indexes: HashMap<Vec<Literal>, usize> = HashMap::new();
+    for lits in formula.iter() {
+        let mut lits = lits.to_vec();
+        lits.sort();
+        lits.dedup();
+        for lit in &lits {
+            reconstruction.variables.insert(lit.var());
+        }
+        let tautology = lits
+            .iter()
+            .any(|lit| lits.binary_search(&lit.negate()).is_ok());
+        if !tautology && !indexes.contains_key(&lits) {
+            indexes.insert(lits.clone(), database.len());
+            database.push(Some(lits));
+        }
+    }
+    let mut occurrences: HashMap<Literal, Vec<usize>> = HashMap::new();
+    for (i, lits) in database.iter().enumerate() {
+        for lit in lits.iter().flatten() {
+            occurrences.entry(*lit).or_default().push(i);
+        }
+    }
+    let mut next_var = reconstruction
+        .variables
+        .iter()
+        .next_back()
+        .map_or(1, |var| var.index() + 1);
+
+    loop {
+        let mut candidates: Vec<(usize, Literal)> = occurrences
+            .keys()
+            .map(|lit| (live(&occurrences, &database, *lit).len(), *lit))
+            .collect();
+        candidates.sort_unstable_by(|a, b| b.cmp(a));
+
+        let mut changed = false;
+        for (_, lit) in candidates {
+            let (lits, rests) = match factor(&occurrences, &database, &indexes, lit) {
+                Some(pattern) => pattern,
+                None => continue,
+            };
+
+            let var = Var::new(next_var);
+            next_var += 1;
+            reconstruction.added.insert(var);
+            let mut added = Vec::new();
+            for l in &lits {
+                for rest in &rests {
+                    let mut lits = rest.clone();
+                    lits.push(*l);
+                    lits.sort();
+                    if let Some(i) = indexes.remove(&lits) {
+                        database[i] = None;
+                    }
+                }
+                let mut clause = vec![*l, var.positive()];
+                clause.sort();
+                added.push(clause);
+            }
+            for rest in rests {
+                let mut clause = rest;
+                clause.push(var.negative());
+                clause.sort();
+                added.push(clause);
+            }
+            for clause in added {
+                for lit in &clause {
+                    occurrences.entry(*lit).or_default().push(database.len());
+                }
+                indexes.insert(clause.clone(), database.len());
+                database.push(Some(clause));
+            }
+            changed = true;
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    *formula = database.into_iter().flatten().map(Clause::from).collect();
+    reconstruction
+}
+
+/// Find a pattern to factor out from the clauses containing a
+/// literal: the literals l_1 ... l_m, including the given one, and the
+/// clauses C_1 ... C_n such that every (l_i C_j) is live.  Returns
+/// 'None' if there's no pattern which would remove any clauses.
+fn factor(
+    occurrences: &HashMap<Literal, Vec<usize>>,
+    database: &[Option<Vec<Literal>>],
+    indexes: &HashMap<Vec<Literal>, usize>,
+    lit: Literal,
+) -> Option<(Vec<Literal>, Vec<Vec<Literal>>)> {
+    let mut lits = vec![lit];
+    let mut rests: Vec<Vec<Literal>> = live(occurrences, database, lit)
+        .into_iter()
+        .map(|i| {
+            let mut rest = database[i].clone().unwrap();
+            rest.retain(|l| *l != lit);
+            rest
+        })
+        .filter(|rest| !rest.is_empty())
+        .collect();
+    let reduction = |m: usize, n: usize| (m * n) as isize - (m + n) as isize;
+
+    loop {
+        // the literals l such that (l C) is live for each C, taking
+        // the candidates from the clauses with the least common
+        // literal of C.
+        let mut matches: BTreeMap<Literal, Vec<usize>> = BTreeMap::new();
+        for (k, rest) in rests.iter().enumerate() {
+            let rarest = rest
+                .iter()
+                .min_by_key(|l| occurrences.get(l).map_or(0, Vec::len))
+                .unwrap();
+            for i in live(occurrences, database, *rarest) {
+                let clause = database[i].as_ref().unwrap();
+                if clause.len() != rest.len() + 1 {
+                    continue;
+                }
+                let mut extra = clause.iter().filter(|l| rest.binary_search(l).is_err());
+                if let (Some(l), None) = (extra.next(), extra.next()) {
+                    if !lits.contains(l) {
+                        let mut lits = rest.clone();
+                        lits.push(*l);
+                        lits.sort();
+                        if indexes.contains_key(&lits) {
+                            matches.entry(*l).or_default().push(k);
+                        }
+                    }
+                }
+            }
+        }
+
+        let best = matches
+            .into_iter()
+            .map(|(l, mut ks)| {
+                ks.dedup();
+                (l, ks)
+            })
+            .max_by_key(|(l, ks)| (ks.len(), Reverse(*l)));
+        match best {
+            Some((l, ks))
+                if reduction(lits.len() + 1, ks.len()) > reduction(lits.len(), rests.len()) =>
+            {
+                lits.push(l);
+                rests = ks.into_iter().map(|k| mem::take(&mut rests[k])).collect();
+            }
+            _ => break,
+        }
+    }
+
+    if lits.len() > 1 && reduction(lits.len(), rests.len()) > 0 {
+        Some((lits, rests))
+    } else {
+        None
+    }
+}
+
+/// Find the clauses defining a literal as the and of some others:
+/// o = a && b && ... is the clauses (-o a), (-o b), ..., and
+/// (o -a -b ...).  Takes the live clauses containing the literal and
+/// its negation, and returns the defining clauses of each.
+fn gate_clauses(
+    database: &[Option<Vec<Literal>>],
+    pos: &[usize],
+    neg: &[usize],
+    output: Literal,
+) -> Option<(Vec<usize>, Vec<usize>)> {
+    let implied: HashMap<Literal, usize> = neg
+        .iter()
+        .filter_map(|n| match database[*n].as_deref() {
+            Some([a, b]) => Some((if *a == output.negate() { *b } else { *a }, *n)),
+            _ => None,
+        })
+        .collect();
+
+    pos.iter().find_map(|p| {
+        let mut gn = Vec::new();
+        for lit in database[*p].as_ref().unwrap() {
+            if *lit != output {
+                gn.push(*implied.get(&lit.negate())?);
+            }
+        }
+        gn.sort_unstable();
+        Some((vec![*p], gn))
+    })
+}
+
+/// Resolve two sorted clauses on a variable, which must occur
+/// positively in the first and negatively in the second.  Returns
+/// 'None' if the resolvent is a tautology.
+fn resolve(pos: &[Literal], neg: &[Literal], var: Var) -> Option<Vec<Literal>> {
+    let mut resolvent: Vec<Literal> = pos
+        .iter()
+        .chain(neg)
+        .copied()
+        .filter(|lit| lit.var() != var)
+        .collect();
+    resolvent.sort();
+    resolvent.dedup();
+    if resolvent
+        .iter()
+        .any(|lit| resolvent.binary_search(&lit.negate()).is_ok())
+    {
+        None
+    } else {
+        Some(resolvent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cnf::*;
+    use crate::testing::*;
+    use crate::{sat, sat_assignment};
+
+    #[test]
+    fn removes_subsumed_clauses() {
+        let mut formula = Formula::new(vec![
+            Clause::new(vec![1, 2, 3]),
+            Clause::new(vec![2, 1]),
+            Clause::new(vec![-1, 3]),
+            Clause::new(vec![2, 1, 4]),
+        ]);
+        let stats = simplify(&mut formula);
+
+        assert_eq!(
+            Formula::new(vec![Clause::new(vec![1, 2]), Clause::new(vec![-1, 3])]),
+            formula
+        );
+        assert_eq!(2, stats.subsumed_clauses);
+        assert_eq!(0, stats.strengthened_literals);
+    }
+
+    #[test]
+    fn removes_tautologies() {
+        // (-1 1) must not strengthen (1 2) to (2).
+        let mut formula = Formula::new(vec![Clause::new(vec![-1, 1]), Clause::new(vec![1, 2])]);
+        let stats = simplify(&mut formula);
+
+        assert_eq!(Formula::new(vec![Clause::new(vec![1, 2])]), formula);
+        assert_eq!(1, stats.tautologies);
+    }
+
+    #[test]
+    fn strengthens_by_self_subsuming_resolution() {
+        // (1 2) strengthens (-1 2 3) to (2 3), and then (1 -2) and
+        // (1 2) resolve to (1), which subsumes both.
+        let mut formula = Formula::new(vec![
+            Clause::new(vec![1, 2]),
+            Clause::new(vec![-1, 2, 3]),
+            Clause::new(vec![1, -2]),
+        ]);
+        simplify(&mut formula);
+
+        assert_eq!(
+            Formula::new(vec![Clause::new(vec![2, 3]), Clause::new(vec![1])]),
+            formula
+        );
+    }
+
+    #[test]
+    fn preserves_unsatisfiability() {
+        let mut formula = Formula::new(vec![
+            Clause::new(vec![1, 2]),
+            Clause::new(vec![1, -2]),
+            Clause::new(vec![-1, 2]),
+            Clause::new(vec![-1, -2]),
+        ]);
+        simplify(&mut formula);
+
+        assert_eq!(Formula::new(vec![Clause::new(vec![])]), formula);
+    }
+
+    #[test]
+    fn substitutes_equivalent_literals() {
+        // 1 and 2 are equivalent, and 3 is their negation.
+        let original = Formula::new(vec![
+            Clause::new(vec![-1, 2]),
+            Clause::new(vec![1, -2]),
+            Clause::new(vec![2, 3]),
+            Clause::new(vec![-2, -3]),
+            Clause::new(vec![3, 4, 5]),
+        ]);
+        let mut formula = original.clone();
+        let reconstruction = substitute_equivalent_literals(&mut formula);
+
+        assert_eq!(2, reconstruction.eliminated());
+        assert_eq!(Formula::new(vec![Clause::new(vec![-1, 4, 5])]), formula);
+
+        let model = reconstruction.extend(&[Literal::new(-1), Literal::new(4), Literal::new(-5)]);
+        for lits in original.iter() {
+            assert!(lits.iter().any(|lit| model.contains(lit)));
+        }
+
+        let mut formula = Formula::new(vec![
+            Clause::new(vec![1, 2]),
+            Clause::new(vec![-1, -2]),
+            Clause::new(vec![1, -2]),
+            Clause::new(vec![-1, 2]),
+        ]);
+        substitute_equivalent_literals(&mut formula);
+        assert_eq!(Formula::new(vec![Clause::new(vec![])]), formula);
+    }
+
+    #[test]
+    fn eliminates_variables_and_reconstructs_models() {
+        // eliminating 2 replaces (1 2) (-2 3) with (1 3), and then
+        // every other variable is pure.
+        let original = Formula::new(vec![
+            Clause::new(vec![1, 2]),
+            Clause::new(vec![-2, 3]),
+            Clause::new(vec![-1, -3, 4]),
+        ]);
+        let mut formula = original.clone();
+        let reconstruction = eliminate_variables(&mut formula);
+
+        assert_eq!(4, reconstruction.eliminated());
+        assert_eq!(Formula::new(vec![]), formula);
+
+        let model = reconstruction.extend(&[]);
+        assert_eq!(4, model.len());
+        for lits in original.iter() {
+            assert!(lits.iter().any(|lit| model.contains(lit)));
+        }
+    }
+
+    #[test]
+    fn eliminates_gate_outputs() {
+        // 3 = 1 && 2, used by (3 4) and (-3 5): eliminating 3 only
+        // needs the resolvents (1 4), (2 4), and (-1 -2 5).
+        let formula = Formula::new(vec![
+            Clause::new(vec![-3, 1]),
+            Clause::new(vec![-3, 2]),
+            Clause::new(vec![3, -1, -2]),
+            Clause::new(vec![3, 4]),
+            Clause::new(vec![-3, 5]),
+        ]);
+        let database: Vec<Option<Vec<Literal>>> = formula
+            .iter()
+            .map(|lits| {
+                let mut lits = lits.to_vec();
+                lits.sort();
+                Some(lits)
+            })
+            .collect();
+        assert_eq!(
+            Some((vec![2], vec![0, 1])),
+            gate_clauses(&database, &[2, 3], &[0, 1, 4], Literal::new(3))
+        );
+        assert_eq!(
+            None,
+            gate_clauses(&database, &[0, 1, 4], &[2, 3], Literal::new(-3))
+        );
+
+        let mut simplified = formula.clone();
+        let reconstruction = eliminate_variables(&mut simplified);
+        let model = reconstruction.extend(&[]);
+        for lits in formula.iter() {
+            assert!(lits.iter().any(|lit| model.contains(lit)));
+        }
+    }
+
+    #[test]
+    fn adds_variables_to_factor_out_patterns() {
+        // every clause (a b) with a in {1, 2} and b in {3, 4, 5}, which
+        // becomes (a 6) and (-6 b).
+        let original = Formula::new(vec![
+            Clause::new(vec![1, 3]),
+            Clause::new(vec![1, 4]),
+            Clause::new(vec![1, 5]),
+            Clause::new(vec![2, 3]),
+            Clause::new(vec![2, 4]),
+            Clause::new(vec![2, 5]),
+            Clause::new(vec![-1, -3]),
+        ]);
+        let mut formula = original.clone();
+        let reconstruction = add_variables(&mut formula);
+
+        assert_eq!(1, reconstruction.added());
+        assert_eq!(
+            Formula::new(vec![
+                Clause::new(vec![-3, -1]),
+                Clause::new(vec![2, 6]),
+                Clause::new(vec![1, 6]),
+                Clause::new(vec![-6, 3]),
+                Clause::new(vec![-6, 4]),
+                Clause::new(vec![-6, 5]),
+            ]),
+            formula
+        );
+
+        let model = reconstruction.extend(&[
+            Literal::new(-1),
+            Literal::new(2),
+            Literal::new(3),
+            Literal::new(4),
+            Literal::new(5),
+            Literal::new(6),
+        ]);
+        assert_eq!(5, model.len());
+        for lits in original.iter() {
+            assert!(lits.iter().any(|lit| model.contains(lit)));
+        }
+    }
+
+    #[test]
+    fn substitution_agrees_with_brute_force() {
+        for (num_vars, formula) in random_formulae(0x2545_f491_4f6c_dd1d, 300) {
+            let brute_force = brute_force_sat(num_vars, &formula, &[]);
+            let mut substituted = formula.clone();
+            let equivalences = substitute_equivalent_literals(&mut substituted);
+            match sat_assignment(substituted) {
+                Some(lits) => {
+                    let lits = equivalences.extend(&lits);
+                    assert!(brute_force, "{} is unsat but got {:?}", formula, lits);
+                    assert!(is_model(&formula, &lits));
+                }
+                None => assert!(!brute_force, "{} is sat", formula),
+            }
+        }
+    }
+
+    #[test]
+    fn adding_variables_agrees_with_brute_force() {
+        for (num_vars, formula) in random_formulae(0x2545_f491_4f6c_dd1d, 300) {
+            let brute_force = brute_force_sat(num_vars, &formula, &[]);
+            let mut factored = formula.clone();
+            let additions = add_variables(&mut factored);
+            match sat_assignment(factored) {
+                Some(lits) => {
+                    let lits = additions.extend(&lits);
+                    assert!(brute_force, "{} is unsat but got {:?}", formula, lits);
+                    assert!(is_model(&formula, &lits));
+                    assert!(lits.iter().all(|lit| lit.var().index() <= num_vars));
+                }
+                None => assert!(!brute_force, "{} is sat", formula),
+            }
+        }
+    }
+
+    #[test]
+    fn simplifying_agrees_with_brute_force() {
+        for (num_vars, formula) in random_formulae(0x2545_f491_4f6c_dd1d, 300) {
+            let brute_force = brute_force_sat(num_vars, &formula, &[]);
+            let mut simplified = formula.clone();
+            simplify(&mut simplified);
+            assert_eq!(brute_force, sat(simplified), "simplifying {}", formula);
+        }
+    }
+}
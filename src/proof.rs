@@ -0,0 +1,334 @@
+//! Proofs of unsatisfiability.
+//!
+//! A proof starts from some premises (the clauses of the formula) and
+//! derives new clauses, ending with the empty clause.  Each derived
+//! clause comes from a chain of resolutions: the first antecedent is
+//! resolved with the second, the result with the third, and so on.
+
+pub mod drat;
+
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+
+use crate::cnf::{Clause, Literal};
+use crate::parse::binary;
+
+/// A resolution proof.  Clauses are identified by number: the
+/// premises come first, followed by the derived clauses in order.
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd, Eq, Ord)]
+pub struct Proof {
+    premises: Vec<Clause>,
+    steps: Vec<Step>,
+}
+
+/// A derived clause, and the clauses it is resolved from.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub struct Step {
+    clause: Clause,
+    antecedents: Vec<usize>,
+}
+
+impl Step {
+    /// The derived clause.
+    pub fn clause(&self) -> &Clause {
+        &self.clause
+    }
+
+    /// The clauses resolved, in order, to derive this one.
+    pub fn antecedents(&self) -> &[usize] {
+        &self.antecedents
+    }
+
+    /// The antecedents in the order they become unit when the clause
+    /// is negated, ending with the one which becomes false.  The
+    /// last resolutions in a chain are with clauses assigned earlier,
+    /// so this is the reverse of the resolution order.
+    pub fn hints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.antecedents.iter().rev().copied()
+    }
+
+    /// The resolutions making up this step: the pivot atom, and the
+    /// clause resolved with at that point.  Each resolution removes
+    /// the pivot from the clause derived so far, starting from the
+    /// first antecedent.
+    pub fn resolutions<'a>(
+        &'a self,
+        proof: &'a Proof,
+    ) -> impl Iterator<Item = (isize, usize)> + 'a {
+        let mut lits: BTreeSet<Literal> = match self.antecedents.first() {
+            Some(first) => proof.clause(*first).literals().iter().copied().collect(),
+            None => BTreeSet::new(),
+        };
+        self.antecedents.iter().skip(1).map(move |id| {
+            let other = proof.clause(*id).literals();
+            let pivot = other
+                .iter()
+                .find(|lit| lits.contains(&lit.negate()))
+                .map_or(0, |lit| lit.get_id());
+            for lit in other {
+                if lit.get_id() == pivot {
+                    lits.remove(&lit.negate());
+                } else {
+                    lits.insert(*lit);
+                }
+            }
+            (pivot, *id)
+        })
+    }
+}
+
+impl Proof {
+    /// Construct a proof with no steps.
+    pub(crate) fn new(premises: Vec<Clause>) -> Proof {
+        Proof {
+            premises,
+            steps: Vec::new(),
+        }
+    }
+
+    /// Add a derived clause, returning its number.
+    pub(crate) fn derive(&mut self, clause: Clause, antecedents: Vec<usize>) -> usize {
+        self.steps.push(Step {
+            clause,
+            antecedents,
+        });
+        self.premises.len() + self.steps.len() - 1
+    }
+
+    /// The clauses the proof starts from.
+    pub fn premises(&self) -> &[Clause] {
+        &self.premises
+    }
+
+    /// The derived clauses, with their numbers.
+    pub fn steps(&self) -> impl Iterator<Item = (usize, &Step)> + '_ {
+        let offset = self.premises.len();
+        self.steps
+            .iter()
+            .enumerate()
+            .map(move |(i, s)| (i + offset, s))
+    }
+
+    /// Get a clause by number.
+    pub fn clause(&self, id: usize) -> &Clause {
+        match id.checked_sub(self.premises.len()) {
+            Some(i) => &self.steps[i].clause,
+            None => &self.premises[id],
+        }
+    }
+
+    /// Check if the proof derives the empty clause.
+    pub fn is_complete(&self) -> bool {
+        self.steps.last().is_some_and(|s| s.clause.is_empty())
+    }
+
+    /// The numbers of the premises used by the last step, directly or
+    /// indirectly.  For a complete proof these form an unsatisfiable
+    /// core of the formula.
+    pub fn core(&self) -> Vec<usize> {
+        let mut used = BTreeSet::new();
+        let mut todo: Vec<usize> = self.steps().last().map(|(id, _)| id).into_iter().collect();
+        while let Some(id) = todo.pop() {
+            if !used.insert(id) {
+                continue;
+            }
+            if let Some(i) = id.checked_sub(self.premises.len()) {
+                todo.extend(&self.steps[i].antecedents);
+            }
+        }
+        used.into_iter()
+            .take_while(|id| *id < self.premises.len())
+            .collect()
+    }
+
+    /// Write the derived clauses in DRAT format, which most proof
+    /// checkers accept.
+    pub fn write_drat<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for step in &self.steps {
+            for lit in step.clause.literals() {
+                write!(writer, "{} ", lit)?;
+            }
+            writeln!(writer, "0")?;
+        }
+        writer.flush()
+    }
+
+    /// Write the derived clauses in the binary DRAT format, which is
+    /// smaller and faster for drat-trim to read.
+    pub fn write_binary_drat<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for step in &self.steps {
+            binary::write_clause(&mut writer, step.clause.literals())?;
+        }
+        writer.flush()
+    }
+
+    /// Write the derived clauses in LRAT format, where each clause
+    /// comes with hints saying which clauses become unit, in order,
+    /// when it is negated.  This is much faster to check than DRAT.
+    /// The premises are numbered from 1, in order.
+    pub fn write_lrat<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for (id, step) in self.steps() {
+            write!(writer, "{} ", id + 1)?;
+            for lit in step.clause.literals() {
+                write!(writer, "{} ", lit)?;
+            }
+            write!(writer, "0 ")?;
+            for hint in step.hints() {
+                write!(writer, "{} ", hint + 1)?;
+            }
+            writeln!(writer, "0")?;
+        }
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cnf::*;
+    use crate::dpll::{Config, SolveResult};
+    use crate::rng::Rng;
+    use crate::solver::Solver;
+    use crate::testing::random_formula;
+    use crate::theory::empty::Empty;
+
+    /// Check every step of a proof by carrying out its resolutions.
+    fn check(proof: &Proof) {
+        for (id, step) in proof.steps() {
+            let mut lits: BTreeSet<Literal> = proof
+                .clause(step.antecedents()[0])
+                .literals()
+                .iter()
+                .copied()
+                .collect();
+            for (pivot, other) in step.resolutions(proof) {
+                assert_ne!(0, pivot, "step {} has no pivot with {}", id, other);
+                for lit in proof.clause(other).literals() {
+                    if lit.get_id() == pivot {
+                        assert!(lits.remove(&lit.negate()));
+                    } else {
+                        lits.insert(*lit);
+                    }
+                }
+            }
+            let expected: BTreeSet<Literal> = step.clause().literals().iter().copied().collect();
+            assert_eq!(expected, lits, "step {}", id);
+        }
+        assert!(proof.is_complete());
+        check_hints(proof);
+    }
+
+    /// Check the LRAT hints of every step by unit propagation.
+    fn check_hints(proof: &Proof) {
+        for (id, step) in proof.steps() {
+            let mut falsified: BTreeSet<Literal> =
+                step.clause().literals().iter().copied().collect();
+            let mut conflict = false;
+            for hint in step.hints() {
+                assert!(!conflict, "step {} has hints after a conflict", id);
+                let mut open = proof
+                    .clause(hint)
+                    .literals()
+                    .iter()
+                    .filter(|l| !falsified.contains(l));
+                match (open.next(), open.next()) {
+                    (None, _) => conflict = true,
+                    (Some(lit), None) => {
+                        falsified.insert(lit.negate());
+                    }
+                    _ => panic!("step {} hint {} is not unit", id, hint),
+                }
+            }
+            assert!(conflict, "step {} has no conflict", id);
+        }
+    }
+
+    #[test]
+    fn proves_unsatisfiability() {
+        let formula = Formula::new(vec![
+            Clause::new(vec![1, 2]),
+            Clause::new(vec![1, -2]),
+            Clause::new(vec![-1, 2, 3]),
+            Clause::new(vec![-1, 2, -3]),
+            Clause::new(vec![-1, -2]),
+            Clause::new(vec![4, 5]),
+        ]);
+        let mut solver = Solver::new(Empty::new(), formula);
+        solver.record_proof(true);
+        assert_eq!(Ok(SolveResult::Unsat), solver.solve());
+
+        let proof = solver.proof().unwrap();
+        check(proof);
+        assert_eq!(vec![0, 1, 2, 3, 4], proof.core());
+
+        let mut drat = Vec::new();
+        proof.write_drat(&mut drat).unwrap();
+        assert!(String::from_utf8(drat).unwrap().ends_with("\n0\n"));
+
+        let mut lrat = Vec::new();
+        proof.write_lrat(&mut lrat).unwrap();
+        let lrat = String::from_utf8(lrat).unwrap();
+        assert!(lrat.starts_with("7 "));
+        assert!(lrat.lines().last().unwrap().contains(" 0 "));
+
+        let mut binary = Vec::new();
+        proof.write_binary_drat(&mut binary).unwrap();
+        let derived: Vec<Clause> = proof
+            .steps()
+            .map(|(_, step)| step.clause().clone())
+            .collect();
+        assert_eq!(Ok(Formula::new(derived)), binary::from_reader(&binary[..]));
+    }
+
+    #[test]
+    fn proves_pigeonhole() {
+        // n+1 pigeons in n holes: pigeon p is in hole h if p*n+h.
+        let mut minimized = 0;
+        for n in 2..=5 {
+            let mut clauses = Vec::new();
+            for p in 0..=n {
+                clauses.push(Clause::new((1..=n).map(|h| p * n + h).collect()));
+            }
+            for h in 1..=n {
+                for p in 0..=n {
+                    for q in p + 1..=n {
+                        clauses.push(Clause::new(vec![-(p * n + h), -(q * n + h)]));
+                    }
+                }
+            }
+            for config in &[
+                Config::default(),
+                Config {
+                    random_decisions: 0.5,
+                    ..Config::default()
+                },
+            ] {
+                let mut solver = Solver::new(Empty::new(), Formula::new(clauses.clone()));
+                solver.set_config(*config);
+                solver.record_proof(true);
+                assert_eq!(Ok(SolveResult::Unsat), solver.solve());
+                check(solver.proof().unwrap());
+                minimized += solver.stats().minimized_literals;
+            }
+        }
+        // minimizing learned clauses adds resolutions too.
+        assert!(minimized > 0);
+    }
+
+    #[test]
+    fn proves_random_formulae() {
+        let mut rng = Rng::new(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..200 {
+            let num_vars = 1 + rng.below(6);
+            let formula = random_formula(&mut rng, num_vars, 5 * num_vars, 3);
+
+            let mut solver = Solver::new(Empty::new(), formula);
+            solver.record_proof(true);
+            match solver.solve().unwrap() {
+                SolveResult::Sat(_) => assert!(solver.proof().is_none()),
+                SolveResult::Unsat => check(solver.proof().unwrap()),
+                SolveResult::Unknown => unreachable!(),
+            }
+        }
+    }
+}
@@ -0,0 +1,419 @@
+//! Parsing and checking DRAT proofs, like those written by
+//! 'Proof::write_drat'.
+//!
+//! A DRAT proof is a list of lemmas, each adding or deleting a clause.
+//! Each added clause must be RUP (unit propagation on the clauses so
+//! far and its negation gives a conflict) or RAT (every resolvent on
+//! its first literal is RUP).  The proof is valid if, at the end, unit
+//! propagation alone gives a conflict.  Lemmas are checked forwards,
+//! in order.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::BufRead;
+use std::mem;
+
+use crate::cnf::{Clause, Formula, Literal};
+use crate::parse::{offset, Position};
+
+/// A step of a DRAT proof.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub enum Lemma {
+    /// Add a clause, which must be implied by the clauses so far.
+    Add(Clause),
+    /// Delete a clause, which doesn't need checking.
+    Delete(Clause),
+}
+
+/// Parse a proof in DRAT format.
+pub fn from_string(drat: String) -> Result<Vec<Lemma>, ParseError> {
+    let mut parser = Parser::default();
+    for line in drat.lines() {
+        parser.line(line)?;
+    }
+    parser.finish()
+}
+
+/// Parse DRAT format from a reader, a line at a time.
+pub fn from_reader<R: BufRead>(mut reader: R) -> Result<Vec<Lemma>, ParseError> {
+    let mut parser = Parser::default();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => parser.line(&line)?,
+            Err(e) => return Err(ParseError::ReadError(e.to_string())),
+        }
+    }
+    parser.finish()
+}
+
+/// The state of parsing, between lines.
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd, Eq, Ord)]
+struct Parser {
+    /// The number of the last line parsed, from 1.
+    line_number: usize,
+    /// Whether the current lemma is a deletion, if in the middle of
+    /// one.
+    deletion: Option<bool>,
+    lits: Vec<isize>,
+    lemmas: Vec<Lemma>,
+}
+
+impl Parser {
+    fn line(&mut self, line: &str) -> Result<(), ParseError> {
+        self.line_number += 1;
+        let mut words = line.split_ascii_whitespace().peekable();
+        if self.deletion.is_none() && words.peek() == Some(&"c") {
+            return Ok(());
+        }
+
+        for word in words {
+            if word == "d" && self.deletion.is_none() {
+                self.deletion = Some(true);
+                continue;
+            }
+            match word.parse::<isize>() {
+                Ok(0) => {
+                    // the first literal is the pivot of a RAT lemma, so
+                    // the literals are kept in the order written.
+                    let lits = mem::take(&mut self.lits);
+                    let clause = Clause::unnormalized(lits.into_iter().map(Literal::new).collect());
+                    self.lemmas.push(if self.deletion == Some(true) {
+                        Lemma::Delete(clause)
+                    } else {
+                        Lemma::Add(clause)
+                    });
+                    self.deletion = None;
+                }
+                Ok(n) => {
+                    self.deletion.get_or_insert(false);
+                    self.lits.push(n);
+                }
+                Err(_) => {
+                    let at = Position::new(self.line_number, line, offset(line, word));
+                    return Err(ParseError::CannotParseLiteral(at));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Vec<Lemma>, ParseError> {
+        if self.deletion.is_some() {
+            Err(ParseError::UnterminatedLemma)
+        } else {
+            Ok(self.lemmas)
+        }
+    }
+}
+
+/// A parser error.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub enum ParseError {
+    CannotParseLiteral(Position),
+    UnterminatedLemma,
+    ReadError(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::CannotParseLiteral(at) => write!(f, "cannot parse literal at {}", at),
+            ParseError::UnterminatedLemma => write!(f, "last lemma has no terminating 0"),
+            ParseError::ReadError(s) => write!(f, "cannot read input: {}", s),
+        }
+    }
+}
+
+/// Check a DRAT proof that a formula is unsatisfiable.
+pub fn check(formula: &Formula, lemmas: &[Lemma]) -> Result<(), CheckError> {
+    let mut checker = Checker::default();
+    for lits in formula.iter() {
+        checker.add(lits);
+    }
+    for (i, lemma) in lemmas.iter().enumerate() {
+        match lemma {
+            Lemma::Add(clause) => {
+                let lits = clause.literals();
+                if !checker.is_rup(lits) && !checker.is_rat(lits) {
+                    return Err(CheckError::NotImplied(i));
+                }
+                checker.add(lits);
+            }
+            Lemma::Delete(clause) => checker.delete(clause.literals()),
+        }
+    }
+    if checker.is_rup(&[]) {
+        Ok(())
+    } else {
+        Err(CheckError::NotRefuted)
+    }
+}
+
+/// A reason a proof is invalid.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub enum CheckError {
+    /// The lemma at this index, from 0, is neither RUP nor RAT.
+    NotImplied(usize),
+    /// Unit propagation doesn't give a conflict after the last lemma.
+    NotRefuted,
+}
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheckError::NotImplied(i) => write!(f, "lemma {} is neither RUP nor RAT", i + 1),
+            CheckError::NotRefuted => write!(f, "proof doesn't derive the empty clause"),
+        }
+    }
+}
+
+impl Error for CheckError {}
+
+/// A clause database with two watched literals, for unit propagation.
+#[derive(Clone, Debug, Default)]
+struct Checker {
+    /// The literals of each clause, sorted and without duplicates,
+    /// with the watched literals first.
+    clauses: Vec<Vec<Literal>>,
+    deleted: Vec<bool>,
+    /// The clauses watching each literal, indexed by 'code'.  Deleted
+    /// clauses are dropped when they're found.
+    watches: Vec<Vec<usize>>,
+    /// The live clauses with each set of literals, for deletion.
+    ids: HashMap<Vec<Literal>, Vec<usize>>,
+    /// The clauses with one literal, which aren't watched.
+    units: Vec<usize>,
+    /// The number of live empty clauses.
+    empty: usize,
+    /// The value of each atom, indexed by atom.
+    values: Vec<Option<bool>>,
+    trail: Vec<Literal>,
+}
+
+impl Checker {
+    fn add(&mut self, lits: &[Literal]) {
+        let key = normal(lits);
+        let id = self.clauses.len();
+        self.reserve(&key);
+        match key.len() {
+            0 => self.empty += 1,
+            1 => self.units.push(id),
+            _ => {
+                self.watches[code(key[0])].push(id);
+                self.watches[code(key[1])].push(id);
+            }
+        }
+        self.clauses.push(key.clone());
+        self.deleted.push(false);
+        self.ids.entry(key).or_default().push(id);
+    }
+
+    /// Make room for the atoms of some literals.
+    fn reserve(&mut self, lits: &[Literal]) {
+        if let Some(var) = lits.iter().map(|lit| lit.var().index()).max() {
+            if self.values.len() <= var {
+                self.values.resize(var + 1, None);
+                self.watches.resize(2 * var + 2, Vec::new());
+            }
+        }
+    }
+
+    /// Delete a clause, if there is one with these literals.
+    fn delete(&mut self, lits: &[Literal]) {
+        if let Some(id) = self.ids.get_mut(&normal(lits)).and_then(Vec::pop) {
+            self.deleted[id] = true;
+            if self.clauses[id].is_empty() {
+                self.empty -= 1;
+            }
+        }
+    }
+
+    /// Check if unit propagation on the negation of a clause gives a
+    /// conflict.
+    fn is_rup(&mut self, lits: &[Literal]) -> bool {
+        let conflict = self.empty > 0 || self.refute(lits);
+        for lit in self.trail.drain(..) {
+            self.values[lit.var().index()] = None;
+        }
+        conflict
+    }
+
+    /// Check if every resolvent of a clause on its first literal, with
+    /// a clause containing the negation, is RUP.
+    fn is_rat(&mut self, lits: &[Literal]) -> bool {
+        let pivot = match lits.first() {
+            Some(lit) => lit.negate(),
+            None => return false,
+        };
+        let others: Vec<usize> = (0..self.clauses.len())
+            .filter(|id| !self.deleted[*id] && self.clauses[*id].contains(&pivot))
+            .collect();
+        others.into_iter().all(|id| {
+            let mut resolvent = lits.to_vec();
+            resolvent.extend(self.clauses[id].iter().filter(|lit| **lit != pivot));
+            self.is_rup(&resolvent)
+        })
+    }
+
+    /// Assign the negation of a clause and the units, and propagate.
+    /// Returns true on a conflict.
+    fn refute(&mut self, lits: &[Literal]) -> bool {
+        self.reserve(lits);
+        let negated = lits.iter().map(|lit| lit.negate());
+        let units: Vec<Literal> = self
+            .units
+            .iter()
+            .filter(|id| !self.deleted[**id])
+            .map(|id| self.clauses[*id][0])
+            .collect();
+        for lit in negated.chain(units) {
+            match self.value(lit) {
+                Some(true) => (),
+                Some(false) => return true,
+                None => self.assign(lit),
+            }
+        }
+        self.propagate()
+    }
+
+    /// Propagate the trail.  Returns true on a conflict.
+    fn propagate(&mut self) -> bool {
+        let mut next = 0;
+        while next < self.trail.len() {
+            let falsified = self.trail[next].negate();
+            next += 1;
+
+            let mut watchers = mem::take(&mut self.watches[code(falsified)]);
+            let mut conflict = false;
+            let mut i = 0;
+            while i < watchers.len() {
+                let id = watchers[i];
+                if self.deleted[id] {
+                    watchers.swap_remove(i);
+                    continue;
+                }
+                if self.clauses[id][0] == falsified {
+                    self.clauses[id].swap(0, 1);
+                }
+                let other = self.clauses[id][0];
+                if self.value(other) == Some(true) {
+                    i += 1;
+                    continue;
+                }
+                let replacement = (2..self.clauses[id].len())
+                    .find(|k| self.value(self.clauses[id][*k]) != Some(false));
+                if let Some(k) = replacement {
+                    self.clauses[id].swap(1, k);
+                    self.watches[code(self.clauses[id][1])].push(id);
+                    watchers.swap_remove(i);
+                    continue;
+                }
+                if self.value(other) == Some(false) {
+                    conflict = true;
+                    break;
+                }
+                self.assign(other);
+                i += 1;
+            }
+            self.watches[code(falsified)] = watchers;
+            if conflict {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn value(&self, lit: Literal) -> Option<bool> {
+        self.values[lit.var().index()].map(|value| value != lit.is_negated())
+    }
+
+    fn assign(&mut self, lit: Literal) {
+        self.values[lit.var().index()] = Some(!lit.is_negated());
+        self.trail.push(lit);
+    }
+}
+
+/// The index of a literal in the watch lists.
+fn code(lit: Literal) -> usize {
+    2 * lit.var().index() + usize::from(lit.is_negated())
+}
+
+/// The literals of a clause, sorted and without duplicates.
+fn normal(lits: &[Literal]) -> Vec<Literal> {
+    let mut lits = lits.to_vec();
+    lits.sort();
+    lits.dedup();
+    lits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dpll::SolveResult;
+    use crate::solver::Solver;
+    use crate::theory::empty::Empty;
+
+    #[test]
+    fn checks_solver_proofs() {
+        // 4 pigeons in 3 holes: pigeon p is in hole h if p*3+h.
+        let mut clauses = Vec::new();
+        for p in 0..4 {
+            clauses.push(Clause::new((1..=3).map(|h| p * 3 + h).collect()));
+        }
+        for h in 1..=3 {
+            for p in 0..4 {
+                for q in p + 1..4 {
+                    clauses.push(Clause::new(vec![-(p * 3 + h), -(q * 3 + h)]));
+                }
+            }
+        }
+        let formula = Formula::new(clauses);
+        let mut solver = Solver::new(Empty::new(), formula.clone());
+        solver.record_proof(true);
+        assert_eq!(Ok(SolveResult::Unsat), solver.solve());
+
+        let mut drat = Vec::new();
+        solver.proof().unwrap().write_drat(&mut drat).unwrap();
+        let lemmas = from_reader(&drat[..]).unwrap();
+        assert_eq!(Ok(()), check(&formula, &lemmas));
+        assert_eq!(Err(CheckError::NotRefuted), check(&formula, &lemmas[..1]));
+    }
+
+    #[test]
+    fn checks_rup_and_rat() {
+        let formula = cnf![[1, 2], [-1, 2], [1, -2], [-1, -2]];
+        // 3 is new, so "3" is RAT but not RUP.
+        let proof = "c a comment\n3 0\nd 3 0\n2 0\n0\n";
+        let lemmas = from_string(proof.to_string()).unwrap();
+        assert_eq!(Lemma::Delete(Clause::new(vec![3])), lemmas[1]);
+        let mut checker = Checker::default();
+        for lits in formula.iter() {
+            checker.add(lits);
+        }
+        assert!(!checker.is_rup(&[Literal::new(3)]));
+        assert_eq!(Ok(()), check(&formula, &lemmas));
+
+        let formula = cnf![[1, 2], [-1, 2]];
+        let lemmas = from_string("-2 0\n".to_string()).unwrap();
+        assert_eq!(Err(CheckError::NotImplied(0)), check(&formula, &lemmas));
+
+        // "3 1" is RAT on 3, which is new, but not on 1.
+        let formula = cnf![[-1, 2]];
+        let lemmas = from_string("3 1 0\n".to_string()).unwrap();
+        assert_eq!(
+            Lemma::Add(Clause::unnormalized(vec![Literal::new(3), Literal::new(1)])),
+            lemmas[0]
+        );
+        assert_eq!(Err(CheckError::NotRefuted), check(&formula, &lemmas));
+
+        assert_eq!(
+            Err(ParseError::UnterminatedLemma),
+            from_string("1 2 0\nd 1".to_string())
+        );
+        assert!(from_string("1 x 0\n".to_string()).is_err());
+    }
+}
@@ -0,0 +1,135 @@
+//! Shrinking formulae, to make small reproducers for bug reports and
+//! test cases: given a formula with some property, like making the
+//! solver give a wrong answer, find a smaller formula which still has
+//! it.
+//!
+//! This is delta debugging (ddmin): the clauses are split into chunks,
+//! and if some chunk, or everything but some chunk, still has the
+//! property, the rest is dropped; otherwise the chunks get smaller.
+//! The same is then done to the literals of each clause.  The result
+//! is 1-minimal: dropping any single clause or literal loses the
+//! property.
+
+use crate::cnf::{Clause, Formula};
+
+/// Shrink a formula while 'keep' holds of it.  If 'keep' doesn't hold
+/// of the formula to begin with, it's returned unchanged.
+pub fn shrink<P: FnMut(&Formula) -> bool>(formula: &Formula, mut keep: P) -> Formula {
+    if !keep(formula) {
+        return formula.clone();
+    }
+    let mut clauses = formula.clauses();
+
+    // dropping literals can make clauses droppable, and the other way
+    // around, so go until neither helps.
+    loop {
+        let before = size(&clauses);
+        clauses = ddmin(clauses, |cs| keep(&Formula::new(cs.to_vec())));
+        for i in 0..clauses.len() {
+            let lits = ddmin(clauses[i].literals().to_vec(), |ls| {
+                let mut cs = clauses.clone();
+                cs[i] = Clause::unnormalized(ls.to_vec());
+                keep(&Formula::new(cs))
+            });
+            clauses[i] = Clause::unnormalized(lits);
+        }
+        if size(&clauses) == before {
+            return Formula::new(clauses);
+        }
+    }
+}
+
+/// The number of clauses and literals.
+fn size(clauses: &[Clause]) -> usize {
+    clauses.len() + clauses.iter().map(Clause::len).sum::<usize>()
+}
+
+/// Find a 1-minimal subsequence of some items for which 'test' holds,
+/// given that it holds of all of them.
+fn ddmin<T: Clone, F: FnMut(&[T]) -> bool>(mut items: Vec<T>, mut test: F) -> Vec<T> {
+    if test(&[]) {
+        return Vec::new();
+    }
+
+    let mut granularity = 2;
+    while items.len() >= 2 {
+        let chunk = items.len().div_ceil(granularity);
+        let starts: Vec<usize> = (0..items.len()).step_by(chunk).collect();
+
+        if let Some(subset) = starts
+            .iter()
+            .map(|start| items[*start..(start + chunk).min(items.len())].to_vec())
+            .find(|subset| test(subset))
+        {
+            items = subset;
+            granularity = 2;
+            continue;
+        }
+
+        if let Some(complement) = starts
+            .iter()
+            .map(|start| {
+                let mut rest = items[..*start].to_vec();
+                rest.extend_from_slice(&items[(start + chunk).min(items.len())..]);
+                rest
+            })
+            .find(|complement| test(complement))
+        {
+            items = complement;
+            granularity = (granularity - 1).max(2);
+            continue;
+        }
+
+        if granularity >= items.len() {
+            break;
+        }
+        granularity = (2 * granularity).min(items.len());
+    }
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cnf::*;
+
+    #[test]
+    fn shrinks_unsatisfiable_formulae() {
+        // an empty clause would do, so rule that out.  Dropping
+        // clauses leaves 1 or 4, 1 -> 6, -6, and -4; then dropping
+        // literals leaves 1 and -1, and -6 and -4 can go.
+        let formula = cnf![
+            [2, 3],
+            [1, 4],
+            [-4, 5],
+            [-1, 6],
+            [-2, -5],
+            [-6],
+            [3, 4],
+            [-4]
+        ];
+        let unsat = |f: &Formula| !f.iter().any(<[Literal]>::is_empty) && !crate::sat(f.clone());
+        let shrunk = shrink(&formula, unsat);
+        assert_eq!(cnf![[1], [-1]], shrunk);
+
+        let clauses = shrunk.clauses();
+        for i in 0..clauses.len() {
+            let mut fewer = clauses.clone();
+            fewer.remove(i);
+            assert!(!unsat(&Formula::new(fewer)));
+        }
+    }
+
+    #[test]
+    fn shrinks_to_a_property() {
+        // shrink to something containing 3, which is a single literal.
+        let formula = cnf![[1, 2, 3], [-3, 4], [3, 5, 6]];
+        let has_3 = |f: &Formula| f.iter().any(|lits| lits.contains(&Literal::new(3)));
+        assert_eq!(cnf![[3]], shrink(&formula, has_3));
+
+        // nothing to do.
+        let no_7 = |f: &Formula| f.iter().all(|lits| !lits.contains(&Literal::new(7)));
+        assert_eq!(Formula::new(Vec::new()), shrink(&formula, no_7));
+        assert_eq!(formula, shrink(&formula, |_| false));
+    }
+}
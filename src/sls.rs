@@ -0,0 +1,274 @@
+//! Stochastic local search.  Starting from a random assignment,
+//! WalkSAT repeatedly picks a false clause and flips one of its atoms,
+//! preferring atoms which make no other clauses false.  This can't
+//! show a formula is unsatisfiable, but it often finds a model of a
+//! satisfiable formula much more quickly than a complete search.
+
+use crate::cnf::{Formula, Literal};
+use crate::dpll::lit_index;
+use crate::rng::Rng;
+
+/// The default number of flips before giving up.
+const DEFAULT_MAX_FLIPS: usize = 100_000;
+
+/// The default probability of a random flip.
+const DEFAULT_NOISE: f64 = 0.5;
+
+/// Options for WalkSAT.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct WalkSat {
+    /// The probability, from 0 to 1, of flipping a random atom of the
+    /// false clause, rather than the one which makes the fewest other
+    /// clauses false, when every atom would make some clause false.
+    pub noise: f64,
+    /// Give up after this many flips.
+    pub max_flips: usize,
+    /// The seed for the initial assignment and the random choices.
+    pub seed: u64,
+}
+
+impl Default for WalkSat {
+    fn default() -> WalkSat {
+        WalkSat {
+            noise: DEFAULT_NOISE,
+            max_flips: DEFAULT_MAX_FLIPS,
+            seed: 0,
+        }
+    }
+}
+
+impl WalkSat {
+    /// Search for a model, which assigns every atom up to the largest
+    /// in the formula.  Returns 'None' if there's no model or the
+    /// search gives up: there's no way to tell which.
+    pub fn solve(&self, formula: &Formula) -> Option<Vec<Literal>> {
+        let (values, unsatisfied) = self.walk(formula);
+        if unsatisfied > 0 {
+            return None;
+        }
+        let model = (1..values.len())
+            .map(|id| {
+                let lit = Literal::new(id as isize);
+                if values[id] {
+                    lit
+                } else {
+                    lit.negate()
+                }
+            })
+            .collect();
+        Some(model)
+    }
+
+    /// Search for a model, returning the assignment, indexed by atom,
+    /// with the fewest false clauses found, and how many there are.
+    pub(crate) fn walk(&self, formula: &Formula) -> (Vec<bool>, usize) {
+        let mut rng = Rng::new(self.seed);
+        let mut state = State::new(formula, &mut rng);
+        let mut best = (state.values.clone(), state.unsatisfied.len());
+        // an empty clause can't be made true.
+        if formula.iter().any(<[Literal]>::is_empty) {
+            return best;
+        }
+
+        for _ in 0..self.max_flips {
+            if state.unsatisfied.is_empty() {
+                break;
+            }
+            let i = state.unsatisfied[rng.below(state.unsatisfied.len())];
+            let lits = &formula[i];
+
+            let (lit, breaks) = lits
+                .iter()
+                .map(|lit| (*lit, state.breaks(*lit)))
+                .min_by_key(|(_, breaks)| *breaks)
+                .unwrap();
+            let lit = if breaks > 0 && rng.next_f64() < self.noise {
+                lits[rng.below(lits.len())]
+            } else {
+                lit
+            };
+
+            state.flip(lit.get_id() as usize);
+            if state.unsatisfied.len() < best.1 {
+                best = (state.values.clone(), state.unsatisfied.len());
+            }
+        }
+
+        best
+    }
+}
+
+/// The state of a search: the assignment, and which clauses it makes
+/// false.
+#[derive(Debug)]
+struct State {
+    /// The clauses each literal occurs in, indexed by 'lit_index'.
+    occurrences: Vec<Vec<usize>>,
+    /// The truth of each atom, indexed by atom.
+    values: Vec<bool>,
+    /// The number of true literals in each clause.
+    true_counts: Vec<usize>,
+    /// The false clauses, in no particular order.
+    unsatisfied: Vec<usize>,
+    /// Where each false clause is in 'unsatisfied'.
+    positions: Vec<usize>,
+}
+
+impl State {
+    /// Start from a random assignment.
+    fn new(formula: &Formula, rng: &mut Rng) -> State {
+        let num_atoms = formula
+            .iter()
+            .flatten()
+            .map(|lit| lit.get_id() as usize)
+            .max()
+            .unwrap_or(0);
+
+        let mut occurrences = vec![Vec::new(); 2 * num_atoms + 2];
+        for (i, lits) in formula.iter().enumerate() {
+            for lit in lits {
+                occurrences[lit_index(*lit)].push(i);
+            }
+        }
+
+        let values: Vec<bool> = (0..=num_atoms).map(|_| rng.next_bool()).collect();
+        let mut state = State {
+            occurrences,
+            values,
+            true_counts: vec![0; formula.len()],
+            unsatisfied: Vec::new(),
+            positions: vec![0; formula.len()],
+        };
+        for (i, lits) in formula.iter().enumerate() {
+            state.true_counts[i] = lits.iter().filter(|lit| state.value(**lit)).count();
+            if state.true_counts[i] == 0 {
+                state.positions[i] = state.unsatisfied.len();
+                state.unsatisfied.push(i);
+            }
+        }
+        state
+    }
+
+    fn value(&self, lit: Literal) -> bool {
+        self.values[lit.get_id() as usize] != lit.is_negated()
+    }
+
+    /// The number of clauses which making a false literal true would
+    /// make false: those where its negation is the only true literal.
+    fn breaks(&self, lit: Literal) -> usize {
+        self.occurrences[lit_index(lit.negate())]
+            .iter()
+            .filter(|i| self.true_counts[**i] == 1)
+            .count()
+    }
+
+    /// Flip the truth of an atom.
+    fn flip(&mut self, id: usize) {
+        self.values[id] = !self.values[id];
+        let lit = Literal::new(id as isize);
+        let (now_true, now_false) = if self.values[id] {
+            (lit, lit.negate())
+        } else {
+            (lit.negate(), lit)
+        };
+
+        for k in 0..self.occurrences[lit_index(now_true)].len() {
+            let i = self.occurrences[lit_index(now_true)][k];
+            self.true_counts[i] += 1;
+            if self.true_counts[i] == 1 {
+                // move the last false clause into this one's place.
+                let last = self.unsatisfied.pop().unwrap();
+                if last != i {
+                    self.unsatisfied[self.positions[i]] = last;
+                    self.positions[last] = self.positions[i];
+                }
+            }
+        }
+        for k in 0..self.occurrences[lit_index(now_false)].len() {
+            let i = self.occurrences[lit_index(now_false)][k];
+            self.true_counts[i] -= 1;
+            if self.true_counts[i] == 0 {
+                self.positions[i] = self.unsatisfied.len();
+                self.unsatisfied.push(i);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cnf::*;
+    use crate::sat_assignment_with_local_search;
+    use crate::testing::*;
+
+    #[test]
+    fn finds_planted_models() {
+        // random 3-SAT with every clause true in a hidden model.
+        let mut rng = Rng::new(7);
+        let num_vars = 50;
+        let planted: Vec<bool> = (0..=num_vars).map(|_| rng.next_bool()).collect();
+        let mut clauses = Vec::new();
+        while clauses.len() < 200 {
+            let lits: Vec<isize> = (0..3)
+                .map(|_| {
+                    let var = 1 + rng.below(num_vars) as isize;
+                    if rng.next_bool() {
+                        var
+                    } else {
+                        -var
+                    }
+                })
+                .collect();
+            if lits
+                .iter()
+                .any(|lit| planted[lit.unsigned_abs()] == (*lit > 0))
+            {
+                clauses.push(Clause::new(lits));
+            }
+        }
+        let formula = Formula::new(clauses);
+
+        let model = WalkSat::default().solve(&formula).unwrap();
+        for lits in formula.iter() {
+            assert!(lits.iter().any(|lit| model.contains(lit)));
+        }
+    }
+
+    #[test]
+    fn gives_up_on_unsat() {
+        let formula = Formula::new(vec![
+            Clause::new(vec![1, 2]),
+            Clause::new(vec![1, -2]),
+            Clause::new(vec![-1, 2]),
+            Clause::new(vec![-1, -2]),
+        ]);
+        let walksat = WalkSat {
+            max_flips: 1000,
+            ..WalkSat::default()
+        };
+        assert_eq!(None, walksat.solve(&formula));
+        assert_eq!(
+            None,
+            walksat.solve(&Formula::new(vec![Clause::from(vec![])]))
+        );
+    }
+
+    #[test]
+    fn agrees_with_brute_force() {
+        let walksat = WalkSat {
+            max_flips: 100,
+            ..WalkSat::default()
+        };
+        for (num_vars, formula) in random_formulae(0x2545_f491_4f6c_dd1d, 300) {
+            let brute_force = brute_force_sat(num_vars, &formula, &[]);
+            match sat_assignment_with_local_search(formula.clone(), &walksat) {
+                Some(lits) => {
+                    assert!(brute_force, "{} is unsat but got {:?}", formula, lits);
+                    assert!(is_model(&formula, &lits));
+                }
+                None => assert!(!brute_force, "{} is sat", formula),
+            }
+        }
+    }
+}
@@ -0,0 +1,1151 @@
+//! A solver object, bundling a theory with the formula to solve.
+//!
+//! Solvers are incremental: clauses can be added between searches,
+//! and clauses learned by one search are kept for the next.
+
+#[cfg(feature = "async")]
+pub mod future;
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::mem;
+use std::time::Duration;
+
+use crate::cnf::{Clause, Formula, Literal};
+use crate::dpll::clauses::ClauseDb;
+use crate::dpll::conflict::ConflictGraph;
+use crate::dpll::heuristic::Vmtf;
+use crate::dpll::trace::Transition;
+use crate::dpll::{
+    search, Config, Control, Model, OnEvent, SaveSnapshot, SearchEvent, Snapshot, SolveResult,
+    SolverError, Stats,
+};
+use crate::proof::Proof;
+use crate::theory::Theory;
+
+/// A solver for a formula modulo a theory.
+///
+/// Cloning a solver forks it: the clone has its own copy of the
+/// formula, the learned clauses, the saved phases and heuristic
+/// state, and the theory, so the two can go on to be given different
+/// clauses or assumptions, on different threads if need be, without
+/// affecting each other.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub struct Solver<T> {
+    theory: T,
+    formula: Formula,
+    config: Config,
+    start: Snapshot,
+    /// The number of clauses and learned clauses when each level was
+    /// pushed.
+    levels: Vec<(usize, usize)>,
+    record: ConflictGraphs,
+    conflict_graphs: Vec<(usize, ConflictGraph)>,
+    record_proof: bool,
+    proof: Option<Proof>,
+    record_trace: bool,
+    trace: Vec<Transition>,
+    stats: Stats,
+    /// The literals fixed at decision level 0 by the searches so far.
+    fixed: BTreeSet<Literal>,
+    /// The assumptions which made the last search unsatisfiable.
+    core: Vec<Literal>,
+}
+
+/// Which conflicts to record the conflict graphs of.  Conflicts are
+/// numbered from 1.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub enum ConflictGraphs {
+    None,
+    Final,
+    All,
+    Nth(usize),
+}
+
+impl<T: Theory> Solver<T> {
+    /// Construct a new solver for the given formula.
+    pub fn new(theory: T, formula: Formula) -> Solver<T> {
+        Solver {
+            theory,
+            formula,
+            config: Config::default(),
+            start: Snapshot::default(),
+            levels: Vec::new(),
+            record: ConflictGraphs::None,
+            conflict_graphs: Vec::new(),
+            record_proof: false,
+            proof: None,
+            record_trace: false,
+            trace: Vec::new(),
+            stats: Stats::default(),
+            fixed: BTreeSet::new(),
+            core: Vec::new(),
+        }
+    }
+
+    /// Get the formula being solved.
+    pub fn formula(&self) -> &Formula {
+        &self.formula
+    }
+
+    /// Get the search options.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Change the search options.
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+    }
+
+    /// Start the next search from a previous or expected model, by
+    /// making its literals the saved phases of their atoms: each atom
+    /// is decided the way the model has it, unless that conflicts.
+    /// If the formula has only changed slightly since the model was
+    /// found, this often finds a new model quickly.  The model can be
+    /// partial; other atoms keep their saved phases.
+    pub fn set_initial_model(&mut self, model: &[Literal]) {
+        let phases = &mut self.start.phases;
+        for lit in model {
+            let id = lit.get_id() as usize;
+            if id >= phases.len() {
+                phases.resize(id + 1, true);
+            }
+            phases[id] = !lit.is_negated();
+        }
+    }
+
+    /// Add a clause to the formula.
+    pub fn add_clause(&mut self, clause: Clause) {
+        self.formula.insert_clause(clause);
+    }
+
+    /// Start a new level: 'pop' removes the clauses added since.
+    pub fn push(&mut self) {
+        self.levels
+            .push((self.formula.len(), self.start.learned.len()));
+    }
+
+    /// Remove the clauses added since the last 'push', and the
+    /// clauses learned since then, which may depend on them.  Returns
+    /// false if there was no level to pop.
+    pub fn pop(&mut self) -> bool {
+        match self.levels.pop() {
+            Some((num_clauses, num_learned)) => {
+                self.formula.truncate(num_clauses);
+                self.start.learned.truncate(num_learned);
+                self.start.model = Model::new();
+                self.fixed.clear();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Find a model which satisfies the formula, if one exists.
+    pub fn solve(&mut self) -> Result<SolveResult, SolverError> {
+        self.run(&[], None, None)
+    }
+
+    /// Like 'solve', but calls 'on_event' on every conflict, learned
+    /// clause, and restart, to report progress.
+    pub fn solve_with_progress<F: FnMut(&SearchEvent)>(
+        &mut self,
+        mut on_event: F,
+    ) -> Result<SolveResult, SolverError> {
+        self.run(&[], None, Some(&mut on_event))
+    }
+
+    /// Find a model which satisfies the formula in which the assumed
+    /// literals are true, if one exists.  The assumptions only apply
+    /// to this search.
+    pub fn solve_with_assumptions(
+        &mut self,
+        assumptions: &[Literal],
+    ) -> Result<SolveResult, SolverError> {
+        self.run(assumptions, None, None)
+    }
+
+    /// Like 'solve', but periodically calls 'save' with a checkpoint
+    /// of the search, which can be passed to 'resume' to pick up
+    /// where it left off.
+    pub fn solve_with_checkpoints<F: FnMut(&Checkpoint)>(
+        &mut self,
+        interval: Duration,
+        mut save: F,
+    ) -> Result<SolveResult, SolverError> {
+        let fingerprint = stable_fingerprint(&self.formula);
+        let mut save_snapshot = |snapshot: &Snapshot| {
+            save(&Checkpoint {
+                fingerprint,
+                snapshot: snapshot.clone(),
+            })
+        };
+        self.run(&[], Some((interval, &mut save_snapshot)), None)
+    }
+
+    /// Get the assumptions which made the last search unsatisfiable:
+    /// the formula is unsatisfiable with just these assumed, and they
+    /// are usually far fewer than all of them.  If the formula is
+    /// unsatisfiable on its own, or the last search wasn't, this is
+    /// empty.
+    pub fn failed_assumptions(&self) -> &[Literal] {
+        &self.core
+    }
+
+    /// Make the next search start from a checkpoint, rather than from
+    /// scratch.  The checkpoint must have been taken from a solver
+    /// for the same formula.
+    pub fn resume(&mut self, checkpoint: Checkpoint) -> Result<(), CheckpointError> {
+        if checkpoint.fingerprint != stable_fingerprint(&self.formula) {
+            return Err(CheckpointError::WrongFormula);
+        }
+        self.start = checkpoint.snapshot;
+        Ok(())
+    }
+
+    /// Record the conflict graphs of some conflicts in the next
+    /// search, for explaining why it went the way it did.
+    pub fn record_conflict_graphs(&mut self, which: ConflictGraphs) {
+        self.record = which;
+    }
+
+    /// Get the conflict graphs recorded by the last search, with
+    /// their conflict numbers.
+    pub fn conflict_graphs(&self) -> &[(usize, ConflictGraph)] {
+        &self.conflict_graphs
+    }
+
+    /// Record a proof in the next searches which find the formula
+    /// unsatisfiable.  Proofs can only be recorded for propositional
+    /// theories, and searches under assumptions don't produce them.
+    pub fn record_proof(&mut self, record: bool) {
+        self.record_proof = record;
+    }
+
+    /// Get the proof recorded by the last search, if there is one.
+    /// Its premises are the clauses of the formula, followed by any
+    /// clauses learned by earlier searches.
+    pub fn proof(&self) -> Option<&Proof> {
+        self.proof.as_ref()
+    }
+
+    /// Record the transitions made by the next searches.
+    pub fn record_trace(&mut self, record: bool) {
+        self.record_trace = record;
+    }
+
+    /// Get the transitions made by the last search, if they were
+    /// recorded.
+    pub fn trace(&self) -> &[Transition] {
+        &self.trace
+    }
+
+    /// Get the literals which the searches so far have found to be
+    /// true in every model, because they're implied at decision level
+    /// 0 by unit clauses and learned clauses.  These are part of the
+    /// backbone of the formula, and stay true as clauses are added,
+    /// but popping a level forgets them.
+    pub fn fixed_literals(&self) -> Vec<Literal> {
+        self.fixed.iter().copied().collect()
+    }
+
+    /// Get statistics about the last search.
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Write the state of the solver in a line-based text format: the
+    /// clauses, the learned clauses, the saved phases, the heuristic
+    /// state, the pushed levels, and the fixed literals.  The theory,
+    /// the search options, and what was recorded about the last search
+    /// aren't saved.
+    pub fn save<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "solver")?;
+        for lits in self.formula.iter() {
+            write_lits(&mut writer, "clause", lits)?;
+        }
+        for lits in self.start.learned.iter().map(Clause::literals) {
+            write_lits(&mut writer, "learned", lits)?;
+        }
+        let phases: Vec<Literal> = (1..self.start.phases.len())
+            .map(|id| {
+                let lit = Literal::new(id as isize);
+                if self.start.phases[id] {
+                    lit
+                } else {
+                    lit.negate()
+                }
+            })
+            .collect();
+        write_lits(&mut writer, "phases", &phases)?;
+        if let Some(vmtf) = &self.start.vmtf {
+            let order: Vec<Literal> = vmtf
+                .order()
+                .into_iter()
+                .map(|id| Literal::new(id as isize))
+                .collect();
+            write_lits(&mut writer, "vmtf", &order)?;
+        }
+        for (num_clauses, num_learned) in &self.levels {
+            writeln!(writer, "level {} {}", num_clauses, num_learned)?;
+        }
+        let fixed: Vec<Literal> = self.fixed.iter().copied().collect();
+        write_lits(&mut writer, "fixed", &fixed)?;
+        for line in self.start.model.to_lines() {
+            writeln!(writer, "model {}", line)?;
+        }
+        writer.flush()
+    }
+
+    /// Read a solver written by 'save', with a theory and the default
+    /// search options.
+    pub fn load<R: BufRead>(theory: T, reader: R) -> Result<Solver<T>, CheckpointError> {
+        let lines = reader.lines().collect::<io::Result<Vec<String>>>()?;
+        match lines.first() {
+            Some(header) if header == "solver" => (),
+            Some(header) => return Err(CheckpointError::Malformed(header.clone())),
+            None => return Err(CheckpointError::Malformed(String::new())),
+        }
+
+        let mut solver = Solver::new(theory, Formula::default());
+        let mut model_lines = Vec::new();
+        for line in &lines[1..] {
+            let malformed = || CheckpointError::Malformed(line.clone());
+            let (tag, rest) = line.split_once(' ').ok_or_else(malformed)?;
+            match tag {
+                "clause" => {
+                    let clause = Clause::from(parse_lits(rest).ok_or_else(malformed)?);
+                    solver.formula.insert_clause(clause);
+                }
+                "learned" => {
+                    let clause = Clause::from(parse_lits(rest).ok_or_else(malformed)?);
+                    solver.start.learned.push(clause);
+                }
+                "phases" => {
+                    let lits = parse_lits(rest).ok_or_else(malformed)?;
+                    solver.set_initial_model(&lits);
+                }
+                "vmtf" => {
+                    let lits = parse_lits(rest).ok_or_else(malformed)?;
+                    let order: Vec<usize> = lits.iter().map(|lit| lit.get_id() as usize).collect();
+                    let len = order.iter().max().map_or(1, |id| id + 1);
+                    solver.start.vmtf = Some(Vmtf::from_order(&order, len));
+                }
+                "level" => {
+                    let mut words = rest.split_ascii_whitespace().map(|w| w.parse::<usize>());
+                    match (words.next(), words.next(), words.next()) {
+                        (Some(Ok(num_clauses)), Some(Ok(num_learned)), None) => {
+                            solver.levels.push((num_clauses, num_learned));
+                        }
+                        _ => return Err(malformed()),
+                    }
+                }
+                "fixed" => {
+                    let lits = parse_lits(rest).ok_or_else(malformed)?;
+                    solver.fixed.extend(lits);
+                }
+                "model" => model_lines.push(rest),
+                _ => return Err(malformed()),
+            }
+        }
+
+        solver.start.model = Model::from_lines(model_lines).map_err(CheckpointError::Malformed)?;
+        Ok(solver)
+    }
+
+    fn run(
+        &mut self,
+        assumptions: &[Literal],
+        checkpoint: Option<(Duration, SaveSnapshot)>,
+        event: Option<OnEvent>,
+    ) -> Result<SolveResult, SolverError> {
+        let which = self.record;
+        let graphs = &mut self.conflict_graphs;
+        graphs.clear();
+
+        let mut conflicts = 0;
+        let mut record = |clauses: &ClauseDb, model: &Model, clause: usize| {
+            conflicts += 1;
+            let keep = match which {
+                ConflictGraphs::None => false,
+                ConflictGraphs::Final => {
+                    graphs.clear();
+                    true
+                }
+                ConflictGraphs::All => true,
+                ConflictGraphs::Nth(n) => n == conflicts,
+            };
+            if keep {
+                graphs.push((conflicts, ConflictGraph::new(clauses, model, clause)));
+            }
+        };
+
+        let control = Control {
+            // reborrow, to shorten the lifetime to match 'record'
+            checkpoint: match checkpoint {
+                Some((interval, save)) => {
+                    let save: SaveSnapshot = &mut *save;
+                    Some((interval, save))
+                }
+                None => None,
+            },
+            conflict: if which == ConflictGraphs::None {
+                None
+            } else {
+                Some(&mut record)
+            },
+            proof: self.record_proof,
+            event: match event {
+                Some(on_event) => {
+                    let on_event: OnEvent = &mut *on_event;
+                    Some(on_event)
+                }
+                None => None,
+            },
+            trace: self.record_trace,
+            ..Control::default()
+        };
+
+        let start = mem::take(&mut self.start);
+        let finished = search(
+            &mut self.theory,
+            self.formula.clone(),
+            start,
+            assumptions,
+            &self.config,
+            control,
+        );
+        self.stats = finished.stats;
+        self.fixed.extend(finished.fixed);
+        self.core = finished.core;
+        self.start = finished.state;
+        self.proof = finished.proof;
+        self.trace = finished.trace;
+        finished
+            .outcome
+            .into_result()
+            .map(|result| result.map(|model| model.get_assignments()))
+    }
+}
+
+/// A snapshot of a search in progress.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub struct Checkpoint {
+    fingerprint: u64,
+    snapshot: Snapshot,
+}
+
+impl Checkpoint {
+    /// Write a checkpoint in a line-based text format.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "checkpoint {}", self.fingerprint)?;
+        for lits in self.snapshot.learned.iter().map(Clause::literals) {
+            write!(writer, "l")?;
+            for lit in lits {
+                write!(writer, " {}", lit)?;
+            }
+            writeln!(writer, " 0")?;
+        }
+        for line in self.snapshot.model.to_lines() {
+            writeln!(writer, "{}", line)?;
+        }
+        writer.flush()
+    }
+
+    /// Read a checkpoint written by 'write'.
+    pub fn read<R: BufRead>(reader: R) -> Result<Checkpoint, CheckpointError> {
+        let lines = reader.lines().collect::<io::Result<Vec<String>>>()?;
+        let (header, rest) = match lines.split_first() {
+            Some(split) => split,
+            None => return Err(CheckpointError::Malformed(String::new())),
+        };
+
+        let mut words = header.split_ascii_whitespace();
+        let fingerprint = match (words.next(), words.next().map(|w| w.parse::<u64>())) {
+            (Some("checkpoint"), Some(Ok(fingerprint))) => fingerprint,
+            _ => return Err(CheckpointError::Malformed(header.to_string())),
+        };
+
+        let mut learned = Vec::new();
+        let mut model_lines = Vec::new();
+        for line in rest {
+            if let Some(lits) = line.strip_prefix("l ") {
+                match parse_lits(lits) {
+                    Some(lits) => learned.push(Clause::from(lits)),
+                    None => return Err(CheckpointError::Malformed(line.clone())),
+                }
+            } else {
+                model_lines.push(line.as_str());
+            }
+        }
+
+        match Model::from_lines(model_lines) {
+            Ok(model) => Ok(Checkpoint {
+                fingerprint,
+                snapshot: Snapshot {
+                    learned,
+                    model,
+                    ..Snapshot::default()
+                },
+            }),
+            Err(line) => Err(CheckpointError::Malformed(line)),
+        }
+    }
+}
+
+/// A hash of the canonical form of a formula, for checking that a
+/// checkpoint is resumed with the same one.  Unlike
+/// 'Formula::fingerprint', this is the same in every build, as it's
+/// FNV-1a over the literals of each clause, and a 0 after each.
+fn stable_fingerprint(formula: &Formula) -> u64 {
+    let canonical = formula.canonical();
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for lits in canonical.iter() {
+        let ids = lits.iter().map(|lit| lit.get_id() as i64).chain([0]);
+        for byte in ids.flat_map(i64::to_le_bytes) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+/// Write a tagged, zero-terminated list of literals.
+fn write_lits<W: Write>(writer: &mut W, tag: &str, lits: &[Literal]) -> io::Result<()> {
+    write!(writer, "{}", tag)?;
+    for lit in lits {
+        write!(writer, " {}", lit)?;
+    }
+    writeln!(writer, " 0")
+}
+
+/// Parse a zero-terminated list of literals.
+fn parse_lits(s: &str) -> Option<Vec<Literal>> {
+    let mut lits = Vec::new();
+    for word in s.split_ascii_whitespace() {
+        match word.parse::<isize>() {
+            Ok(0) => return Some(lits),
+            Ok(n) => lits.push(Literal::new(n)),
+            Err(_) => return None,
+        }
+    }
+    None
+}
+
+/// An error loading or resuming from a checkpoint.
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(io::Error),
+    Malformed(String),
+    WrongFormula,
+}
+
+impl From<io::Error> for CheckpointError {
+    fn from(e: io::Error) -> CheckpointError {
+        CheckpointError::Io(e)
+    }
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheckpointError::Io(e) => write!(f, "cannot read checkpoint: {}", e),
+            CheckpointError::Malformed(s) => write!(f, "cannot parse checkpoint line '{}'", s),
+            CheckpointError::WrongFormula => write!(f, "checkpoint is for a different formula"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cnf::*;
+    use crate::dpll::heuristic::Heuristic;
+    use crate::dpll::restart::RestartPolicy;
+    use crate::sls::WalkSat;
+    use crate::testing::*;
+    use crate::theory::empty::Empty;
+
+    use std::thread;
+    use std::time::Instant;
+
+    #[test]
+    fn checkpoint_roundtrips() {
+        let formula = Formula::new(vec![
+            Clause::new(vec![-3, 4]),
+            Clause::new(vec![-1, -3, -5]),
+            Clause::new(vec![-2, -4, -5]),
+            Clause::new(vec![-2, 3, 5, -6]),
+            Clause::new(vec![-1, 2]),
+            Clause::new(vec![-1, 3, -5, -6]),
+            Clause::new(vec![1, -6]),
+            Clause::new(vec![1, 7]),
+        ]);
+
+        let mut checkpoints = Vec::new();
+        let mut solver = Solver::new(Empty::new(), formula.clone());
+        let expected = solver.solve_with_checkpoints(Duration::from_secs(0), |checkpoint| {
+            let mut buffer = Vec::new();
+            checkpoint.write(&mut buffer).unwrap();
+            checkpoints.push(buffer);
+        });
+        assert!(expected.as_ref().unwrap().is_sat());
+        assert!(!checkpoints.is_empty());
+
+        for buffer in checkpoints {
+            let checkpoint = Checkpoint::read(&buffer[..]).unwrap();
+            let mut resumed = Solver::new(Empty::new(), formula.clone());
+            resumed.resume(checkpoint).unwrap();
+            assert_eq!(expected, resumed.solve());
+        }
+    }
+
+    #[test]
+    fn records_final_conflict_graph() {
+        let mut solver = Solver::new(
+            Empty::new(),
+            Formula::new(vec![
+                Clause::new(vec![1, 2]),
+                Clause::new(vec![1, -2]),
+                Clause::new(vec![-1, 2]),
+                Clause::new(vec![-1, -2]),
+            ]),
+        );
+        solver.record_conflict_graphs(ConflictGraphs::Final);
+        assert_eq!(Ok(SolveResult::Unsat), solver.solve());
+
+        let graphs = solver.conflict_graphs();
+        assert_eq!(1, graphs.len());
+        let (_, graph) = &graphs[0];
+        // the final conflict is with the first decision flipped: -1
+        // forces 2 by clause 0, which falsifies clause 1.
+        assert_eq!(1, graph.clause());
+        assert_eq!(3, graph.nodes().len());
+        assert_eq!(3, graph.edges().len());
+    }
+
+    #[test]
+    fn save_roundtrips() {
+        let mut solver = Solver::new(
+            Empty::new(),
+            Formula::new(vec![
+                Clause::new(vec![-1, 2]),
+                Clause::new(vec![-1, -2]),
+                Clause::new(vec![1, 3, 4]),
+                Clause::new(vec![-3, -4]),
+            ]),
+        );
+        solver.set_config(Config {
+            heuristic: Heuristic::Vmtf,
+            ..Config::default()
+        });
+        assert!(solver.solve().unwrap().is_sat());
+        solver.push();
+        solver.add_clause(Clause::new(vec![-3]));
+        assert!(solver.solve().unwrap().is_sat());
+
+        let mut buf = Vec::new();
+        solver.save(&mut buf).unwrap();
+        let mut loaded = Solver::load(Empty::new(), &buf[..]).unwrap();
+        assert_eq!(solver.formula, loaded.formula);
+        assert_eq!(solver.start.learned, loaded.start.learned);
+        assert_eq!(solver.start.phases, loaded.start.phases);
+        assert_eq!(
+            solver.start.vmtf.as_ref().map(Vmtf::order),
+            loaded.start.vmtf.as_ref().map(Vmtf::order)
+        );
+        assert_eq!(solver.levels, loaded.levels);
+        assert_eq!(solver.fixed_literals(), loaded.fixed_literals());
+        assert!(!loaded.start.learned.is_empty());
+
+        loaded.set_config(*solver.config());
+        assert_eq!(solver.solve(), loaded.solve());
+        assert!(loaded.pop());
+        assert_eq!(4, loaded.formula().len());
+
+        assert!(Solver::load(Empty::new(), &b"solver\nclause 1"[..]).is_err());
+        assert!(Solver::load(Empty::new(), &b"checkpoint 0\n"[..]).is_err());
+    }
+
+    #[test]
+    fn checkpoint_rejects_other_formulae() {
+        let mut checkpoint = None;
+        let mut solver = Solver::new(Empty::new(), Formula::new(vec![Clause::new(vec![1, 2])]));
+        solver
+            .solve_with_checkpoints(Duration::from_secs(0), |c| checkpoint = Some(c.clone()))
+            .unwrap();
+        // the fingerprint is saved, so it mustn't change between builds.
+        assert_eq!(
+            17953250456259815718,
+            checkpoint.as_ref().unwrap().fingerprint
+        );
+
+        let mut other = Solver::new(Empty::new(), Formula::new(vec![Clause::new(vec![3])]));
+        assert!(other.resume(checkpoint.unwrap()).is_err());
+    }
+
+    #[test]
+    fn random_decisions_are_reproducible() {
+        let formula = Formula::new(vec![
+            Clause::new(vec![1, 2, 3]),
+            Clause::new(vec![-1, -2]),
+            Clause::new(vec![-2, -3]),
+            Clause::new(vec![-1, -3]),
+            Clause::new(vec![4, 5, -1]),
+        ]);
+        let config = Config {
+            random_decisions: 1.0,
+            seed: 12345,
+            ..Config::default()
+        };
+
+        let mut first = Solver::new(Empty::new(), formula.clone());
+        first.set_config(config);
+        let mut second = Solver::new(Empty::new(), formula);
+        second.set_config(config);
+
+        assert_eq!(first.solve(), second.solve());
+        assert_eq!(first.stats(), second.stats());
+    }
+
+    #[test]
+    fn incremental_solving() {
+        let mut solver = Solver::new(
+            Empty::new(),
+            Formula::new(vec![Clause::new(vec![1, 2]), Clause::new(vec![-1, 2])]),
+        );
+        assert!(solver.solve().unwrap().is_sat());
+
+        solver.push();
+        solver.add_clause(Clause::new(vec![-2, 3]));
+        solver.add_clause(Clause::new(vec![-2, -3]));
+        assert_eq!(Ok(SolveResult::Unsat), solver.solve());
+        assert_eq!(
+            Ok(SolveResult::Unsat),
+            solver.solve_with_assumptions(&[Literal::new(-1)])
+        );
+
+        assert!(solver.pop());
+        assert!(!solver.pop());
+        let lits = solver.solve().unwrap().into_model().unwrap();
+        assert!(lits.contains(&Literal::new(2)));
+
+        solver.add_clause(Clause::new(vec![-2]));
+        assert_eq!(Ok(SolveResult::Unsat), solver.solve());
+    }
+
+    #[test]
+    fn forks_by_cloning() {
+        // three pigeons in two holes, and a fourth hole for pigeon 0.
+        let mut solver = Solver::new(
+            Empty::new(),
+            Formula::new(vec![
+                Clause::new(vec![1, 2, 7]),
+                Clause::new(vec![3, 4]),
+                Clause::new(vec![5, 6]),
+                Clause::new(vec![-1, -3]),
+                Clause::new(vec![-1, -5]),
+                Clause::new(vec![-3, -5]),
+                Clause::new(vec![-2, -4]),
+                Clause::new(vec![-2, -6]),
+                Clause::new(vec![-4, -6]),
+            ]),
+        );
+        assert_eq!(
+            Ok(SolveResult::Unsat),
+            solver.solve_with_assumptions(&[Literal::new(-7)])
+        );
+        let learned = solver.start.learned.len();
+        assert!(learned > 0);
+
+        let forks: Vec<Solver<Empty>> = vec![solver.clone(), solver.clone()];
+        let results: Vec<bool> = thread::scope(|scope| {
+            let handles: Vec<_> = forks
+                .into_iter()
+                .zip([Literal::new(7), Literal::new(-7)])
+                .map(|(mut fork, lit)| {
+                    scope.spawn(move || {
+                        assert_eq!(learned, fork.start.learned.len());
+                        fork.add_clause(Clause::from(vec![lit]));
+                        fork.solve().unwrap().is_sat()
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(vec![true, false], results);
+
+        // the original is unchanged.
+        assert_eq!(9, solver.formula().len());
+        assert!(solver.solve().unwrap().is_sat());
+    }
+
+    #[test]
+    fn finds_failed_assumptions() {
+        let mut solver = Solver::new(
+            Empty::new(),
+            Formula::new(vec![
+                Clause::new(vec![-1, 2]),
+                Clause::new(vec![-2, 3]),
+                Clause::new(vec![4, 5, 6]),
+                Clause::new(vec![-4, -5, -6]),
+            ]),
+        );
+        let assumptions: Vec<Literal> = [4, 1, 5, -3].iter().map(|l| Literal::new(*l)).collect();
+        assert_eq!(
+            Ok(SolveResult::Unsat),
+            solver.solve_with_assumptions(&assumptions)
+        );
+        assert_eq!(
+            &[Literal::new(1), Literal::new(-3)],
+            solver.failed_assumptions()
+        );
+
+        assert!(solver.solve().unwrap().is_sat());
+        assert!(solver.failed_assumptions().is_empty());
+    }
+
+    #[test]
+    fn warm_starts_from_a_model() {
+        // the models are {1, -2, 3} and {-1, 2, -3}.
+        let formula = Formula::new(vec![
+            Clause::new(vec![1, 2]),
+            Clause::new(vec![-1, -2]),
+            Clause::new(vec![2, 3]),
+            Clause::new(vec![-2, -3]),
+        ]);
+        let model = vec![Literal::new(-1), Literal::new(2), Literal::new(-3)];
+
+        let mut solver = Solver::new(Empty::new(), formula.clone());
+        assert_ne!(Ok(SolveResult::Sat(model.clone())), solver.solve());
+
+        let mut solver = Solver::new(Empty::new(), formula);
+        solver.set_initial_model(&model);
+        assert_eq!(Ok(SolveResult::Sat(model)), solver.solve());
+        assert_eq!(0, solver.stats().conflicts);
+    }
+
+    #[test]
+    fn exposes_fixed_literals() {
+        // deciding 1 is a conflict, so -1 is learned, and then 3 and
+        // -4 are implied.
+        let mut solver = Solver::new(
+            Empty::new(),
+            Formula::new(vec![
+                Clause::new(vec![-1, 2]),
+                Clause::new(vec![-1, -2]),
+                Clause::new(vec![1, 3]),
+                Clause::new(vec![-3, -4]),
+                Clause::new(vec![4, 5, 6]),
+                Clause::new(vec![-5, -6]),
+            ]),
+        );
+        assert!(solver.fixed_literals().is_empty());
+        assert!(solver.solve().unwrap().is_sat());
+        assert_eq!(
+            vec![Literal::new(-4), Literal::new(-1), Literal::new(3)],
+            solver.fixed_literals()
+        );
+
+        solver.push();
+        solver.add_clause(Clause::new(vec![-3, 7]));
+        solver.add_clause(Clause::new(vec![-7, 5, 6]));
+        assert!(solver.solve().unwrap().is_sat());
+        assert!(solver.fixed_literals().contains(&Literal::new(7)));
+        solver.pop();
+        assert!(solver.fixed_literals().is_empty());
+    }
+
+    #[test]
+    fn keeps_learned_clauses_between_searches() {
+        let mut solver = Solver::new(
+            Empty::new(),
+            Formula::new(vec![
+                Clause::new(vec![-1, 2, 3]),
+                Clause::new(vec![-1, 2, -3]),
+                Clause::new(vec![-1, -2, 3]),
+                Clause::new(vec![-1, -2, -3]),
+                Clause::new(vec![1, 2, 3]),
+                Clause::new(vec![1, -2, -3]),
+            ]),
+        );
+        solver.set_config(Config {
+            heuristic: Heuristic::Vmtf,
+            ..Config::default()
+        });
+
+        // deciding 1 leads to a conflict, and learning (-1).
+        assert!(solver.solve().unwrap().is_sat());
+        assert!(solver.stats().conflicts > 0);
+
+        assert!(solver.solve().unwrap().is_sat());
+        assert_eq!(0, solver.stats().conflicts);
+    }
+
+    #[test]
+    fn records_learned_clause_stats() {
+        let mut solver = Solver::new(
+            Empty::new(),
+            Formula::new(vec![
+                Clause::new(vec![1, 2]),
+                Clause::new(vec![1, -2]),
+                Clause::new(vec![-1, 2]),
+                Clause::new(vec![-1, -2]),
+            ]),
+        );
+        assert_eq!(Ok(SolveResult::Unsat), solver.solve());
+
+        let stats = solver.stats();
+        assert_eq!(1, stats.conflicts);
+        assert_eq!(1, stats.learned_clauses);
+        assert_eq!(1, stats.glue_clauses);
+        assert!((stats.average_lbd() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn gives_up_when_out_of_budget() {
+        let mut solver = Solver::new(
+            Empty::new(),
+            Formula::new(vec![
+                Clause::new(vec![1, 2]),
+                Clause::new(vec![1, -2]),
+                Clause::new(vec![-1, 2]),
+                Clause::new(vec![-1, -2]),
+            ]),
+        );
+        solver.set_config(Config {
+            deadline: Some(Instant::now()),
+            ..Config::default()
+        });
+        assert_eq!(Ok(SolveResult::Unknown), solver.solve());
+
+        solver.set_config(Config {
+            max_conflicts: Some(1),
+            ..Config::default()
+        });
+        assert_eq!(Ok(SolveResult::Unknown), solver.solve());
+        assert_eq!(1, solver.stats().conflicts);
+
+        // the clause learned from the first conflict is kept.
+        solver.set_config(Config::default());
+        assert_eq!(Ok(SolveResult::Unsat), solver.solve());
+        assert_eq!(0, solver.stats().conflicts);
+    }
+
+    #[test]
+    fn deterministic_searches_repeat() {
+        // four pigeons in three holes: pigeon p is in hole h if p*3+h.
+        let mut clauses = Vec::new();
+        for p in 0..4 {
+            clauses.push(Clause::new(vec![p * 3 + 1, p * 3 + 2, p * 3 + 3]));
+        }
+        for h in 1..=3 {
+            for p in 0..4 {
+                for q in p + 1..4 {
+                    clauses.push(Clause::new(vec![-(p * 3 + h), -(q * 3 + h)]));
+                }
+            }
+        }
+        let config = Config {
+            heuristic: Heuristic::Vmtf,
+            random_decisions: 0.2,
+            seed: 7,
+            walk: Some(WalkSat::default()),
+            deadline: Some(Instant::now()),
+            deterministic: true,
+            ..Config::default()
+        };
+
+        let mut traces = Vec::new();
+        for _ in 0..2 {
+            let mut solver = Solver::new(Empty::new(), Formula::new(clauses.clone()));
+            solver.set_config(config);
+            solver.record_trace(true);
+            assert_eq!(Ok(SolveResult::Unsat), solver.solve());
+            traces.push(solver.trace().to_vec());
+        }
+        assert!(!traces[0].is_empty());
+        assert_eq!(traces[0], traces[1]);
+    }
+
+    #[test]
+    fn starts_from_local_search_phases() {
+        // deciding 1 or 2 true is a conflict.
+        let formula = Formula::new(vec![
+            Clause::new(vec![-1, -2]),
+            Clause::new(vec![-1, 2]),
+            Clause::new(vec![-2, -3]),
+            Clause::new(vec![-2, 3]),
+            Clause::new(vec![1, 2, 3]),
+        ]);
+        let mut solver = Solver::new(Empty::new(), formula.clone());
+        assert!(solver.solve().unwrap().is_sat());
+        assert!(solver.stats().conflicts > 0);
+
+        let mut solver = Solver::new(Empty::new(), formula);
+        solver.set_config(Config {
+            walk: Some(WalkSat::default()),
+            ..Config::default()
+        });
+        assert!(solver.solve().unwrap().is_sat());
+        assert_eq!(0, solver.stats().conflicts);
+    }
+
+    #[test]
+    fn reports_progress() {
+        // three pigeons in two holes: pigeon p is in hole h if p*2+h.
+        let mut clauses = Vec::new();
+        for p in 0..3 {
+            clauses.push(Clause::new(vec![p * 2 + 1, p * 2 + 2]));
+        }
+        for h in 1..=2 {
+            for p in 0..3 {
+                for q in p + 1..3 {
+                    clauses.push(Clause::new(vec![-(p * 2 + h), -(q * 2 + h)]));
+                }
+            }
+        }
+        let mut solver = Solver::new(Empty::new(), Formula::new(clauses));
+        solver.set_config(Config {
+            restart_policy: RestartPolicy::Luby { unit: 1 },
+            ..Config::default()
+        });
+
+        let (mut conflicts, mut learned, mut restarts) = (0, 0, 0);
+        let result = solver.solve_with_progress(|event| match event {
+            SearchEvent::Conflict(stats) => {
+                conflicts += 1;
+                assert_eq!(conflicts, stats.conflicts);
+            }
+            SearchEvent::Learned(_, _) => learned += 1,
+            SearchEvent::Restart(stats) => {
+                restarts += 1;
+                assert_eq!(restarts, stats.restarts);
+            }
+        });
+        assert_eq!(Ok(SolveResult::Unsat), result);
+        assert_eq!(solver.stats().conflicts, conflicts);
+        assert_eq!(solver.stats().learned_clauses, learned);
+        assert_eq!(solver.stats().restarts, restarts);
+        assert!(restarts > 0);
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn logs_the_search() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // counts of decisions, conflicts, and restarts, from every
+        // test running at the time.
+        static COUNTS: [AtomicUsize; 3] = [
+            AtomicUsize::new(0),
+            AtomicUsize::new(0),
+            AtomicUsize::new(0),
+        ];
+        struct Counter;
+        impl log::Log for Counter {
+            fn enabled(&self, _: &log::Metadata) -> bool {
+                true
+            }
+            fn log(&self, record: &log::Record) {
+                let message = record.args().to_string();
+                for (i, prefix) in ["decide", "conflict", "restart"].iter().enumerate() {
+                    if message.starts_with(prefix) {
+                        COUNTS[i].fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            }
+            fn flush(&self) {}
+        }
+        log::set_logger(&Counter).unwrap();
+        log::set_max_level(log::LevelFilter::Trace);
+
+        // three pigeons in two holes.
+        let mut clauses = Vec::new();
+        for p in 0..3 {
+            clauses.push(Clause::new(vec![p * 2 + 1, p * 2 + 2]));
+        }
+        for h in 1..=2 {
+            for p in 0..3 {
+                for q in p + 1..3 {
+                    clauses.push(Clause::new(vec![-(p * 2 + h), -(q * 2 + h)]));
+                }
+            }
+        }
+        let mut solver = Solver::new(Empty::new(), Formula::new(clauses));
+        solver.set_config(Config {
+            restart_policy: RestartPolicy::Luby { unit: 1 },
+            ..Config::default()
+        });
+        assert_eq!(Ok(SolveResult::Unsat), solver.solve());
+        for count in &COUNTS {
+            assert!(count.load(Ordering::Relaxed) > 0);
+        }
+    }
+
+    #[test]
+    fn random_decisions_agree_with_brute_force() {
+        for (i, (num_vars, formula)) in random_formulae(0x2545_f491_4f6c_dd1d, 300).enumerate() {
+            let mut solver = Solver::new(Empty::new(), formula.clone());
+            solver.set_config(Config {
+                random_decisions: 0.5,
+                seed: i as u64,
+                ..Config::default()
+            });
+            assert_eq!(
+                brute_force_sat(num_vars, &formula, &[]),
+                solver.solve().unwrap().is_sat(),
+                "{}",
+                formula
+            );
+        }
+    }
+
+    #[test]
+    fn chronological_backtracking_agrees_with_brute_force() {
+        for (num_vars, formula) in random_formulae(0x2545_f491_4f6c_dd1d, 300) {
+            let mut solver = Solver::new(Empty::new(), formula.clone());
+            solver.set_config(Config {
+                chronological_backtracking: Some(0),
+                ..Config::default()
+            });
+            assert_eq!(
+                brute_force_sat(num_vars, &formula, &[]),
+                solver.solve().unwrap().is_sat(),
+                "{}",
+                formula
+            );
+        }
+    }
+
+    #[test]
+    fn push_and_pop_agree_with_brute_force() {
+        for (num_vars, formula) in random_formulae(0x2545_f491_4f6c_dd1d, 300) {
+            let mut clauses = formula.clauses();
+            let second = clauses.split_off(clauses.len() / 2);
+            let first = Formula::new(clauses);
+            let mut solver = Solver::new(Empty::new(), first.clone());
+            let first_sat = brute_force_sat(num_vars, &first, &[]);
+            assert_eq!(first_sat, solver.solve().unwrap().is_sat());
+            solver.push();
+            for clause in second {
+                solver.add_clause(clause);
+            }
+            assert_eq!(
+                brute_force_sat(num_vars, &formula, &[]),
+                solver.solve().unwrap().is_sat(),
+                "pushing {}",
+                formula
+            );
+            solver.pop();
+            assert_eq!(
+                first_sat,
+                solver.solve().unwrap().is_sat(),
+                "popping {}",
+                formula
+            );
+        }
+    }
+}
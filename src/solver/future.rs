@@ -0,0 +1,215 @@
+//! Solving in the background, for use from async code.  This doesn't
+//! depend on any particular executor: the search runs on its own
+//! thread, which wakes the future up when it's done.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+use crate::dpll::{search, Control, Outcome, SolveResult, SolverError};
+use crate::solver::Solver;
+use crate::theory::Theory;
+
+impl<T: Theory + Clone + Send + 'static> Solver<T> {
+    /// Find a model which satisfies the formula, if one exists,
+    /// without blocking the calling thread.  Dropping the returned
+    /// future before it completes cancels the search.
+    pub fn solve_async(&self) -> SolveFuture {
+        let shared = Arc::new(Shared {
+            interrupt: AtomicBool::new(false),
+            state: Mutex::new(State {
+                result: None,
+                waker: None,
+            }),
+        });
+
+        let mut theory = self.theory.clone();
+        let formula = self.formula.clone();
+        let start = self.start.clone();
+        let config = self.config;
+        let thread_shared = Arc::clone(&shared);
+        thread::spawn(move || {
+            let control = Control {
+                interrupt: Some(&thread_shared.interrupt),
+                ..Control::default()
+            };
+            let result = match search(&mut theory, formula, start, &[], &config, control).outcome {
+                Outcome::Sat(model) => Ok(SolveResult::Sat(model.get_assignments())),
+                Outcome::Unsat => Ok(SolveResult::Unsat),
+                Outcome::Interrupted => return,
+                Outcome::OutOfBudget => Ok(SolveResult::Unknown),
+                Outcome::Failed(e) => Err(e),
+            };
+
+            let mut state = thread_shared.state.lock().unwrap();
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+
+        SolveFuture { shared }
+    }
+}
+
+/// A search running in the background.
+#[derive(Debug)]
+pub struct SolveFuture {
+    shared: Arc<Shared>,
+}
+
+/// State shared between the future and the search thread.
+#[derive(Debug)]
+struct Shared {
+    interrupt: AtomicBool,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    result: Option<Result<SolveResult, SolverError>>,
+    waker: Option<Waker>,
+}
+
+impl Future for SolveFuture {
+    type Output = Result<SolveResult, SolverError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.shared.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for SolveFuture {
+    fn drop(&mut self) {
+        self.shared.interrupt.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cnf::*;
+    use crate::theory::empty::Empty;
+
+    use std::sync::mpsc::{channel, Sender};
+    use std::task::Wake;
+    use std::time::{Duration, Instant};
+
+    use crate::theory::TheoryError;
+
+    struct ChannelWaker(Mutex<Sender<()>>);
+
+    impl Wake for ChannelWaker {
+        fn wake(self: Arc<Self>) {
+            let _ = self.0.lock().unwrap().send(());
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let (tx, rx) = channel();
+        let waker = Waker::from(Arc::new(ChannelWaker(Mutex::new(tx))));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            if let Poll::Ready(result) = future.as_mut().poll(&mut cx) {
+                return result;
+            }
+            rx.recv().unwrap();
+        }
+    }
+
+    #[test]
+    fn solves_sat() {
+        let solver = Solver::new(
+            Empty::new(),
+            Formula::new(vec![Clause::new(vec![1, 2]), Clause::new(vec![-1])]),
+        );
+        assert_eq!(
+            Ok(SolveResult::Sat(vec![Literal::new(-1), Literal::new(2)])),
+            block_on(solver.solve_async())
+        );
+    }
+
+    #[test]
+    fn solves_unsat() {
+        let solver = Solver::new(
+            Empty::new(),
+            Formula::new(vec![Clause::new(vec![1]), Clause::new(vec![-1])]),
+        );
+        assert_eq!(Ok(SolveResult::Unsat), block_on(solver.solve_async()));
+    }
+
+    /// A theory with no meaning, which lets the tests tell when a
+    /// search has finished with its copy.
+    #[derive(Clone, Debug)]
+    struct Tracked {
+        _token: Arc<()>,
+    }
+
+    impl Theory for Tracked {
+        fn decide(&self, _: Literal) -> Result<Option<bool>, TheoryError> {
+            Ok(None)
+        }
+
+        fn incorporate(&mut self, _: Literal) -> Result<(), TheoryError> {
+            Ok(())
+        }
+
+        fn push(&mut self) {}
+
+        fn pop(&mut self, _: usize) {}
+
+        fn forget(&mut self) {}
+
+        fn is_propositional(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn dropping_cancels_the_search() {
+        // 12 pigeons in 11 holes takes far longer than the test.
+        let (pigeons, holes) = (12, 11);
+        let var = |p: isize, h: isize| p * holes + h + 1;
+        let mut clauses = Vec::new();
+        for p in 0..pigeons {
+            clauses.push(Clause::new((0..holes).map(|h| var(p, h)).collect()));
+        }
+        for h in 0..holes {
+            for p in 0..pigeons {
+                for q in p + 1..pigeons {
+                    clauses.push(Clause::new(vec![-var(p, h), -var(q, h)]));
+                }
+            }
+        }
+
+        let token = Arc::new(());
+        let solver = Solver::new(
+            Tracked {
+                _token: Arc::clone(&token),
+            },
+            Formula::new(clauses),
+        );
+        let future = solver.solve_async();
+        drop(solver);
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(2, Arc::strong_count(&token));
+
+        drop(future);
+        let start = Instant::now();
+        while Arc::strong_count(&token) > 1 {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
@@ -0,0 +1,439 @@
+//! Symmetries of a formula, and clauses which break them.
+//!
+//! A symmetry is a permutation of the literals which commutes with
+//! negation and maps the clauses of the formula to themselves, so it
+//! maps models to models.  Symmetries are found as automorphisms of
+//! the graph with a node for each literal and each clause, by colour
+//! refinement: two copies of the graph are refined in lockstep, with
+//! a node individualised in each, until every node has a colour of
+//! its own, at which point the colours give a permutation.  Only one
+//! choice is tried at each step, so not every symmetry is found.
+//!
+//! Adding lex-leader clauses for a symmetry rules out every model
+//! which the symmetry maps to a lexicographically smaller one.  This
+//! keeps at least one model from each set of symmetric models, so
+//! preserves satisfiability, and can make unsatisfiable instances
+//! with a lot of symmetry, like the pigeonhole problem, much easier.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::cnf::{Clause, Formula, Literal, Var, VarAllocator};
+use crate::dpll::lit_index;
+
+/// A permutation of literals, which commutes with negation.
+#[derive(Clone, Debug, Default, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub struct Symmetry {
+    /// The image of each variable (as a positive literal) which isn't
+    /// mapped to itself.
+    images: BTreeMap<Var, Literal>,
+}
+
+/// The maximum number of pairs of variables to try to find a
+/// symmetry between.
+const MAX_ATTEMPTS: usize = 1000;
+
+/// Colours given to nodes in the refinement: literals of variables
+/// which don't occur, and the first colours of literals and clauses.
+const ABSENT: usize = 0;
+const LITERAL: usize = 1;
+const CLAUSE: usize = 2;
+
+/// A marker in colour signatures for an individualised node.
+const INDIVIDUAL: usize = usize::MAX;
+
+impl Symmetry {
+    /// Construct a symmetry from the images of variables.  Variables
+    /// not mentioned map to themselves.
+    pub fn new(images: BTreeMap<Var, Literal>) -> Symmetry {
+        Symmetry {
+            images: images
+                .into_iter()
+                .filter(|(var, lit)| var.positive() != *lit)
+                .collect(),
+        }
+    }
+
+    /// The image of a literal.
+    pub fn apply(&self, lit: Literal) -> Literal {
+        match self.images.get(&lit.var()) {
+            Some(image) if lit.is_negated() => image.negate(),
+            Some(image) => *image,
+            None => lit,
+        }
+    }
+
+    /// The variables which aren't mapped to themselves, in ascending
+    /// order.
+    pub fn support(&self) -> impl Iterator<Item = Var> + '_ {
+        self.images.keys().copied()
+    }
+
+    /// Check if this is a symmetry of a formula: it maps the set of
+    /// clauses to itself.
+    pub fn is_symmetry_of(&self, formula: &Formula) -> bool {
+        let canonical = formula.canonical();
+        let set: HashSet<&[Literal]> = canonical.iter().collect();
+        let maps_to_itself = canonical.iter().all(|lits| {
+            let image: Clause = lits.iter().map(|lit| self.apply(*lit)).collect();
+            set.contains(image.literals())
+        });
+        maps_to_itself
+    }
+
+    /// Encode the lex-leader constraint: with the variables in
+    /// ascending order, and false before true, a model is no greater
+    /// than its image under the symmetry.  New atoms are taken from
+    /// 'vars', after reserving the support.
+    pub fn lex_leader(&self, vars: &mut VarAllocator) -> Vec<Clause> {
+        for var in self.support() {
+            vars.reserve(var);
+        }
+
+        let mut clauses = Vec::new();
+        // the variables so far are equal to their images.
+        let mut equal: Option<Literal> = None;
+        let support: Vec<Var> = self.support().collect();
+        for (i, var) in support.iter().enumerate() {
+            let lit = var.positive();
+            let image = self.apply(lit);
+            let mut clause: Vec<Literal> = equal.iter().map(|e| e.negate()).collect();
+            clause.extend([lit.negate(), image]);
+            clauses.push(Clause::from(clause));
+
+            // a variable mapped to its negation is never equal to its
+            // image, and nothing after the last variable matters.
+            if image == lit.negate() || i + 1 == support.len() {
+                break;
+            }
+            let next = vars.fresh().positive();
+            for (a, b) in [(lit.negate(), image.negate()), (lit, image)] {
+                let mut clause: Vec<Literal> = equal.iter().map(|e| e.negate()).collect();
+                clause.extend([a, b, next]);
+                clauses.push(Clause::from(clause));
+            }
+            equal = Some(next);
+        }
+        clauses
+    }
+}
+
+impl Formula {
+    /// Find symmetries of the formula.  These generate a group of
+    /// symmetries, in which any two variables related by a symmetry
+    /// found are in the same orbit, though there may be symmetries
+    /// which aren't found.
+    pub fn symmetries(&self) -> Vec<Symmetry> {
+        let graph = SymmetryGraph::new(self);
+        let mut dict = HashMap::new();
+        let base = match graph.refine_pair(&mut dict, graph.colours.clone(), graph.colours.clone())
+        {
+            Some((base, _)) => base,
+            None => return Vec::new(),
+        };
+
+        // which variables are known to be in the same orbit, as a
+        // union-find over atoms.
+        let mut orbits: Vec<usize> = (0..=graph.num_atoms).collect();
+        fn find(orbits: &mut [usize], mut atom: usize) -> usize {
+            while orbits[atom] != atom {
+                orbits[atom] = orbits[orbits[atom]];
+                atom = orbits[atom];
+            }
+            atom
+        }
+
+        let mut symmetries = Vec::new();
+        let mut attempts = 0;
+        for x in 1..=graph.num_atoms {
+            for y in x + 1..=graph.num_atoms {
+                let (from, to) = (2 * x, 2 * y);
+                if base[from] == ABSENT || base[from] != base[to] {
+                    continue;
+                }
+                if find(&mut orbits, x) == find(&mut orbits, y) {
+                    continue;
+                }
+                if attempts == MAX_ATTEMPTS {
+                    return symmetries;
+                }
+                attempts += 1;
+                if let Some(symmetry) = graph.find(&mut dict, &base, from, to) {
+                    if symmetry.is_symmetry_of(self) {
+                        for var in symmetry.support() {
+                            let a = find(&mut orbits, var.index());
+                            let b = find(&mut orbits, symmetry.apply(var.positive()).var().index());
+                            orbits[a] = b;
+                        }
+                        symmetries.push(symmetry);
+                    }
+                }
+            }
+        }
+        symmetries
+    }
+
+    /// Find symmetries of the formula, and add lex-leader clauses to
+    /// break them.  The formula stays satisfiable if it was, but loses
+    /// models.  Returns the number of symmetries broken.
+    pub fn break_symmetries(&mut self) -> usize {
+        let symmetries = self.symmetries();
+        let mut vars = VarAllocator::above(self);
+        for symmetry in &symmetries {
+            let clauses = symmetry.lex_leader(&mut vars);
+            self.extend(clauses);
+        }
+        symmetries.len()
+    }
+}
+
+/// The graph of a formula which symmetries are found in: the literal
+/// nodes are numbered by 'lit_index', and followed by the clause
+/// nodes.
+struct SymmetryGraph {
+    num_atoms: usize,
+    /// The neighbours of each node.
+    neighbours: Vec<Vec<usize>>,
+    /// The initial colour of each node.
+    colours: Vec<usize>,
+}
+
+impl SymmetryGraph {
+    fn new(formula: &Formula) -> SymmetryGraph {
+        let canonical = formula.canonical();
+        let num_atoms = canonical
+            .iter()
+            .flatten()
+            .map(|lit| lit.get_id() as usize)
+            .max()
+            .unwrap_or(0);
+        let num_lits = 2 * num_atoms + 2;
+
+        let mut neighbours = vec![Vec::new(); num_lits + canonical.len()];
+        let mut colours = vec![ABSENT; num_lits + canonical.len()];
+        for (i, lits) in canonical.iter().enumerate() {
+            colours[num_lits + i] = CLAUSE;
+            for lit in lits {
+                neighbours[lit_index(*lit)].push(num_lits + i);
+                neighbours[num_lits + i].push(lit_index(*lit));
+                colours[lit_index(*lit)] = LITERAL;
+                colours[lit_index(lit.negate())] = LITERAL;
+            }
+        }
+
+        SymmetryGraph {
+            num_atoms,
+            neighbours,
+            colours,
+        }
+    }
+
+    /// The node of the negation of a literal node, if it is one.
+    fn negation(&self, node: usize) -> Option<usize> {
+        if node < 2 * self.num_atoms + 2 {
+            Some(node ^ 1)
+        } else {
+            None
+        }
+    }
+
+    /// Refine a colouring once: each node's new colour is determined
+    /// by its old colour, the colour of its negation, and the colours
+    /// of its neighbours.  Colours are numbered by 'dict', so the same
+    /// signature gets the same colour in every colouring.
+    fn refine(&self, dict: &mut HashMap<Vec<usize>, usize>, colours: &[usize]) -> Vec<usize> {
+        (0..colours.len())
+            .map(|node| {
+                if colours[node] == ABSENT {
+                    return ABSENT;
+                }
+                let mut signature: Vec<usize> =
+                    self.neighbours[node].iter().map(|n| colours[*n]).collect();
+                signature.sort_unstable();
+                signature.insert(0, self.negation(node).map_or(ABSENT, |n| colours[n]));
+                signature.insert(0, colours[node]);
+                colour(dict, signature)
+            })
+            .collect()
+    }
+
+    /// Refine two colourings in lockstep until neither changes.
+    /// Returns 'None' if they stop having the same number of nodes of
+    /// each colour, so no permutation maps one to the other.
+    fn refine_pair(
+        &self,
+        dict: &mut HashMap<Vec<usize>, usize>,
+        mut a: Vec<usize>,
+        mut b: Vec<usize>,
+    ) -> Option<(Vec<usize>, Vec<usize>)> {
+        let mut num_colours = count_colours(&a);
+        loop {
+            a = self.refine(dict, &a);
+            b = self.refine(dict, &b);
+            let (counts_a, counts_b) = (cell_sizes(&a), cell_sizes(&b));
+            if counts_a != counts_b {
+                return None;
+            }
+            if counts_a.len() == num_colours {
+                return Some((a, b));
+            }
+            num_colours = counts_a.len();
+        }
+    }
+
+    /// Try to find a symmetry mapping one literal node to another, by
+    /// individualising nodes until every node has its own colour.
+    fn find(
+        &self,
+        dict: &mut HashMap<Vec<usize>, usize>,
+        base: &[usize],
+        from: usize,
+        to: usize,
+    ) -> Option<Symmetry> {
+        let (mut a, mut b) = (base.to_vec(), base.to_vec());
+        let (mut u, mut v) = (from, to);
+        for depth in 0.. {
+            let individual = colour(dict, vec![a[u], INDIVIDUAL, depth]);
+            a[u] = individual;
+            b[v] = individual;
+            let (refined_a, refined_b) = self.refine_pair(dict, a, b)?;
+            a = refined_a;
+            b = refined_b;
+
+            // individualise the first node of the smallest colour
+            // shared by more than one node.
+            let sizes = cell_sizes(&a);
+            let next = sizes
+                .iter()
+                .filter(|(colour, size)| **colour != ABSENT && **size > 1)
+                .min_by_key(|(colour, size)| (**size, **colour))
+                .map(|(colour, _)| *colour);
+            match next {
+                Some(c) => {
+                    u = a.iter().position(|colour| *colour == c)?;
+                    v = b.iter().position(|colour| *colour == c)?;
+                }
+                None => break,
+            }
+        }
+
+        let nodes_b: HashMap<usize, usize> = b
+            .iter()
+            .enumerate()
+            .filter(|(_, colour)| **colour != ABSENT)
+            .map(|(node, colour)| (*colour, node))
+            .collect();
+        let mut images = BTreeMap::new();
+        for atom in 1..=self.num_atoms {
+            let node = 2 * atom;
+            if a[node] == ABSENT {
+                continue;
+            }
+            let image = nodes_b[&a[node]];
+            let lit = Literal::new((image / 2) as isize);
+            images.insert(
+                Var::new(atom),
+                if image % 2 == 1 { lit.negate() } else { lit },
+            );
+        }
+        Some(Symmetry::new(images))
+    }
+}
+
+/// The colour of a signature, numbering it if it's new.
+fn colour(dict: &mut HashMap<Vec<usize>, usize>, signature: Vec<usize>) -> usize {
+    let next = dict.len() + CLAUSE + 1;
+    *dict.entry(signature).or_insert(next)
+}
+
+/// The number of distinct colours.
+fn count_colours(colours: &[usize]) -> usize {
+    cell_sizes(colours).len()
+}
+
+/// The number of nodes of each colour.
+fn cell_sizes(colours: &[usize]) -> BTreeMap<usize, usize> {
+    let mut sizes = BTreeMap::new();
+    for colour in colours {
+        *sizes.entry(*colour).or_insert(0) += 1;
+    }
+    sizes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Each of the pigeons is in one of the holes, and no two pigeons
+    /// share a hole.
+    fn pigeonhole(pigeons: isize, holes: isize) -> Formula {
+        let var = |p: isize, h: isize| p * holes + h + 1;
+        let mut formula: Formula = (0..pigeons)
+            .map(|p| (0..holes).map(|h| Literal::new(var(p, h))).collect())
+            .collect();
+        for h in 0..holes {
+            for p in 0..pigeons {
+                for q in p + 1..pigeons {
+                    formula.insert_clause(Clause::new(vec![-var(p, h), -var(q, h)]));
+                }
+            }
+        }
+        formula
+    }
+
+    #[test]
+    fn finds_symmetries_of_pigeonhole_problems() {
+        let formula = pigeonhole(4, 3);
+        let symmetries = formula.symmetries();
+        assert!(!symmetries.is_empty());
+        for symmetry in &symmetries {
+            assert!(symmetry.is_symmetry_of(&formula));
+        }
+
+        let mut broken = formula.clone();
+        assert_eq!(symmetries.len(), broken.break_symmetries());
+        assert!(!crate::sat(broken));
+
+        let mut broken = pigeonhole(3, 3);
+        assert!(broken.break_symmetries() > 0);
+        assert!(crate::sat(broken));
+    }
+
+    #[test]
+    fn lex_leader_keeps_the_least_model() {
+        // swapping 1 and 2 (and negating 3) is a symmetry of these.
+        let symmetry = Symmetry::new(
+            vec![
+                (Var::new(1), Literal::new(2)),
+                (Var::new(2), Literal::new(1)),
+                (Var::new(3), Literal::new(-3)),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let mut vars = VarAllocator::new();
+        let clauses = symmetry.lex_leader(&mut vars);
+        for bits in 0..1 << 3 {
+            let model: Vec<Literal> = (1..=3)
+                .map(|id| Literal::new(if bits & (1 << (id - 1)) != 0 { id } else { -id }))
+                .collect();
+            // the image of a model gives each variable the value of
+            // its image.
+            let holds = |lit: Literal| model.contains(&lit);
+            let key = |f: &dyn Fn(Literal) -> Literal| {
+                (1..=3)
+                    .map(|id| holds(f(Literal::new(id))))
+                    .collect::<Vec<_>>()
+            };
+            let mut formula = Formula::new(clauses.clone());
+            for lit in &model {
+                formula.insert_clause(Clause::from(vec![*lit]));
+            }
+            assert_eq!(
+                key(&|lit| lit) <= key(&|lit| symmetry.apply(lit)),
+                crate::sat(formula),
+                "{:b}",
+                bits
+            );
+        }
+    }
+}
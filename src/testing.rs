@@ -0,0 +1,58 @@
+//! Helpers for the tests of several modules: random formulae, and a
+//! brute force check to compare the solver against.
+
+use crate::cnf::{Clause, Formula, Literal, Var};
+use crate::rng::Rng;
+
+/// Generate a clause of 'len' random literals over the atoms 1 to
+/// 'num_vars'.  The same atom can come up more than once.
+pub(crate) fn random_clause(rng: &mut Rng, num_vars: usize, len: usize) -> Clause {
+    (0..len)
+        .map(|_| Var::new(1 + rng.below(num_vars)).literal(rng.next_bool()))
+        .collect()
+}
+
+/// Generate a formula of 'num_clauses' random clauses, each of 1 to
+/// 'max_len' literals over the atoms 1 to 'num_vars'.
+pub(crate) fn random_formula(
+    rng: &mut Rng,
+    num_vars: usize,
+    num_clauses: usize,
+    max_len: usize,
+) -> Formula {
+    let clauses = (0..num_clauses)
+        .map(|_| {
+            let len = 1 + rng.below(max_len);
+            random_clause(rng, num_vars, len)
+        })
+        .collect();
+    Formula::new(clauses)
+}
+
+/// Check if some assignment of the variables 1 to 'num_vars' makes
+/// all of the clauses of a formula and the assumptions true.
+pub(crate) fn brute_force_sat(num_vars: usize, formula: &Formula, assumptions: &[Literal]) -> bool {
+    (0..1u32 << num_vars).any(|bits| {
+        let is_true = |lit: &Literal| (bits & (1 << (lit.get_id() - 1)) != 0) != lit.is_negated();
+        assumptions.iter().all(is_true) && formula.iter().all(|lits| lits.iter().any(is_true))
+    })
+}
+
+/// Generate pseudo-random formulae around the phase transition, of 1
+/// to 3 literals a clause over up to 8 atoms, each with its number of
+/// atoms.
+pub(crate) fn random_formulae(seed: u64, count: usize) -> impl Iterator<Item = (usize, Formula)> {
+    let mut rng = Rng::new(seed);
+    (0..count).map(move |_| {
+        let num_vars = 1 + rng.below(8);
+        let num_clauses = 1 + rng.below(5 * num_vars);
+        (num_vars, random_formula(&mut rng, num_vars, num_clauses, 3))
+    })
+}
+
+/// Check if some literals make every clause of a formula true.
+pub(crate) fn is_model(formula: &Formula, lits: &[Literal]) -> bool {
+    formula
+        .iter()
+        .all(|clause| clause.iter().any(|lit| lits.contains(lit)))
+}
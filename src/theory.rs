@@ -1,21 +1,101 @@
 //! Theories
 
+pub mod arrays;
+pub mod bv;
+pub mod composite;
 pub mod empty;
 pub mod euf;
+pub mod idl;
+pub mod lia;
+
+use std::error::Error;
+use std::fmt;
 
 use crate::cnf::Literal;
 
 /// A trait for theories, allowing you to implement your own.  See
 /// submodules of sat::smt:: for theories this solver comes with.
+///
+/// The trait is object-safe, and a boxed theory is a theory, so which
+/// theory to use can be picked at runtime.  See 'composite' for
+/// combining several.
 pub trait Theory {
     /// Decide the truth value of a literal in a model under the
     /// theory, if possible.
-    fn decide(&self, lit: Literal) -> Option<bool>;
+    fn decide(&self, lit: Literal) -> Result<Option<bool>, TheoryError>;
 
     /// Add a new literal to the theory.  This will only be called if
-    /// self.decide(lit) is Some(true) or None.
-    fn incorporate(&mut self, lit: Literal);
+    /// self.decide(lit) is Ok(Some(true)) or Ok(None).
+    fn incorporate(&mut self, lit: Literal) -> Result<(), TheoryError>;
+
+    /// Mark a point to return to with 'pop'.  This is done before
+    /// each decision.
+    fn push(&mut self);
+
+    /// Undo every literal incorporated since the 'n'th most recent
+    /// 'push', and drop those points (used for backjumping).
+    fn pop(&mut self, n: usize);
 
-    /// Forget all literals (used for backjumping).
+    /// Forget all literals and points (used to start a new search).
     fn forget(&mut self);
+
+    /// Explain why the theory decided a literal is true: some other
+    /// literals, true before it, which imply it under the theory.
+    /// This lets the solver learn from conflicts involving theory
+    /// decisions.  'None' means there's no explanation, in which case
+    /// every earlier decision is taken as the reason.
+    fn explain(&self, _lit: Literal) -> Option<Vec<Literal>> {
+        None
+    }
+
+    /// Check if the theory gives atoms no meaning, so they can be
+    /// assigned freely.  Some simplifications, like pure literal
+    /// elimination, are only sound if this is true.
+    fn is_propositional(&self) -> bool {
+        false
+    }
 }
+
+impl<T: Theory + ?Sized> Theory for Box<T> {
+    fn decide(&self, lit: Literal) -> Result<Option<bool>, TheoryError> {
+        (**self).decide(lit)
+    }
+
+    fn incorporate(&mut self, lit: Literal) -> Result<(), TheoryError> {
+        (**self).incorporate(lit)
+    }
+
+    fn push(&mut self) {
+        (**self).push()
+    }
+
+    fn pop(&mut self, n: usize) {
+        (**self).pop(n)
+    }
+
+    fn forget(&mut self) {
+        (**self).forget()
+    }
+
+    fn explain(&self, lit: Literal) -> Option<Vec<Literal>> {
+        (**self).explain(lit)
+    }
+
+    fn is_propositional(&self) -> bool {
+        (**self).is_propositional()
+    }
+}
+
+/// Something the theory can't handle, like a literal it knows
+/// nothing about, or a set of literals it finds contradictory.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub struct TheoryError(pub String);
+
+impl fmt::Display for TheoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let TheoryError(message) = self;
+        write!(f, "{}", message)
+    }
+}
+
+impl Error for TheoryError {}
@@ -0,0 +1,255 @@
+//! The extensional theory of arrays, over EUF terms.  Two function
+//! atoms are picked to be 'select' (reading an array at an index) and
+//! 'store' (writing a value to an array at an index), and the others
+//! stay uninterpreted, so indices and values can use them.  This
+//! allows expressing problems like:
+//!
+//!    select(store(a, i, v), j) != v && select(a, j) = w && f(i) = f(j) && v != w
+//!
+//! On top of the congruence closure of EUF, there are the axioms:
+//!
+//!    select(store(a, i, v), i) = v
+//!    i = j || select(store(a, i, v), j) = select(a, j)
+//!    a = b || select(a, k) != select(b, k), for a new index k
+//!
+//! Which are instantiated lazily: only for the stores and selects
+//! which come up, and only once it's known whether the indices are
+//! equal.  If that's not known, both cases are tried.
+
+use std::collections::BTreeSet;
+
+use crate::cnf::Literal;
+use crate::theory::euf::{EUFLiteral, EUFTerm, EUF};
+use crate::theory::{Theory, TheoryError};
+
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub struct Arrays {
+    select: usize,
+    store: usize,
+    /// The literals incorporated so far, and the instances of the
+    /// axioms which don't need a case split.
+    euf: EUF,
+    /// The terms which are arrays: the stores, the arrays which are
+    /// read or written, and the terms which are compared with them.
+    arrays: Vec<EUFTerm>,
+    /// The first atom not used by the literals.  The new indices for
+    /// extensionality come after it.
+    fresh: usize,
+}
+
+impl Arrays {
+    /// Construct an arrays theory for the given set of literals, where
+    /// 'select' is applied to an array and index, and 'store' to an
+    /// array, index, and value.  For cnf literal X, lits[X-1] should be
+    /// the corresponding euf literal.
+    pub fn new(select: usize, store: usize, lits: Vec<EUFLiteral>) -> Arrays {
+        let mut arrays = BTreeSet::new();
+        let mut fresh = 0;
+        let mut todo: Vec<&EUFTerm> = lits.iter().flat_map(|lit| lit.terms()).collect();
+        while let Some(term) = todo.pop() {
+            match term {
+                EUFTerm::Atom(atom) => fresh = fresh.max(atom + 1),
+                EUFTerm::Application {
+                    function_atom,
+                    parameters,
+                } => {
+                    if (*function_atom == select && parameters.len() == 2)
+                        || (*function_atom == store && parameters.len() == 3)
+                    {
+                        arrays.insert(parameters[0].clone());
+                    }
+                    if *function_atom == store && parameters.len() == 3 {
+                        arrays.insert(term.clone());
+                    }
+                    todo.extend(parameters);
+                }
+            }
+        }
+        loop {
+            let before = arrays.len();
+            for lit in &lits {
+                if lit.terms().iter().any(|term| arrays.contains(term)) {
+                    arrays.extend(lit.terms().iter().cloned());
+                }
+            }
+            if arrays.len() == before {
+                break;
+            }
+        }
+
+        Arrays {
+            select,
+            store,
+            euf: EUF::new(lits),
+            arrays: arrays.into_iter().collect(),
+            fresh,
+        }
+    }
+
+    /// Read an array at an index.
+    fn select(&self, array: &EUFTerm, index: &EUFTerm) -> EUFTerm {
+        EUFTerm::ap(self.select, vec![array.clone(), index.clone()])
+    }
+
+    /// The array and index of a select.
+    fn as_select<'a>(&self, term: &'a EUFTerm) -> Option<(&'a EUFTerm, &'a EUFTerm)> {
+        match term {
+            EUFTerm::Application {
+                function_atom,
+                parameters,
+            } if *function_atom == self.select => match &parameters[..] {
+                [array, index] => Some((array, index)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The array, index, and value of a store.
+    fn as_store<'a>(&self, term: &'a EUFTerm) -> Option<(&'a EUFTerm, &'a EUFTerm, &'a EUFTerm)> {
+        match term {
+            EUFTerm::Application {
+                function_atom,
+                parameters,
+            } if *function_atom == self.store => match &parameters[..] {
+                [array, index, value] => Some((array, index, value)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Add the instances of the axioms which don't need a case split,
+    /// until there are no more, or there's a contradiction.  Returns
+    /// two indices which aren't known to be equal or unequal, but need
+    /// to be, if there are any.
+    fn instantiate(&self, euf: &mut EUF) -> Option<(EUFTerm, EUFTerm)> {
+        euf.close();
+        loop {
+            if !euf.is_consistent() {
+                return None;
+            }
+            let mut equalities = Vec::new();
+            let mut inequalities = Vec::new();
+            let mut undecided = None;
+
+            for (x, a) in self.arrays.iter().enumerate() {
+                for (y, b) in self.arrays.iter().enumerate().skip(x + 1) {
+                    if euf.are_unequal(a, b) {
+                        let k = EUFTerm::atom(self.fresh + x * self.arrays.len() + y);
+                        inequalities.push((self.select(a, &k), self.select(b, &k)));
+                    }
+                }
+            }
+
+            let selects: Vec<(EUFTerm, EUFTerm)> = euf
+                .terms()
+                .filter_map(|term| self.as_select(term))
+                .map(|(array, index)| (array.clone(), index.clone()))
+                .collect();
+            for store in &self.arrays {
+                let (a, i, v) = match self.as_store(store) {
+                    Some(store) => store,
+                    None => continue,
+                };
+                equalities.push((self.select(store, i), v.clone()));
+                for (b, j) in &selects {
+                    if !euf.are_equal(b, store) && !euf.are_equal(b, a) || euf.are_equal(i, j) {
+                        continue;
+                    }
+                    if euf.are_unequal(i, j) {
+                        equalities.push((self.select(store, j), self.select(a, j)));
+                    } else if undecided.is_none() {
+                        undecided = Some((i.clone(), j.clone()));
+                    }
+                }
+            }
+
+            let mut is_changed = false;
+            for (left, right) in equalities {
+                if !euf.are_equal(&left, &right) {
+                    euf.assume_equal(&left, &right);
+                    is_changed = true;
+                }
+            }
+            for (left, right) in inequalities {
+                if !euf.are_unequal(&left, &right) {
+                    euf.assume_unequal(&left, &right);
+                    is_changed = true;
+                }
+            }
+            if !is_changed {
+                return undecided;
+            }
+        }
+    }
+
+    /// Check if some literals, and the axioms, have a model, trying
+    /// both cases of each undecided pair of indices.
+    fn is_consistent(&self, mut euf: EUF) -> bool {
+        let undecided = self.instantiate(&mut euf);
+        if !euf.is_consistent() {
+            return false;
+        }
+        match undecided {
+            None => true,
+            Some((i, j)) => {
+                let mut same = euf.clone();
+                same.assume_equal(&i, &j);
+                if self.is_consistent(same) {
+                    return true;
+                }
+                euf.assume_unequal(&i, &j);
+                self.is_consistent(euf)
+            }
+        }
+    }
+
+    /// Check if the literals incorporated so far, and another, have a
+    /// model.
+    fn is_consistent_with(&self, model_lit: Literal) -> bool {
+        let mut euf = self.euf.clone();
+        euf.incorporate(model_lit).is_ok() && self.is_consistent(euf)
+    }
+}
+
+/// Literals are not explained, as the case splits aren't recorded.
+impl Theory for Arrays {
+    fn decide(&self, model_lit: Literal) -> Result<Option<bool>, TheoryError> {
+        if let Some(b) = self.euf.decide(model_lit)? {
+            Ok(Some(b))
+        } else if !self.is_consistent_with(model_lit) {
+            Ok(Some(false))
+        } else if !self.is_consistent_with(model_lit.negate()) {
+            Ok(Some(true))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn incorporate(&mut self, model_lit: Literal) -> Result<(), TheoryError> {
+        let mut euf = self.euf.clone();
+        euf.incorporate(model_lit)?;
+        self.instantiate(&mut euf);
+        if !euf.is_consistent() {
+            return Err(TheoryError(format!(
+                "contradiction: {} breaks an axiom of arrays",
+                model_lit
+            )));
+        }
+        self.euf = euf;
+        Ok(())
+    }
+
+    fn push(&mut self) {
+        self.euf.push();
+    }
+
+    fn pop(&mut self, n: usize) {
+        self.euf.pop(n);
+    }
+
+    fn forget(&mut self) {
+        self.euf.forget();
+    }
+}
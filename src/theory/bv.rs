@@ -0,0 +1,346 @@
+//! The theory of fixed-width bit-vectors, with arithmetic modulo
+//! 2^width, bitwise operators, and unsigned comparisons.  This allows
+//! expressing problems like:
+//!
+//!    x * y == 12 && x + y < 8 && (x & 1) == 0
+//!
+//! Rather than being checked during the search, like the other
+//! theories, bit-vectors are translated to CNF up front (bit-blasting):
+//! each variable gets an atom for each bit, each term and comparison
+//! becomes a circuit over them, and each atom is made equivalent to
+//! the output of its comparison.  The result is solved with the empty
+//! theory.
+
+use std::collections::BTreeMap;
+
+use crate::cnf::encodings::circuit;
+use crate::cnf::{Clause, Formula, Literal, Var, VarAllocator};
+
+/// A binary operator on bit-vectors.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub enum BVOp {
+    Add,
+    Sub,
+    Mul,
+    And,
+    Or,
+    Xor,
+}
+
+/// A bit-vector term: a variable, a constant, or an operator applied
+/// to terms.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub enum BVTerm {
+    Var(usize),
+    /// A constant, cut down to the width.
+    Const(u64),
+    Not(Box<BVTerm>),
+    Op(BVOp, Box<BVTerm>, Box<BVTerm>),
+}
+
+impl BVTerm {
+    /// Construct a bit-vector variable term.
+    pub fn var(var: usize) -> BVTerm {
+        BVTerm::Var(var)
+    }
+
+    /// Construct a bit-vector constant term.
+    pub fn constant(value: u64) -> BVTerm {
+        BVTerm::Const(value)
+    }
+
+    /// Construct the bitwise negation of a term.
+    pub fn complement(term: BVTerm) -> BVTerm {
+        BVTerm::Not(Box::new(term))
+    }
+
+    /// Construct an operator application term.
+    pub fn op(op: BVOp, left: BVTerm, right: BVTerm) -> BVTerm {
+        BVTerm::Op(op, Box::new(left), Box::new(right))
+    }
+}
+
+/// An unsigned comparison of bit-vectors.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub enum BVComparison {
+    Equal,
+    Less,
+    LessOrEqual,
+}
+
+/// A BV literal is a comparison of two terms, or its negation.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub struct BVLiteral {
+    is_negated: bool,
+    comparison: BVComparison,
+    left: BVTerm,
+    right: BVTerm,
+}
+
+impl BVLiteral {
+    /// Construct a BV comparison literal.
+    pub fn new(comparison: BVComparison, left: BVTerm, right: BVTerm) -> BVLiteral {
+        BVLiteral {
+            is_negated: false,
+            comparison,
+            left,
+            right,
+        }
+    }
+
+    /// Turn a comparison into its negation, or the other way around.
+    pub fn negate(self) -> BVLiteral {
+        BVLiteral {
+            is_negated: !self.is_negated,
+            ..self
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub struct BV {
+    width: usize,
+    lits: Vec<BVLiteral>,
+}
+
+impl BV {
+    /// Construct a BV theory for the given set of literals, over
+    /// bit-vectors of some width.  For cnf literal X, lits[X-1] should
+    /// be the corresponding bv literal.
+    pub fn new(width: usize, lits: Vec<BVLiteral>) -> BV {
+        BV { width, lits }
+    }
+
+    /// Bit-blast the literals, giving a formula with the clauses of
+    /// the original and of the circuits.  The new atoms come after
+    /// those of the formula and the literals.
+    pub fn to_cnf(&self, formula: &Formula) -> BitBlasted {
+        let num_atoms = formula
+            .iter()
+            .flatten()
+            .map(|lit| lit.get_id() as usize)
+            .chain(Some(self.lits.len()))
+            .max()
+            .unwrap_or(0);
+        let mut vars = VarAllocator::new();
+        if num_atoms > 0 {
+            vars.reserve(Var::new(num_atoms));
+        }
+        let mut blaster = Blaster {
+            width: self.width,
+            vars: &mut vars,
+            clauses: formula.clauses(),
+            bits: BTreeMap::new(),
+            truth: None,
+        };
+
+        for (i, lit) in self.lits.iter().enumerate() {
+            let left = blaster.term(&lit.left);
+            let right = blaster.term(&lit.right);
+            let (vars, clauses) = (&mut *blaster.vars, &mut blaster.clauses);
+            let out = match lit.comparison {
+                BVComparison::Equal => circuit::equal(&left, &right, vars, clauses),
+                BVComparison::Less => circuit::less_than(&left, &right, vars, clauses),
+                BVComparison::LessOrEqual => circuit::less_or_equal(&left, &right, vars, clauses),
+            };
+            let out = if lit.is_negated { out.negate() } else { out };
+            let atom = Literal::new(i as isize + 1);
+            clauses.push(Clause::from(vec![atom.negate(), out]));
+            clauses.push(Clause::from(vec![atom, out.negate()]));
+        }
+
+        BitBlasted {
+            formula: Formula::new(blaster.clauses),
+            num_atoms,
+            bits: blaster.bits,
+        }
+    }
+}
+
+/// A formula with its bit-vector literals turned into circuits.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub struct BitBlasted {
+    pub formula: Formula,
+    /// The atoms of the original formula and literals, which come
+    /// before the new ones.
+    pub num_atoms: usize,
+    /// The bits of each variable, least significant first.
+    pub bits: BTreeMap<usize, Vec<Literal>>,
+}
+
+impl BitBlasted {
+    /// The value of a variable in a model of the formula.
+    pub fn value(&self, var: usize, model: &[Literal]) -> Option<u64> {
+        let bits = self.bits.get(&var)?;
+        Some(
+            bits.iter()
+                .take(64)
+                .enumerate()
+                .filter(|(_, bit)| model.contains(bit))
+                .map(|(i, _)| 1 << i)
+                .sum(),
+        )
+    }
+}
+
+/// The state of bit-blasting, between terms.
+#[derive(Debug)]
+struct Blaster<'a> {
+    width: usize,
+    vars: &'a mut VarAllocator,
+    clauses: Vec<Clause>,
+    bits: BTreeMap<usize, Vec<Literal>>,
+    /// A literal which is always true, once one is needed.
+    truth: Option<Literal>,
+}
+
+impl Blaster<'_> {
+    /// The bits of a term.
+    fn term(&mut self, term: &BVTerm) -> Vec<Literal> {
+        match term {
+            BVTerm::Var(var) => {
+                let (width, vars) = (self.width, &mut *self.vars);
+                self.bits
+                    .entry(*var)
+                    .or_insert_with(|| (0..width).map(|_| vars.fresh().positive()).collect())
+                    .clone()
+            }
+            BVTerm::Const(value) => (0..self.width)
+                .map(|i| self.constant(i < 64 && value >> i & 1 == 1))
+                .collect(),
+            BVTerm::Not(term) => self.term(term).iter().map(|bit| bit.negate()).collect(),
+            BVTerm::Op(op, left, right) => {
+                let left = self.term(left);
+                let right = self.term(right);
+                self.op(*op, &left, &right)
+            }
+        }
+    }
+
+    /// The bits of an operator applied to some bits.
+    fn op(&mut self, op: BVOp, left: &[Literal], right: &[Literal]) -> Vec<Literal> {
+        match op {
+            BVOp::Add => self.add(left, right),
+            // x - y is x + ~y + 1.
+            BVOp::Sub => {
+                let not: Vec<Literal> = right.iter().map(|bit| bit.negate()).collect();
+                let one = self.constant(true);
+                let negated = self.add(&not, &[one]);
+                self.add(left, &negated)
+            }
+            // shift and add.
+            BVOp::Mul => {
+                let zero = self.constant(false);
+                let mut product = vec![zero; self.width];
+                for (i, y) in right.iter().enumerate() {
+                    let mut partial = vec![zero; i];
+                    for x in &left[..self.width - i] {
+                        partial.push(circuit::and(&[*x, *y], self.vars, &mut self.clauses));
+                    }
+                    product = self.add(&product, &partial);
+                }
+                product
+            }
+            BVOp::And => left
+                .iter()
+                .zip(right)
+                .map(|(x, y)| circuit::and(&[*x, *y], self.vars, &mut self.clauses))
+                .collect(),
+            BVOp::Or => left
+                .iter()
+                .zip(right)
+                .map(|(x, y)| circuit::or(&[*x, *y], self.vars, &mut self.clauses))
+                .collect(),
+            BVOp::Xor => left
+                .iter()
+                .zip(right)
+                .map(|(x, y)| circuit::xor(*x, *y, self.vars, &mut self.clauses))
+                .collect(),
+        }
+    }
+
+    /// The sum of two numbers, modulo 2^width.
+    fn add(&mut self, left: &[Literal], right: &[Literal]) -> Vec<Literal> {
+        let mut sum = circuit::add(left, right, self.vars, &mut self.clauses);
+        sum.truncate(self.width);
+        sum
+    }
+
+    /// A literal which is always true or always false.
+    fn constant(&mut self, value: bool) -> Literal {
+        let truth = match self.truth {
+            Some(truth) => truth,
+            None => {
+                let truth = circuit::constant(true, self.vars, &mut self.clauses);
+                self.truth = Some(truth);
+                truth
+            }
+        };
+        if value {
+            truth
+        } else {
+            truth.negate()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::theory::empty::Empty;
+
+    #[test]
+    fn blasts_arithmetic() {
+        // x * y == 6, x - y == 1, and (x ^ y) | 4 == 5, in 3 bits.
+        let x = || BVTerm::var(1);
+        let y = || BVTerm::var(2);
+        let bv = BV::new(
+            3,
+            vec![
+                BVLiteral::new(
+                    BVComparison::Equal,
+                    BVTerm::op(BVOp::Mul, x(), y()),
+                    BVTerm::constant(6),
+                ),
+                BVLiteral::new(
+                    BVComparison::Equal,
+                    BVTerm::op(BVOp::Sub, x(), y()),
+                    BVTerm::constant(1),
+                ),
+                BVLiteral::new(
+                    BVComparison::Equal,
+                    BVTerm::op(
+                        BVOp::Or,
+                        BVTerm::op(BVOp::Xor, x(), y()),
+                        BVTerm::constant(4),
+                    ),
+                    BVTerm::constant(5),
+                ),
+            ],
+        );
+        let blasted = bv.to_cnf(&cnf![[1], [2], [3]]);
+        let model = crate::sat_assignment(blasted.formula.clone()).unwrap();
+        assert_eq!(Some(3), blasted.value(1, &model));
+        assert_eq!(Some(2), blasted.value(2, &model));
+
+        // x + 1 < x only when x + 1 wraps around, and not x < x.
+        let bv = BV::new(
+            3,
+            vec![
+                BVLiteral::new(
+                    BVComparison::Less,
+                    BVTerm::op(BVOp::Add, x(), BVTerm::constant(1)),
+                    x(),
+                ),
+                BVLiteral::new(BVComparison::LessOrEqual, x(), x()).negate(),
+            ],
+        );
+        let blasted = bv.to_cnf(&cnf![[1]]);
+        let model = crate::sat_assignment(blasted.formula.clone()).unwrap();
+        assert_eq!(Some(7), blasted.value(1, &model));
+        assert_eq!(
+            Ok(false),
+            crate::smt(&mut Empty::new(), bv.to_cnf(&cnf![[2]]).formula)
+        );
+    }
+}
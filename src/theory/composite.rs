@@ -0,0 +1,128 @@
+//! A theory made of independent theories, each giving meaning to some
+//! of the atoms of the formula.  The theories are trait objects, so
+//! they can be of different types, picked at runtime.  Atoms which no
+//! theory gives meaning to can be assigned freely.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::cnf::Literal;
+use crate::theory::{Theory, TheoryError};
+
+#[derive(Default)]
+pub struct Composite {
+    theories: Vec<Box<dyn Theory>>,
+    /// The atoms of each theory: for its atom X, atoms[X-1] is the
+    /// atom of the formula.
+    atoms: Vec<Vec<usize>>,
+    /// The theory of each atom of the formula which has one, and its
+    /// atom in that theory.
+    owners: BTreeMap<usize, (usize, usize)>,
+}
+
+impl Composite {
+    /// Construct a composite theory with no theories in it.
+    pub fn new() -> Composite {
+        Composite::default()
+    }
+
+    /// Add a theory, where for its atom X, atoms[X-1] is the atom of
+    /// the formula.  Returns an error if one of the atoms already
+    /// belongs to a theory, as the theories can't share atoms.
+    pub fn add(&mut self, theory: Box<dyn Theory>, atoms: Vec<usize>) -> Result<(), TheoryError> {
+        let index = self.theories.len();
+        let mut owners = self.owners.clone();
+        for (i, atom) in atoms.iter().enumerate() {
+            if *atom == 0 || owners.insert(*atom, (index, i + 1)).is_some() {
+                return Err(TheoryError(format!(
+                    "atom {} can't be given to theory {}",
+                    atom, index
+                )));
+            }
+        }
+        self.theories.push(theory);
+        self.atoms.push(atoms);
+        self.owners = owners;
+        Ok(())
+    }
+
+    /// The theory of a literal, and the literal in that theory.
+    fn to_local(&self, lit: Literal) -> Option<(usize, Literal)> {
+        let (index, atom) = self.owners.get(&(lit.get_id() as usize))?;
+        let local = Literal::new(*atom as isize);
+        if lit.is_negated() {
+            Some((*index, local.negate()))
+        } else {
+            Some((*index, local))
+        }
+    }
+
+    /// A literal of a theory, as a literal of the formula.
+    fn to_global(&self, index: usize, local: Literal) -> Option<Literal> {
+        let atom = self.atoms[index].get((local.get_id() as usize).checked_sub(1)?)?;
+        let lit = Literal::new(*atom as isize);
+        if local.is_negated() {
+            Some(lit.negate())
+        } else {
+            Some(lit)
+        }
+    }
+}
+
+/// The theories can't be shown, only their atoms.
+impl fmt::Debug for Composite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Composite")
+            .field("atoms", &self.atoms)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Theory for Composite {
+    fn decide(&self, lit: Literal) -> Result<Option<bool>, TheoryError> {
+        match self.to_local(lit) {
+            Some((index, local)) => self.theories[index].decide(local),
+            None => Ok(None),
+        }
+    }
+
+    fn incorporate(&mut self, lit: Literal) -> Result<(), TheoryError> {
+        match self.to_local(lit) {
+            Some((index, local)) => self.theories[index].incorporate(local),
+            None => Ok(()),
+        }
+    }
+
+    fn push(&mut self) {
+        for theory in &mut self.theories {
+            theory.push();
+        }
+    }
+
+    fn pop(&mut self, n: usize) {
+        for theory in &mut self.theories {
+            theory.pop(n);
+        }
+    }
+
+    fn forget(&mut self) {
+        for theory in &mut self.theories {
+            theory.forget();
+        }
+    }
+
+    /// A literal is explained by its theory, so the explanation only
+    /// has literals of that theory.
+    fn explain(&self, lit: Literal) -> Option<Vec<Literal>> {
+        let (index, local) = self.to_local(lit)?;
+        self.theories[index]
+            .explain(local)?
+            .into_iter()
+            .map(|local| self.to_global(index, local))
+            .collect()
+    }
+
+    fn is_propositional(&self) -> bool {
+        self.theories.iter().all(|theory| theory.is_propositional())
+    }
+}
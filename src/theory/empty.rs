@@ -1,7 +1,7 @@
 //! The empty theory.  Instantiate this to get a SAT solver.
 
 use crate::cnf::Literal;
-use crate::theory::Theory;
+use crate::theory::{Theory, TheoryError};
 
 /// The empty theory has no state.
 #[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd, Eq, Ord)]
@@ -14,11 +14,21 @@ impl Empty {
 }
 
 impl Theory for Empty {
-    fn decide(&self, _lit: Literal) -> Option<bool> {
-        None
+    fn decide(&self, _lit: Literal) -> Result<Option<bool>, TheoryError> {
+        Ok(None)
     }
 
-    fn incorporate(&mut self, _lit: Literal) {}
+    fn incorporate(&mut self, _lit: Literal) -> Result<(), TheoryError> {
+        Ok(())
+    }
+
+    fn push(&mut self) {}
+
+    fn pop(&mut self, _n: usize) {}
 
     fn forget(&mut self) {}
+
+    fn is_propositional(&self) -> bool {
+        true
+    }
 }
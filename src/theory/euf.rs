@@ -2,12 +2,16 @@
 //! allows expressing problems like:
 //!
 //!    g(a) = c && (f(g(a)) != f(c) || g(a) = d) && c != d
+//!
+//! As well as (in)equalities, there are 'distinct' literals, saying
+//! that no two of some terms are equal.  These are kept as one
+//! constraint, rather than an inequality for each pair.
 
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
 
 use crate::cnf::Literal;
-use crate::theory::Theory;
+use crate::theory::{Theory, TheoryError};
 
 /// An EUF term is either an atom (represented as numbers) or a
 /// function applied to an EUF term.
@@ -35,12 +39,14 @@ impl EUFTerm {
     }
 }
 
-/// An EUF literal is an (in)equality applied to two EUF terms.
+/// An EUF literal is an (in)equality applied to two EUF terms, or a
+/// 'distinct' of any number of terms, or its negation.  As an
+/// inequality is a 'distinct' of two terms, both are kept as whether
+/// some two of the terms are equal.
 #[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
 pub struct EUFLiteral {
     is_equality: bool,
-    left: EUFTerm,
-    right: EUFTerm,
+    terms: Vec<EUFTerm>,
 }
 
 impl EUFLiteral {
@@ -48,8 +54,16 @@ impl EUFLiteral {
     pub fn new(left: EUFTerm, right: EUFTerm) -> EUFLiteral {
         EUFLiteral {
             is_equality: true,
-            left,
-            right,
+            terms: vec![left, right],
+        }
+    }
+
+    /// Construct an EUF literal saying no two of the terms are equal.
+    /// Its negation says some two of them are.
+    pub fn distinct(terms: Vec<EUFTerm>) -> EUFLiteral {
+        EUFLiteral {
+            is_equality: false,
+            terms,
         }
     }
 
@@ -58,18 +72,59 @@ impl EUFLiteral {
     pub fn negate(self) -> EUFLiteral {
         EUFLiteral {
             is_equality: !self.is_equality,
-            left: self.left,
-            right: self.right,
+            terms: self.terms,
         }
     }
+
+    /// The terms of the literal.
+    pub(crate) fn terms(&self) -> &[EUFTerm] {
+        &self.terms
+    }
+
+    /// Each pair of terms of the literal.
+    fn pairs(&self) -> impl Iterator<Item = (&EUFTerm, &EUFTerm)> {
+        pairs(&self.terms)
+    }
+}
+
+/// Why two terms are joined in the proof forest.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+enum Reason {
+    /// An equality literal of the model.
+    Asserted(Literal),
+    /// Two applications of the same function to equal parameters.
+    Congruence(EUFTerm, EUFTerm),
+    /// An instance of an axiom of a theory built on this one, which
+    /// has no literals.
+    Axiom,
+    /// The only two terms of a negated 'distinct' which can be equal.
+    /// This isn't explained, as the inequalities of the other terms
+    /// could depend on it.
+    Chosen(Literal),
 }
 
 #[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
 pub struct EUF {
     lits: Vec<EUFLiteral>,
     superterms: BTreeMap<EUFTerm, BTreeSet<EUFTerm>>,
-    equivs: BTreeMap<EUFTerm, BTreeSet<EUFTerm>>,
-    inequivs: BTreeSet<(EUFTerm, EUFTerm)>,
+    /// The proof forest: the parent of each term which isn't the root
+    /// of its tree, and why they're equal.  Terms are equal if they're
+    /// in the same tree, and the path between them explains why.
+    forest: BTreeMap<EUFTerm, (EUFTerm, Reason)>,
+    /// The groups of terms which are all different, with the literals
+    /// which gave them, if any.
+    inequivs: Vec<(Vec<EUFTerm>, Option<Literal>)>,
+    /// The groups of terms some two of which are equal, from negated
+    /// 'distinct' literals.
+    choices: Vec<(Vec<EUFTerm>, Literal)>,
+    /// The literals incorporated, in order.
+    incorporated: Vec<Literal>,
+    /// The old parent of each term changed in the proof forest since
+    /// the first point, in order, for undoing.
+    undo: Vec<(EUFTerm, Option<(EUFTerm, Reason)>)>,
+    /// The lengths of 'undo', 'inequivs', 'choices', and
+    /// 'incorporated' at each point to return to.
+    points: Vec<(usize, usize, usize, usize)>,
 }
 
 impl EUF {
@@ -81,314 +136,477 @@ impl EUF {
         EUF {
             lits,
             superterms,
-            equivs: BTreeMap::new(),
-            inequivs: BTreeSet::new(),
+            forest: BTreeMap::new(),
+            inequivs: Vec::new(),
+            choices: Vec::new(),
+            incorporated: Vec::new(),
+            undo: Vec::new(),
+            points: Vec::new(),
         }
     }
 
-    fn to_euf_lit(&self, model_lit: Literal) -> EUFLiteral {
-        let euf_lit = self.lits[(model_lit.get_id() as usize) - 1].clone();
-        if model_lit.is_negated() {
-            EUFLiteral {
-                is_equality: !euf_lit.is_equality,
-                left: euf_lit.left,
-                right: euf_lit.right,
+    fn to_euf_lit(&self, model_lit: Literal) -> Result<EUFLiteral, TheoryError> {
+        let euf_lit = match self.lits.get((model_lit.get_id() as usize) - 1) {
+            Some(euf_lit) => euf_lit.clone(),
+            None => {
+                return Err(TheoryError(format!(
+                    "no EUF literal for atom {}",
+                    model_lit.get_id()
+                )))
             }
+        };
+        if model_lit.is_negated() {
+            Ok(euf_lit.negate())
         } else {
-            euf_lit
+            Ok(euf_lit)
         }
     }
-}
-
-impl Theory for EUF {
-    fn decide(&self, model_lit: Literal) -> Option<bool> {
-        let euf_lit = self.to_euf_lit(model_lit);
 
-        if euf_lit.left == euf_lit.right {
-            return Some(euf_lit.is_equality);
+    /// The root of the tree a term is in.
+    fn root<'a>(&'a self, mut term: &'a EUFTerm) -> &'a EUFTerm {
+        while let Some((parent, _)) = self.forest.get(term) {
+            term = parent;
         }
+        term
+    }
+
+    /// Check if two terms are known to be equal.
+    pub(crate) fn are_equal(&self, left: &EUFTerm, right: &EUFTerm) -> bool {
+        left == right || self.root(left) == self.root(right)
+    }
+
+    /// The inequalities which make two terms unequal, as the terms
+    /// equal to the left and right, and the literal.
+    fn inequalities<'a>(
+        &'a self,
+        left: &'a EUFTerm,
+        right: &'a EUFTerm,
+    ) -> impl Iterator<Item = (&'a EUFTerm, &'a EUFTerm, Option<Literal>)> + 'a {
+        self.inequivs.iter().filter_map(move |(terms, lit)| {
+            let a = terms.iter().position(|a| self.are_equal(left, a))?;
+            let b = (0..terms.len()).find(|b| *b != a && self.are_equal(right, &terms[*b]))?;
+            Some((&terms[a], &terms[b], *lit))
+        })
+    }
+
+    /// Check if two terms are known to be unequal.
+    pub(crate) fn are_unequal(&self, left: &EUFTerm, right: &EUFTerm) -> bool {
+        left != right && self.inequalities(left, right).next().is_some()
+    }
+
+    /// Make two terms equal, and then any applications which are
+    /// equal by congruence.
+    fn merge(&mut self, left: &EUFTerm, right: &EUFTerm, reason: Reason) {
+        self.link(left, right, reason);
+        self.close();
+    }
 
-        match (
-            euf_lit.is_equality,
-            are_equal(&self.equivs, &euf_lit.left, &euf_lit.right),
-            are_unequal(&self.equivs, &self.inequivs, &euf_lit.left, &euf_lit.right),
-        ) {
-            (true, true, false) => Some(true),
-            (true, false, true) => Some(false),
-            (false, true, false) => Some(false),
-            (false, false, true) => Some(true),
-            (_, true, true) => panic!(
-                "contradiction: {:?} and {:?} are both equal and unequal",
-                euf_lit.left, euf_lit.right
-            ),
-            (_, false, false) => None,
+    /// Make any applications which are equal by congruence equal.
+    /// This is only needed after adding terms.
+    pub(crate) fn close(&mut self) {
+        while let Some((a, b)) = self.find_congruence() {
+            let reason = Reason::Congruence(a.clone(), b.clone());
+            self.link(&a, &b, reason);
         }
     }
 
-    fn incorporate(&mut self, model_lit: Literal) {
-        let el = self.to_euf_lit(model_lit);
-        if el.is_equality {
-            if el.left == el.right {
-                return;
-            }
-            add_equiv(&mut self.equivs, &self.superterms, &el.left, &el.right)
-        } else {
-            if el.left == el.right {
-                panic!("contradiction: {:?} is not equal to itself", el.left);
-            }
-            self.inequivs.insert((el.left.clone(), el.right.clone()));
+    /// The terms, and their subterms.
+    pub(crate) fn terms(&self) -> impl Iterator<Item = &EUFTerm> {
+        self.superterms.keys()
+    }
+
+    /// Add a term which isn't in any literal, and its subterms.  Terms
+    /// aren't removed when returning to a point.
+    pub(crate) fn add_term(&mut self, term: &EUFTerm) {
+        if !self.superterms.contains_key(term) {
+            add_superterms(&mut self.superterms, term);
         }
+    }
 
-        infer_implicit_equalities(&mut self.equivs, &self.superterms);
+    /// Make two terms equal, without a literal.  This is for theories
+    /// built on this one, which can't be explained.
+    pub(crate) fn assume_equal(&mut self, left: &EUFTerm, right: &EUFTerm) {
+        self.add_term(left);
+        self.add_term(right);
+        self.merge(left, right, Reason::Axiom);
     }
 
-    fn forget(&mut self) {
-        self.equivs = BTreeMap::new();
-        self.inequivs = BTreeSet::new();
+    /// Make two terms unequal, without a literal.
+    pub(crate) fn assume_unequal(&mut self, left: &EUFTerm, right: &EUFTerm) {
+        self.add_term(left);
+        self.add_term(right);
+        self.close();
+        self.inequivs
+            .push((vec![left.clone(), right.clone()], None));
+        self.choose();
     }
-}
 
-/// Given a set of literals, compute the superterm relation.
-fn compute_superterms(lits: &[EUFLiteral]) -> BTreeMap<EUFTerm, BTreeSet<EUFTerm>> {
-    fn go(superterms: &mut BTreeMap<EUFTerm, BTreeSet<EUFTerm>>, term: &EUFTerm) {
-        superterms.entry(term.clone()).or_default();
-
-        if let EUFTerm::Application { parameters, .. } = term {
-            for p in parameters {
-                superterms
-                    .entry(p.clone())
-                    .or_default()
-                    .insert(term.clone());
-                go(superterms, p);
-            }
+    /// Check that no terms are both equal and unequal, and that some
+    /// two terms of each negated 'distinct' can be equal.  Making two
+    /// terms equal can make others equal by congruence, so each pair
+    /// which isn't known to be unequal is tried in turn.
+    pub(crate) fn is_consistent(&self) -> bool {
+        if !self
+            .inequivs
+            .iter()
+            .all(|(terms, _)| pairs(terms).all(|(a, b)| !self.are_equal(a, b)))
+        {
+            return false;
         }
+        let (terms, lit) = match self
+            .choices
+            .iter()
+            .find(|(terms, _)| !pairs(terms).any(|(a, b)| self.are_equal(a, b)))
+        {
+            Some(choice) => choice,
+            None => return true,
+        };
+        pairs(terms)
+            .filter(|(a, b)| !self.are_unequal(a, b))
+            .any(|(a, b)| {
+                let mut euf = self.clone();
+                euf.merge(a, b, Reason::Chosen(*lit));
+                euf.choose();
+                euf.is_consistent()
+            })
     }
 
-    let mut superterms: BTreeMap<EUFTerm, BTreeSet<EUFTerm>> = BTreeMap::new();
-    for lit in lits {
-        go(&mut superterms, &lit.left);
-        go(&mut superterms, &lit.right);
+    /// Whether some two of the terms are equal, if that's known.
+    fn has_equal(&self, terms: &[EUFTerm]) -> Result<Option<bool>, TheoryError> {
+        let mut all_unequal = true;
+        for (a, b) in pairs(terms) {
+            match (self.are_equal(a, b), self.are_unequal(a, b)) {
+                (true, true) => {
+                    return Err(TheoryError(format!(
+                        "contradiction: {:?} and {:?} are both equal and unequal",
+                        a, b
+                    )))
+                }
+                (true, false) => return Ok(Some(true)),
+                (false, is_unequal) => all_unequal &= is_unequal,
+            }
+        }
+        Ok(if all_unequal { Some(false) } else { None })
     }
-    superterms
-}
 
-/// Add an equivalence relation to the proof tree.
-fn add_equiv(
-    rel: &mut BTreeMap<EUFTerm, BTreeSet<EUFTerm>>,
-    superterms: &BTreeMap<EUFTerm, BTreeSet<EUFTerm>>,
-    left: &EUFTerm,
-    right: &EUFTerm,
-) {
-    #[allow(clippy::too_many_arguments)]
-    fn go(
-        rel: &mut BTreeMap<EUFTerm, BTreeSet<EUFTerm>>,
-        superterms: &BTreeMap<EUFTerm, BTreeSet<EUFTerm>>,
-        term: &EUFTerm,
-        equiv: &EUFTerm,
-        function_atom: usize,
-        parameters: &[EUFTerm],
-        prefix: Vec<EUFTerm>,
-        i: usize,
-        changed: bool,
-    ) {
-        if i == parameters.len() {
-            if changed {
-                let new_term = EUFTerm::Application {
-                    function_atom,
-                    parameters: prefix,
-                };
-                // only add equivalences for terms which exist in the
-                // problem.
-                if !superterms.contains_key(&new_term) {
-                    return;
+    /// Make the terms of each negated 'distinct' equal when they're
+    /// the only two which can be, until nothing changes.
+    fn choose(&mut self) {
+        loop {
+            let chosen = self.choices.iter().find_map(|(terms, lit)| {
+                if pairs(terms).any(|(a, b)| self.are_equal(a, b)) {
+                    return None;
                 }
-                add_equiv(
-                    rel,
-                    superterms,
-                    &EUFTerm::Application {
-                        function_atom,
-                        parameters: parameters.to_vec(),
-                    },
-                    &new_term,
-                );
-            }
-        } else {
-            let p = parameters[i].clone();
-            if p == *term {
-                let mut prefix_changed = prefix.clone();
-                prefix_changed.push(equiv.clone());
-                go(
-                    rel,
-                    superterms,
-                    term,
-                    equiv,
-                    function_atom,
-                    parameters,
-                    prefix_changed,
-                    i + 1,
-                    true,
-                );
+                let mut open = pairs(terms).filter(|(a, b)| !self.are_unequal(a, b));
+                match (open.next(), open.next()) {
+                    (Some((a, b)), None) => Some((a.clone(), b.clone(), *lit)),
+                    _ => None,
+                }
+            });
+            match chosen {
+                Some((a, b, lit)) => self.merge(&a, &b, Reason::Chosen(lit)),
+                None => return,
             }
-            let mut prefix_same = prefix.clone();
-            prefix_same.push(p);
-            go(
-                rel,
-                superterms,
-                term,
-                equiv,
-                function_atom,
-                parameters,
-                prefix_same,
-                i + 1,
-                changed,
-            );
         }
     }
 
-    rel.entry(left.clone()).or_default().insert(right.clone());
-    rel.entry(right.clone()).or_default().insert(left.clone());
-
-    let empty_set = BTreeSet::new();
-    for superterm in superterms.get(left).unwrap_or(&empty_set).iter() {
-        match superterm {
-            EUFTerm::Application {
-                function_atom,
-                parameters,
-            } => go(
-                rel,
-                superterms,
-                left,
-                right,
-                *function_atom,
-                parameters,
-                Vec::new(),
-                0,
-                false,
-            ),
-            _ => continue,
+    /// Check if the literals incorporated so far, and another, are
+    /// consistent.
+    fn is_consistent_with(&self, model_lit: Literal) -> bool {
+        let mut euf = self.clone();
+        euf.incorporate(model_lit).is_ok() && euf.is_consistent()
+    }
+
+    /// Join the trees of two terms, if they're not already joined, by
+    /// making the left the root of its tree and giving it the right as
+    /// its parent.
+    fn link(&mut self, left: &EUFTerm, right: &EUFTerm, reason: Reason) {
+        if self.are_equal(left, right) {
+            return;
         }
+        let mut path = Vec::new();
+        let mut term = left.clone();
+        while let Some((parent, reason)) = self.forest.get(&term).cloned() {
+            path.push((term, parent.clone(), reason));
+            term = parent;
+        }
+        for (term, parent, reason) in path {
+            self.set_parent(parent, (term, reason));
+        }
+        self.set_parent(left.clone(), (right.clone(), reason));
     }
-    for superterm in superterms.get(right).unwrap_or(&empty_set).iter() {
-        match superterm {
-            EUFTerm::Application {
-                function_atom,
-                parameters,
-            } => go(
-                rel,
-                superterms,
-                right,
-                left,
-                *function_atom,
-                parameters,
-                Vec::new(),
-                0,
-                false,
-            ),
-            _ => continue,
+
+    /// Change the parent of a term, remembering the old one if there
+    /// is a point to return to.
+    fn set_parent(&mut self, term: EUFTerm, parent: (EUFTerm, Reason)) {
+        let old = self.forest.insert(term.clone(), parent);
+        if !self.points.is_empty() {
+            self.undo.push((term, old));
         }
     }
-}
 
-/// Infer any new implicit equalities.
-///
-/// Currently just finds new function equalities.  It might also be
-/// good to compute the transitive closure of the equivalence sets, to
-/// avoid tree-walking in are_equal.
-fn infer_implicit_equalities(
-    rel: &mut BTreeMap<EUFTerm, BTreeSet<EUFTerm>>,
-    superterms: &BTreeMap<EUFTerm, BTreeSet<EUFTerm>>,
-) {
-    loop {
-        let mut new_equivalences = Vec::new();
-
-        // find function equalities
-        'fn_a: for a in superterms.keys() {
-            match a {
+    /// Find two applications which are equal by congruence, but not
+    /// yet known to be.  Only terms which exist in the problem are
+    /// considered.
+    fn find_congruence(&self) -> Option<(EUFTerm, EUFTerm)> {
+        for a in self.superterms.keys() {
+            let (af, aps) = match a {
                 EUFTerm::Application {
-                    function_atom: af,
-                    parameters: aps,
-                } => {
-                    'fn_b: for b in superterms.keys() {
-                        if *a == *b {
-                            continue 'fn_b;
-                        }
-                        match b {
-                            EUFTerm::Application {
-                                function_atom: bf,
-                                parameters: bps,
-                            } if af == bf && aps.len() == bps.len() && !are_equal(rel, a, b) => {
-                                for (ap, bp) in aps.iter().zip(bps.iter()) {
-                                    if !are_equal(rel, ap, bp) {
-                                        continue 'fn_b;
-                                    }
-                                }
-                                // atoms, arities, and parameters are equal
-                                new_equivalences.push((a.clone(), b.clone()));
-                            }
-                            _ => continue 'fn_b,
-                        }
+                    function_atom,
+                    parameters,
+                } => (function_atom, parameters),
+                _ => continue,
+            };
+            for b in self.superterms.range(a..).map(|(b, _)| b).skip(1) {
+                match b {
+                    EUFTerm::Application {
+                        function_atom: bf,
+                        parameters: bps,
+                    } if af == bf
+                        && aps.len() == bps.len()
+                        && !self.are_equal(a, b)
+                        && aps.iter().zip(bps).all(|(ap, bp)| self.are_equal(ap, bp)) =>
+                    {
+                        return Some((a.clone(), b.clone()));
                     }
+                    _ => continue,
                 }
-                _ => continue 'fn_a,
             }
         }
+        None
+    }
 
-        if new_equivalences.is_empty() {
-            break;
+    /// The literals which make two equal terms equal: the asserted
+    /// equalities on the path between them in the proof forest, and
+    /// those which make the parameters of congruent applications on
+    /// the path equal.  Returns 'false' if there's an edge on the
+    /// path which can't be explained.
+    fn explain_equal(&self, left: &EUFTerm, right: &EUFTerm, lits: &mut BTreeSet<Literal>) -> bool {
+        let mut is_complete = true;
+        let mut seen = BTreeSet::new();
+        let mut todo = vec![(left.clone(), right.clone())];
+        while let Some((left, right)) = todo.pop() {
+            if left == right || !seen.insert((left.clone(), right.clone())) {
+                continue;
+            }
+            let left_path = self.path(&left);
+            let right_path = self.path(&right);
+            let ancestors: BTreeSet<&EUFTerm> = left_path.iter().map(|(term, _)| *term).collect();
+            let common = right_path
+                .iter()
+                .map(|(term, _)| *term)
+                .find(|term| ancestors.contains(term))
+                .unwrap_or(&left);
+
+            let edges = left_path
+                .iter()
+                .take_while(|(term, _)| *term != common)
+                .chain(right_path.iter().take_while(|(term, _)| *term != common));
+            for (_, reason) in edges {
+                match reason {
+                    Some(Reason::Asserted(lit)) => {
+                        lits.insert(*lit);
+                    }
+                    Some(Reason::Congruence(
+                        EUFTerm::Application {
+                            parameters: aps, ..
+                        },
+                        EUFTerm::Application {
+                            parameters: bps, ..
+                        },
+                    )) => todo.extend(aps.iter().cloned().zip(bps.iter().cloned())),
+                    Some(Reason::Axiom) | Some(Reason::Chosen(_)) => is_complete = false,
+                    _ => (),
+                }
+            }
         }
+        is_complete
+    }
+
+    /// The literals which make two terms unequal: an earlier
+    /// inequality between equal terms, picking the one whose
+    /// explanation was complete soonest.
+    fn explain_unequal(
+        &self,
+        left: &EUFTerm,
+        right: &EUFTerm,
+        order: &BTreeMap<Literal, usize>,
+    ) -> Option<BTreeSet<Literal>> {
+        self.inequalities(left, right)
+            .filter_map(|(a, b, lit)| {
+                let mut lits = BTreeSet::new();
+                lits.insert(lit?);
+                if self.explain_equal(left, a, &mut lits) && self.explain_equal(right, b, &mut lits)
+                {
+                    Some(lits)
+                } else {
+                    None
+                }
+            })
+            .min_by_key(|lits| lits.iter().map(|lit| order.get(lit)).max())
+    }
 
-        for (a, b) in new_equivalences {
-            add_equiv(rel, superterms, &a, &b);
+    /// A term and its ancestors, each with the reason it's equal to
+    /// its parent.
+    fn path<'a>(&'a self, mut term: &'a EUFTerm) -> Vec<(&'a EUFTerm, Option<&'a Reason>)> {
+        let mut path = Vec::new();
+        while let Some((parent, reason)) = self.forest.get(term) {
+            path.push((term, Some(reason)));
+            term = parent;
         }
+        path.push((term, None));
+        path
     }
 }
 
-/// Check if two terms are known to be equal.
-fn are_equal(rel: &BTreeMap<EUFTerm, BTreeSet<EUFTerm>>, left: &EUFTerm, right: &EUFTerm) -> bool {
-    if *left == *right {
-        return true;
+impl Theory for EUF {
+    /// If the terms aren't known to be equal or unequal, the literal
+    /// and its negation are tried, as either might make terms which
+    /// are unequal equal by congruence, or leave some negated
+    /// 'distinct' with no two terms which can be equal.  Without
+    /// negated 'distinct' literals, only the one which makes terms
+    /// equal needs trying, and if nothing is unequal either, neither
+    /// does.
+    fn decide(&self, model_lit: Literal) -> Result<Option<bool>, TheoryError> {
+        let euf_lit = self.to_euf_lit(model_lit)?;
+        match self.has_equal(&euf_lit.terms)? {
+            Some(has_equal) => Ok(Some(has_equal == euf_lit.is_equality)),
+            None if self.inequivs.is_empty() && self.choices.is_empty() => Ok(None),
+            None if (euf_lit.is_equality || !self.choices.is_empty())
+                && !self.is_consistent_with(model_lit) =>
+            {
+                Ok(Some(false))
+            }
+            None if (!euf_lit.is_equality || !self.choices.is_empty())
+                && !self.is_consistent_with(model_lit.negate()) =>
+            {
+                Ok(Some(true))
+            }
+            None => Ok(None),
+        }
     }
 
-    let mut seen = BTreeSet::new();
-    let mut todo = vec![left];
-    let empty_set = BTreeSet::new();
-
-    while let Some(next) = todo.pop() {
-        {
-            for candidate in rel.get(next).unwrap_or(&empty_set) {
-                if seen.contains(candidate) {
-                    continue;
-                }
-                if *candidate == *right {
-                    return true;
+    fn incorporate(&mut self, model_lit: Literal) -> Result<(), TheoryError> {
+        let el = self.to_euf_lit(model_lit)?;
+        self.incorporated.push(model_lit);
+        match (el.is_equality, &el.terms[..]) {
+            (true, [left, right]) => self.merge(left, right, Reason::Asserted(model_lit)),
+            (true, [_, _, _, ..]) => self.choices.push((el.terms, model_lit)),
+            (true, _) => {
+                return Err(TheoryError(format!(
+                    "contradiction: {:?} has no two terms to be equal",
+                    el.terms
+                )))
+            }
+            (false, _) => {
+                if let Some((a, _)) = el.pairs().find(|(a, b)| a == b) {
+                    return Err(TheoryError(format!(
+                        "contradiction: {:?} is not equal to itself",
+                        a
+                    )));
                 }
-                todo.push(candidate);
+                self.inequivs.push((el.terms, Some(model_lit)));
             }
         }
-        seen.insert(next.clone());
+        self.choose();
+        Ok(())
     }
 
-    false
-}
+    fn push(&mut self) {
+        self.points.push((
+            self.undo.len(),
+            self.inequivs.len(),
+            self.choices.len(),
+            self.incorporated.len(),
+        ));
+    }
 
-/// Check if two terms are known to be unequal.
-fn are_unequal(
-    rel: &BTreeMap<EUFTerm, BTreeSet<EUFTerm>>,
-    inequivs: &BTreeSet<(EUFTerm, EUFTerm)>,
-    left: &EUFTerm,
-    right: &EUFTerm,
-) -> bool {
-    if *left == *right {
-        return false;
+    fn pop(&mut self, n: usize) {
+        if n == 0 || self.points.is_empty() {
+            return;
+        }
+        let point = self.points.len().saturating_sub(n);
+        let (undo, inequivs, choices, incorporated) = self.points[point];
+        self.points.truncate(point);
+        for (term, parent) in self.undo.drain(undo..).rev() {
+            match parent {
+                Some(parent) => self.forest.insert(term, parent),
+                None => self.forest.remove(&term),
+            };
+        }
+        self.inequivs.truncate(inequivs);
+        self.choices.truncate(choices);
+        self.incorporated.truncate(incorporated);
     }
 
-    for (a, b) in inequivs {
-        if (are_equal(rel, left, a) && are_equal(rel, right, b))
-            || (are_equal(rel, left, b) && are_equal(rel, right, a))
-        {
-            return true;
+    fn forget(&mut self) {
+        self.forest = BTreeMap::new();
+        self.inequivs = Vec::new();
+        self.choices = Vec::new();
+        self.incorporated = Vec::new();
+        self.undo = Vec::new();
+        self.points = Vec::new();
+    }
+
+    /// An equality is explained by the path between its terms in the
+    /// proof forest, and a negated 'distinct' by that of some two of
+    /// its terms.  An inequality is explained by an earlier one
+    /// between equal terms, and a 'distinct' by one for each pair of
+    /// its terms.
+    fn explain(&self, model_lit: Literal) -> Option<Vec<Literal>> {
+        let el = self.to_euf_lit(model_lit).ok()?;
+        if el.is_equality {
+            let (left, right) = el.pairs().find(|(a, b)| self.are_equal(a, b))?;
+            let mut lits = BTreeSet::new();
+            return if self.explain_equal(left, right, &mut lits) {
+                Some(lits.into_iter().collect())
+            } else {
+                None
+            };
+        }
+
+        let order: BTreeMap<Literal, usize> = self
+            .incorporated
+            .iter()
+            .enumerate()
+            .map(|(i, lit)| (*lit, i))
+            .collect();
+        let mut lits = BTreeSet::new();
+        for (left, right) in el.pairs() {
+            lits.extend(self.explain_unequal(left, right, &order)?);
         }
+        Some(lits.into_iter().collect())
     }
+}
+
+/// Each pair of some terms.
+fn pairs(terms: &[EUFTerm]) -> impl Iterator<Item = (&EUFTerm, &EUFTerm)> {
+    terms
+        .iter()
+        .enumerate()
+        .flat_map(move |(i, a)| terms[i + 1..].iter().map(move |b| (a, b)))
+}
+
+/// Given a set of literals, compute the superterm relation.
+fn compute_superterms(lits: &[EUFLiteral]) -> BTreeMap<EUFTerm, BTreeSet<EUFTerm>> {
+    let mut superterms: BTreeMap<EUFTerm, BTreeSet<EUFTerm>> = BTreeMap::new();
+    for term in lits.iter().flat_map(|lit| &lit.terms) {
+        add_superterms(&mut superterms, term);
+    }
+    superterms
+}
 
-    false
+/// Add a term, and its subterms, to the superterm relation.
+fn add_superterms(superterms: &mut BTreeMap<EUFTerm, BTreeSet<EUFTerm>>, term: &EUFTerm) {
+    superterms.entry(term.clone()).or_default();
+
+    if let EUFTerm::Application { parameters, .. } = term {
+        for p in parameters {
+            superterms
+                .entry(p.clone())
+                .or_default()
+                .insert(term.clone());
+            add_superterms(superterms, p);
+        }
+    }
 }
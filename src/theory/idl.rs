@@ -0,0 +1,255 @@
+//! The theory of integer difference logic, where atoms are bounds on
+//! the difference of two integer variables.  This allows expressing
+//! problems like:
+//!
+//!    x - y <= 3 && y - z <= -1 && (z - x <= -3 || x - z <= 0)
+//!
+//! The bounds form a graph, with an edge from y to x of weight c for
+//! each x - y <= c, and they're consistent exactly when the graph has
+//! no negative cycle.  A solution is kept as the potential of each
+//! variable, and fixed up by Bellman-Ford relaxation as edges are
+//! added.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::collections::VecDeque;
+
+use crate::cnf::Literal;
+use crate::theory::{Theory, TheoryError};
+
+/// An IDL literal is a bound on the difference of two variables:
+/// left - right <= bound.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub struct IDLLiteral {
+    left: usize,
+    right: usize,
+    bound: isize,
+}
+
+impl IDLLiteral {
+    /// Construct an IDL literal.
+    pub fn new(left: usize, right: usize, bound: isize) -> IDLLiteral {
+        IDLLiteral { left, right, bound }
+    }
+
+    /// Turn a bound into its negation.  Over the integers, the
+    /// negation of x - y <= c is y - x <= -c - 1.
+    pub fn negate(self) -> IDLLiteral {
+        IDLLiteral {
+            left: self.right,
+            right: self.left,
+            bound: -self.bound - 1,
+        }
+    }
+}
+
+/// An edge of the constraint graph, with the literal which gave it.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord)]
+struct Edge {
+    from: usize,
+    to: usize,
+    weight: isize,
+    lit: Literal,
+}
+
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub struct IDL {
+    lits: Vec<IDLLiteral>,
+    /// The edges, in the order they were added.
+    edges: Vec<Edge>,
+    /// The edges out of each variable, as indices into 'edges'.
+    outgoing: Vec<Vec<usize>>,
+    /// A value for each variable which satisfies every edge.  This
+    /// stays a solution when edges are removed, so it's only changed
+    /// when they're added.
+    potential: Vec<isize>,
+    /// The lengths of 'edges' at each point to return to.
+    points: Vec<usize>,
+}
+
+impl IDL {
+    /// Construct an IDL theory for the given set of literals.  For
+    /// cnf literal X, lits[X-1] should be the corresponding idl
+    /// literal.
+    pub fn new(lits: Vec<IDLLiteral>) -> IDL {
+        let num_vars = lits
+            .iter()
+            .map(|lit| lit.left.max(lit.right) + 1)
+            .max()
+            .unwrap_or(0);
+        IDL {
+            lits,
+            edges: Vec::new(),
+            outgoing: vec![Vec::new(); num_vars],
+            potential: vec![0; num_vars],
+            points: Vec::new(),
+        }
+    }
+
+    /// The value of each variable in a solution of the bounds
+    /// incorporated so far, indexed by variable.
+    pub fn values(&self) -> &[isize] {
+        &self.potential
+    }
+
+    fn to_idl_lit(&self, model_lit: Literal) -> Result<IDLLiteral, TheoryError> {
+        match self.lits.get((model_lit.get_id() as usize) - 1) {
+            Some(idl_lit) if model_lit.is_negated() => Ok(idl_lit.negate()),
+            Some(idl_lit) => Ok(*idl_lit),
+            None => Err(TheoryError(format!(
+                "no IDL literal for atom {}",
+                model_lit.get_id()
+            ))),
+        }
+    }
+
+    /// The shortest path from one variable to another, as its weight
+    /// and edges, if there is one.  This is Dijkstra's algorithm, with
+    /// the weights reduced by the potentials so none are negative.
+    fn shortest_path(&self, from: usize, to: usize) -> Option<(isize, Vec<usize>)> {
+        let reduced = |e: &Edge| self.potential[e.from] + e.weight - self.potential[e.to];
+        let mut distance: Vec<Option<isize>> = vec![None; self.potential.len()];
+        let mut via: Vec<Option<usize>> = vec![None; self.potential.len()];
+        let mut queue = BinaryHeap::new();
+        distance[from] = Some(0);
+        queue.push(Reverse((0, from)));
+
+        while let Some(Reverse((d, var))) = queue.pop() {
+            if distance[var].is_some_and(|best| d > best) {
+                continue;
+            }
+            if var == to {
+                let mut path = Vec::new();
+                let mut var = to;
+                while let Some(i) = via[var] {
+                    path.push(i);
+                    var = self.edges[i].from;
+                }
+                path.reverse();
+                return Some((d - self.potential[from] + self.potential[to], path));
+            }
+            for i in &self.outgoing[var] {
+                let edge = &self.edges[*i];
+                let next = d + reduced(edge);
+                if distance[edge.to].is_none_or(|best| next < best) {
+                    distance[edge.to] = Some(next);
+                    via[edge.to] = Some(*i);
+                    queue.push(Reverse((next, edge.to)));
+                }
+            }
+        }
+        None
+    }
+
+    /// Check if a bound is implied by those incorporated, returning
+    /// the edges which imply it.
+    fn implied(&self, lit: IDLLiteral) -> Option<Vec<usize>> {
+        match self.shortest_path(lit.right, lit.left) {
+            Some((weight, path)) if weight <= lit.bound => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Add an edge, restoring the potentials by relaxing the edges
+    /// out of any variable whose potential goes down.  If that reaches
+    /// back to the start of the new edge, it's on a negative cycle,
+    /// and nothing is changed.
+    fn add_edge(&mut self, edge: Edge) -> Result<(), TheoryError> {
+        let mut potential = self.potential.clone();
+        let mut queue = VecDeque::new();
+        if potential[edge.from] + edge.weight < potential[edge.to] {
+            potential[edge.to] = potential[edge.from] + edge.weight;
+            queue.push_back(edge.to);
+        }
+        while let Some(var) = queue.pop_front() {
+            for i in &self.outgoing[var] {
+                let next = &self.edges[*i];
+                if potential[var] + next.weight < potential[next.to] {
+                    if next.to == edge.from {
+                        return Err(TheoryError(format!(
+                            "contradiction: negative cycle through {} and {}",
+                            edge.from, edge.to
+                        )));
+                    }
+                    potential[next.to] = potential[var] + next.weight;
+                    queue.push_back(next.to);
+                }
+            }
+        }
+
+        self.potential = potential;
+        self.outgoing[edge.from].push(self.edges.len());
+        self.edges.push(edge);
+        Ok(())
+    }
+}
+
+impl Theory for IDL {
+    fn decide(&self, model_lit: Literal) -> Result<Option<bool>, TheoryError> {
+        let idl_lit = self.to_idl_lit(model_lit)?;
+        if self.implied(idl_lit).is_some() {
+            Ok(Some(true))
+        } else if self.implied(idl_lit.negate()).is_some() {
+            Ok(Some(false))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn incorporate(&mut self, model_lit: Literal) -> Result<(), TheoryError> {
+        let il = self.to_idl_lit(model_lit)?;
+        if il.left == il.right {
+            return if il.bound >= 0 {
+                Ok(())
+            } else {
+                Err(TheoryError(format!(
+                    "contradiction: {} - {} is not at most {}",
+                    il.left, il.right, il.bound
+                )))
+            };
+        }
+        self.add_edge(Edge {
+            from: il.right,
+            to: il.left,
+            weight: il.bound,
+            lit: model_lit,
+        })
+    }
+
+    fn push(&mut self) {
+        self.points.push(self.edges.len());
+    }
+
+    fn pop(&mut self, n: usize) {
+        if n == 0 || self.points.is_empty() {
+            return;
+        }
+        let point = self.points.len().saturating_sub(n);
+        let edges = self.points[point];
+        self.points.truncate(point);
+        for edge in self.edges.drain(edges..).rev() {
+            self.outgoing[edge.from].pop();
+        }
+    }
+
+    fn forget(&mut self) {
+        self.edges = Vec::new();
+        self.outgoing = vec![Vec::new(); self.potential.len()];
+        self.potential = vec![0; self.potential.len()];
+        self.points = Vec::new();
+    }
+
+    /// A bound is explained by the bounds on a shortest path between
+    /// its variables.
+    fn explain(&self, model_lit: Literal) -> Option<Vec<Literal>> {
+        let il = self.to_idl_lit(model_lit).ok()?;
+        let mut lits: Vec<Literal> = self
+            .implied(il)?
+            .iter()
+            .map(|i| self.edges[*i].lit)
+            .collect();
+        lits.sort();
+        lits.dedup();
+        Some(lits)
+    }
+}
@@ -0,0 +1,275 @@
+//! The theory of linear integer arithmetic, where atoms are bounds on
+//! linear combinations of integer variables.  This allows expressing
+//! problems like:
+//!
+//!    x + y <= 3 && x - y >= 0 && (2x + y >= 4 || y <= -1)
+//!
+//! Bounds are checked over the rationals by the simplex, and then for
+//! an integer solution by branch and bound: if some variable has a
+//! fractional value v, it's split into the cases x <= floor(v) and
+//! x >= ceil(v).  The bounds of atoms are tightened first, by dividing
+//! through by the gcd of their coefficients and rounding, which cuts
+//! off some fractional solutions without any search.
+
+mod simplex;
+
+use std::collections::BTreeMap;
+use std::mem;
+
+use crate::cnf::Literal;
+use crate::theory::lia::simplex::Simplex;
+use crate::theory::{Theory, TheoryError};
+
+/// The most branches to try when looking for an integer solution,
+/// before giving up.
+const MAX_BRANCHES: usize = 10_000;
+
+/// An LIA literal is a bound on a linear combination of variables:
+/// sum of coefficient * variable <= bound.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub struct LIALiteral {
+    coefficients: Vec<(usize, isize)>,
+    bound: isize,
+}
+
+impl LIALiteral {
+    /// Construct an LIA literal from each variable with its
+    /// coefficient.
+    pub fn new(coefficients: Vec<(usize, isize)>, bound: isize) -> LIALiteral {
+        LIALiteral {
+            coefficients,
+            bound,
+        }
+    }
+
+    /// Turn a bound into its negation.  Over the integers, the
+    /// negation of t <= c is -t <= -c - 1.
+    pub fn negate(self) -> LIALiteral {
+        LIALiteral {
+            coefficients: self
+                .coefficients
+                .into_iter()
+                .map(|(var, a)| (var, -a))
+                .collect(),
+            bound: -self.bound - 1,
+        }
+    }
+}
+
+/// A literal as a bound on one variable of the simplex.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord)]
+enum Bound {
+    /// A literal with no variables, which is always true or false.
+    Constant(bool),
+    Upper(usize, isize),
+    Lower(usize, isize),
+}
+
+impl Bound {
+    fn negate(self) -> Bound {
+        match self {
+            Bound::Constant(b) => Bound::Constant(!b),
+            Bound::Upper(var, c) => Bound::Lower(var, c + 1),
+            Bound::Lower(var, c) => Bound::Upper(var, c - 1),
+        }
+    }
+
+    /// Add the bound to the simplex, returning 'false' if it
+    /// conflicts with another bound.
+    fn assert(self, simplex: &mut Simplex) -> bool {
+        match self {
+            Bound::Constant(b) => b,
+            Bound::Upper(var, c) => simplex.assert_upper(var, c),
+            Bound::Lower(var, c) => simplex.assert_lower(var, c),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LIA {
+    lits: Vec<LIALiteral>,
+    /// The bound of each literal.
+    bounds: Vec<Bound>,
+    /// The number of variables of the literals, which come first in
+    /// the simplex, before the combinations.
+    num_vars: usize,
+    /// The simplex with the bounds incorporated so far, which have a
+    /// rational solution.
+    simplex: Simplex,
+    /// The simplex at each point to return to.
+    points: Vec<Simplex>,
+}
+
+impl LIA {
+    /// Construct an LIA theory for the given set of literals.  For
+    /// cnf literal X, lits[X-1] should be the corresponding lia
+    /// literal.
+    pub fn new(lits: Vec<LIALiteral>) -> LIA {
+        let num_vars = lits
+            .iter()
+            .flat_map(|lit| lit.coefficients.iter().map(|(var, _)| var + 1))
+            .max()
+            .unwrap_or(0);
+        let mut simplex = Simplex::new(num_vars);
+        let mut combinations = BTreeMap::new();
+        let bounds = lits
+            .iter()
+            .map(|lit| {
+                let (coefficients, bound) = tighten(lit);
+                let upper = match coefficients.first() {
+                    Some((_, a)) => *a > 0,
+                    None => return Bound::Constant(bound >= 0),
+                };
+                // t <= c is -t >= -c, so only one of them needs a row.
+                let (coefficients, bound) = if upper {
+                    (coefficients, bound)
+                } else {
+                    let negated = coefficients.iter().map(|(var, a)| (*var, -a)).collect();
+                    (negated, -bound)
+                };
+                let var = match coefficients[..] {
+                    [(var, 1)] => var,
+                    _ => *combinations
+                        .entry(coefficients)
+                        .or_insert_with_key(|coefficients| simplex.add_row(coefficients)),
+                };
+                if upper {
+                    Bound::Upper(var, bound)
+                } else {
+                    Bound::Lower(var, bound)
+                }
+            })
+            .collect();
+
+        LIA {
+            lits,
+            bounds,
+            num_vars,
+            simplex,
+            points: Vec::new(),
+        }
+    }
+
+    fn to_bound(&self, model_lit: Literal) -> Result<Bound, TheoryError> {
+        match self.bounds.get((model_lit.get_id() as usize) - 1) {
+            Some(bound) if model_lit.is_negated() => Ok(bound.negate()),
+            Some(bound) => Ok(*bound),
+            None => Err(TheoryError(format!(
+                "no LIA literal for atom {}",
+                model_lit.get_id()
+            ))),
+        }
+    }
+
+    /// Check if the bounds incorporated so far, and another, have an
+    /// integer solution.
+    fn is_consistent_with(&self, bound: Bound) -> Result<bool, TheoryError> {
+        let mut simplex = self.simplex.clone();
+        if !bound.assert(&mut simplex) {
+            return Ok(false);
+        }
+        let mut branches = 0;
+        self.branch_and_bound(simplex, &mut branches)
+    }
+
+    /// Search for an integer solution, splitting on the first variable
+    /// with a fractional value.
+    fn branch_and_bound(
+        &self,
+        mut simplex: Simplex,
+        branches: &mut usize,
+    ) -> Result<bool, TheoryError> {
+        if !simplex.check() {
+            return Ok(false);
+        }
+        let value = match (0..self.num_vars)
+            .map(|var| (var, simplex.value(var)))
+            .find(|(_, value)| !value.is_integer())
+        {
+            Some(value) => value,
+            None => return Ok(true),
+        };
+
+        *branches += 1;
+        if *branches > MAX_BRANCHES {
+            return Err(TheoryError(format!(
+                "gave up looking for an integer solution after {} branches",
+                MAX_BRANCHES
+            )));
+        }
+        let (var, value) = value;
+        let mut below = simplex.clone();
+        if below.assert_upper(var, value.floor()) && self.branch_and_bound(below, branches)? {
+            return Ok(true);
+        }
+        Ok(simplex.assert_lower(var, value.ceil()) && self.branch_and_bound(simplex, branches)?)
+    }
+}
+
+/// Combine the coefficients of each variable, dropping zeros, and
+/// divide through by their gcd, rounding the bound down: as the
+/// combination is then an integer, this loses no integer solutions.
+fn tighten(lit: &LIALiteral) -> (Vec<(usize, isize)>, isize) {
+    let mut combined: BTreeMap<usize, isize> = BTreeMap::new();
+    for (var, a) in &lit.coefficients {
+        *combined.entry(*var).or_default() += a;
+    }
+    combined.retain(|_, a| *a != 0);
+
+    let g = combined.values().fold(0, |g, a| gcd(g, a.abs())).max(1);
+    let coefficients = combined.into_iter().map(|(var, a)| (var, a / g)).collect();
+    (coefficients, lit.bound.div_euclid(g))
+}
+
+/// The greatest common divisor of two nonnegative numbers.
+fn gcd(a: isize, b: isize) -> isize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+impl Theory for LIA {
+    fn decide(&self, model_lit: Literal) -> Result<Option<bool>, TheoryError> {
+        let bound = self.to_bound(model_lit)?;
+        if !self.is_consistent_with(bound)? {
+            Ok(Some(false))
+        } else if !self.is_consistent_with(bound.negate())? {
+            Ok(Some(true))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn incorporate(&mut self, model_lit: Literal) -> Result<(), TheoryError> {
+        let bound = self.to_bound(model_lit)?;
+        let mut simplex = self.simplex.clone();
+        if !bound.assert(&mut simplex) || !simplex.check() {
+            return Err(TheoryError(format!(
+                "contradiction: {} has no rational solution",
+                model_lit
+            )));
+        }
+        self.simplex = simplex;
+        Ok(())
+    }
+
+    fn push(&mut self) {
+        self.points.push(self.simplex.clone());
+    }
+
+    fn pop(&mut self, n: usize) {
+        if n == 0 || self.points.is_empty() {
+            return;
+        }
+        let point = self.points.len().saturating_sub(n);
+        if let Some(simplex) = self.points.drain(point..).next() {
+            self.simplex = simplex;
+        }
+    }
+
+    fn forget(&mut self) {
+        *self = LIA::new(mem::take(&mut self.lits));
+    }
+}
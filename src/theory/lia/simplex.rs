@@ -0,0 +1,326 @@
+//! The general simplex of Dutertre and de Moura, which checks if some
+//! bounds on variables, and on linear combinations of them, have a
+//! solution over the rationals.  Each combination is a variable of its
+//! own, defined by a row of the tableau, so bounds are only ever on
+//! variables, and adding one doesn't change the tableau.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::mem;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// A fraction in lowest terms, with a positive denominator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct Rational {
+    num: i128,
+    den: i128,
+}
+
+impl Rational {
+    fn new(num: i128, den: i128) -> Rational {
+        let g = gcd(num, den).max(1) * den.signum();
+        Rational {
+            num: num / g,
+            den: den / g,
+        }
+    }
+
+    pub(crate) fn integer(n: isize) -> Rational {
+        Rational {
+            num: n as i128,
+            den: 1,
+        }
+    }
+
+    pub(crate) fn is_integer(self) -> bool {
+        self.den == 1
+    }
+
+    pub(crate) fn floor(self) -> isize {
+        self.num.div_euclid(self.den) as isize
+    }
+
+    pub(crate) fn ceil(self) -> isize {
+        -(-self).floor()
+    }
+
+    fn is_zero(self) -> bool {
+        self.num == 0
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Rational) -> Ordering {
+        (self.num * other.den).cmp(&(other.num * self.den))
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Rational) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Add for Rational {
+    type Output = Rational;
+    fn add(self, other: Rational) -> Rational {
+        Rational::new(
+            self.num * other.den + other.num * self.den,
+            self.den * other.den,
+        )
+    }
+}
+
+impl Sub for Rational {
+    type Output = Rational;
+    fn sub(self, other: Rational) -> Rational {
+        self + -other
+    }
+}
+
+impl Mul for Rational {
+    type Output = Rational;
+    fn mul(self, other: Rational) -> Rational {
+        Rational::new(self.num * other.num, self.den * other.den)
+    }
+}
+
+impl Div for Rational {
+    type Output = Rational;
+    fn div(self, other: Rational) -> Rational {
+        Rational::new(self.num * other.den, self.den * other.num)
+    }
+}
+
+impl Neg for Rational {
+    type Output = Rational;
+    fn neg(self) -> Rational {
+        Rational {
+            num: -self.num,
+            den: self.den,
+        }
+    }
+}
+
+/// The greatest common divisor, which is never negative.
+fn gcd(mut a: i128, mut b: i128) -> i128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.abs()
+}
+
+/// A tableau, and an assignment which satisfies its rows and the
+/// bounds of the nonbasic variables.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Simplex {
+    /// Each basic variable, as a combination of nonbasic ones.
+    rows: Vec<(usize, BTreeMap<usize, Rational>)>,
+    /// The row of each basic variable.
+    row_of: Vec<Option<usize>>,
+    lower: Vec<Option<Rational>>,
+    upper: Vec<Option<Rational>>,
+    value: Vec<Rational>,
+}
+
+impl Simplex {
+    /// A tableau with some unbounded variables, all 0, and no rows.
+    pub(crate) fn new(num_vars: usize) -> Simplex {
+        Simplex {
+            rows: Vec::new(),
+            row_of: vec![None; num_vars],
+            lower: vec![None; num_vars],
+            upper: vec![None; num_vars],
+            value: vec![Rational::integer(0); num_vars],
+        }
+    }
+
+    /// Add a new variable, equal to a combination of others.
+    pub(crate) fn add_row(&mut self, coefficients: &[(usize, isize)]) -> usize {
+        let mut row = BTreeMap::new();
+        let mut value = Rational::integer(0);
+        for (var, a) in coefficients {
+            let a = Rational::integer(*a);
+            value = value + a * self.value[*var];
+            match self.row_of[*var] {
+                Some(r) => add_scaled(&mut row, &self.rows[r].1, a),
+                None => add_scaled(&mut row, &BTreeMap::from([(*var, Rational::integer(1))]), a),
+            }
+        }
+
+        let var = self.value.len();
+        self.row_of.push(Some(self.rows.len()));
+        self.rows.push((var, row));
+        self.lower.push(None);
+        self.upper.push(None);
+        self.value.push(value);
+        var
+    }
+
+    /// The value of a variable.
+    pub(crate) fn value(&self, var: usize) -> Rational {
+        self.value[var]
+    }
+
+    /// Bound a variable from above.  Returns 'false' if that conflicts
+    /// with its lower bound.
+    pub(crate) fn assert_upper(&mut self, var: usize, bound: isize) -> bool {
+        let bound = Rational::integer(bound);
+        if self.lower[var].is_some_and(|lower| lower > bound) {
+            return false;
+        }
+        if self.upper[var].is_none_or(|upper| bound < upper) {
+            self.upper[var] = Some(bound);
+            if self.row_of[var].is_none() && self.value[var] > bound {
+                self.update(var, bound);
+            }
+        }
+        true
+    }
+
+    /// Bound a variable from below.  Returns 'false' if that conflicts
+    /// with its upper bound.
+    pub(crate) fn assert_lower(&mut self, var: usize, bound: isize) -> bool {
+        let bound = Rational::integer(bound);
+        if self.upper[var].is_some_and(|upper| upper < bound) {
+            return false;
+        }
+        if self.lower[var].is_none_or(|lower| bound > lower) {
+            self.lower[var] = Some(bound);
+            if self.row_of[var].is_none() && self.value[var] < bound {
+                self.update(var, bound);
+            }
+        }
+        true
+    }
+
+    /// Pivot until every variable is within its bounds, returning
+    /// 'false' if that's impossible.  Variables are picked by Bland's
+    /// rule, lowest first, so this can't cycle.
+    pub(crate) fn check(&mut self) -> bool {
+        loop {
+            let violated = (0..self.value.len())
+                .filter(|var| self.row_of[*var].is_some())
+                .find(|var| self.below(*var) || self.above(*var));
+            let basic = match violated {
+                Some(basic) => basic,
+                None => return true,
+            };
+
+            // to raise the basic variable, raise a nonbasic one with a
+            // positive coefficient or lower one with a negative one,
+            // and the other way around to lower it.
+            let raise = self.below(basic);
+            let (_, row) = &self.rows[self.row_of[basic].unwrap()];
+            let nonbasic = row.iter().find(|(var, a)| {
+                let up = (**a > Rational::integer(0)) == raise;
+                if up {
+                    self.upper[**var].is_none_or(|upper| self.value[**var] < upper)
+                } else {
+                    self.lower[**var].is_none_or(|lower| self.value[**var] > lower)
+                }
+            });
+            let nonbasic = match nonbasic {
+                Some((var, _)) => *var,
+                None => return false,
+            };
+            let target = if raise {
+                self.lower[basic]
+            } else {
+                self.upper[basic]
+            };
+            self.pivot_and_update(basic, nonbasic, target.unwrap());
+        }
+    }
+
+    /// Check if a variable is below its lower bound.
+    fn below(&self, var: usize) -> bool {
+        self.lower[var].is_some_and(|lower| self.value[var] < lower)
+    }
+
+    /// Check if a variable is above its upper bound.
+    fn above(&self, var: usize) -> bool {
+        self.upper[var].is_some_and(|upper| self.value[var] > upper)
+    }
+
+    /// Change the value of a nonbasic variable, and so of the basic
+    /// variables which depend on it.
+    fn update(&mut self, var: usize, value: Rational) {
+        let delta = value - self.value[var];
+        for (basic, row) in &self.rows {
+            if let Some(a) = row.get(&var) {
+                self.value[*basic] = self.value[*basic] + *a * delta;
+            }
+        }
+        self.value[var] = value;
+    }
+
+    /// Change the value of a basic variable, by changing a nonbasic
+    /// one in its row, and then swap them.
+    fn pivot_and_update(&mut self, basic: usize, nonbasic: usize, value: Rational) {
+        let r = self.row_of[basic].unwrap();
+        let a = self.rows[r].1[&nonbasic];
+        let theta = (value - self.value[basic]) / a;
+        self.update(nonbasic, self.value[nonbasic] + theta);
+
+        // basic = a * nonbasic + rest, so nonbasic = (basic - rest) / a.
+        let (_, mut row) = mem::take(&mut self.rows[r]);
+        row.remove(&nonbasic);
+        let mut solved: BTreeMap<usize, Rational> =
+            row.into_iter().map(|(v, c)| (v, -c / a)).collect();
+        solved.insert(basic, Rational::integer(1) / a);
+
+        for (i, (_, other)) in self.rows.iter_mut().enumerate() {
+            if i != r {
+                if let Some(c) = other.remove(&nonbasic) {
+                    add_scaled(other, &solved, c);
+                }
+            }
+        }
+        self.rows[r] = (nonbasic, solved);
+        self.row_of[basic] = None;
+        self.row_of[nonbasic] = Some(r);
+    }
+}
+
+/// Add a multiple of one combination to another.
+fn add_scaled(
+    row: &mut BTreeMap<usize, Rational>,
+    other: &BTreeMap<usize, Rational>,
+    scale: Rational,
+) {
+    for (var, a) in other {
+        let sum = row.get(var).copied().unwrap_or(Rational::integer(0)) + *a * scale;
+        if sum.is_zero() {
+            row.remove(var);
+        } else {
+            row.insert(*var, sum);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_rational_solutions() {
+        // x + y = 1 and x - y = 0.
+        let mut simplex = Simplex::new(2);
+        let sum = simplex.add_row(&[(0, 1), (1, 1)]);
+        let difference = simplex.add_row(&[(0, 1), (1, -1)]);
+        assert!(simplex.assert_lower(sum, 1));
+        assert!(simplex.assert_upper(sum, 1));
+        assert!(simplex.assert_lower(difference, 0));
+        assert!(simplex.assert_upper(difference, 0));
+        assert!(simplex.check());
+        assert_eq!(Rational::new(1, 2), simplex.value(0));
+        assert_eq!(Rational::new(1, 2), simplex.value(1));
+        assert_eq!((0, 1), (simplex.value(0).floor(), simplex.value(0).ceil()));
+
+        // and x >= 1.
+        assert!(simplex.assert_lower(0, 1));
+        assert!(!simplex.check());
+        assert!(!simplex.assert_upper(0, 0));
+    }
+}
@@ -0,0 +1,62 @@
+//! Checking answers.
+
+use crate::cnf::{Formula, Literal};
+
+/// Check that an assignment satisfies every clause of a formula.  On
+/// failure, returns the indices of the clauses without a true literal.
+/// Atoms which aren't assigned are neither true nor false, so a
+/// partial assignment only passes if it's enough by itself.
+pub fn check_model(formula: &Formula, model: &[Literal]) -> Result<(), Vec<usize>> {
+    let violated = violated(formula.iter(), model);
+    if violated.is_empty() {
+        Ok(())
+    } else {
+        Err(violated)
+    }
+}
+
+/// The indices of the clauses without a true literal.
+pub(crate) fn violated<'a>(
+    clauses: impl Iterator<Item = &'a [Literal]>,
+    model: &[Literal],
+) -> Vec<usize> {
+    let num_atoms = model
+        .iter()
+        .map(|lit| lit.get_id() as usize)
+        .max()
+        .unwrap_or(0);
+    let mut values = vec![None; num_atoms + 1];
+    for lit in model {
+        values[lit.get_id() as usize] = Some(!lit.is_negated());
+    }
+    let is_true = |lit: &Literal| {
+        values.get(lit.get_id() as usize).copied().flatten() == Some(!lit.is_negated())
+    };
+
+    clauses
+        .enumerate()
+        .filter(|(_, lits)| !lits.iter().any(is_true))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cnf::*;
+
+    #[test]
+    fn reports_violated_clauses() {
+        let formula = Formula::new(vec![
+            Clause::new(vec![1, 2]),
+            Clause::new(vec![-1, 3]),
+            Clause::new(vec![-2, -3]),
+            Clause::new(vec![4]),
+        ]);
+        let model = |lits: &[isize]| lits.iter().map(|l| Literal::new(*l)).collect::<Vec<_>>();
+
+        assert_eq!(Ok(()), check_model(&formula, &model(&[1, -2, 3, 4])));
+        assert_eq!(Err(vec![1, 3]), check_model(&formula, &model(&[1, -2, -3])));
+        assert_eq!(Err(vec![0, 1, 3]), check_model(&formula, &model(&[-3])));
+    }
+}
@@ -0,0 +1,173 @@
+//! Solving from a string, with nothing which depends on a terminal or
+//! a clock, for running in the browser.  The answer can be converted
+//! to JSON for JavaScript.
+//!
+//! Without a bindings generator, JavaScript calls the exported
+//! functions directly: it writes the DIMACS input into memory from
+//! 'sat_alloc', calls 'sat_solve', reads the NUL-terminated JSON
+//! answer from the returned pointer, and frees the input with
+//! 'sat_free'.
+
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::mem;
+use std::slice;
+use std::str;
+
+use crate::cnf::Literal;
+use crate::dpll::{Config, SolveResult};
+use crate::parse::dimacs::{self, ParseError};
+use crate::solver::Solver;
+use crate::theory::empty::Empty;
+
+/// The answer to a problem.
+#[derive(Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
+pub enum Answer {
+    /// A model, as DIMACS literals.
+    Sat(Vec<isize>),
+    Unsat,
+    /// The search ran out of conflicts.
+    Unknown,
+}
+
+impl Answer {
+    /// Render as JSON: '{"result":"sat","model":[1,-2]}',
+    /// '{"result":"unsat"}', or '{"result":"unknown"}'.
+    pub fn to_json(&self) -> String {
+        match self {
+            Answer::Sat(model) => {
+                let lits: Vec<String> = model.iter().map(|lit| lit.to_string()).collect();
+                format!("{{\"result\":\"sat\",\"model\":[{}]}}", lits.join(","))
+            }
+            Answer::Unsat => "{\"result\":\"unsat\"}".to_string(),
+            Answer::Unknown => "{\"result\":\"unknown\"}".to_string(),
+        }
+    }
+}
+
+/// Solve a problem in DIMACS format, giving up after 'max_conflicts'
+/// conflicts if that's set, so a page isn't left hanging.  The search
+/// is deterministic.
+pub fn solve_dimacs(input: &str, max_conflicts: Option<usize>) -> Result<Answer, ParseError> {
+    let formula = dimacs::from_string(input.to_string())?;
+    let mut solver = Solver::new(Empty::new(), formula);
+    solver.set_config(Config {
+        max_conflicts,
+        deterministic: true,
+        ..Config::default()
+    });
+    // the empty theory never fails.
+    Ok(match solver.solve() {
+        Ok(SolveResult::Sat(model)) => {
+            Answer::Sat(model.into_iter().map(|Literal(lit)| lit).collect())
+        }
+        Ok(SolveResult::Unsat) => Answer::Unsat,
+        Ok(SolveResult::Unknown) | Err(_) => Answer::Unknown,
+    })
+}
+
+/// Render a parse error as JSON: '{"error":"..."}'.
+fn error_json(error: &ParseError) -> String {
+    let mut json = String::from("{\"error\":\"");
+    for c in error.to_string().chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push_str("\"}");
+    json
+}
+
+thread_local! {
+    /// The JSON answer from the last call to 'sat_solve'.
+    static ANSWER: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Allocate 'len' bytes for the input.
+#[no_mangle]
+pub extern "C" fn sat_alloc(len: usize) -> *mut u8 {
+    let mut bytes = Vec::<u8>::with_capacity(len);
+    let ptr = bytes.as_mut_ptr();
+    mem::forget(bytes);
+    ptr
+}
+
+/// Free memory from 'sat_alloc'.
+///
+/// # Safety
+///
+/// 'ptr' must have come from 'sat_alloc(len)', and not be used again.
+#[no_mangle]
+pub unsafe extern "C" fn sat_free(ptr: *mut u8, len: usize) {
+    drop(Vec::from_raw_parts(ptr, 0, len));
+}
+
+/// Solve the DIMACS problem in the 'len' bytes at 'ptr', as with
+/// 'solve_dimacs', with a negative 'max_conflicts' meaning no limit.
+/// Returns the answer as a NUL-terminated JSON string, or a parse
+/// error as '{"error":"..."}', which is valid until the next call.
+///
+/// # Safety
+///
+/// 'ptr' must point to 'len' initialised bytes.
+#[no_mangle]
+pub unsafe extern "C" fn sat_solve(ptr: *const u8, len: usize, max_conflicts: i64) -> *const u8 {
+    let json = match str::from_utf8(slice::from_raw_parts(ptr, len)) {
+        Ok(input) => {
+            let max_conflicts = if max_conflicts < 0 {
+                None
+            } else {
+                Some(max_conflicts as usize)
+            };
+            match solve_dimacs(input, max_conflicts) {
+                Ok(answer) => answer.to_json(),
+                Err(error) => error_json(&error),
+            }
+        }
+        Err(_) => error_json(&ParseError::ReadError("input is not UTF-8".to_string())),
+    };
+    ANSWER.with(|answer| {
+        let mut answer = answer.borrow_mut();
+        *answer = json.into_bytes();
+        answer.push(0);
+        answer.as_ptr()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    #[test]
+    fn solves_from_strings() {
+        assert_eq!(
+            Ok(Answer::Sat(vec![1, 2])),
+            solve_dimacs("p cnf 2 2\n1 0\n-1 2 0\n", None)
+        );
+        assert_eq!(
+            Ok(Answer::Unsat),
+            solve_dimacs("p cnf 1 2\n1 0\n-1 0\n", None)
+        );
+        assert!(solve_dimacs("p dnf 1 1\n1 0\n", None).is_err());
+
+        let input = "p cnf 1 2\n1 0\n-1 0\n";
+        let json = unsafe {
+            let ptr = sat_alloc(input.len());
+            ptr.copy_from_nonoverlapping(input.as_ptr(), input.len());
+            let json = CStr::from_ptr(sat_solve(ptr, input.len(), -1) as *const _);
+            sat_free(ptr, input.len());
+            json.to_str().unwrap().to_string()
+        };
+        assert_eq!("{\"result\":\"unsat\"}", json);
+        assert_eq!(
+            "{\"result\":\"sat\",\"model\":[1,-2]}",
+            Answer::Sat(vec![1, -2]).to_json()
+        );
+    }
+}